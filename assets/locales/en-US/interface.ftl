@@ -36,6 +36,8 @@ lbl_Checking = Checking
 lbl_Connected = Connected
 lbl_QuillDemo = Quill Demo
 lbl_Edit = Edit
+lbl_Undo = Undo
+lbl_Redo = Redo
 lbl_Copy = Copy
 lbl_Cut = Cut
 lbl_Paste = Paste
@@ -54,4 +56,32 @@ lbl_BuildRelease = Build release
 lbl_Profile = Profile
 lbl_Window = Window
 lbl_Help = Help
-lbl_About = About
\ No newline at end of file
+lbl_About = About
+lbl_ConnectTo = Connect to…
+lbl_NoServersFound = No running games found
+lbl_RemoteCamera = Remote camera
+lbl_NoRemoteCameras = The game has no cameras
+lbl_Inspector = Inspector
+lbl_NothingSelected = Nothing selected
+lbl_AddItem = Add
+lbl_RemoveItem = Remove
+lbl_Game = Game
+lbl_Search = Search
+lbl_Rename = Rename
+lbl_Duplicate = Duplicate
+lbl_Delete = Delete
+lbl_NewProject = New Project
+lbl_Empty3dGame = Empty 3D game
+lbl_Empty3dGameDescription = A camera, a light and a cube to start from
+lbl_Empty2dGame = Empty 2D game
+lbl_Empty2dGameDescription = A 2D camera and a sprite to start from
+lbl_BrpServer = BRP server
+lbl_BrpServerDescription = Serves its scene for the editor to work on, and can record what the editor does
+lbl_ProjectName = Name
+lbl_CrateName = Crate name
+lbl_Port = Port
+lbl_Folder = Folder
+lbl_ChooseFolder = Choose…
+lbl_NoFolderChosen = No folder chosen
+lbl_Create = Create
+lbl_Cancel = Cancel
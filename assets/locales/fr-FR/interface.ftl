@@ -36,6 +36,8 @@ lbl_Checking = Test de connexion
 lbl_Connected = Connecté
 lbl_QuillDemo = Démo "Quill"
 lbl_Edit = Éditer
+lbl_Undo = Annuler
+lbl_Redo = Rétablir
 lbl_Copy = Copier
 lbl_Cut = Couper
 lbl_Paste = Coller
@@ -54,4 +56,32 @@ lbl_BuildRelease = Créer une version
 lbl_Profile = Profiler
 lbl_Window = Fenêtre
 lbl_Help = Assistant
-lbl_About = Àpropos
\ No newline at end of file
+lbl_About = Àpropos
+lbl_ConnectTo = Se connecter à…
+lbl_NoServersFound = Aucun jeu en cours trouvé
+lbl_RemoteCamera = Caméra distante
+lbl_NoRemoteCameras = Le jeu n’a aucune caméra
+lbl_Inspector = Inspecteur
+lbl_NothingSelected = Aucune sélection
+lbl_AddItem = Ajouter
+lbl_RemoveItem = Retirer
+lbl_Game = Jeu
+lbl_Search = Rechercher
+lbl_Rename = Renommer
+lbl_Duplicate = Dupliquer
+lbl_Delete = Supprimer
+lbl_NewProject = Nouveau projet
+lbl_Empty3dGame = Jeu 3D vide
+lbl_Empty3dGameDescription = Une caméra, une lumière et un cube pour commencer
+lbl_Empty2dGame = Jeu 2D vide
+lbl_Empty2dGameDescription = Une caméra 2D et un sprite pour commencer
+lbl_BrpServer = Serveur BRP
+lbl_BrpServerDescription = Sert sa scène pour que l’éditeur la modifie, et peut enregistrer ce que fait l’éditeur
+lbl_ProjectName = Nom
+lbl_CrateName = Nom du crate
+lbl_Port = Port
+lbl_Folder = Dossier
+lbl_ChooseFolder = Choisir…
+lbl_NoFolderChosen = Aucun dossier choisi
+lbl_Create = Créer
+lbl_Cancel = Annuler
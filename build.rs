@@ -0,0 +1,45 @@
+// Works out how the games New Project makes should depend on beverage_remote (see
+// src/activity/new_project.rs), and hands it to the crate as the BEVERAGE_REMOTE_DEPENDENCY env
+// variable, as it's written in a Cargo.toml.
+
+// an editor built from a checkout points games at the beverage_remote next to it, since whatever
+// it's at may not be pushed, let alone published. an editor built from a published package asks
+// for the published beverage_remote it depends on, so both sides speak the same protocol.
+
+use std::{ env, fs, path::{ Path, PathBuf } };
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap_or_default());
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+
+    let crate_dir = manifest_dir.join("crates").join("beverage_remote");
+    let version = required_version(&manifest_dir.join("Cargo.toml"));
+    let dependency = match version {
+        Some(version) if !is_checkout(&manifest_dir) => format!("\"{}\"", version),
+        // (cargo takes forward slashes everywhere, and they need no escaping in TOML)
+        _ => format!("{{ path = \"{}\" }}", crate_dir.display().to_string().replace('\\', "/")),
+    };
+
+    println!("cargo:rustc-env=BEVERAGE_REMOTE_DEPENDENCY={}", dependency);
+}
+
+// whether a folder is a git checkout (or a worktree of one), rather than an unpacked package
+fn is_checkout(folder: &Path) -> bool {
+    folder.join(".git").exists()
+}
+
+// the version requirement of the `beverage_remote` dependency in a manifest
+fn required_version(path: &Path) -> Option<String> {
+    let manifest = fs::read_to_string(path).ok()?;
+    manifest
+        .lines()
+        .find(|line| line.starts_with("beverage_remote = "))
+        .and_then(|line| quoted_after(line, "version = "))
+}
+
+fn quoted_after(line: &str, prefix: &str) -> Option<String> {
+    let rest = &line[line.find(prefix)? + prefix.len()..];
+    let rest = rest.strip_prefix('"')?;
+    Some(rest[..rest.find('"')?].to_owned())
+}
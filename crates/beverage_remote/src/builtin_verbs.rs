@@ -0,0 +1,1207 @@
+// from pcwalton's bevy/brp branch
+
+//! Built-in verbs for the Bevy Remote Protocol.
+
+use std::any::TypeId;
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::asset::Handle;
+use bevy::ecs::{
+    component::ComponentId,
+    entity::Entity,
+    query::{ QueryBuilder, With },
+    reflect::{ AppTypeRegistry, ReflectComponent },
+    system::{ In, Res },
+    world::{ EntityRef, EntityWorldMut, FilteredEntityRef, World },
+};
+use bevy::hierarchy::{ BuildWorldChildren as _, Children, Parent };
+use bevy::reflect::{
+    serde::{ ReflectSerializer, TypedReflectDeserializer },
+    Reflect,
+    TypeRegistration,
+    TypeRegistry,
+};
+use bevy::scene::{ DynamicScene, DynamicSceneBuilder };
+use bevy::time::{ Real, Time };
+use bevy::utils::{ get_short_name, prelude::default, HashMap, HashSet };
+use bevy::window::Window;
+use schemars::JsonSchema;
+use serde::de::DeserializeSeed as _;
+use serde::{ Deserialize, Serialize };
+use serde_json::Value;
+
+use super::{ RemoteClient, RemotePendingRequests, RemotePoll, RemoteVerbPoll, RemoteVerbs };
+
+/// `GET`: Retrieves one or more components from the entity with the given
+/// ID.
+///
+/// The server responds with a `BrpResponse::Get`.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpGetRequest {
+    /// The ID of the entity from which components are to be requested.
+    #[schemars(with = "u64")]
+    pub entity: Entity,
+
+    /// The paths of the component types that are to be requested
+    /// from the entity.
+    ///
+    /// A short name like `Transform` is also accepted, as long as only one
+    /// registered component goes by it (see [`resolve_type_path`]).
+    pub components: Vec<String>,
+}
+
+/// `QUERY`: Performs a query over components in the ECS, returning entities
+/// and component values that match.
+///
+/// The server responds with a `BrpResponse::Query`.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpQueryRequest {
+    /// The components to select.
+    pub data: BrpQuery,
+
+    /// An optional filter that specifies which entities to include or
+    /// exclude from the results.
+    #[serde(default)]
+    pub filter: BrpQueryFilter,
+}
+
+/// `SPAWN`: Creates a new entity with the given components and responds
+/// with its ID.
+///
+/// The server responds with a `BrpResponse::Entity`. Servers from before
+/// `DISCOVER` respond with an empty object instead, so a client that needs the
+/// ID should check that it's there.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpSpawnRequest {
+    /// A map from each component's type path to its serialized value.
+    ///
+    /// These components will be added to the entity.
+    ///
+    /// A short name like `Transform` is also accepted, as long as only one
+    /// registered component goes by it (see [`resolve_type_path`]).
+    #[schemars(with = "std::collections::HashMap<String, Value>")]
+    pub components: HashMap<String, Value>,
+}
+
+/// `DESTROY`: Given an ID, despawns the entity with that ID.
+///
+/// The server responds with a `BrpResponse::Ok`.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpDestroyRequest {
+    /// The ID of the entity to despawn.
+    #[schemars(with = "u64")]
+    pub entity: Entity,
+}
+
+/// `REMOVE`: Deletes one or more components from an entity.
+///
+/// The server responds with a `BrpResponse::Ok`.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpRemoveRequest {
+    /// The ID of the entity from which components are to be removed.
+    #[schemars(with = "u64")]
+    pub entity: Entity,
+
+    /// The paths of the component types that are to be removed from
+    /// the entity.
+    ///
+    /// A short name like `Transform` is also accepted, as long as only one
+    /// registered component goes by it (see [`resolve_type_path`]).
+    pub components: Vec<String>,
+}
+
+/// `INSERT`: Adds one or more components to an entity.
+///
+/// The server responds with a `BrpResponse::Ok`.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpInsertRequest {
+    /// The ID of the entity that components are to be added to.
+    #[schemars(with = "u64")]
+    pub entity: Entity,
+
+    /// A map from each component's type path to its serialized value.
+    ///
+    /// These components will be added to the entity.
+    ///
+    /// A short name like `Transform` is also accepted, as long as only one
+    /// registered component goes by it (see [`resolve_type_path`]).
+    #[schemars(with = "std::collections::HashMap<String, Value>")]
+    pub components: HashMap<String, Value>,
+}
+
+/// `REPARENT`: Changes the parent of an entity.
+///
+/// The server responds with a `BrpResponse::Ok`.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpReparentRequest {
+    /// The IDs of the entities that are to become the new children of the
+    /// `parent`.
+    #[schemars(with = "Vec<u64>")]
+    pub entities: Vec<Entity>,
+
+    /// The IDs of the entity that will become the new parent of the
+    /// `entities`.
+    ///
+    /// If this is `None`, then the entities are removed from all parents.
+    #[schemars(with = "Option<u64>")]
+    pub parent: Option<Entity>,
+}
+
+/// `LIST`: Returns a list of all type names of registered components in the
+/// system, or those on an entity.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpListRequest {
+    /// The entity to query.
+    ///
+    /// If not specified, this request returns the names of all registered
+    /// components.
+    #[schemars(with = "Option<u64>")]
+    pub entity: Option<Entity>,
+}
+
+/// `AWAIT`: Waits until the entity with the given ID has all of the given
+/// components, then retrieves them like `GET`.
+///
+/// This is a long-running verb: the server doesn't respond until the
+/// components are present. It can be checked on with `POLL` and abandoned with
+/// `CANCEL`.
+///
+/// The server responds with a `BrpResponse::Get`.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpAwaitRequest {
+    /// The ID of the entity to wait for.
+    #[schemars(with = "u64")]
+    pub entity: Entity,
+
+    /// The paths of the component types that must all be present on
+    /// the entity.
+    pub components: Vec<String>,
+}
+
+/// `POLL`: Checks on a long-running request that hasn't responded yet.
+///
+/// Only the requests of the client asking are seen; see [`super::CLIENT_HEADER`].
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpPollRequest {
+    /// The `id` that was sent with the long-running request.
+    pub id: Value,
+}
+
+/// `CANCEL`: Abandons a long-running request that hasn't responded yet.
+///
+/// The cancelled request responds with an error. The server responds to this
+/// request with a `BrpResponse::Ok`. Only the client that sent a request can
+/// cancel it.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpCancelRequest {
+    /// The `id` that was sent with the long-running request.
+    pub id: Value,
+}
+
+/// `DISCOVER`: Describes every verb known to the server, along with the
+/// schemas of its parameters and results.
+///
+/// The server responds with a `BrpResponse::Discover`. The same document is
+/// also served to plain HTTP `GET` requests.
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
+pub struct BrpDiscoverRequest {}
+
+/// `PING`: Checks that the server is still there.
+///
+/// Clients send this periodically as a heartbeat. The server responds with a
+/// `BrpResponse::Ping`.
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
+pub struct BrpPingRequest {}
+
+/// `SCENE`: Saves the entities of the world as a scene.
+///
+/// If the app spawned a scene from a file, only the entities under it are
+/// saved, as they would be spawned under it again. Otherwise the whole world
+/// is, except for its windows. Either way, components that can't be serialized
+/// are left out, along with entities that are left with none. The server
+/// responds with a `BrpResponse::Scene`.
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
+pub struct BrpSceneRequest {}
+
+/// Describes the data that is to be fetched in a query.
+///
+/// Components can be given by short name when only one registered component
+/// goes by it (see [`resolve_type_path`]). The rows of the response are always
+/// keyed by full type path.
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
+pub struct BrpQuery {
+    /// The type path of each component that is to be
+    /// fetched.
+    #[serde(default)]
+    pub components: Vec<String>,
+
+    /// The type path of each component that is to be
+    /// optionally fetched.
+    #[serde(default)]
+    pub option: Vec<String>,
+
+    /// The type path of each component that is to be checked
+    /// for presence.
+    #[serde(default)]
+    pub has: Vec<String>,
+}
+
+/// Additional constraints that can be placed on a query to include or exclude
+/// certain entities.
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
+pub struct BrpQueryFilter {
+    /// The type path of each component that may not be
+    /// present on the entity for it to be included in the results.
+    #[serde(default)]
+    pub without: Vec<String>,
+
+    /// The type path of each component that must be present
+    /// on the entity for it to be included in the results.
+    #[serde(default)]
+    pub with: Vec<String>,
+}
+
+/// A response from the world to the client that specifies a single entity.
+///
+/// This is sent in response to `SPAWN`.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpEntityResponse {
+    /// The ID of the entity in question.
+    #[schemars(with = "u64")]
+    pub entity: Entity,
+}
+
+/// An empty response, for verbs that only report success or failure.
+///
+/// This is sent in response to `INSERT`, `REMOVE`, `DESTROY`, and `REPARENT`.
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
+pub struct BrpOkResponse {}
+
+/// The response to a `GET` request.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpGetResponse {
+    /// The ID of the entity for which components were requested.
+    #[schemars(with = "u64")]
+    pub entity: Entity,
+
+    /// The values of the requested components.
+    #[schemars(with = "std::collections::HashMap<String, Value>")]
+    pub components: HashMap<String, Value>,
+}
+
+/// The response to a `LIST` request.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpListResponse {
+    /// The ID of the entity for which component names were requested, if
+    /// present.
+    ///
+    /// If this is `None`, then `components` contains the name of all
+    /// reflectable components known to the system.
+    #[schemars(with = "Option<u64>")]
+    pub entity: Option<Entity>,
+
+    /// The full type names of the registered components.
+    pub components: Vec<String>,
+}
+
+/// The response to a `QUERY` request.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpQueryResponse {
+    /// All results of the query: the entities and the requested components.
+    pub rows: Vec<BrpQueryRow>,
+}
+
+/// One query match result: a single entity paired with the requested components.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpQueryRow {
+    /// The ID of the entity that matched.
+    #[schemars(with = "u64")]
+    pub entity: Entity,
+
+    /// The serialized values of the requested components.
+    #[serde(flatten)]
+    #[schemars(with = "std::collections::HashMap<String, Value>")]
+    pub components: HashMap<String, Value>,
+}
+
+/// The response to a `POLL` request.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpPollResponse {
+    /// The `id` of the long-running request.
+    pub id: Value,
+
+    /// Whether the request is still waiting to respond.
+    pub pending: bool,
+
+    /// The verb of the request, if it is still pending.
+    pub verb: Option<String>,
+}
+
+/// The response to a `PING` request.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpPingResponse {
+    /// How long the server app has been running, in seconds.
+    pub elapsed: f64,
+}
+
+/// The response to a `SCENE` request.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpSceneResponse {
+    /// The scene in RON, the way a `DynamicScene` is loaded from a `.scn.ron` file.
+    pub ron: String,
+}
+
+/// The response to a `DISCOVER` request.
+///
+/// This is laid out like an [OpenRPC](https://spec.open-rpc.org) document, so
+/// that existing tooling can make sense of it.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpDiscoverResponse {
+    /// The version of the OpenRPC specification that the document follows.
+    pub openrpc: String,
+
+    /// Information about the server.
+    pub info: BrpDiscoverInfo,
+
+    /// A description of each verb, sorted by name.
+    pub methods: Vec<BrpVerbDescription>,
+}
+
+/// Information about the server that responded to a `DISCOVER` request.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpDiscoverInfo {
+    /// The name of the protocol.
+    pub title: String,
+
+    /// The version of the crate that implements the server.
+    pub version: String,
+}
+
+/// A single verb in the response to a `DISCOVER` request.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpVerbDescription {
+    /// The verb, as it is sent in the `request` field.
+    pub name: String,
+
+    /// The documentation of the verb, if there is any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// The `params` of the verb.
+    ///
+    /// This is empty if the verb takes raw JSON.
+    pub params: Vec<BrpContentDescriptor>,
+
+    /// The value the verb responds with, if its type is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<BrpContentDescriptor>,
+
+    /// Whether the verb is long-running, i.e. whether it can be checked on with
+    /// `POLL` and `CANCEL`.
+    #[serde(rename = "x-long-running")]
+    pub long_running: bool,
+}
+
+/// A named JSON schema in the response to a `DISCOVER` request.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BrpContentDescriptor {
+    /// The name of the value.
+    pub name: String,
+
+    /// The JSON schema of the value.
+    pub schema: Value,
+}
+
+/// Handles a `GET` request coming from a client.
+pub fn process_remote_get_request(
+    In(request): In<Value>,
+    world: &mut World
+) -> AnyhowResult<Value> {
+    let BrpGetRequest { entity, components } = serde_json::from_value(request)?;
+
+    let app_type_registry = world.resource::<AppTypeRegistry>();
+    let type_registry = app_type_registry.read();
+    let Some(entity_ref) = world.get_entity(entity) else {
+        return Err(anyhow!("Entity {:?} didn't exist", entity));
+    };
+
+    let mut serialized_components_map = HashMap::new();
+
+    for component_path in components {
+        let reflect_component = get_reflect_component(&type_registry, &component_path)?;
+
+        let Some(reflected) = reflect_component.reflect(entity_ref) else {
+            return Err(anyhow!("Entity {:?} has no component `{}`", entity, component_path));
+        };
+
+        let reflect_serializer = ReflectSerializer::new(reflected, &type_registry);
+        let Value::Object(serialized_object) = serde_json::to_value(&reflect_serializer)? else {
+            return Err(
+                anyhow!("Component didn't serialize into a JSON object: `{}`", component_path)
+            );
+        };
+
+        serialized_components_map.extend(serialized_object.into_iter());
+    }
+
+    Ok(
+        serde_json::to_value(BrpGetResponse {
+            entity,
+            components: serialized_components_map,
+        })?
+    )
+}
+
+/// Handles a `QUERY` request coming from a client.
+pub fn process_remote_query_request(
+    In(request): In<Value>,
+    world: &mut World
+) -> AnyhowResult<Value> {
+    let BrpQueryRequest {
+        data: BrpQuery { components, option, has },
+        filter: BrpQueryFilter { without, with },
+    } = serde_json::from_value(request)?;
+
+    let app_type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = app_type_registry.read();
+
+    let components = get_component_ids(&type_registry, world, components)?;
+    let option = get_used_component_ids(&type_registry, world, option)?;
+    let has = get_used_component_ids(&type_registry, world, has)?;
+    let without = get_used_component_ids(&type_registry, world, without)?;
+    let with = get_component_ids(&type_registry, world, with)?;
+
+    let mut query = QueryBuilder::<FilteredEntityRef>::new(world);
+    for (_, component) in &components {
+        query.ref_id(*component);
+    }
+    for (_, option) in &option {
+        query.optional(|query| {
+            query.ref_id(*option);
+        });
+    }
+    for (_, has) in has {
+        query.optional(|query| {
+            query.ref_id(has);
+        });
+    }
+    for (_, without) in without {
+        query.without_id(without);
+    }
+    for (_, with) in with {
+        query.with_id(with);
+    }
+
+    let mut rows = vec![];
+    let mut query = query.build();
+    for row in query.iter(world) {
+        let mut components_map = serialize_components(
+            row.clone(),
+            components.iter().map(|(type_id, _)| *type_id),
+            &type_registry
+        )?;
+
+        // optional components are only included when the entity has them
+        let present = option
+            .iter()
+            .filter(|(_, component_id)| row.contains_id(*component_id))
+            .map(|(type_id, _)| *type_id);
+        components_map.extend(serialize_components(row.clone(), present, &type_registry)?);
+
+        rows.push(BrpQueryRow {
+            entity: row.id(),
+            components: components_map,
+        });
+    }
+
+    Ok(serde_json::to_value(BrpQueryResponse { rows })?)
+}
+
+/// Handles a `SPAWN` request coming from a client.
+pub fn process_remote_spawn_request(
+    In(request): In<Value>,
+    world: &mut World
+) -> AnyhowResult<Value> {
+    let BrpSpawnRequest { components } = serde_json::from_value(request)?;
+
+    let app_type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = app_type_registry.read();
+
+    let reflect_components = deserialize_components(&type_registry, components)?;
+    let entity_world_mut = world.spawn_empty();
+    let entity = entity_world_mut.id();
+    insert_reflected_components(&type_registry, entity_world_mut, reflect_components)?;
+
+    Ok(serde_json::to_value(BrpEntityResponse { entity })?)
+}
+
+/// Handles an `INSERT` request (insert components) coming from a client.
+pub fn process_remote_insert_request(
+    In(request): In<Value>,
+    world: &mut World
+) -> AnyhowResult<Value> {
+    let BrpInsertRequest { entity, components } = serde_json::from_value(request)?;
+
+    let app_type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = app_type_registry.read();
+
+    let reflect_components = deserialize_components(&type_registry, components)?;
+
+    insert_reflected_components(
+        &type_registry,
+        get_entity_mut(world, entity)?,
+        reflect_components
+    )?;
+
+    Ok(Value::Object(default()))
+}
+
+/// Handles a `REMOVE` request (remove components) coming from a client.
+pub fn process_remote_remove_request(
+    In(request): In<Value>,
+    world: &mut World
+) -> AnyhowResult<Value> {
+    let BrpRemoveRequest { entity, components } = serde_json::from_value(request)?;
+
+    let app_type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = app_type_registry.read();
+
+    let component_ids = get_component_ids(&type_registry, world, components)?;
+
+    // Remove the components.
+    let mut entity_world_mut = get_entity_mut(world, entity)?;
+    for (_, component_id) in component_ids {
+        entity_world_mut.remove_by_id(component_id);
+    }
+
+    Ok(Value::Object(default()))
+}
+
+/// Handles a `DESTROY` (despawn entity) request coming from a client.
+pub fn process_remote_destroy_request(
+    In(request): In<Value>,
+    world: &mut World
+) -> AnyhowResult<Value> {
+    let BrpDestroyRequest { entity } = serde_json::from_value(request)?;
+
+    get_entity_mut(world, entity)?.despawn();
+
+    Ok(Value::Object(default()))
+}
+
+/// Handles a `REPARENT` request coming from a client.
+pub fn process_remote_reparent_request(
+    In(request): In<Value>,
+    world: &mut World
+) -> AnyhowResult<Value> {
+    let BrpReparentRequest { entities, parent: maybe_parent } = serde_json::from_value(request)?;
+
+    match maybe_parent {
+        // If `Some`, reparent the entities.
+        Some(parent) => {
+            let mut parent_commands = get_entity_mut(world, parent)?;
+            for entity in entities {
+                if entity == parent {
+                    return Err(anyhow!("Can't parent an object to itself"));
+                }
+                parent_commands.add_child(entity);
+            }
+        }
+
+        // If `None`, remove the entities from their parents.
+        None => {
+            for entity in entities {
+                get_entity_mut(world, entity)?.remove_parent();
+            }
+        }
+    }
+
+    Ok(Value::Object(default()))
+}
+
+/// Handles a `LIST` request (list all components) coming from a client.
+pub fn process_remote_list_request(
+    In(request): In<Value>,
+    world: &mut World
+) -> AnyhowResult<Value> {
+    let BrpListRequest { entity } = serde_json::from_value(request)?;
+
+    let app_type_registry = world.resource::<AppTypeRegistry>();
+    let type_registry = app_type_registry.read();
+
+    let mut result = vec![];
+
+    match entity {
+        Some(entity) => {
+            let entity = get_entity(world, entity)?;
+            for component_id in entity.archetype().components() {
+                let Some(component_info) = world.components().get_info(component_id) else {
+                    continue;
+                };
+                result.push(component_info.name().to_owned());
+            }
+        }
+
+        None => {
+            for registered_type in type_registry.iter() {
+                if registered_type.data::<ReflectComponent>().is_some() {
+                    result.push(registered_type.type_info().type_path().to_owned());
+                }
+            }
+        }
+    }
+
+    // Sort both for cleanliness and to reduce the risk that clients start
+    // accidentally start depending on the order.
+    result.sort();
+
+    Ok(
+        serde_json::to_value(BrpListResponse {
+            entity,
+            components: result,
+        })?
+    )
+}
+
+/// Polls an `AWAIT` request coming from a client.
+pub fn process_remote_await_request(
+    In(poll): In<RemoteVerbPoll>,
+    world: &mut World
+) -> AnyhowResult<RemotePoll> {
+    // nothing to clean up
+    if poll.cancelled {
+        return Ok(RemotePoll::Ready(Value::Null));
+    }
+
+    let BrpAwaitRequest { entity, components } = serde_json::from_value(poll.params)?;
+
+    {
+        let app_type_registry = world.resource::<AppTypeRegistry>();
+        let type_registry = app_type_registry.read();
+        let entity_ref = get_entity(world, entity)?;
+
+        for component_path in &components {
+            let reflect_component = get_reflect_component(&type_registry, component_path)?;
+            if reflect_component.reflect(entity_ref).is_none() {
+                return Ok(RemotePoll::Pending(Value::Null));
+            }
+        }
+    }
+
+    let request = serde_json::to_value(BrpGetRequest { entity, components })?;
+    Ok(RemotePoll::Ready(process_remote_get_request(In(request), world)?))
+}
+
+/// Handles a `POLL` request coming from a client.
+pub fn process_remote_poll_request(
+    In(request): In<BrpPollRequest>,
+    client: Res<RemoteClient>,
+    pending: Res<RemotePendingRequests>
+) -> AnyhowResult<BrpPollResponse> {
+    let BrpPollRequest { id } = request;
+    let verb = pending.get(&client.0, &id).map(|request| request.verb.clone());
+
+    Ok(BrpPollResponse {
+        id,
+        pending: verb.is_some(),
+        verb,
+    })
+}
+
+/// Handles a `CANCEL` request coming from a client.
+pub fn process_remote_cancel_request(
+    In(request): In<BrpCancelRequest>,
+    world: &mut World
+) -> AnyhowResult<()> {
+    let BrpCancelRequest { id } = request;
+
+    // only the client that sent a request gets to cancel it
+    let client = world.resource::<RemoteClient>().0.clone();
+    let Some(pending) = world.resource_mut::<RemotePendingRequests>().remove(&client, &id) else {
+        return Err(anyhow!("Request {} isn't pending", id));
+    };
+    pending.cancel(world);
+
+    Ok(())
+}
+
+/// Handles a `DISCOVER` request coming from a client.
+pub fn process_remote_discover_request(
+    In(_): In<BrpDiscoverRequest>,
+    verbs: Res<RemoteVerbs>
+) -> AnyhowResult<BrpDiscoverResponse> {
+    let mut names: Vec<&String> = verbs.names().collect();
+    names.sort();
+
+    let methods = names
+        .into_iter()
+        .map(|name| {
+            let shape = verbs.shape(name);
+            BrpVerbDescription {
+                name: name.clone(),
+                description: shape.and_then(|shape| shape.description.clone()),
+                params: shape
+                    .map(|shape| {
+                        vec![BrpContentDescriptor {
+                            name: "params".to_owned(),
+                            schema: shape.params.clone(),
+                        }]
+                    })
+                    .unwrap_or_default(),
+                result: shape.map(|shape| BrpContentDescriptor {
+                    name: "result".to_owned(),
+                    schema: shape.result.clone(),
+                }),
+                long_running: verbs.get_async(name).is_some(),
+            }
+        })
+        .collect();
+
+    Ok(BrpDiscoverResponse {
+        openrpc: "1.2.6".to_owned(),
+        info: BrpDiscoverInfo {
+            title: "Bevy Remote Protocol".to_owned(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+        },
+        methods,
+    })
+}
+
+/// Handles a `PING` request coming from a client.
+pub fn process_remote_ping_request(
+    In(_): In<BrpPingRequest>,
+    time: Option<Res<Time<Real>>>
+) -> AnyhowResult<BrpPingResponse> {
+    Ok(BrpPingResponse {
+        elapsed: time.map(|time| time.elapsed_seconds_f64()).unwrap_or_default(),
+    })
+}
+
+/// Handles a `SCENE` request coming from a client.
+pub fn process_remote_scene_request(
+    In(_): In<BrpSceneRequest>,
+    world: &mut World
+) -> AnyhowResult<BrpSceneResponse> {
+    // (a game that spawned its scene from a file has only what is under it saved, so what it makes
+    // in code, like its cameras, isn't saved along with it and spawned twice the next time)
+    let mut q_roots = world.query_filtered::<Entity, With<Handle<DynamicScene>>>();
+    let roots: Vec<Entity> = q_roots.iter(world).collect();
+    let entities = if roots.is_empty() {
+        let mut q_windows = world.query_filtered::<Entity, With<Window>>();
+        let windows: HashSet<Entity> = q_windows.iter(world).collect();
+        world
+            .iter_entities()
+            .map(|entity_ref| entity_ref.id())
+            .filter(|entity| !windows.contains(entity))
+            .collect::<Vec<_>>()
+    } else {
+        descendants(world, &roots)
+    };
+
+    let mut scene = DynamicSceneBuilder::from_world(world)
+        .deny_all_resources()
+        .extract_entities(entities.into_iter())
+        .build();
+
+    // (components that can't be written out, like handles to assets made in code, are left out)
+    let type_registry = world.resource::<AppTypeRegistry>().read();
+    for entity in &mut scene.entities {
+        entity.components.retain(|component| {
+            serde_json::to_value(ReflectSerializer::new(&**component, &type_registry)).is_ok()
+        });
+    }
+    scene.entities.retain(|entity| !entity.components.is_empty());
+
+    // (the top of the saved scene is put back under the root it is spawned with)
+    let parent = Some(TypeId::of::<Parent>());
+    for entity in &mut scene.entities {
+        let under_root = world.get::<Parent>(entity.entity).map(Parent::get);
+        if under_root.is_some_and(|under_root| roots.contains(&under_root)) {
+            entity.components.retain(|component| {
+                component.get_represented_type_info().map(|info| info.type_id()) != parent
+            });
+        }
+    }
+
+    let ron = scene
+        .serialize(&type_registry)
+        .map_err(|error| anyhow!("Could not serialize the scene: {}", error))?;
+
+    Ok(BrpSceneResponse { ron })
+}
+
+/// Returns everything under the given entities, but not the entities
+/// themselves.
+fn descendants(world: &World, roots: &[Entity]) -> Vec<Entity> {
+    let mut descendants = vec![];
+    let mut stack = roots.to_vec();
+    while let Some(entity) = stack.pop() {
+        let Some(children) = world.get::<Children>(entity) else {
+            continue;
+        };
+        descendants.extend(children.iter().copied());
+        stack.extend(children.iter().copied());
+    }
+    descendants
+}
+
+/// Immutably retrieves an entity from the [`World`], returning an error if the
+/// entity isn't present.
+fn get_entity(world: &World, entity: Entity) -> AnyhowResult<EntityRef<'_>> {
+    world.get_entity(entity).ok_or_else(|| anyhow!("Entity {:?} not found", entity))
+}
+
+/// Mutably retrieves an entity from the [`World`], returning an error if the
+/// entity isn't present.
+fn get_entity_mut(world: &mut World, entity: Entity) -> AnyhowResult<EntityWorldMut<'_>> {
+    world.get_entity_mut(entity).ok_or_else(|| anyhow!("Entity {:?} not found", entity))
+}
+
+/// Returns the [`TypeId`] and [`ComponentId`] of the components with the given
+/// path names (full, or short if unambiguous).
+fn get_component_ids(
+    type_registry: &TypeRegistry,
+    world: &World,
+    component_paths: Vec<String>
+) -> AnyhowResult<Vec<(TypeId, ComponentId)>> {
+    let mut component_ids = vec![];
+
+    for component_path in component_paths {
+        let type_id = get_component_type_registration(type_registry, &component_path)?.type_id();
+        let Some(component_id) = world.components().get_id(type_id) else {
+            return Err(anyhow!("Component `{}` isn't used in the world", component_path));
+        };
+
+        component_ids.push((type_id, component_id));
+    }
+
+    Ok(component_ids)
+}
+
+/// Like [`get_component_ids`], but leaves out the components that no entity
+/// has ever had, for the parts of a query that an entity doesn't need to have.
+fn get_used_component_ids(
+    type_registry: &TypeRegistry,
+    world: &World,
+    component_paths: Vec<String>
+) -> AnyhowResult<Vec<(TypeId, ComponentId)>> {
+    let mut component_ids = vec![];
+
+    for component_path in component_paths {
+        let type_id = get_component_type_registration(type_registry, &component_path)?.type_id();
+        if let Some(component_id) = world.components().get_id(type_id) {
+            component_ids.push((type_id, component_id));
+        }
+    }
+
+    Ok(component_ids)
+}
+
+fn serialize_components(
+    entity_ref: FilteredEntityRef,
+    component_type_ids: impl Iterator<Item = TypeId>,
+    type_registry: &TypeRegistry
+) -> AnyhowResult<HashMap<String, Value>> {
+    let mut serialized_components_map = HashMap::new();
+
+    for component_type_id in component_type_ids {
+        let Some(type_registration) = type_registry.get(component_type_id) else {
+            return Err(anyhow!("Component `{:?}` isn't registered", component_type_id));
+        };
+
+        let type_path = type_registration.type_info().type_path();
+
+        let Some(reflect_component) = type_registration.data::<ReflectComponent>() else {
+            return Err(anyhow!("Component `{}` isn't reflectable", type_path));
+        };
+
+        let Some(reflected) = reflect_component.reflect(entity_ref.clone()) else {
+            return Err(anyhow!("Entity {:?} has no component `{}`", entity_ref.id(), type_path));
+        };
+
+        let reflect_serializer = ReflectSerializer::new(reflected, type_registry);
+        let Value::Object(serialized_object) = serde_json::to_value(&reflect_serializer)? else {
+            return Err(anyhow!("Component didn't serialize into a JSON object: `{}`", type_path));
+        };
+
+        serialized_components_map.extend(serialized_object.into_iter());
+    }
+
+    Ok(serialized_components_map)
+}
+
+/// Deserializes components keyed by their type paths (full, or short if
+/// unambiguous).
+pub fn deserialize_components(
+    type_registry: &TypeRegistry,
+    components: HashMap<String, Value>
+) -> AnyhowResult<Vec<Box<dyn Reflect>>> {
+    let mut reflect_components = vec![];
+    for (component_path, component) in components {
+        let component_type = get_component_type_registration(type_registry, &component_path)?;
+        match TypedReflectDeserializer::new(component_type, type_registry).deserialize(&component) {
+            Ok(reflected) => {
+                reflect_components.push(reflected);
+            }
+            Err(error) => {
+                return Err(error.into());
+            }
+        }
+    }
+
+    Ok(reflect_components)
+}
+
+/// Inserts deserialized components into an entity.
+pub fn insert_reflected_components(
+    type_registry: &TypeRegistry,
+    mut entity_world_mut: EntityWorldMut,
+    reflect_components: Vec<Box<dyn Reflect>>
+) -> AnyhowResult<()> {
+    for reflected in reflect_components {
+        if let Some(represented_type_info) = reflected.get_represented_type_info() {
+            let represented_type_path = represented_type_info.type_path();
+            let reflect_component = get_reflect_component(type_registry, represented_type_path)?;
+            reflect_component.insert(&mut entity_world_mut, &*reflected, type_registry);
+        } else {
+            return Err(
+                anyhow!(
+                    "Unknown component type (no represented `TypeInfo`): `{}`",
+                    reflected.reflect_type_path()
+                )
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Returns the [`ReflectComponent`] of a component, given its full path or
+/// its short name.
+pub fn get_reflect_component<'a>(
+    type_registry: &'a TypeRegistry,
+    component_path: &str
+) -> AnyhowResult<&'a ReflectComponent> {
+    let component_registration = get_component_type_registration(type_registry, component_path)?;
+    let Some(reflect_component) = component_registration.data::<ReflectComponent>() else {
+        return Err(anyhow!("Type isn't a reflectable component: `{}`", component_path));
+    };
+
+    Ok(reflect_component)
+}
+
+fn get_component_type_registration<'r>(
+    type_registry: &'r TypeRegistry,
+    component_path: &str
+) -> AnyhowResult<&'r TypeRegistration> {
+    if let Some(component_registration) = type_registry.get_with_type_path(component_path) {
+        return Ok(component_registration);
+    }
+
+    // not a full path, so look for a component that goes by that name
+    let component_paths = type_registry
+        .iter()
+        .filter(|registration| registration.data::<ReflectComponent>().is_some())
+        .map(|registration| registration.type_info().type_path());
+    let component_path = resolve_type_path(component_path, component_paths)?;
+    type_registry
+        .get_with_type_path(component_path)
+        .ok_or_else(|| anyhow!("Unknown component type: `{}`", component_path))
+}
+
+/// Returns the full type path of a component registered in the editor, given
+/// its full path or its short name.
+///
+/// Responses are always keyed by full path, so anything comparing them with
+/// paths given by the user should go through this first.
+pub fn component_type_path<'r>(
+    type_registry: &'r TypeRegistry,
+    component_path: &str
+) -> AnyhowResult<&'r str> {
+    Ok(get_component_type_registration(type_registry, component_path)?.type_info().type_path())
+}
+
+/// Reads an entity out of a serialized component that holds one, like
+/// `Parent`, which may or may not have been unwrapped from its newtype.
+pub fn entity_from_json(value: &Value) -> Option<Entity> {
+    let bits = match value {
+        Value::Array(fields) => fields.first()?.as_u64()?,
+        value => value.as_u64()?,
+    };
+    Entity::try_from_bits(bits).ok()
+}
+
+/// Points the entities in a serialized component at others, like when copies of entities that
+/// refer to each other are spawned. An entity is serialized as its bits, which (with a generation
+/// in the upper half) are too large to be taken for anything else in practice.
+pub fn remap_entities_in_json(value: &mut Value, map: &HashMap<Entity, Entity>) {
+    match value {
+        Value::Number(number) => {
+            let mapped = number
+                .as_u64()
+                .and_then(|bits| Entity::try_from_bits(bits).ok())
+                .and_then(|entity| map.get(&entity));
+            if let Some(mapped) = mapped {
+                *value = mapped.to_bits().into();
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                remap_entities_in_json(value, map);
+            }
+        }
+        Value::Object(fields) => {
+            for value in fields.values_mut() {
+                remap_entities_in_json(value, map);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Picks the type path that `name` refers to out of `paths`.
+///
+/// `name` is either one of the full paths, or a short name like `Transform`
+/// (or `Handle<Mesh>`) that exactly one of them ends with. Otherwise the error
+/// lists the candidates, or the closest matches if there are none.
+pub fn resolve_type_path<'a>(
+    name: &str,
+    paths: impl IntoIterator<Item = &'a str>
+) -> AnyhowResult<&'a str> {
+    let paths: Vec<&str> = paths.into_iter().collect();
+    if let Some(path) = paths.iter().find(|path| **path == name) {
+        return Ok(path);
+    }
+
+    let mut candidates: Vec<&str> = paths
+        .iter()
+        .filter(|path| get_short_name(path) == name)
+        .copied()
+        .collect();
+    candidates.sort();
+    match candidates.as_slice() {
+        [path] => {
+            return Ok(path);
+        }
+        [] => {}
+        _ => {
+            return Err(
+                anyhow!(
+                    "Component type `{}` is ambiguous, did you mean one of: {}?",
+                    name,
+                    quoted_list(&candidates)
+                )
+            );
+        }
+    }
+
+    // typos get the closest short names, so `Transfrom` suggests `Transform`
+    let name_lowercase = name.to_lowercase();
+    let threshold = name.len() / 3 + 1;
+    let mut suggestions: Vec<(usize, &str)> = paths
+        .iter()
+        .filter_map(|path| {
+            let short_name = get_short_name(path).to_lowercase();
+            let distance = if short_name == name_lowercase || path.to_lowercase() == name_lowercase {
+                0
+            } else {
+                edit_distance(&short_name, &name_lowercase)
+            };
+            (distance <= threshold).then_some((distance, *path))
+        })
+        .collect();
+    suggestions.sort();
+    suggestions.truncate(3);
+
+    if suggestions.is_empty() {
+        return Err(anyhow!("Unknown component type: `{}`", name));
+    }
+    let suggestions: Vec<&str> = suggestions
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect();
+    Err(
+        anyhow!(
+            "Unknown component type: `{}`, did you mean {}?",
+            name,
+            quoted_list(&suggestions)
+        )
+    )
+}
+
+fn quoted_list(paths: &[&str]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("`{}`", path))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// the number of single-character edits that turn one string into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ prelude::*, reflect::TypeRegistry };
+
+    use super::*;
+
+    // stands in for a game's own component that has the same name as one of Bevy's
+    mod game {
+        use bevy::prelude::*;
+
+        #[derive(Component, Reflect)]
+        #[reflect(Component)]
+        pub struct Transform;
+    }
+
+    const PATHS: [&str; 3] = [
+        "bevy_transform::components::transform::Transform",
+        "bevy_core::name::Name",
+        "bevy_asset::handle::Handle<bevy_render::mesh::mesh::Mesh>",
+    ];
+
+    #[test]
+    fn short_names_resolve_to_full_paths() {
+        assert_eq!(resolve_type_path("Name", PATHS).unwrap(), PATHS[1]);
+        assert_eq!(resolve_type_path(PATHS[0], PATHS).unwrap(), PATHS[0]);
+        assert_eq!(resolve_type_path("Handle<Mesh>", PATHS).unwrap(), PATHS[2]);
+    }
+
+    #[test]
+    fn unresolved_names_suggest_others() {
+        let error = resolve_type_path("Transfrom", PATHS).unwrap_err().to_string();
+        assert!(error.contains(&format!("did you mean `{}`", PATHS[0])), "{}", error);
+
+        let error = resolve_type_path("Velocity", PATHS).unwrap_err().to_string();
+        assert_eq!(error, "Unknown component type: `Velocity`");
+    }
+
+    #[test]
+    fn ambiguous_short_names_list_the_candidates() {
+        let mut type_registry = TypeRegistry::new();
+        type_registry.register::<Name>();
+        type_registry.register::<Transform>();
+        assert_eq!(component_type_path(&type_registry, "Transform").unwrap(), PATHS[0]);
+
+        type_registry.register::<game::Transform>();
+        let error = component_type_path(&type_registry, "Transform").unwrap_err().to_string();
+        assert!(error.contains("is ambiguous"), "{}", error);
+        assert!(error.contains(PATHS[0]) && error.contains("tests::game::Transform"), "{}", error);
+
+        // (full paths are never ambiguous)
+        assert_eq!(component_type_path(&type_registry, PATHS[0]).unwrap(), PATHS[0]);
+    }
+}
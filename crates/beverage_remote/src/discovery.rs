@@ -0,0 +1,257 @@
+//! Lets editors find the Bevy apps running a BRP server on this machine.
+//!
+//! Every server started by [`EditorRemotePlugin`](super::EditorRemotePlugin) writes a small JSON
+//! file to the discovery directory, naming the app, its process ID, the port it listens on and the
+//! version of Bevy it was built with. The file is rewritten every couple of seconds and removed when
+//! the app exits, so an editor only has to list the directory to know which games it can connect
+//! to. Files that haven't been rewritten in a while belong to apps that crashed, and are ignored.
+//!
+//! The directory is `bevy_remote` in the system temp directory, unless the
+//! `BEVY_REMOTE_DISCOVERY_DIR` environment variable says otherwise.
+
+use std::{ fs, path::PathBuf, time::Duration };
+
+use anyhow::Result as AnyhowResult;
+use bevy::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+use super::{ recorder::now_millis, RemoteBevyVersion, RemotePort };
+
+/// The environment variable that overrides the discovery directory.
+pub const DISCOVERY_DIR_VAR: &str = "BEVY_REMOTE_DISCOVERY_DIR";
+
+/// The release of Bevy this crate is built against.
+///
+/// Cargo lets in any patch of it, which only the app knows (see
+/// [`EditorRemotePlugin::with_bevy_version`](crate::EditorRemotePlugin::with_bevy_version)).
+pub const BEVY_VERSION: &str = "0.14";
+
+/// How often a server rewrites its announcement.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How old an announcement can get before its server is presumed dead.
+pub const STALE_AFTER: Duration = Duration::from_secs(10);
+
+/// What a running server tells editors about itself.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BrpAnnouncement {
+    /// The name of the app, which defaults to the name of its executable.
+    pub app: String,
+
+    /// The process ID of the app.
+    pub pid: u32,
+
+    /// The port the server listens on.
+    pub port: u16,
+
+    /// The version of Bevy the app was built with.
+    pub bevy_version: String,
+
+    /// The URL to send requests to.
+    pub url: String,
+
+    /// When the announcement was last written, in milliseconds since the UNIX epoch.
+    pub updated: u64,
+}
+
+impl BrpAnnouncement {
+    /// Describes the announcing app on behalf of the current process.
+    pub fn for_this_process(port: u16, bevy_version: &str) -> Self {
+        Self {
+            app: app_name(),
+            pid: std::process::id(),
+            port,
+            bevy_version: bevy_version.to_owned(),
+            url: format!("http://{}:{}", "127.0.0.1", port),
+            updated: now_millis(),
+        }
+    }
+
+    /// Whether both announcements come from the same server, however recently each was written.
+    pub fn same_server(&self, other: &BrpAnnouncement) -> bool {
+        self.pid == other.pid && self.port == other.port
+    }
+
+    /// Whether the server has stopped rewriting its announcement.
+    pub fn is_stale(&self) -> bool {
+        now_millis().saturating_sub(self.updated) > (STALE_AFTER.as_millis() as u64)
+    }
+
+    fn file_name(&self) -> String {
+        format!("{}-{}.json", self.pid, self.port)
+    }
+}
+
+/// The directory servers announce themselves in.
+pub fn discovery_dir() -> PathBuf {
+    match std::env::var_os(DISCOVERY_DIR_VAR) {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::temp_dir().join("bevy_remote"),
+    }
+}
+
+/// Lists the servers currently running on this machine, ordered by app name.
+///
+/// Announcements that are stale or can't be read are skipped.
+pub fn discover_servers() -> AnyhowResult<Vec<BrpAnnouncement>> {
+    let dir = discovery_dir();
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut servers = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+            continue;
+        }
+
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        match serde_json::from_str::<BrpAnnouncement>(&text) {
+            Ok(announcement) if !announcement.is_stale() => servers.push(announcement),
+            Ok(_) => {}
+            Err(error) => debug!("Ignoring BRP announcement {}: {}", path.display(), error),
+        }
+    }
+
+    servers.sort_by(|a, b| a.app.cmp(&b.app).then(a.pid.cmp(&b.pid)));
+    Ok(servers)
+}
+
+/// The announcement file of the server in this app, which is removed when the app exits.
+#[derive(Resource, Debug)]
+pub struct RemoteAnnouncement {
+    /// What is written to the file.
+    pub announcement: BrpAnnouncement,
+
+    path: PathBuf,
+    timer: Timer,
+}
+
+impl RemoteAnnouncement {
+    fn write(&mut self) -> AnyhowResult<()> {
+        self.announcement.updated = now_millis();
+        fs::create_dir_all(discovery_dir())?;
+
+        // write somewhere else first so editors never read half a file
+        let partial = self.path.with_extension("partial");
+        fs::write(&partial, serde_json::to_vec_pretty(&self.announcement)?)?;
+        fs::rename(partial, &self.path)?;
+        Ok(())
+    }
+}
+
+impl Drop for RemoteAnnouncement {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// announce the server once it is started
+pub(crate) fn announce_server(
+    remote_port: Res<RemotePort>,
+    bevy_version: Res<RemoteBevyVersion>,
+    mut commands: Commands
+) {
+    let announcement = BrpAnnouncement::for_this_process(remote_port.0, &bevy_version.0);
+    let mut remote_announcement = RemoteAnnouncement {
+        path: discovery_dir().join(announcement.file_name()),
+        announcement,
+        timer: Timer::new(ANNOUNCE_INTERVAL, TimerMode::Repeating),
+    };
+
+    match remote_announcement.write() {
+        Ok(()) => {
+            info!("Announcing BRP server at {}", remote_announcement.path.display());
+            commands.insert_resource(remote_announcement);
+        }
+        Err(error) => error!("Can't announce BRP server: {}", error),
+    }
+}
+
+// keep the announcement fresh so editors know we're still here
+pub(crate) fn refresh_announcement(
+    time: Res<Time<Real>>,
+    remote_announcement: Option<ResMut<RemoteAnnouncement>>
+) {
+    let Some(mut remote_announcement) = remote_announcement else {
+        return;
+    };
+
+    if remote_announcement.timer.tick(time.delta()).just_finished() {
+        if let Err(error) = remote_announcement.write() {
+            warn!("Can't refresh BRP announcement: {}", error);
+        }
+    }
+}
+
+// the announcement file won't be dropped with the world if the app exits the hard way
+pub(crate) fn withdraw_announcement(mut exits: EventReader<AppExit>, mut commands: Commands) {
+    if exits.read().next().is_some() {
+        commands.remove_resource::<RemoteAnnouncement>();
+    }
+}
+
+/// The servers found by the last look at the discovery directory.
+#[derive(Resource, Debug)]
+pub struct DiscoveredServers {
+    /// The servers, ordered by app name.
+    pub servers: Vec<BrpAnnouncement>,
+
+    timer: Timer,
+    looked: bool,
+}
+
+impl Default for DiscoveredServers {
+    fn default() -> Self {
+        Self {
+            servers: vec![],
+            timer: Timer::new(ANNOUNCE_INTERVAL, TimerMode::Repeating),
+            looked: false,
+        }
+    }
+}
+
+/// Looks at the discovery directory every so often, for the editor's list of servers.
+pub fn refresh_discovered_servers(
+    time: Res<Time<Real>>,
+    mut discovered: ResMut<DiscoveredServers>
+) {
+    // ticking the timer alone shouldn't count as a change
+    let quiet = discovered.bypass_change_detection();
+    let due = quiet.timer.tick(time.delta()).just_finished() || !quiet.looked;
+    if !due {
+        return;
+    }
+    quiet.looked = true;
+
+    let servers = match discover_servers() {
+        Ok(servers) => servers,
+        Err(error) => {
+            warn!("Can't look for BRP servers: {}", error);
+            return;
+        }
+    };
+
+    // only mark the resource changed when a server came or went, so lists don't flicker
+    let same = servers.len() == discovered.servers.len() &&
+        servers
+            .iter()
+            .zip(&discovered.servers)
+            .all(|(a, b)| a.same_server(b));
+    if same {
+        discovered.bypass_change_detection().servers = servers;
+    } else {
+        discovered.servers = servers;
+    }
+}
+
+// the name of the running executable, without any extension
+fn app_name() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "bevy".to_owned())
+}
@@ -0,0 +1,115 @@
+//! Wire encodings for Bevy Remote Protocol payloads.
+//!
+//! JSON is the default and is always understood. Clients that send a lot of
+//! data, like a whole selection of transforms being dragged around, can ask for
+//! a compact binary encoding instead by setting the `Content-Type` header of
+//! the request. The response comes back in the encoding named by the `Accept`
+//! header, or in the same encoding as the request if there isn't one.
+//!
+//! Only the envelope changes: components are still serialized with the same
+//! reflection serializers, so the layout of the data is identical whatever the
+//! encoding.
+
+use anyhow::Result as AnyhowResult;
+use serde::{ de::DeserializeOwned, Serialize, Serializer };
+use serde_json::Value;
+
+/// How the body of a request or response is encoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BrpEncoding {
+    /// Plain JSON (`application/json`).
+    #[default]
+    Json,
+
+    /// MessagePack (`application/msgpack`).
+    MessagePack,
+
+    /// CBOR (`application/cbor`).
+    Cbor,
+}
+
+impl BrpEncoding {
+    /// The MIME type that identifies this encoding in HTTP headers.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            BrpEncoding::Json => "application/json",
+            BrpEncoding::MessagePack => "application/msgpack",
+            BrpEncoding::Cbor => "application/cbor",
+        }
+    }
+
+    /// Parses the value of a `Content-Type` header.
+    ///
+    /// Returns `None` if the MIME type isn't one we know how to encode.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        // ignore parameters like `; charset=utf-8`
+        let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+
+        match mime.as_str() {
+            "application/json" => Some(BrpEncoding::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(BrpEncoding::MessagePack)
+            }
+            "application/cbor" => Some(BrpEncoding::Cbor),
+            _ => None,
+        }
+    }
+
+    /// Parses the value of an `Accept` header, returning the first encoding we know.
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        accept.split(',').find_map(BrpEncoding::from_content_type)
+    }
+
+    /// Serializes a value with this encoding.
+    pub fn encode<T: Serialize + ?Sized>(&self, value: &T) -> AnyhowResult<Vec<u8>> {
+        if *self == BrpEncoding::Json {
+            return Ok(serde_json::to_vec(value)?);
+        }
+
+        let value = serde_json::to_value(value)?;
+        Ok(match self {
+            // structs go out as maps so the other end can decode them without the Rust types
+            BrpEncoding::MessagePack => rmp_serde::to_vec_named(&Narrowed(&value))?,
+            _ => {
+                let mut bytes = vec![];
+                ciborium::into_writer(&Narrowed(&value), &mut bytes)?;
+                bytes
+            }
+        })
+    }
+
+    /// Deserializes a value with this encoding.
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> AnyhowResult<T> {
+        Ok(match self {
+            BrpEncoding::Json => serde_json::from_slice(bytes)?,
+            BrpEncoding::MessagePack => rmp_serde::from_slice(bytes)?,
+            BrpEncoding::Cbor => ciborium::from_reader(bytes)?,
+        })
+    }
+}
+
+/// Serializes a JSON value, narrowing floats to `f32` wherever that loses nothing.
+///
+/// Almost every float in a component is an `f32`, but they are widened to `f64`
+/// on their way through `serde_json::Value`. JSON doesn't care, but binary
+/// encodings would pay for it in bytes.
+struct Narrowed<'a>(&'a Value);
+
+impl Serialize for Narrowed<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::Number(number) =>
+                match number.as_f64() {
+                    Some(float) if number.is_f64() && ((float as f32) as f64) == float => {
+                        serializer.serialize_f32(float as f32)
+                    }
+                    _ => number.serialize(serializer),
+                }
+            Value::Array(array) => serializer.collect_seq(array.iter().map(Narrowed)),
+            Value::Object(map) => {
+                serializer.collect_map(map.iter().map(|(key, value)| (key, Narrowed(value))))
+            }
+            value => value.serialize(serializer),
+        }
+    }
+}
@@ -0,0 +1,1025 @@
+// from pcwalton's bevy/brp branch
+
+//! An implementation of the Bevy Remote Protocol over HTTP and JSON, to allow
+//! for remote control of a Bevy app.
+//!
+//! This is the part of the beverage editor that games depend on. The editor's
+//! own client, which connects to games like these, lives in the `beverage`
+//! crate.
+//!
+//! Adding the [`RemotePlugin`] to your [`App`] causes Bevy to accept
+//! connections over HTTP (by default, on port 15702) while your app is running.
+//! These *remote clients* can inspect and alter the state of the
+//! entity-component system. Clients are expected to `POST` JSON requests to the
+//! root URL; see the `client` example for a trivial example of use.
+//!
+//! ## Requests
+//!
+//! A typical client request might look like this:
+//!
+//! ```json
+//! {
+//!     "request": "GET",
+//!     "id": 0,
+//!     "params": {
+//!         "data": {
+//!             "entity": 4294967298,
+//!             "components": [
+//!                 "bevy_transform::components::transform::Transform"
+//!             ]
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! The `id`, `request`, and `params` fields are all required:
+//!
+//! * `id` is arbitrary JSON data. The server ignores its contents, and the
+//!   client may use it for any purpose.  It will be copied via serialization
+//!   and deserialization (so object property order, etc. can't be relied upon
+//!   to be identical) and sent back to the client as part of the response.
+//!   The one exception is long-running verbs like `AWAIT`: while such a request
+//!   is pending, its `id` is how `POLL` and `CANCEL` refer to it, so it should
+//!   be unique among the pending requests of that client. Clients are told
+//!   apart by the [`CLIENT_HEADER`], or by their address if they don't send it.
+//!
+//! * `request` is a string that specifies one of the possible [`BrpRequest`]
+//!   variants: `QUERY`, `GET`, `INSERT`, etc. It's case-sensitive and must be in
+//!   all caps.
+//!
+//! * `params` is parameter data specific to the request.
+//!
+//! For more information, see the documentation for [`BrpRequest`].
+//! [`BrpRequest`] is serialized to JSON via `serde`, so [the `serde`
+//! documentation] may be useful to clarify the correspondence between the Rust
+//! structure and the JSON format.
+//!
+//! ## Responses
+//!
+//! A response from the server to the client might look like this:
+//!
+//! ```json
+//! {
+//!     "status": "OK",
+//!     "id": 0,
+//!     "components": {
+//!         "bevy_transform::components::transform::Transform": {
+//!             "rotation": { "x": 0.0, "y": 0.0, "z": 0.0, "w": 1.0 },
+//!             "scale": { "x": 1.0, "y": 1.0, "z": 1.0 },
+//!             "translation": { "x": 0.0, "y": 0.5, "z": 0.0 }
+//!         }
+//!     },
+//!     "entity": 4294967298
+//! }
+//! ```
+//!
+//! The `status` and `id` fields will always be present:
+//!
+//! * `id` is the arbitrary JSON data that was sent as part of the request. It
+//!   will be identical to the `id` data sent during the request, modulo
+//!   serialization and deserialization.
+//!
+//! * `status` will be either the string `"OK"` or `"ERROR"`, reflecting whether
+//!   the request succeeded.
+//!
+//! TODO: Fill in more here.
+//!
+//! ## Encodings
+//!
+//! Requests and responses are JSON by default. A client may instead send
+//! MessagePack or CBOR by setting the `Content-Type` header, and may pick the
+//! encoding of the response with the `Accept` header; see [`BrpEncoding`].
+//!
+//! ## Discovery
+//!
+//! A `DISCOVER` request, or a plain `GET` of the root URL, returns a document
+//! describing every registered verb along with the JSON schemas of its
+//! parameters and results. The layout follows [OpenRPC], so generic tooling
+//! can generate calls against the verbs of any app.
+//!
+//! ## Finding servers
+//!
+//! Unless told otherwise, the server announces itself to editors running on the
+//! same machine by writing a file to a well-known directory; see [`discovery`].
+//!
+//! [the `serde` documentation]: https://serde.rs/
+//! [OpenRPC]: https://spec.open-rpc.org
+
+use std::net::{ SocketAddr, TcpListener, TcpStream };
+use std::path::PathBuf;
+use std::sync::{ Arc, Mutex };
+
+use bevy::{ ecs::system::SystemId, prelude::*, tasks::IoTaskPool, utils::HashMap };
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use http_body_util::{ BodyExt as _, Full };
+use hyper::{
+    body::{ Bytes, Incoming },
+    header,
+    server::conn::http1,
+    service,
+    Method,
+    Request,
+    Response,
+};
+use schemars::{ schema_for, JsonSchema };
+use serde::{ de::DeserializeOwned, Deserialize, Serialize };
+use serde_json::{ json, value, Map, Value };
+use smol::{ channel::{ self, Receiver, Sender }, Async };
+use smol_hyper::rt::{ FuturesIo, SmolTimer };
+
+pub mod builtin_verbs;
+pub mod discovery;
+pub mod encoding;
+pub mod recorder;
+
+use builtin_verbs::{
+    BrpAwaitRequest,
+    BrpDestroyRequest,
+    BrpEntityResponse,
+    BrpGetRequest,
+    BrpGetResponse,
+    BrpInsertRequest,
+    BrpListRequest,
+    BrpListResponse,
+    BrpOkResponse,
+    BrpQueryRequest,
+    BrpQueryResponse,
+    BrpRemoveRequest,
+    BrpReparentRequest,
+    BrpSpawnRequest,
+};
+use encoding::BrpEncoding;
+use recorder::{ now_millis, BrpRecord, BrpRecorder };
+
+/// The default port that Bevy will listen on.
+///
+/// This value was chosen randomly.
+pub const DEFAULT_PORT: u16 = 15702;
+
+const CHANNEL_SIZE: usize = 16;
+
+/// The HTTP header a client names itself with, so that the `id`s of its long-running requests don't
+/// clash with those of other clients.
+pub const CLIENT_HEADER: &str = "X-BRP-Client";
+
+/// Add this plugin to your [`App`] to allow remote connections to inspect and modify entities.
+///
+/// By default, this is [`DEFAULT_PORT`]: 15702.
+pub struct EditorRemotePlugin {
+    /// The port that Bevy will listen on.
+    pub port: u16,
+
+    /// A JSONL file to append every request and response to, if any.
+    ///
+    /// See [`recorder`] for how to replay it.
+    pub record: Option<PathBuf>,
+
+    /// Whether to let editors on this machine find the server; see [`discovery`].
+    pub announce: bool,
+
+    /// Whether to accept connections on `port` at all.
+    ///
+    /// An app that only talks to an editor in the same process (through the
+    /// editor's `InProcessTransport`) doesn't need to.
+    pub listen: bool,
+
+    /// The version of Bevy the app is built with, which editors are told when they find it.
+    ///
+    /// Only the release this crate is built for ([`discovery::BEVY_VERSION`]) is known here;
+    /// an app can give its exact version with [`EditorRemotePlugin::with_bevy_version`].
+    pub bevy_version: String,
+}
+
+// marker for an FPS counter on a remote server
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct RemoteFpsCounter;
+
+// marker to remove an FPS counter on a remote server
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct DespawnRemoteFpsCounter;
+
+/// A resource containing the port number that Bevy will listen on.
+#[derive(Resource, Reflect)]
+pub struct RemotePort(pub u16);
+
+/// A resource containing the version of Bevy that the app says it is built with.
+#[derive(Resource, Reflect)]
+pub struct RemoteBevyVersion(pub String);
+
+/// A resource containing the path of the file that BRP traffic is recorded to.
+#[derive(Resource, Reflect)]
+pub struct RemoteRecording(pub PathBuf);
+
+/// The type of a function that implements a remote verb (`GET`, `QUERY`, etc.)
+///
+/// The first parameter is the JSON value of the `params`. Typically, an
+/// implementation will deserialize these as the first thing they do.
+///
+/// The returned JSON value will be returned as the response. Bevy will
+/// automatically populate the `status` and `id` fields before sending.
+pub type RemoteVerb = SystemId<Value, AnyhowResult<Value>>;
+
+/// The type of a function that implements a long-running remote verb.
+///
+/// The handler is polled once per frame, starting on the frame the request arrives, until it
+/// returns [`RemotePoll::Ready`] or an error. Only then is the response sent back to the client.
+/// In the meantime, the request can be checked on with `POLL` or abandoned with `CANCEL` by passing
+/// its `id`.
+pub type RemoteAsyncVerb = SystemId<RemoteVerbPoll, AnyhowResult<RemotePoll>>;
+
+/// The input to a [`RemoteAsyncVerb`] each time it is polled.
+pub struct RemoteVerbPoll {
+    /// The `params` of the original request.
+    pub params: Value,
+
+    /// The state returned by the previous poll, or `Value::Null` on the first poll.
+    pub state: Value,
+
+    /// Set when the request was cancelled or the client went away.
+    ///
+    /// This is the last time the handler is polled, so it should clean up anything it started.
+    /// Its result is discarded.
+    pub cancelled: bool,
+}
+
+/// The outcome of polling a [`RemoteAsyncVerb`].
+pub enum RemotePoll {
+    /// The verb has finished, and this is the response.
+    Ready(Value),
+
+    /// The verb isn't done yet. The value is handed back as the `state` of the next poll.
+    Pending(Value),
+}
+
+/// Holds all implementations of verbs known to the server.
+///
+/// You can add your own custom verbs to this list.
+#[derive(Resource, Default)]
+pub struct RemoteVerbs {
+    verbs: HashMap<String, RemoteVerb>,
+    async_verbs: HashMap<String, RemoteAsyncVerb>,
+    shapes: HashMap<String, RemoteVerbShape>,
+}
+
+/// Holds the requests for long-running verbs that haven't responded yet, keyed by the client that
+/// sent them and their `id`.
+#[derive(Resource, Default)]
+pub struct RemotePendingRequests(HashMap<(String, String), RemotePendingRequest>);
+
+/// The client whose request is being handled, for verbs like `POLL` that only see their own
+/// requests.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct RemoteClient(pub String);
+
+/// A request for a [`RemoteAsyncVerb`] that is still being polled.
+pub struct RemotePendingRequest {
+    /// The name of the verb.
+    pub verb: String,
+
+    handler: RemoteAsyncVerb,
+    params: Value,
+    state: Value,
+    sender: Sender<AnyhowResult<Value>>,
+}
+
+/// The request and response schemas of a verb, as reported by `DISCOVER`.
+///
+/// Verbs added with [`RemoteVerbExt::add_remote_verb_with_schema`] get one automatically, and those
+/// added with [`RemoteVerbExt::add_remote_verb`] get one that only names their types. Verbs added
+/// with [`RemoteVerbs::insert`] take and return raw JSON, so they have no shape unless one is given
+/// with [`RemoteVerbs::insert_shape`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RemoteVerbShape {
+    /// The documentation of the verb, taken from the doc comment of its request type.
+    pub description: Option<String>,
+
+    /// The JSON schema of the `params` the verb expects.
+    pub params: Value,
+
+    /// The JSON schema of the value the verb responds with.
+    pub result: Value,
+}
+
+/// Adds typed verbs to the [`RemoteVerbs`] registry.
+///
+/// The registry takes care of deserializing the `params` into `Req` and serializing the `Resp`,
+/// so a handler only has to deal with its own types:
+///
+/// ```ignore
+/// fn teleport(In(request): In<TeleportRequest>, world: &mut World) -> AnyhowResult<()> {
+///     // ...
+/// }
+///
+/// app.add_remote_verb("TELEPORT", teleport);
+/// ```
+///
+/// Types that also derive [`JsonSchema`] can be added with
+/// [`add_remote_verb_with_schema`](RemoteVerbExt::add_remote_verb_with_schema) instead, so that
+/// `DISCOVER` describes them in full.
+pub trait RemoteVerbExt {
+    fn add_remote_verb<Req, Resp, M>(
+        &mut self,
+        verb_name: impl Into<String>,
+        handler: impl IntoSystem<Req, AnyhowResult<Resp>, M> + 'static
+    ) -> &mut Self
+        where Req: DeserializeOwned + 'static, Resp: Serialize + 'static;
+
+    fn add_remote_verb_with_schema<Req, Resp, M>(
+        &mut self,
+        verb_name: impl Into<String>,
+        handler: impl IntoSystem<Req, AnyhowResult<Resp>, M> + 'static
+    ) -> &mut Self
+        where
+            Req: DeserializeOwned + JsonSchema + 'static,
+            Resp: Serialize + JsonSchema + 'static;
+}
+
+/// A single request from a Bevy Remote Protocol client to the server,
+/// serialized in JSON.
+///
+/// The JSON payload is expected to look like this:
+///
+/// ```json
+/// {
+///     "request": "GET",
+///     "id": 0,
+///     "params": {
+///         "data": {
+///             "entity": 4294967298,
+///             "components": [
+///                 "bevy_transform::components::transform::Transform"
+///             ]
+///         }
+///     }
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BrpRequest {
+    /// The verb: i.e. the action to be performed.
+    pub request: String,
+
+    /// Arbitrary data that will be returned verbatim to the client as part of
+    /// the response.
+    pub id: Value,
+
+    /// The parameters, specific to each verb.
+    ///
+    /// These are passed as the first argument to the verb handler.
+    pub params: Value,
+}
+
+/// A message from the Bevy Remote Protocol server thread (or a client in the
+/// same process) to the main world.
+///
+/// This is placed in the [`BrpMailbox`].
+#[derive(Clone)]
+pub struct BrpMessage {
+    /// The deserialized request from the client.
+    pub request: BrpRequest,
+
+    /// Who sent it: the [`CLIENT_HEADER`], or the address of the client if it didn't send one.
+    pub client: String,
+
+    /// The channel on which the response is to be sent.
+    ///
+    /// The value sent here is serialized and sent back to the client.
+    pub sender: Arc<Mutex<Option<Sender<AnyhowResult<Value>>>>>,
+}
+
+/// A resource that receives messages sent by Bevy Remote Protocol clients.
+///
+/// Every frame, the `process_remote_requests` system drains this mailbox, and
+/// processes the messages within.
+#[derive(Resource, Deref, DerefMut)]
+pub struct BrpMailbox(Receiver<BrpMessage>);
+
+/// A resource holding the sending end of the [`BrpMailbox`].
+///
+/// The HTTP server places the requests of its clients here, and so does the
+/// editor's `InProcessTransport`.
+#[derive(Resource, Clone)]
+pub struct BrpMailboxSender(pub Sender<BrpMessage>);
+
+impl Default for EditorRemotePlugin {
+    fn default() -> Self {
+        EditorRemotePlugin {
+            port: DEFAULT_PORT,
+            record: None,
+            announce: true,
+            listen: true,
+            bevy_version: discovery::BEVY_VERSION.to_owned(),
+        }
+    }
+}
+
+impl EditorRemotePlugin {
+    /// Records every request and response to the given JSONL file.
+    pub fn with_recording(mut self, path: impl Into<PathBuf>) -> Self {
+        self.record = Some(path.into());
+        self
+    }
+
+    /// Tells editors which version of Bevy the app is built with, e.g. `"0.14.2"`.
+    pub fn with_bevy_version(mut self, version: impl Into<String>) -> Self {
+        self.bevy_version = version.into();
+        self
+    }
+
+    /// Keeps the server out of the discovery directory, so editors have to be told its port.
+    pub fn without_announcement(mut self) -> Self {
+        self.announce = false;
+        self
+    }
+
+    /// Doesn't open a port, so the only way in is the editor's `InProcessTransport`.
+    pub fn without_listening(mut self) -> Self {
+        self.listen = false;
+        self.announce = false;
+        self
+    }
+}
+
+impl Plugin for EditorRemotePlugin {
+    fn build(&self, app: &mut App) {
+        // other plugins may have already added verbs of their own
+        let mut remote_verbs = app.world_mut().remove_resource::<RemoteVerbs>().unwrap_or_default();
+        remote_verbs.insert(
+            "GET".to_owned(),
+            app.register_system(builtin_verbs::process_remote_get_request)
+        );
+        remote_verbs.insert(
+            "QUERY".to_owned(),
+            app.register_system(builtin_verbs::process_remote_query_request)
+        );
+        remote_verbs.insert(
+            "SPAWN".to_owned(),
+            app.register_system(builtin_verbs::process_remote_spawn_request)
+        );
+        remote_verbs.insert(
+            "INSERT".to_owned(),
+            app.register_system(builtin_verbs::process_remote_insert_request)
+        );
+        remote_verbs.insert(
+            "REMOVE".to_owned(),
+            app.register_system(builtin_verbs::process_remote_remove_request)
+        );
+        remote_verbs.insert(
+            "DESTROY".to_owned(),
+            app.register_system(builtin_verbs::process_remote_destroy_request)
+        );
+        remote_verbs.insert(
+            "REPARENT".to_owned(),
+            app.register_system(builtin_verbs::process_remote_reparent_request)
+        );
+        remote_verbs.insert(
+            "LIST".to_owned(),
+            app.register_system(builtin_verbs::process_remote_list_request)
+        );
+        remote_verbs.insert_async(
+            "AWAIT".to_owned(),
+            app.register_system(builtin_verbs::process_remote_await_request)
+        );
+
+        // the built-in verbs take raw JSON, so describe them by hand
+        remote_verbs.insert_shape("GET", RemoteVerbShape::of::<BrpGetRequest, BrpGetResponse>());
+        remote_verbs.insert_shape(
+            "QUERY",
+            RemoteVerbShape::of::<BrpQueryRequest, BrpQueryResponse>()
+        );
+        remote_verbs.insert_shape(
+            "SPAWN",
+            RemoteVerbShape::of::<BrpSpawnRequest, BrpEntityResponse>()
+        );
+        remote_verbs.insert_shape("INSERT", RemoteVerbShape::of::<BrpInsertRequest, BrpOkResponse>());
+        remote_verbs.insert_shape("REMOVE", RemoteVerbShape::of::<BrpRemoveRequest, BrpOkResponse>());
+        remote_verbs.insert_shape("DESTROY", RemoteVerbShape::of::<BrpDestroyRequest, BrpOkResponse>());
+        remote_verbs.insert_shape("REPARENT", RemoteVerbShape::of::<BrpReparentRequest, BrpOkResponse>());
+        remote_verbs.insert_shape("LIST", RemoteVerbShape::of::<BrpListRequest, BrpListResponse>());
+        remote_verbs.insert_shape("AWAIT", RemoteVerbShape::of::<BrpAwaitRequest, BrpGetResponse>());
+
+        if let Some(path) = &self.record {
+            app.insert_resource(RemoteRecording(path.clone()));
+        }
+
+        // the mailbox exists from the start, so in-process clients can be hooked up before the first update
+        let (request_sender, request_receiver) = channel::bounded(CHANNEL_SIZE);
+
+        app.insert_resource(RemotePort(self.port))
+            .insert_resource(RemoteBevyVersion(self.bevy_version.clone()))
+            .insert_resource(remote_verbs)
+            .insert_resource(BrpMailbox(request_receiver))
+            .insert_resource(BrpMailboxSender(request_sender))
+            .init_resource::<RemotePendingRequests>()
+            .init_resource::<RemoteClient>()
+            .add_remote_verb_with_schema("POLL", builtin_verbs::process_remote_poll_request)
+            .add_remote_verb_with_schema("CANCEL", builtin_verbs::process_remote_cancel_request)
+            .add_remote_verb_with_schema("DISCOVER", builtin_verbs::process_remote_discover_request)
+            .add_remote_verb_with_schema("PING", builtin_verbs::process_remote_ping_request)
+            .add_remote_verb_with_schema("SCENE", builtin_verbs::process_remote_scene_request)
+            .add_systems(Update, (process_remote_requests, poll_remote_requests).chain());
+
+        if self.listen {
+            app.add_systems(Startup, start_server);
+        }
+
+        if self.listen && self.announce {
+            app.add_systems(Startup, discovery::announce_server.after(start_server))
+                .add_systems(Update, discovery::refresh_announcement)
+                .add_systems(Last, discovery::withdraw_announcement);
+        }
+    }
+}
+
+impl RemoteVerbs {
+    /// Creates a new [`RemoteVerbs`] resource with no verbs registered in it.
+    pub fn new() -> Self {
+        default()
+    }
+
+    /// Adds a new verb, replacing any existing verb with that name.
+    ///
+    /// If there was an existing verb with that name, returns its handler.
+    pub fn insert(
+        &mut self,
+        verb_name: impl Into<String>,
+        handler: RemoteVerb
+    ) -> Option<RemoteVerb> {
+        let verb_name = verb_name.into();
+        self.async_verbs.remove(&verb_name);
+        self.verbs.insert(verb_name, handler)
+    }
+
+    /// Adds a new long-running verb, replacing any existing verb with that name.
+    ///
+    /// If there was an existing long-running verb with that name, returns its handler.
+    pub fn insert_async(
+        &mut self,
+        verb_name: impl Into<String>,
+        handler: RemoteAsyncVerb
+    ) -> Option<RemoteAsyncVerb> {
+        let verb_name = verb_name.into();
+        self.verbs.remove(&verb_name);
+        self.async_verbs.insert(verb_name, handler)
+    }
+
+    /// Returns the handler for the given long-running verb, if there is one.
+    pub fn get_async(&self, verb_name: &str) -> Option<RemoteAsyncVerb> {
+        self.async_verbs.get(verb_name).copied()
+    }
+
+    /// Records the request and response types of a verb so clients can discover them.
+    pub fn insert_shape(&mut self, verb_name: impl Into<String>, shape: RemoteVerbShape) {
+        self.shapes.insert(verb_name.into(), shape);
+    }
+
+    /// Returns the handler for the given verb, if there is one.
+    pub fn get(&self, verb_name: &str) -> Option<RemoteVerb> {
+        self.verbs.get(verb_name).copied()
+    }
+
+    /// Returns the request and response types of the given verb, if they are known.
+    pub fn shape(&self, verb_name: &str) -> Option<&RemoteVerbShape> {
+        self.shapes.get(verb_name)
+    }
+
+    /// Returns the names of all registered verbs.
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.verbs.keys().chain(self.async_verbs.keys())
+    }
+}
+
+impl RemoteVerbShape {
+    /// Describes a verb that takes `Req` as its `params` and responds with `Resp`.
+    pub fn of<Req: JsonSchema, Resp: JsonSchema>() -> Self {
+        let params = schema_for!(Req);
+        let description = params.schema.metadata
+            .as_ref()
+            .and_then(|metadata| metadata.description.clone());
+
+        Self {
+            description,
+            params: serde_json::to_value(params).unwrap_or_default(),
+            result: serde_json::to_value(schema_for!(Resp)).unwrap_or_default(),
+        }
+    }
+
+    /// Describes a verb by the names of its types alone, for types without a [`JsonSchema`].
+    ///
+    /// A schema with nothing but a title accepts any value.
+    pub fn untyped<Req, Resp>() -> Self {
+        Self {
+            description: None,
+            params: json!({ "title": std::any::type_name::<Req>() }),
+            result: json!({ "title": std::any::type_name::<Resp>() }),
+        }
+    }
+}
+
+impl RemotePendingRequests {
+    /// Returns the pending request of a client with the given `id`, if it hasn't responded yet.
+    pub fn get(&self, client: &str, id: &Value) -> Option<&RemotePendingRequest> {
+        self.0.get(&(client.to_owned(), id.to_string()))
+    }
+
+    /// Removes the pending request of a client with the given `id` without responding to it.
+    pub fn remove(&mut self, client: &str, id: &Value) -> Option<RemotePendingRequest> {
+        self.0.remove(&(client.to_owned(), id.to_string()))
+    }
+}
+
+impl RemotePendingRequest {
+    /// Polls the verb one last time so it can clean up, then tells the client it was cancelled.
+    pub fn cancel(self, world: &mut World) {
+        let poll = RemoteVerbPoll {
+            params: self.params,
+            state: self.state,
+            cancelled: true,
+        };
+        let _ = world.run_system_with_input(self.handler, poll);
+        let _ = self.sender.send_blocking(Err(anyhow!("Request `{}` was cancelled", self.verb)));
+    }
+}
+
+impl RemoteVerbExt for App {
+    fn add_remote_verb<Req, Resp, M>(
+        &mut self,
+        verb_name: impl Into<String>,
+        handler: impl IntoSystem<Req, AnyhowResult<Resp>, M> + 'static
+    ) -> &mut Self
+        where Req: DeserializeOwned + 'static, Resp: Serialize + 'static
+    {
+        let shape = RemoteVerbShape::untyped::<Req, Resp>();
+        add_typed_remote_verb(self, verb_name.into(), handler, shape)
+    }
+
+    fn add_remote_verb_with_schema<Req, Resp, M>(
+        &mut self,
+        verb_name: impl Into<String>,
+        handler: impl IntoSystem<Req, AnyhowResult<Resp>, M> + 'static
+    ) -> &mut Self
+        where
+            Req: DeserializeOwned + JsonSchema + 'static,
+            Resp: Serialize + JsonSchema + 'static
+    {
+        add_typed_remote_verb(self, verb_name.into(), handler, RemoteVerbShape::of::<Req, Resp>())
+    }
+}
+
+// registers a typed handler behind a verb that takes and returns JSON
+fn add_typed_remote_verb<Req, Resp, M>(
+    app: &mut App,
+    verb_name: String,
+    handler: impl IntoSystem<Req, AnyhowResult<Resp>, M> + 'static,
+    shape: RemoteVerbShape
+) -> &mut App
+    where Req: DeserializeOwned + 'static, Resp: Serialize + 'static
+{
+    let handler = app.register_system(handler);
+
+    // the registered verb is a thin JSON wrapper that runs the typed handler
+    let verb = app.register_system(
+        move |In(params): In<Value>, world: &mut World| -> AnyhowResult<Value> {
+            let request = serde_json::from_value::<Req>(params)?;
+            let response = world
+                .run_system_with_input(handler, request)
+                .map_err(|error| anyhow!("Failed to run handler: {}", error))??;
+
+            // a handler that returns `()` just means "OK"
+            match serde_json::to_value(response)? {
+                Value::Null => Ok(Value::Object(default())),
+                value => Ok(value),
+            }
+        }
+    );
+
+    let mut remote_verbs = app.world_mut().get_resource_or_insert_with(RemoteVerbs::new);
+    remote_verbs.insert(verb_name.clone(), verb);
+    remote_verbs.insert_shape(verb_name, shape);
+
+    app
+}
+
+/// A system that starts up the Bevy Remote Protocol server.
+fn start_server(
+    remote_port: Res<RemotePort>,
+    mailbox: Res<BrpMailboxSender>,
+    remote_recording: Option<Res<RemoteRecording>>
+) {
+    let request_sender = mailbox.0.clone();
+
+    // a broken recording shouldn't take the server down with it
+    let recorder = remote_recording.and_then(|recording| {
+        match BrpRecorder::open(&recording.0) {
+            Ok(recorder) => {
+                info!("Recording BRP traffic to {}", recording.0.display());
+                Some(recorder)
+            }
+            Err(error) => {
+                error!("Can't record BRP traffic to {}: {}", recording.0.display(), error);
+                None
+            }
+        }
+    });
+
+    IoTaskPool::get().spawn(server_main(remote_port.0, request_sender, recorder)).detach();
+}
+
+/// A system that receives requests placed in the [`BrpMailbox`] and processes
+/// them.
+///
+/// This needs exclusive access to the [`World`] because clients can manipulate
+/// anything in the ECS.
+fn process_remote_requests(world: &mut World) {
+    if !world.contains_resource::<BrpMailbox>() {
+        return;
+    }
+
+    while let Ok(message) = world.resource_mut::<BrpMailbox>().try_recv() {
+        let Ok(mut sender) = message.sender.lock() else {
+            continue;
+        };
+        let Some(sender) = sender.take() else {
+            continue;
+        };
+
+        // Long-running verbs are parked with the other pending requests and
+        // polled by `poll_remote_requests`.
+        if let Some(handler) = world.resource::<RemoteVerbs>().get_async(&message.request.request) {
+            let key = (message.client, message.request.id.to_string());
+            let mut pending = world.resource_mut::<RemotePendingRequests>();
+            if pending.0.contains_key(&key) {
+                let _ = sender.send_blocking(Err(anyhow!("Request {} is already pending", key.1)));
+                continue;
+            }
+
+            pending.0.insert(key, RemotePendingRequest {
+                verb: message.request.request,
+                handler,
+                params: message.request.params,
+                state: Value::Null,
+                sender,
+            });
+            continue;
+        }
+
+        // Fetch the handler for the verb. If there's no such handler
+        // registered, return an error.
+        let verbs = world.resource::<RemoteVerbs>();
+        let Some(handler) = verbs.get(&message.request.request) else {
+            let _ = sender.send_blocking(
+                Err(anyhow!("Unknown verb: `{}`", message.request.request))
+            );
+            continue;
+        };
+
+        // Execute the handler, and send the result back to the client.
+        world.insert_resource(RemoteClient(message.client));
+        let result = match world.run_system_with_input(handler, message.request.params) {
+            Ok(result) => result,
+            Err(error) => {
+                let _ = sender.send_blocking(Err(anyhow!("Failed to run handler: {}", error)));
+                continue;
+            }
+        };
+
+        let _ = sender.send_blocking(result);
+    }
+}
+
+/// A system that polls every long-running verb that hasn't responded yet, and
+/// sends back the responses of the ones that have finished.
+fn poll_remote_requests(world: &mut World) {
+    let keys: Vec<(String, String)> = world
+        .resource::<RemotePendingRequests>()
+        .0.keys()
+        .cloned()
+        .collect();
+
+    for key in keys {
+        let Some(mut request) = world.resource_mut::<RemotePendingRequests>().0.remove(&key) else {
+            continue;
+        };
+
+        // Nobody is waiting for the response anymore.
+        if request.sender.is_closed() {
+            request.cancel(world);
+            continue;
+        }
+
+        let poll = RemoteVerbPoll {
+            params: request.params.clone(),
+            state: std::mem::take(&mut request.state),
+            cancelled: false,
+        };
+
+        let result = match world.run_system_with_input(request.handler, poll) {
+            Ok(Ok(RemotePoll::Pending(state))) => {
+                request.state = state;
+                world.resource_mut::<RemotePendingRequests>().0.insert(key, request);
+                continue;
+            }
+            Ok(Ok(RemotePoll::Ready(value))) => Ok(value),
+            Ok(Err(error)) => Err(error),
+            Err(error) => Err(anyhow!("Failed to run handler: {}", error)),
+        };
+
+        let _ = request.sender.send_blocking(result);
+    }
+}
+
+/// The Bevy Remote Protocol server main loop.
+async fn server_main(
+    port: u16,
+    sender: Sender<BrpMessage>,
+    recorder: Option<BrpRecorder>
+) -> AnyhowResult<()> {
+    listen(Async::<TcpListener>::bind(([127, 0, 0, 1], port))?, sender, recorder).await?;
+    Ok(())
+}
+
+async fn listen(
+    listener: Async<TcpListener>,
+    sender: Sender<BrpMessage>,
+    recorder: Option<BrpRecorder>
+) -> AnyhowResult<()> {
+    loop {
+        let (client, address) = listener.accept().await?;
+
+        let sender = sender.clone();
+        let recorder = recorder.clone();
+        IoTaskPool::get()
+            .spawn(async move {
+                let _ = handle_client(client, address, sender, recorder).await;
+            })
+            .detach();
+    }
+}
+
+async fn handle_client(
+    client: Async<TcpStream>,
+    address: SocketAddr,
+    sender: Sender<BrpMessage>,
+    recorder: Option<BrpRecorder>
+) -> AnyhowResult<()> {
+    http1::Builder
+        ::new()
+        .timer(SmolTimer::new())
+        .serve_connection(
+            FuturesIo::new(client),
+            service::service_fn(|request| {
+                process_request(request, address, sender.clone(), recorder.clone())
+            })
+        ).await?;
+
+    Ok(())
+}
+
+/// A helper function for the Bevy Remote Protocol server that handles a single
+/// request coming from a client.
+async fn process_request(
+    request: Request<Incoming>,
+    address: SocketAddr,
+    sender: Sender<BrpMessage>,
+    recorder: Option<BrpRecorder>
+) -> AnyhowResult<Response<Full<Bytes>>> {
+    // clients that don't name themselves are told apart by where they connect from
+    let client = request
+        .headers()
+        .get(CLIENT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+        .unwrap_or_else(|| address.ip().to_string());
+
+    // a plain `GET` gets the discovery document, for tools that would rather not `POST`
+    if request.method() == Method::GET {
+        return process_discover_request(client, sender).await;
+    }
+
+    // anything we don't recognize is assumed to be JSON, which is what clients have always sent
+    let request_encoding = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(BrpEncoding::from_content_type)
+        .unwrap_or_default();
+
+    // respond in kind unless the client asked for something else
+    let response_encoding = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .and_then(BrpEncoding::from_accept)
+        .unwrap_or(request_encoding);
+
+    let request_bytes = request.into_body().collect().await?.to_bytes();
+    let request: BrpRequest = match request_encoding.decode(&request_bytes) {
+        Ok(request) => request,
+        Err(error) => {
+            // (without a request there's no `id` to echo, nor anything worth recording)
+            let error = anyhow!("Malformed request: {}", error);
+            return encoded_response(response_encoding, &response_from(Value::Null, Err(error)));
+        }
+    };
+    let requested = now_millis();
+
+    // Save the `id` field so we can echo it back, and the rest for the recording.
+    let id = request.id.clone();
+    let recorded_request = recorder.as_ref().map(|_| request.clone());
+
+    let value = response_from(id, process_request_body(request, client, &sender).await);
+
+    if let (Some(recorder), Some(request)) = (recorder, recorded_request) {
+        let record = BrpRecord {
+            requested,
+            responded: now_millis(),
+            request,
+            response: Value::Object(value.clone()),
+        };
+        if let Err(error) = recorder.record(record) {
+            error!("Can't record BRP traffic: {}", error);
+        }
+    }
+
+    encoded_response(response_encoding, &value)
+}
+
+/// Serializes a response in the encoding the client asked for.
+fn encoded_response(
+    encoding: BrpEncoding,
+    value: &Map<String, Value>
+) -> AnyhowResult<Response<Full<Bytes>>> {
+    let bytes = encoding.encode(value)?;
+    Ok(
+        Response::builder()
+            .header(header::CONTENT_TYPE, encoding.content_type())
+            .body(Full::new(Bytes::from(bytes)))?
+    )
+}
+
+/// A helper function for the Bevy Remote Protocol server that responds to a
+/// plain HTTP `GET` with the bare `DISCOVER` document.
+async fn process_discover_request(
+    client: String,
+    sender: Sender<BrpMessage>
+) -> AnyhowResult<Response<Full<Bytes>>> {
+    let request = BrpRequest {
+        request: "DISCOVER".to_owned(),
+        id: Value::Null,
+        params: Value::Object(default()),
+    };
+    let value = process_request_body(request, client, &sender).await?;
+
+    let string = serde_json::to_string_pretty(&value)?;
+    Ok(
+        Response::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(string.into_bytes())))?
+    )
+}
+
+/// A helper function for the Bevy Remote Protocol server that parses a single
+/// request coming from a client and places it in the [`BrpMailbox`].
+async fn process_request_body(
+    request: BrpRequest,
+    client: String,
+    sender: &Sender<BrpMessage>
+) -> AnyhowResult<Map<String, Value>> {
+    let (response_sender, response_receiver) = channel::bounded(1);
+
+    let _ = sender.send(BrpMessage {
+        request,
+        client,
+        sender: Arc::new(Mutex::new(Some(response_sender))),
+    }).await;
+
+    let response = response_receiver.recv().await??;
+    match value::to_value(response)? {
+        Value::Object(map) => Ok(map),
+        _ => Err(anyhow!("Response wasn't an object")),
+    }
+}
+
+/// A helper function for the Bevy Remote Protocol server that adds the
+/// `status` and `id` fields to the result of a request, making it the response
+/// the client sees.
+pub fn response_from(id: Value, result: AnyhowResult<Map<String, Value>>) -> Map<String, Value> {
+    let mut value = match result {
+        Ok(mut value) => {
+            value.insert("status".to_owned(), "OK".into());
+            value
+        }
+        Err(err) => {
+            let mut response = Map::new();
+            response.insert("status".to_owned(), "ERROR".into());
+            response.insert("message".to_owned(), err.to_string().into());
+            response
+        }
+    };
+
+    // Echo the same `id` value back to the client.
+    value.insert("id".to_owned(), id);
+    value
+}
@@ -0,0 +1,237 @@
+//! Records Bevy Remote Protocol traffic so it can be replayed later.
+//!
+//! When [`EditorRemotePlugin::record`](super::EditorRemotePlugin::record) is set, every request the
+//! server answers is appended to a JSONL file along with its response and timestamps. The
+//! `brp_replay` binary sends a recording to a fresh server and reports where the responses differ,
+//! which makes for reproducible bug reports and regression tests of the verbs.
+
+use std::{
+    fs::{ File, OpenOptions },
+    io::{ BufRead, BufReader, LineWriter, Write },
+    path::Path,
+    sync::mpsc::{ self, Sender },
+    thread,
+    time::{ SystemTime, UNIX_EPOCH },
+};
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::{ log::error, utils::HashMap };
+use serde::{ Deserialize, Serialize };
+use serde_json::Value;
+
+use super::BrpRequest;
+
+/// One line of a recording: a request and the response it got.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BrpRecord {
+    /// When the request arrived, in milliseconds since the UNIX epoch.
+    pub requested: u64,
+
+    /// When the response was sent, in milliseconds since the UNIX epoch.
+    pub responded: u64,
+
+    /// The request, as the client sent it.
+    pub request: BrpRequest,
+
+    /// The response, as the client received it (including `status` and `id`).
+    pub response: Value,
+}
+
+/// Appends [`BrpRecord`]s to a file, one JSON object per line.
+///
+/// The file is written on a thread of its own, so recording never holds up a response. Cloning a
+/// recorder shares that thread, so every connection can write to the same file.
+#[derive(Clone)]
+pub struct BrpRecorder(Sender<BrpRecord>);
+
+impl BrpRecorder {
+    /// Opens a recording for appending, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> AnyhowResult<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (sender, receiver) = mpsc::channel();
+
+        // the thread ends once every recorder is dropped
+        thread::Builder
+            ::new()
+            .name("brp recorder".to_owned())
+            .spawn(move || {
+                let mut writer = LineWriter::new(file);
+                for record in receiver {
+                    if let Err(error) = write_record(&mut writer, &record) {
+                        error!("Can't record BRP traffic: {}", error);
+                    }
+                }
+            })?;
+
+        Ok(Self(sender))
+    }
+
+    /// Appends a record.
+    ///
+    /// Each line is flushed as it's written, so a recording survives the app crashing.
+    pub fn record(&self, record: BrpRecord) -> AnyhowResult<()> {
+        self.0.send(record).map_err(|_| anyhow!("BRP recording has stopped"))
+    }
+}
+
+fn write_record(writer: &mut LineWriter<File>, record: &BrpRecord) -> AnyhowResult<()> {
+    let line = serde_json::to_string(record)?;
+    writeln!(writer, "{}", line)?;
+    Ok(())
+}
+
+/// Reads every record in a recording, in the order the requests arrived.
+pub fn read_recording(path: impl AsRef<Path>) -> AnyhowResult<Vec<BrpRecord>> {
+    let mut records = vec![];
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records.push(serde_json::from_str::<BrpRecord>(&line)?);
+    }
+
+    // lines are written as responses go out, and long-running verbs respond late
+    records.sort_by_key(|record| record.requested);
+    Ok(records)
+}
+
+/// The current time in milliseconds since the UNIX epoch.
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Maps entity IDs in a recording to the IDs of the same entities on the server being replayed to.
+///
+/// A fresh server doesn't necessarily hand out the same IDs, so whenever a response names a
+/// different entity than the recording did, the new ID is substituted into later requests.
+#[derive(Default, Debug)]
+pub struct BrpEntityMap(HashMap<u64, u64>);
+
+impl BrpEntityMap {
+    /// Learns the mapping from a recorded response and the response the server actually sent.
+    pub fn learn(&mut self, recorded: &Value, actual: &Value) {
+        match (recorded, actual) {
+            (Value::Object(recorded), Value::Object(actual)) => {
+                for (key, recorded_value) in recorded {
+                    let Some(actual_value) = actual.get(key) else {
+                        continue;
+                    };
+                    if is_entity_key(key) {
+                        self.learn_ids(recorded_value, actual_value);
+                    } else {
+                        self.learn(recorded_value, actual_value);
+                    }
+                }
+            }
+            (Value::Array(recorded), Value::Array(actual)) => {
+                for (recorded, actual) in recorded.iter().zip(actual) {
+                    self.learn(recorded, actual);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // an entity ID, or a list of them like `entities`, in the same order in both responses
+    fn learn_ids(&mut self, recorded: &Value, actual: &Value) {
+        match (recorded, actual) {
+            (Value::Array(recorded), Value::Array(actual)) => {
+                for (recorded, actual) in recorded.iter().zip(actual) {
+                    self.learn_ids(recorded, actual);
+                }
+            }
+            (recorded, actual) => {
+                if let (Some(recorded_id), Some(actual_id)) = (recorded.as_u64(), actual.as_u64()) {
+                    if recorded_id != actual_id {
+                        self.0.insert(recorded_id, actual_id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rewrites the recorded entity IDs in a value to the ones the server knows them by.
+    pub fn apply(&self, value: &mut Value) {
+        self.apply_inner(value, false);
+    }
+
+    fn apply_inner(&self, value: &mut Value, is_entity: bool) {
+        match value {
+            Value::Number(number) if is_entity => {
+                if let Some(actual) = number.as_u64().and_then(|id| self.0.get(&id)) {
+                    *value = (*actual).into();
+                }
+            }
+            Value::Array(array) => {
+                for value in array {
+                    self.apply_inner(value, is_entity);
+                }
+            }
+            Value::Object(map) => {
+                for (key, value) in map {
+                    self.apply_inner(value, is_entity_key(key));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// the fields that hold entity IDs in the built-in requests and responses
+fn is_entity_key(key: &str) -> bool {
+    matches!(key, "entity" | "entities" | "parent")
+}
+
+/// Compares a recorded response with the one the server actually sent.
+///
+/// Returns a description of each difference, prefixed with its JSON pointer. Floats are compared
+/// with a small tolerance since replaying at a different pace rarely lands on the same frame.
+pub fn diff_responses(recorded: &Value, actual: &Value) -> Vec<String> {
+    let mut differences = vec![];
+    diff_inner("", recorded, actual, &mut differences);
+    differences
+}
+
+fn diff_inner(path: &str, recorded: &Value, actual: &Value, differences: &mut Vec<String>) {
+    match (recorded, actual) {
+        (Value::Object(recorded), Value::Object(actual)) => {
+            for (key, recorded_value) in recorded {
+                let path = format!("{}/{}", path, key);
+                match actual.get(key) {
+                    Some(actual_value) => diff_inner(&path, recorded_value, actual_value, differences),
+                    None => differences.push(format!("{}: missing", path)),
+                }
+            }
+            for key in actual.keys().filter(|key| !recorded.contains_key(*key)) {
+                differences.push(format!("{}/{}: unexpected", path, key));
+            }
+        }
+        (Value::Array(recorded), Value::Array(actual)) => {
+            if recorded.len() != actual.len() {
+                differences.push(
+                    format!("{}: expected {} items, got {}", path, recorded.len(), actual.len())
+                );
+            }
+            for (index, (recorded, actual)) in recorded.iter().zip(actual).enumerate() {
+                diff_inner(&format!("{}/{}", path, index), recorded, actual, differences);
+            }
+        }
+        (Value::Number(recorded), Value::Number(actual)) if recorded.is_f64() || actual.is_f64() => {
+            let (recorded, actual) = (
+                recorded.as_f64().unwrap_or_default(),
+                actual.as_f64().unwrap_or_default(),
+            );
+            if (recorded - actual).abs() > 1e-4 {
+                differences.push(format!("{}: expected {}, got {}", path, recorded, actual));
+            }
+        }
+        (recorded, actual) if recorded != actual => {
+            differences.push(format!("{}: expected {}, got {}", path, recorded, actual));
+        }
+        _ => {}
+    }
+}
@@ -14,8 +14,15 @@ fn main() {
     // init FPS widget
     sickle_example::fps_widget::plugin(&mut app);
 
+    // set BRP_RECORD=some_file.jsonl to record everything the editor does to this app
+    // (replay it later with: cargo run --bin brp_replay -- some_file.jsonl)
+    let mut remote = EditorRemotePlugin::default();
+    if let Ok(path) = std::env::var("BRP_RECORD") {
+        remote = remote.with_recording(path);
+    }
+
     app.add_plugins(DefaultPlugins)
-        .add_plugins(EditorRemotePlugin::default())
+        .add_plugins(remote)
         .init_state::<FpsVisibility>()
         // types must be registered on both sides for serde_json to work
         .register_type::<RemoteFpsCounter>()
@@ -12,11 +12,20 @@ pub mod new_project;
 #[derive(Resource, Debug)]
 pub struct ActivityService {
     pub current_activity: Box<dyn Activity>,
+
+    // what the current activity was started as
+    current: EditorId,
 }
 
 impl ActivityService {
     pub fn start(&mut self) -> EditorId {
-        self.current_activity.start()
+        self.current = self.current_activity.start();
+        self.current.clone()
+    }
+
+    /// The ID of the activity that was started last.
+    pub fn current(&self) -> &EditorId {
+        &self.current
     }
 
     pub fn stop(&mut self) {
@@ -31,7 +40,10 @@ impl ActivityService {
 
 impl Default for ActivityService {
     fn default() -> Self {
-        Self { current_activity: Box::new(DefaultActivity) }
+        Self {
+            current_activity: Box::new(DefaultActivity),
+            current: default(),
+        }
     }
 }
 
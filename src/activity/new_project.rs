@@ -0,0 +1,684 @@
+//! The New Project activity makes a project from a template: a Cargo workspace with a `game`
+//! member that has [`EditorRemotePlugin`](crate::remote::EditorRemotePlugin) already added, so that
+//! the editor is connected to it as soon as it runs, along with a starter scene and locale folders.
+//! The game depends on the small `beverage_remote` crate rather than the whole editor: the one in
+//! the editor's checkout, or the published one an editor from a release was built with (see
+//! build.rs).
+//!
+//! Templates are filled in with [`TemplateVariables`]: the project's name, the name of its crate,
+//! and the port the game listens on for BRP requests.
+
+use std::{ fs, path::{ Path, PathBuf } };
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::prelude::*;
+
+use bevy_fluent::Localization;
+use bevy_simple_text_input::TextInputValue;
+use native_dialog::FileDialog;
+use sickle_ui::prelude::*;
+
+use crate::{
+    activity::{ Activity, DefaultActivity },
+    clear_content_on_menu_change,
+    framework::*,
+    locale::Translator,
+    project::{ self, ProjectManifest, ProjectPlugin, ProjectRemote },
+    remote::DEFAULT_PORT,
+    service::EditorService,
+    widget::{ text_input, TextInputFocusPlugin },
+};
+
+// files every template has, by path in the project folder
+const COMMON_FILES: &[(&str, &str)] = &[
+    ("Cargo.toml", include_str!("../../templates/Cargo.toml.template")),
+    ("game/Cargo.toml", include_str!("../../templates/game/Cargo.toml.template")),
+    (".gitignore", include_str!("../../templates/gitignore.template")),
+    ("assets/locales/en-US/game.ftl", include_str!("../../templates/game.ftl.template")),
+];
+
+// the scene the game starts with, the first in the manifest (see ProjectManifest::new)
+const SCENE_FILE: &str = "assets/scenes/main.scn.ron";
+
+// how the game depends on beverage_remote, worked out by build.rs
+const BEVERAGE_REMOTE_DEPENDENCY: &str = env!("BEVERAGE_REMOTE_DEPENDENCY");
+
+/// The templates the gallery shows, in order.
+pub const TEMPLATES: &[ProjectTemplate] = &[
+    ProjectTemplate {
+        label: "Empty3dGame",
+        description: "Empty3dGameDescription",
+        files: &[
+            ("game/src/main.rs", include_str!("../../templates/3d/main.rs.template")),
+            (SCENE_FILE, include_str!("../../templates/3d/main.scn.ron.template")),
+        ],
+    },
+    ProjectTemplate {
+        label: "Empty2dGame",
+        description: "Empty2dGameDescription",
+        files: &[
+            ("game/src/main.rs", include_str!("../../templates/2d/main.rs.template")),
+            (SCENE_FILE, include_str!("../../templates/2d/main.scn.ron.template")),
+        ],
+    },
+    ProjectTemplate {
+        label: "BrpServer",
+        description: "BrpServerDescription",
+        files: &[
+            ("game/src/main.rs", include_str!("../../templates/server/main.rs.template")),
+            (SCENE_FILE, include_str!("../../templates/server/main.scn.ron.template")),
+        ],
+    },
+];
+
+// the page to go back to (the editor's start page, see setup::build)
+const EDITOR_PAGE: Page = Page::QuillDemo;
+
+pub struct NewProjectPlugin;
+
+impl Plugin for NewProjectPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<ProjectPlugin>() {
+            app.add_plugins(ProjectPlugin);
+        }
+        if !app.is_plugin_added::<TextInputFocusPlugin>() {
+            app.add_plugins(TextInputFocusPlugin);
+        }
+
+        app.add_systems(OnEnter(Page::NewProject), (start_new_project, layout))
+            .add_systems(OnExit(Page::NewProject), (stop_new_project, clear_content_on_menu_change))
+            .add_systems(
+                Update,
+                (
+                    update_new_project_panels,
+                    read_new_project_fields,
+                    press_new_project_controls,
+                    show_project_folder,
+                )
+                    .chain()
+                    .after(WidgetLibraryUpdate)
+                    .run_if(in_state(EditorState::Running))
+            );
+    }
+}
+
+/// Making a new project, which keeps a history of its own, so undoing while at it doesn't reach
+/// into the activity it was started from.
+#[derive(Reflect, Debug, Default)]
+pub struct NewProjectActivity;
+
+impl Activity for NewProjectActivity {
+    fn start(&mut self) -> EditorId {
+        EditorId("new_project".into())
+    }
+
+    fn stop(&mut self) {}
+}
+
+/// A kind of game a new project can start out as.
+#[derive(Clone, Copy, Debug)]
+pub struct ProjectTemplate {
+    /// Localized name.
+    pub label: &'static str,
+
+    /// Localized description of what is in it.
+    pub description: &'static str,
+
+    /// Files to write besides the ones every template has, by path in the project folder, each
+    /// filled in with the [`TemplateVariables`].
+    pub files: &'static [(&'static str, &'static str)],
+}
+
+/// What a template is filled in with, in place of `{{name}}`, `{{crate_name}}` and `{{port}}`.
+///
+/// `{{crate_ident}}` is the crate name as it's written in Rust (and so in type paths), and
+/// `{{beverage_remote}}` is how the game depends on the `beverage_remote` crate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateVariables {
+    pub name: String,
+    pub crate_name: String,
+    pub port: u16,
+}
+
+impl TemplateVariables {
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            crate_name: crate_name(&name),
+            name,
+            port: DEFAULT_PORT,
+        }
+    }
+
+    pub fn fill(&self, text: &str) -> String {
+        text.replace("{{name}}", &self.name)
+            .replace("{{crate_name}}", &self.crate_name)
+            .replace("{{crate_ident}}", &self.crate_name.replace('-', "_"))
+            .replace("{{port}}", &self.port.to_string())
+            .replace("{{beverage_remote}}", BEVERAGE_REMOTE_DEPENDENCY)
+    }
+
+    // the name goes into Rust and TOML strings as it is, and the crate name has to be a valid one
+    fn check(&self) -> AnyhowResult<()> {
+        if self.name.trim().is_empty() {
+            return Err(anyhow!("The project needs a name"));
+        }
+        if self.name.contains(['"', '\\']) {
+            return Err(anyhow!("The project name can't have quotes or backslashes in it"));
+        }
+
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+        let starts_well = self.crate_name.starts_with(|c: char| c.is_ascii_alphabetic());
+        if !starts_well || !self.crate_name.chars().all(valid) {
+            return Err(anyhow!("`{}` isn't a valid crate name", self.crate_name));
+        }
+        Ok(())
+    }
+}
+
+/// The crate name that goes with a project name, like `my_game` for "My Game".
+pub fn crate_name(name: &str) -> String {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+
+    let crate_name = words.join("_");
+    if crate_name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        crate_name
+    } else {
+        format!("game_{}", crate_name).trim_end_matches('_').to_string()
+    }
+}
+
+/// Makes a project from a template in a folder that doesn't have one yet, and returns its
+/// manifest. Nothing is written if any of the template's files are already there.
+pub fn generate(
+    template: &ProjectTemplate,
+    folder: &Path,
+    variables: &TemplateVariables
+) -> AnyhowResult<ProjectManifest> {
+    variables.check()?;
+
+    let files: Vec<&(&str, &str)> = COMMON_FILES.iter().chain(template.files).collect();
+    for (path, _) in &files {
+        let path = folder.join(variables.fill(path));
+        if path.exists() {
+            return Err(anyhow!("{} is already there", path.display()));
+        }
+    }
+
+    let manifest = ProjectManifest {
+        remote: ProjectRemote::local(variables.port),
+        ..ProjectManifest::new(variables.name.clone())
+    };
+    project::create(folder, &manifest)?;
+
+    for (path, contents) in files {
+        let path = folder.join(variables.fill(path));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, variables.fill(contents))?;
+    }
+    Ok(manifest)
+}
+
+/// The form for a new project, which holds what has been filled in so far.
+#[derive(Component, Debug)]
+pub struct NewProjectPanel {
+    template: usize,
+    name: String,
+    crate_name: String,
+    port: String,
+
+    // where the project folder goes
+    location: Option<PathBuf>,
+
+    error: Option<String>,
+
+    // rebuild the form, which isn't done while typing
+    dirty: bool,
+}
+
+impl Default for NewProjectPanel {
+    fn default() -> Self {
+        Self {
+            template: 0,
+            name: String::new(),
+            crate_name: String::new(),
+            port: DEFAULT_PORT.to_string(),
+            location: None,
+            error: None,
+            dirty: true,
+        }
+    }
+}
+
+impl NewProjectPanel {
+    fn variables(&self) -> AnyhowResult<TemplateVariables> {
+        let port = self.port
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("`{}` isn't a port number", self.port))?;
+        Ok(TemplateVariables {
+            name: self.name.trim().to_string(),
+            crate_name: self.crate_name.trim().to_string(),
+            port,
+        })
+    }
+
+    // the folder the project is made in
+    fn folder(&self) -> Option<PathBuf> {
+        self.location.as_ref().map(|location| location.join(self.crate_name.trim()))
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+struct NewProjectField {
+    panel: Entity,
+    variable: TemplateVariable,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TemplateVariable {
+    Name,
+    CrateName,
+    Port,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+struct NewProjectControl {
+    panel: Entity,
+    press: NewProjectPress,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum NewProjectPress {
+    Template(usize),
+    ChooseFolder,
+    Create,
+    Cancel,
+}
+
+#[derive(Component, Debug)]
+struct NewProjectFolder {
+    panel: Entity,
+}
+
+// undoing while making a project shouldn't reach into the editor behind the form
+fn start_new_project(mut editor: ResMut<EditorService>) {
+    editor.activity.stop();
+    editor.activity.current_activity = Box::new(NewProjectActivity);
+    editor.activity.start();
+}
+
+fn stop_new_project(mut editor: ResMut<EditorService>) {
+    editor.activity.stop();
+    editor.activity.current_activity = Box::new(DefaultActivity);
+    editor.activity.start();
+}
+
+fn layout(root_node: Query<Entity, With<EditorContainer>>, mut commands: Commands) {
+    let Ok(root_entity) = root_node.get_single() else {
+        return;
+    };
+
+    commands
+        .ui_builder(root_entity)
+        .column(|_| {})
+        .insert((Name::new("New Project"), NewProjectPanel::default()))
+        .style()
+        .width(Val::Percent(100.0))
+        .padding(UiRect::all(Val::Px(20.0)));
+}
+
+fn update_new_project_panels(
+    mut q_panels: Query<(Entity, &mut NewProjectPanel)>,
+    l10n: Res<Localization>,
+    mut commands: Commands
+) {
+    for (panel_entity, mut panel) in &mut q_panels {
+        if !panel.dirty {
+            continue;
+        }
+        panel.bypass_change_detection().dirty = false;
+
+        commands.entity(panel_entity).despawn_descendants();
+        let mut builder = commands.ui_builder(panel_entity);
+
+        builder.label(LabelConfig {
+            label: l10n.lbl("NewProject"),
+            ..default()
+        });
+
+        // the template gallery
+        builder
+            .row(|row| {
+                for (index, template) in TEMPLATES.iter().enumerate() {
+                    let selected = index == panel.template;
+                    spawn_template_card(row, &l10n, panel_entity, index, template, selected);
+                }
+            })
+            .style()
+            .margin(UiRect::vertical(Val::Px(12.0)));
+
+        let fields = [
+            (TemplateVariable::Name, "ProjectName", &panel.name),
+            (TemplateVariable::CrateName, "CrateName", &panel.crate_name),
+            (TemplateVariable::Port, "Port", &panel.port),
+        ];
+        for (variable, label, value) in fields {
+            builder.row(|line| {
+                spawn_field_label(line, l10n.lbl(label));
+                let field = NewProjectField {
+                    panel: panel_entity,
+                    variable,
+                };
+                let mut input = text_input(value);
+                input.0.style.min_width = Val::Px(240.0);
+                input.0.style.margin = UiRect::vertical(Val::Px(2.0));
+                line.spawn((input, field));
+            });
+        }
+
+        builder.row(|line| {
+            spawn_field_label(line, l10n.lbl("Folder"));
+            line.label(LabelConfig {
+                label: folder_label(&panel, &l10n),
+                ..default()
+            }).insert(NewProjectFolder { panel: panel_entity });
+            let choose = NewProjectPress::ChooseFolder;
+            spawn_button(line, l10n.lbl("ChooseFolder"), panel_entity, choose);
+        });
+
+        if let Some(error) = &panel.error {
+            builder.label(LabelConfig {
+                label: error.clone(),
+                color: Color::srgb(0.9, 0.4, 0.4),
+                ..default()
+            });
+        }
+
+        builder
+            .row(|line| {
+                spawn_button(line, l10n.lbl("Create"), panel_entity, NewProjectPress::Create);
+                spawn_button(line, l10n.lbl("Cancel"), panel_entity, NewProjectPress::Cancel);
+            })
+            .style()
+            .margin(UiRect::top(Val::Px(12.0)));
+    }
+}
+
+fn spawn_template_card(
+    builder: &mut UiBuilder<Entity>,
+    l10n: &Localization,
+    panel: Entity,
+    index: usize,
+    template: &ProjectTemplate,
+    selected: bool
+) {
+    let border_color = if selected {
+        Color::srgb(0.9, 0.6, 0.2)
+    } else {
+        Color::srgb(0.4, 0.4, 0.4)
+    };
+    builder.container(
+        (
+            NodeBundle {
+                style: Style {
+                    width: Val::Px(200.0),
+                    flex_direction: FlexDirection::Column,
+                    margin: UiRect::right(Val::Px(12.0)),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    border: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                border_color: border_color.into(),
+                ..default()
+            },
+            Interaction::None,
+            NewProjectControl {
+                panel,
+                press: NewProjectPress::Template(index),
+            },
+        ),
+        |card| {
+            card.label(LabelConfig {
+                label: l10n.lbl(template.label),
+                ..default()
+            });
+            card.label(LabelConfig {
+                label: l10n.lbl(template.description),
+                color: Color::srgb(0.7, 0.7, 0.7),
+                ..default()
+            })
+                .style()
+                .margin(UiRect::top(Val::Px(6.0)));
+        }
+    );
+}
+
+fn spawn_field_label(builder: &mut UiBuilder<Entity>, label: String) {
+    builder
+        .label(LabelConfig {
+            label,
+            ..default()
+        })
+        .style()
+        .min_width(Val::Px(120.0));
+}
+
+fn spawn_button(
+    builder: &mut UiBuilder<Entity>,
+    label: String,
+    panel: Entity,
+    press: NewProjectPress
+) {
+    builder.container(
+        (
+            NodeBundle {
+                style: Style {
+                    margin: UiRect::right(Val::Px(8.0)),
+                    padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)),
+                    border: UiRect::all(Val::Px(1.0)),
+                    ..default()
+                },
+                border_color: Color::srgb(0.4, 0.4, 0.4).into(),
+                ..default()
+            },
+            Interaction::None,
+            NewProjectControl { panel, press },
+        ),
+        |button| {
+            button.label(LabelConfig {
+                label,
+                ..default()
+            });
+        }
+    );
+}
+
+fn folder_label(panel: &NewProjectPanel, l10n: &Localization) -> String {
+    match panel.folder() {
+        Some(folder) => folder.display().to_string(),
+        None => l10n.lbl("NoFolderChosen"),
+    }
+}
+
+fn read_new_project_fields(
+    q_changed: Query<(&TextInputValue, &NewProjectField), Changed<TextInputValue>>,
+    q_fields: Query<(Entity, &NewProjectField)>,
+    mut q_panels: Query<&mut NewProjectPanel>,
+    mut commands: Commands
+) {
+    for (value, field) in &q_changed {
+        let Ok(mut panel) = q_panels.get_mut(field.panel) else {
+            continue;
+        };
+
+        match field.variable {
+            TemplateVariable::Name => {
+                if panel.name == value.0 {
+                    continue;
+                }
+
+                // the crate name follows the name, unless it was filled in by hand
+                let follows = panel.crate_name.is_empty() ||
+                    panel.crate_name == crate_name(&panel.name);
+                panel.name = value.0.clone();
+                if follows {
+                    panel.crate_name = crate_name(&panel.name);
+                    let crate_field = NewProjectField {
+                        panel: field.panel,
+                        variable: TemplateVariable::CrateName,
+                    };
+                    for (entity, _) in q_fields.iter().filter(|(_, other)| **other == crate_field) {
+                        commands.entity(entity).insert(TextInputValue(panel.crate_name.clone()));
+                    }
+                }
+            }
+            TemplateVariable::CrateName => {
+                if panel.crate_name != value.0 {
+                    panel.crate_name = value.0.clone();
+                }
+            }
+            TemplateVariable::Port => {
+                if panel.port != value.0 {
+                    panel.port = value.0.clone();
+                }
+            }
+        }
+    }
+}
+
+fn press_new_project_controls(
+    q_controls: Query<(&Interaction, &NewProjectControl), Changed<Interaction>>,
+    mut q_panels: Query<&mut NewProjectPanel>,
+    mut next_page: ResMut<NextState<Page>>,
+    mut commands: Commands
+) {
+    for (interaction, control) in &q_controls {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let panel = control.panel;
+        match control.press {
+            NewProjectPress::Template(index) => {
+                let Ok(mut panel) = q_panels.get_mut(panel) else {
+                    continue;
+                };
+                if panel.template != index {
+                    panel.template = index;
+                    panel.dirty = true;
+                }
+            }
+            // (commands are applied on the main thread, which is where the native dialogs have to
+            // be shown)
+            NewProjectPress::ChooseFolder => {
+                commands.add(move |world: &mut World| choose_folder(world, panel));
+            }
+            NewProjectPress::Create => {
+                commands.add(move |world: &mut World| create_project(world, panel));
+            }
+            NewProjectPress::Cancel => {
+                next_page.set(EDITOR_PAGE);
+            }
+        }
+    }
+}
+
+fn show_project_folder(
+    q_panels: Query<&NewProjectPanel, Changed<NewProjectPanel>>,
+    mut q_labels: Query<(&mut Text, &NewProjectFolder)>,
+    l10n: Res<Localization>
+) {
+    for (mut text, folder) in &mut q_labels {
+        let Ok(panel) = q_panels.get(folder.panel) else {
+            continue;
+        };
+        let label = folder_label(panel, &l10n);
+        if let Some(section) = text.sections.first_mut() {
+            if section.value != label {
+                section.value = label;
+            }
+        }
+    }
+}
+
+fn choose_folder(world: &mut World, panel: Entity) {
+    let folder = match FileDialog::new().set_location("~").show_open_single_dir() {
+        Ok(Some(folder)) => folder,
+        Ok(None) => {
+            return;
+        }
+        Err(error) => {
+            warn!("Could not pick a folder: {}", error);
+            return;
+        }
+    };
+
+    if let Some(mut panel) = world.get_mut::<NewProjectPanel>(panel) {
+        panel.location = Some(folder);
+        panel.dirty = true;
+    }
+}
+
+fn create_project(world: &mut World, panel_entity: Entity) {
+    let Some(panel) = world.get::<NewProjectPanel>(panel_entity) else {
+        return;
+    };
+    let template = TEMPLATES[panel.template.min(TEMPLATES.len() - 1)];
+    let folder = panel.folder();
+    let (variables, folder) = match (panel.variables(), folder) {
+        (Ok(variables), Some(folder)) => (variables, folder),
+        (Err(error), _) => {
+            show_error(world, panel_entity, error);
+            return;
+        }
+        (_, None) => {
+            show_error(world, panel_entity, anyhow!("Choose a folder to put the project in"));
+            return;
+        }
+    };
+
+    // (the open project is closed first, which may be called off)
+    if !project::close(world) {
+        return;
+    }
+    if let Err(error) = generate(&template, &folder, &variables) {
+        show_error(world, panel_entity, error);
+        return;
+    }
+    project::open_path(world, &folder);
+    world.resource_mut::<NextState<Page>>().set(EDITOR_PAGE);
+}
+
+fn show_error(world: &mut World, panel: Entity, error: anyhow::Error) {
+    warn!("Could not create a project: {}", error);
+    if let Some(mut panel) = world.get_mut::<NewProjectPanel>(panel) {
+        panel.error = Some(error.to_string());
+        panel.dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crate_names_are_snake_case_words() {
+        assert_eq!(crate_name("My Game"), "my_game");
+        assert_eq!(crate_name("Hello-World!! x"), "hello_world_x");
+        assert_eq!(crate_name("__Space__Rocks__"), "space_rocks");
+    }
+
+    #[test]
+    fn crate_names_start_with_a_letter() {
+        assert_eq!(crate_name("2048"), "game_2048");
+        assert_eq!(crate_name("3D Chess"), "game_3d_chess");
+        assert_eq!(crate_name("  "), "game");
+    }
+}
@@ -4,10 +4,11 @@ use bevy::{ log::LogPlugin, prelude::* }; //, winit::WinitSettings };
 
 //use winit::window::Icon;
 
-use beverage::EditorPlugin;
+use beverage::{ project::OpenProjectOnStart, EditorPlugin };
 
 fn main() {
-    App::new()
+    let mut app = App::new();
+    app
         // to make your own editor app, just cut and paste this section
         // into your own app and optionally configure the window title and log filters
 
@@ -37,9 +38,14 @@ fn main() {
 
             // TODO implement this fully and write the documentation
             EditorPlugin,
-        ))
+        ));
 
-        .run();
+    // a project folder, or its manifest, can be opened from the command line
+    if let Some(path) = std::env::args_os().nth(1) {
+        app.insert_resource(OpenProjectOnStart(path.into()));
+    }
+
+    app.run();
 }
 
 // from https://bevy-cheatbook.github.io/window/icon.html
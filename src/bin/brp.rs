@@ -0,0 +1,450 @@
+// A command-line client for any app running the BRP server.
+
+// usage: brp [--url http://127.0.0.1:15702] [--json] <command> [<args>]
+
+// entities can be given as printed by Bevy (5v1) or as the raw bits in the JSON (4294967301), and
+// components by their full type path or by a short name like Transform, as long as only one
+// registered component goes by that name (short names are looked up with LIST).
+
+// results are printed as tables, or as the pretty JSON of the whole response with --json. watch
+// keeps printing components as they change until interrupted.
+
+use std::{ collections::BTreeMap, process::{ self, ExitCode }, thread, time::Duration };
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::{ prelude::Entity, utils::{ get_short_name, HashMap } };
+use serde::{ de::DeserializeOwned, Serialize };
+use serde_json::Value;
+
+use beverage::remote::{
+    builtin_verbs::{
+        resolve_type_path,
+        BrpDestroyRequest,
+        BrpGetRequest,
+        BrpGetResponse,
+        BrpInsertRequest,
+        BrpListRequest,
+        BrpListResponse,
+        BrpQuery,
+        BrpQueryFilter,
+        BrpQueryRequest,
+        BrpQueryResponse,
+    },
+    encoding::BrpEncoding,
+    BrpRequest,
+    CLIENT_HEADER,
+    DEFAULT_PORT,
+};
+
+const USAGE: &str = "\
+usage: brp [--url <server url>] [--json] <command> [<args>]
+
+commands:
+    list [<entity>]                           registered components, or the ones on an entity
+    query [<component>...] [--with <component>] [--without <component>] [--option <component>]
+                                              entities matching a query, with the given components
+    get <entity> <component>...               components of an entity
+    insert <entity> <component> <json>...     add or replace components of an entity
+    destroy <entity>                          despawn an entity
+    watch <entity> <component>... [--interval <ms>]
+                                              print components of an entity whenever they change
+    call <verb> [<json>]                      send any verb, with the given params";
+
+enum Command {
+    List(Option<Entity>),
+    Query {
+        components: Vec<String>,
+        option: Vec<String>,
+        with: Vec<String>,
+        without: Vec<String>,
+    },
+    Get(Entity, Vec<String>),
+    Insert(Entity, Vec<(String, Value)>),
+    Destroy(Entity),
+    Watch(Entity, Vec<String>, Duration),
+    Call(String, Value),
+}
+
+struct CliArgs {
+    url: String,
+    json: bool,
+    command: Command,
+}
+
+// talks to one server, and remembers the registered components once it has asked for them
+struct Session {
+    url: String,
+    // sent as the CLIENT_HEADER, so the IDs of separate invocations don't collide on the server
+    client: String,
+    last_id: u32,
+    registered: Option<Vec<String>>,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut session = Session {
+        url: args.url,
+        client: format!("brp-{}", process::id()),
+        last_id: 0,
+        registered: None,
+    };
+    match run(&mut session, args.command, args.json) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args() -> AnyhowResult<Option<CliArgs>> {
+    let mut url = format!("http://{}:{}", "127.0.0.1", DEFAULT_PORT);
+    let mut json = false;
+    let mut positional = vec![];
+    let mut with = vec![];
+    let mut without = vec![];
+    let mut option = vec![];
+    let mut interval = Duration::from_millis(500);
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| anyhow!("{} needs a value", name));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                return Ok(None);
+            }
+            "--url" => {
+                url = value("--url")?;
+            }
+            "--json" => {
+                json = true;
+            }
+            "--with" => with.push(value("--with")?),
+            "--without" => without.push(value("--without")?),
+            "--option" => option.push(value("--option")?),
+            "--interval" => {
+                interval = Duration::from_millis(value("--interval")?.parse()?);
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let Some(command) = positional.next() else {
+        return Ok(None);
+    };
+    let rest: Vec<String> = positional.collect();
+
+    let command = match command.as_str() {
+        "list" => {
+            match rest.as_slice() {
+                [] => Command::List(None),
+                [entity] => Command::List(Some(parse_entity(entity)?)),
+                _ => {
+                    return Err(anyhow!("list takes at most one entity"));
+                }
+            }
+        }
+        "query" => Command::Query { components: rest, option, with, without },
+        "get" | "watch" => {
+            let (entity, components) = rest
+                .split_first()
+                .ok_or_else(|| anyhow!("{} needs an entity", command))?;
+            if components.is_empty() {
+                return Err(anyhow!("{} needs at least one component", command));
+            }
+            let entity = parse_entity(entity)?;
+            match command.as_str() {
+                "get" => Command::Get(entity, components.to_vec()),
+                _ => Command::Watch(entity, components.to_vec(), interval),
+            }
+        }
+        "insert" => {
+            let (entity, pairs) = rest
+                .split_first()
+                .ok_or_else(|| anyhow!("insert needs an entity"))?;
+            if pairs.is_empty() || pairs.len() % 2 != 0 {
+                return Err(anyhow!("insert needs a JSON value for each component"));
+            }
+            let components = pairs
+                .chunks(2)
+                .map(|pair| {
+                    let value = serde_json::from_str(&pair[1]).map_err(|error| {
+                        anyhow!("Bad JSON for `{}`: {}", pair[0], error)
+                    })?;
+                    Ok((pair[0].clone(), value))
+                })
+                .collect::<AnyhowResult<_>>()?;
+            Command::Insert(parse_entity(entity)?, components)
+        }
+        "destroy" => {
+            match rest.as_slice() {
+                [entity] => Command::Destroy(parse_entity(entity)?),
+                _ => {
+                    return Err(anyhow!("destroy takes exactly one entity"));
+                }
+            }
+        }
+        "call" => {
+            match rest.as_slice() {
+                [verb] => Command::Call(verb.clone(), serde_json::json!({})),
+                [verb, params] => Command::Call(verb.clone(), serde_json::from_str(params)?),
+                _ => {
+                    return Err(anyhow!("call takes a verb and at most one JSON value"));
+                }
+            }
+        }
+        _ => {
+            return Err(anyhow!("Unknown command: `{}`", command));
+        }
+    };
+
+    Ok(Some(CliArgs { url, json, command }))
+}
+
+// either 5v1, as Bevy prints entities, or the bits that BRP sends over the wire
+fn parse_entity(text: &str) -> AnyhowResult<Entity> {
+    let bits = match text.split_once('v') {
+        Some((index, generation)) => {
+            let index: u32 = index.parse()?;
+            let generation: u32 = generation.parse()?;
+            ((generation as u64) << 32) | (index as u64)
+        }
+        None => text.parse()?,
+    };
+
+    Entity::try_from_bits(bits).map_err(|_| anyhow!("Not an entity: `{}`", text))
+}
+
+fn run(session: &mut Session, command: Command, json: bool) -> AnyhowResult<()> {
+    match command {
+        Command::List(entity) => {
+            let response: BrpListResponse = session.call("LIST", BrpListRequest { entity })?;
+            if json {
+                return print_json(&response);
+            }
+
+            let mut components = response.components;
+            components.sort();
+            for component in components {
+                println!("{}", component);
+            }
+        }
+        Command::Query { components, option, with, without } => {
+            let request = BrpQueryRequest {
+                data: BrpQuery {
+                    components: session.resolve_all(&components)?,
+                    option: session.resolve_all(&option)?,
+                    ..Default::default()
+                },
+                filter: BrpQueryFilter {
+                    with: session.resolve_all(&with)?,
+                    without: session.resolve_all(&without)?,
+                },
+            };
+            let columns: Vec<String> = request.data.components
+                .iter()
+                .chain(&request.data.option)
+                .cloned()
+                .collect();
+            let response: BrpQueryResponse = session.call("QUERY", request)?;
+            if json {
+                return print_json(&response);
+            }
+
+            let mut header = vec!["entity".to_string()];
+            header.extend(columns.iter().map(|path| get_short_name(path)));
+            let rows = response.rows
+                .iter()
+                .map(|row| {
+                    let mut cells = vec![row.entity.to_string()];
+                    cells.extend(
+                        columns.iter().map(|path| {
+                            row.components.get(path).map(compact).unwrap_or_default()
+                        })
+                    );
+                    cells
+                })
+                .collect();
+            print_table(header, rows);
+            println!("({} row(s))", response.rows.len());
+        }
+        Command::Get(entity, components) => {
+            let components = session.resolve_all(&components)?;
+            let response: BrpGetResponse = session.call("GET", BrpGetRequest {
+                entity,
+                components,
+            })?;
+            if json {
+                return print_json(&response);
+            }
+
+            print_components(&response.components);
+        }
+        Command::Insert(entity, components) => {
+            let components = components
+                .into_iter()
+                .map(|(name, value)| Ok((session.resolve(&name)?, value)))
+                .collect::<AnyhowResult<HashMap<String, Value>>>()?;
+            let response: Value = session.call("INSERT", BrpInsertRequest { entity, components })?;
+            if json {
+                return print_json(&response);
+            }
+
+            println!("ok");
+        }
+        Command::Destroy(entity) => {
+            let response: Value = session.call("DESTROY", BrpDestroyRequest { entity })?;
+            if json {
+                return print_json(&response);
+            }
+
+            println!("ok");
+        }
+        Command::Watch(entity, components, interval) => {
+            let components = session.resolve_all(&components)?;
+            let mut previous: HashMap<String, Value> = HashMap::new();
+            loop {
+                let response: BrpGetResponse = session.call("GET", BrpGetRequest {
+                    entity,
+                    components: components.clone(),
+                })?;
+
+                // only what changed since the last poll
+                let changed: HashMap<String, Value> = response.components
+                    .into_iter()
+                    .filter(|(path, value)| previous.get(path) != Some(value))
+                    .collect();
+                if !changed.is_empty() {
+                    if json {
+                        print_json(&changed)?;
+                    } else {
+                        print_components(&changed);
+                    }
+                    previous.extend(changed);
+                }
+
+                thread::sleep(interval);
+            }
+        }
+        Command::Call(verb, params) => {
+            let response: Value = session.call(&verb, params)?;
+            print_json(&response)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl Session {
+    // send a request and wait for the response, turning an ERROR status into an error
+    fn call<P: Serialize, T: DeserializeOwned>(&mut self, verb: &str, params: P) -> AnyhowResult<T> {
+        self.last_id += 1;
+        let request = BrpRequest {
+            request: verb.to_string(),
+            id: self.last_id.into(),
+            params: serde_json::to_value(params)?,
+        };
+
+        let encoding = BrpEncoding::Json;
+        let http_request = ehttp::Request {
+            headers: ehttp::Headers::new(
+                &[("Content-Type", encoding.content_type()), (CLIENT_HEADER, &self.client)]
+            ),
+            ..ehttp::Request::post(&self.url, encoding.encode(&request)?)
+        };
+        let response = ehttp
+            ::fetch_blocking(&http_request)
+            .map_err(|error| anyhow!("Can't reach {}: {}", self.url, error))?;
+        let mut value: Value = encoding.decode(&response.bytes)?;
+
+        if value.get("status").and_then(Value::as_str) == Some("ERROR") {
+            let message = value.get("message").and_then(Value::as_str).unwrap_or_default();
+            return Err(anyhow!("{} failed: {}", verb, message));
+        }
+
+        // the envelope isn't part of the result
+        if let Value::Object(map) = &mut value {
+            map.remove("status");
+            map.remove("id");
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    // the full type path of a component, looking short names up in the registered components
+    // (the server would resolve them too, but the tables need the full paths to find the columns)
+    fn resolve(&mut self, name: &str) -> AnyhowResult<String> {
+        if self.registered.is_none() {
+            let response: BrpListResponse = self.call("LIST", BrpListRequest { entity: None })?;
+            self.registered = Some(response.components);
+        }
+
+        let registered = self.registered.iter().flatten().map(String::as_str);
+        Ok(resolve_type_path(name, registered)?.to_string())
+    }
+
+    fn resolve_all(&mut self, names: &[String]) -> AnyhowResult<Vec<String>> {
+        names
+            .iter()
+            .map(|name| self.resolve(name))
+            .collect()
+    }
+}
+
+fn compact(value: &Value) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn print_json<T: Serialize>(value: &T) -> AnyhowResult<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+// one row per component, by short name, sorted so the output is stable
+fn print_components(components: &HashMap<String, Value>) {
+    let components: BTreeMap<String, &Value> = components
+        .iter()
+        .map(|(path, value)| (get_short_name(path), value))
+        .collect();
+    let rows = components
+        .into_iter()
+        .map(|(name, value)| vec![name, compact(value)])
+        .collect();
+    print_table(vec!["component".to_string(), "value".to_string()], rows);
+}
+
+fn print_table(header: Vec<String>, rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = header.iter().map(String::len).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let print_row = |cells: &[String]| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+
+    print_row(&header);
+    let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    print_row(&rule);
+    for row in rows {
+        print_row(&row);
+    }
+}
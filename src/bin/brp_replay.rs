@@ -0,0 +1,249 @@
+// Replays a BRP recording against a running server and reports where the responses differ.
+
+// usage: brp_replay <recording.jsonl> [--url http://127.0.0.1:15702] [--speed 1.0]
+
+// --speed scales the original pace, so 4 replays four times faster and 0 sends everything back to
+// back. requests are sent in the order they originally arrived, each after the previous response,
+// except where the recording shows the next request went out first (like a POLL sent while an
+// AWAIT is still waiting). those responses are waited for in the background.
+
+// record with EditorRemotePlugin::with_recording (or BRP_RECORD=file.jsonl in the server example),
+// then point this at a freshly started server. it exits with an error if any response differed,
+// so it can also be used to guard the verb implementations against regressions.
+
+use std::{
+    collections::HashSet,
+    process::{ self, ExitCode },
+    sync::mpsc::{ self, Receiver, RecvTimeoutError, Sender },
+    thread,
+    time::{ Duration, Instant },
+};
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use serde_json::Value;
+
+use beverage::remote::{
+    encoding::BrpEncoding,
+    recorder::{ diff_responses, read_recording, BrpEntityMap, BrpRecord },
+    BrpRequest,
+    CLIENT_HEADER,
+    DEFAULT_PORT,
+};
+
+// how long to wait for a response before counting it as missing
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+const USAGE: &str = "usage: brp_replay <recording.jsonl> [--url <server url>] [--speed <factor>]";
+
+struct ReplayArgs {
+    path: String,
+    url: String,
+    speed: f64,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match replay(&args) {
+        Ok(0) => {
+            println!("all responses matched");
+            ExitCode::SUCCESS
+        }
+        Ok(mismatches) => {
+            println!("{} response(s) differed", mismatches);
+            ExitCode::FAILURE
+        }
+        Err(error) => {
+            eprintln!("replay failed: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args() -> AnyhowResult<Option<ReplayArgs>> {
+    let mut path = None;
+    let mut url = format!("http://{}:{}", "127.0.0.1", DEFAULT_PORT);
+    let mut speed = 1.0;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                return Ok(None);
+            }
+            "--url" => {
+                url = args.next().ok_or_else(|| anyhow!("--url needs a value"))?;
+            }
+            "--speed" => {
+                let value = args.next().ok_or_else(|| anyhow!("--speed needs a value"))?;
+                speed = value.parse()?;
+                if speed < 0.0 {
+                    return Err(anyhow!("--speed can't be negative"));
+                }
+            }
+            _ => {
+                path = Some(arg);
+            }
+        }
+    }
+
+    let path = path.ok_or_else(|| anyhow!("no recording given"))?;
+    Ok(Some(ReplayArgs { path, url, speed }))
+}
+
+// returns the number of responses that didn't match the recording
+fn replay(args: &ReplayArgs) -> AnyhowResult<usize> {
+    let records = read_recording(&args.path)?;
+    println!("replaying {} request(s) to {}", records.len(), args.url);
+
+    let mut replay = Replay::new(&records);
+    let mut previous = None;
+
+    // named for the server, so the recorded IDs don't collide with another replay's
+    let client = format!("brp_replay-{}", process::id());
+
+    for (index, record) in records.iter().enumerate() {
+        // keep the original pace between requests, scaled by the speed factor
+        if let Some(previous) = previous {
+            let delay = record.requested.saturating_sub(previous) as f64;
+            if args.speed > 0.0 && delay > 0.0 {
+                thread::sleep(Duration::from_secs_f64(delay / 1000.0 / args.speed));
+            }
+        }
+        previous = Some(record.requested);
+
+        // the fresh server may know the entities by different IDs
+        let mut request = record.request.clone();
+        replay.entity_map.apply(&mut request.params);
+        send(&args.url, &client, &request, index, replay.sender.clone())?;
+
+        // the next request may need an entity this one makes, unless it went out before the
+        // response came back
+        let overlapped = records
+            .get(index + 1)
+            .is_some_and(|next| next.requested < record.responded);
+        if !overlapped {
+            replay.wait_for(Some(index))?;
+        }
+    }
+
+    replay.wait_for(None)?;
+    Ok(replay.mismatches)
+}
+
+// the responses that have come back so far, and what they taught us about entity IDs
+struct Replay<'a> {
+    records: &'a [BrpRecord],
+    entity_map: BrpEntityMap,
+    outstanding: HashSet<usize>,
+    mismatches: usize,
+
+    sender: Sender<(usize, AnyhowResult<Value>)>,
+    receiver: Receiver<(usize, AnyhowResult<Value>)>,
+}
+
+impl<'a> Replay<'a> {
+    fn new(records: &'a [BrpRecord]) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            records,
+            entity_map: BrpEntityMap::default(),
+            outstanding: (0..records.len()).collect(),
+            mismatches: 0,
+            sender,
+            receiver,
+        }
+    }
+
+    // checks responses as they come in, until the one for `index` is in (or every one, if there
+    // isn't an index), giving up on those after the RESPONSE_TIMEOUT
+    fn wait_for(&mut self, index: Option<usize>) -> AnyhowResult<()> {
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        let waited_for = |outstanding: &HashSet<usize>| -> Vec<usize> {
+            let mut waited_for: Vec<usize> = outstanding
+                .iter()
+                .copied()
+                .filter(|outstanding| index.map_or(true, |index| index == *outstanding))
+                .collect();
+            waited_for.sort();
+            waited_for
+        };
+
+        while !waited_for(&self.outstanding).is_empty() {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.receiver.recv_timeout(timeout) {
+                Ok((index, actual)) => {
+                    // (unless it's too late for it)
+                    if self.outstanding.remove(&index) {
+                        self.check(index, actual?);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => {
+                    for index in waited_for(&self.outstanding) {
+                        self.outstanding.remove(&index);
+                        let request = &self.records[index].request;
+                        self.mismatches += 1;
+                        println!("MISMATCH {} {}", request.request, request.id);
+                        println!("    no response");
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check(&mut self, index: usize, actual: Value) {
+        let record = &self.records[index];
+        self.entity_map.learn(&record.response, &actual);
+
+        let mut expected = record.response.clone();
+        self.entity_map.apply(&mut expected);
+
+        let request = &record.request;
+        let differences = diff_responses(&expected, &actual);
+        if differences.is_empty() {
+            println!("ok       {} {}", request.request, request.id);
+        } else {
+            self.mismatches += 1;
+            println!("MISMATCH {} {}", request.request, request.id);
+            for difference in differences {
+                println!("    {}", difference);
+            }
+        }
+    }
+}
+
+// sends a request in the background, and the response on to the replay when it comes
+fn send(
+    url: &str,
+    client: &str,
+    request: &BrpRequest,
+    index: usize,
+    sender: Sender<(usize, AnyhowResult<Value>)>
+) -> AnyhowResult<()> {
+    let encoding = BrpEncoding::Json;
+    let request = ehttp::Request {
+        headers: ehttp::Headers::new(
+            &[("Content-Type", encoding.content_type()), (CLIENT_HEADER, client)]
+        ),
+        ..ehttp::Request::post(url, encoding.encode(request)?)
+    };
+
+    ehttp::fetch(request, move |response| {
+        let response = response
+            .map_err(|error| anyhow!(error))
+            .and_then(|response| encoding.decode(&response.bytes));
+        let _ = sender.send((index, response));
+    });
+    Ok(())
+}
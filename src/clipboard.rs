@@ -0,0 +1,756 @@
+//! The clipboard holds copies of entities, along with their descendants, or of single components,
+//! to paste into the editor or into any game it is connected to.
+//!
+//! What was copied is kept by the [`ClipboardService`] and put on the system clipboard as JSON,
+//! with components serialized the way BRP sends them, so that it can be pasted into another
+//! instance of the editor too (or looked at and fixed up by hand). Pasting takes whatever the
+//! system clipboard holds if the editor can make sense of it, and what was copied last in the
+//! editor otherwise.
+//!
+//! Entities are copied from the world of the primary selected entity. They are pasted next to the
+//! primary selected entity (under the same parent), or at the top of the world the focused outliner
+//! shows if nothing is selected, and the copies that refer to each other (like parents and
+//! children) are pointed at each other rather than at the originals. Copied components are pasted
+//! onto every selected entity, once they have all been read. Remote entities are read with
+//! [`history::remote::copy`] and pasted with a [`RemoteEdit::Spawn`], and either way, cutting and
+//! pasting can be undone.
+
+use std::any::TypeId;
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::{ prelude::*, reflect::TypePath, utils::{ get_short_name, HashMap, HashSet } };
+
+use bevy_simple_text_input::TextInputInactive;
+use leafwing_input_manager::prelude::*;
+use serde::{ Deserialize, Serialize };
+use serde_json::Value;
+use sickle_ui::prelude::*;
+
+use crate::{
+    framework::*,
+    history::{
+        self,
+        remote::{ RemoteCopied, RemoteEdit, RemoteEntity, RemoteSnapshot },
+        EntitySnapshot,
+        HistoryOperation,
+        HistoryPlugin,
+        HistoryStep,
+    },
+    input::InputAction,
+    remote::builtin_verbs::{ get_reflect_component, remap_entities_in_json },
+    selection::{ SelectionPlugin, SelectionService },
+    widget::{ inspector::form::{ reflect_from_json, reflect_to_json }, outliner::OutlinerPanel },
+};
+
+pub struct ClipboardPlugin;
+
+impl Plugin for ClipboardPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<SelectionPlugin>() {
+            app.add_plugins(SelectionPlugin);
+        }
+        if !app.is_plugin_added::<HistoryPlugin>() {
+            app.add_plugins(HistoryPlugin);
+        }
+
+        // (without a system clipboard, like on a machine with no display, the editor's own is used)
+        match arboard::Clipboard::new() {
+            Ok(clipboard) => {
+                app.insert_non_send_resource(SystemClipboard(clipboard));
+            }
+            Err(error) => warn!("The system clipboard isn't available: {}", error),
+        }
+
+        app.init_resource::<ClipboardService>().add_systems(
+            Update,
+            (
+                clipboard_on_input,
+                copy_on_menu_item,
+                cut_on_menu_item,
+                paste_on_menu_item,
+                receive_remote_copies,
+            )
+                .after(WidgetLibraryUpdate)
+                .run_if(in_state(EditorState::Running))
+        );
+    }
+}
+
+/// The clipboard service holds what was copied last in the editor.
+#[derive(Resource, Default, Debug)]
+pub struct ClipboardService {
+    content: Option<ClipboardContent>,
+
+    // the remote entities being read, until the game answers
+    pending: Option<PendingCopy>,
+}
+
+impl ClipboardService {
+    /// What was copied last in the editor, which isn't necessarily what is on the system clipboard.
+    pub fn content(&self) -> Option<&ClipboardContent> {
+        self.content.as_ref()
+    }
+}
+
+/// What goes on the clipboard: entities, or components to paste onto other entities.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ClipboardContent {
+    /// Entities along with their descendants, parents before their children.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<ClipboardEntity>,
+
+    /// Serialized components by full type path.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub components: HashMap<String, Value>,
+}
+
+impl ClipboardContent {
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty() && self.components.is_empty()
+    }
+
+    fn from_remote(snapshots: &[RemoteSnapshot]) -> Self {
+        let copied: HashSet<Entity> = snapshots
+            .iter()
+            .map(|snapshot| snapshot.entity.entity)
+            .collect();
+        let entities = snapshots
+            .iter()
+            .map(|snapshot| ClipboardEntity {
+                entity: snapshot.entity.entity,
+                parent: snapshot.parent
+                    .as_ref()
+                    .map(|parent| parent.entity)
+                    .filter(|parent| copied.contains(parent)),
+                components: snapshot.components.clone(),
+            })
+            .collect();
+
+        Self {
+            entities,
+            ..default()
+        }
+    }
+}
+
+/// A copied entity, by the ID it had where it was copied from.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ClipboardEntity {
+    pub entity: Entity,
+
+    /// The parent, if it was copied along with the entity.
+    #[serde(default)]
+    pub parent: Option<Entity>,
+
+    /// Serialized components by full type path, but for `Parent` and `Children`.
+    pub components: HashMap<String, Value>,
+}
+
+impl ClipboardEntity {
+    fn name(&self) -> Option<String> {
+        self.components.get(Name::type_path()).and_then(Value::as_str).map(str::to_owned)
+    }
+}
+
+// the clipboard of the OS, which may only be used from the main thread on some of them
+struct SystemClipboard(arboard::Clipboard);
+
+#[derive(Debug)]
+struct PendingCopy {
+    connection: String,
+
+    // the entities to destroy once they have been read, if they are being cut
+    cut: Option<Vec<Entity>>,
+}
+
+/// Copies the selected entities, along with their descendants.
+pub fn copy(world: &mut World) {
+    if let Err(error) = copy_selected(world, false) {
+        warn!("Could not copy: {}", error);
+    }
+}
+
+/// Copies the selected entities, along with their descendants, and deletes them.
+pub fn cut(world: &mut World) {
+    if let Err(error) = copy_selected(world, true) {
+        warn!("Could not cut: {}", error);
+    }
+}
+
+/// Pastes what was copied last: entities next to the primary selected entity (or at the top of the
+/// world the focused outliner shows), or components onto every selected entity.
+pub fn paste(world: &mut World) {
+    let Some(content) = take_content(world) else {
+        return;
+    };
+
+    let result = if content.entities.is_empty() {
+        paste_components(world, &content.components)
+    } else {
+        paste_entities(world, &content.entities)
+    };
+    if let Err(error) = result {
+        warn!("Could not paste: {}", error);
+    }
+}
+
+/// Puts something on the clipboard, both the editor's and the system's.
+pub fn put(world: &mut World, content: ClipboardContent) {
+    if let Some(mut clipboard) = world.get_non_send_resource_mut::<SystemClipboard>() {
+        let copied = serde_json::to_string_pretty(&content)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| clipboard.0.set_text(text).map_err(anyhow::Error::from));
+        if let Err(error) = copied {
+            warn!("Could not copy to the system clipboard: {}", error);
+        }
+    }
+
+    world.resource_mut::<ClipboardService>().content = Some(content);
+}
+
+// what is on the system clipboard if the editor can paste it, or what was copied in the editor
+fn take_content(world: &mut World) -> Option<ClipboardContent> {
+    let system = world
+        .get_non_send_resource_mut::<SystemClipboard>()
+        .and_then(|mut clipboard| clipboard.0.get_text().ok())
+        .and_then(|text| serde_json::from_str::<ClipboardContent>(&text).ok())
+        .filter(|content| !content.is_empty());
+
+    system.or_else(|| world.resource::<ClipboardService>().content.clone())
+}
+
+fn copy_selected(world: &mut World, cut: bool) -> AnyhowResult<()> {
+    let selection = world.resource::<SelectionService>();
+    let Some(primary) = selection.primary() else {
+        return Ok(());
+    };
+
+    // (only one world can be copied from at a time)
+    let origin = primary.world();
+    let entities: Vec<Entity> = selection
+        .selected()
+        .iter()
+        .filter(|selected| selected.world() == origin)
+        .map(EditorEntity::entity)
+        .collect();
+
+    match origin {
+        EditorWorld::Local => {
+            world.resource_mut::<ClipboardService>().pending = None;
+
+            // an entity below another one is copied along with that one
+            let roots: Vec<Entity> = entities
+                .iter()
+                .copied()
+                .filter(|entity| !has_ancestor_among(world, *entity, &entities))
+                .collect();
+            let content = copy_local_entities(world, &roots)?;
+            put(world, content);
+
+            if cut {
+                delete_local_entities(world, &roots)?;
+            }
+        }
+        EditorWorld::Remote { connection } => {
+            // the game is read first, see receive_remote_copies
+            world.resource_mut::<ClipboardService>().pending = Some(PendingCopy {
+                connection: connection.clone(),
+                cut: cut.then(|| entities.clone()),
+            });
+            history::remote::copy(world, &connection, None, entities);
+        }
+    }
+
+    Ok(())
+}
+
+fn has_ancestor_among(world: &World, entity: Entity, among: &[Entity]) -> bool {
+    let mut entity = entity;
+    while let Some(parent) = world.get::<Parent>(entity) {
+        entity = parent.get();
+        if among.contains(&entity) {
+            return true;
+        }
+    }
+    false
+}
+
+fn copy_local_entities(world: &World, roots: &[Entity]) -> AnyhowResult<ClipboardContent> {
+    let type_registry = world.resource::<AppTypeRegistry>().read();
+
+    let mut entities = vec![];
+    for root in roots {
+        for snapshot in EntitySnapshot::take_recursive(world, *root)? {
+            // (components that can't be serialized, like handles to assets, are left behind)
+            let components = snapshot.components
+                .iter()
+                .filter_map(|value| {
+                    let type_path = value.get_represented_type_info()?.type_path();
+                    let json = reflect_to_json(&**value, &type_registry).ok()?;
+                    Some((type_path.to_string(), json))
+                })
+                .collect();
+
+            entities.push(ClipboardEntity {
+                entity: snapshot.entity,
+                parent: snapshot.parent.filter(|_| snapshot.entity != *root),
+                components,
+            });
+        }
+    }
+
+    Ok(ClipboardContent {
+        entities,
+        ..default()
+    })
+}
+
+fn delete_local_entities(world: &mut World, roots: &[Entity]) -> AnyhowResult<()> {
+    let mut operations = vec![];
+    for root in roots {
+        let entities = EntitySnapshot::take_recursive(world, *root)?;
+        world.entity_mut(*root).despawn_recursive();
+        operations.push(HistoryOperation::Despawn { entities });
+    }
+    history::record(world, HistoryStep::new("Cut", operations));
+
+    let cut = |selected: &EditorEntity| {
+        matches!(selected, EditorEntity::Local(entity) if roots.contains(entity))
+    };
+    world.resource_mut::<SelectionService>().retain(|selected| !cut(selected));
+    Ok(())
+}
+
+// carry on copying (or cutting) once the game has said what the entities are like
+fn receive_remote_copies(
+    mut copies: EventReader<RemoteCopied>,
+    mut clipboard: ResMut<ClipboardService>,
+    mut commands: Commands
+) {
+    for copied in copies.read() {
+        // (anything copied since is what goes on the clipboard, and panels copy for themselves)
+        let is_pending = clipboard.pending
+            .as_ref()
+            .is_some_and(|pending| pending.connection == copied.connection);
+        if copied.origin.is_some() || !is_pending {
+            continue;
+        }
+        let pending = clipboard.pending.take().unwrap();
+
+        if let Some(error) = &copied.error {
+            warn!("Could not copy from {}: {}", copied.connection, error);
+            continue;
+        }
+
+        let content = ClipboardContent::from_remote(&copied.entities);
+        let connection = copied.connection.clone();
+        commands.add(move |world: &mut World| {
+            put(world, content);
+
+            if let Some(entities) = pending.cut {
+                let edit = RemoteEdit::Destroy { entities };
+                let step = HistoryStep::new("Cut", vec![]);
+                history::remote::edit(world, &connection, None, edit, step);
+            }
+        });
+    }
+}
+
+fn paste_entities(world: &mut World, entities: &[ClipboardEntity]) -> AnyhowResult<()> {
+    let selection = world.resource::<SelectionService>();
+    let (target, sibling) = match selection.primary() {
+        Some(primary) => (primary.world(), Some(primary.entity())),
+        None => {
+            // (the focused outliner, or else any one)
+            let focused = selection.focused();
+            let mut q_panels = world.query::<(Entity, &OutlinerPanel)>();
+            let panel = q_panels
+                .iter(world)
+                .find(|(panel, _)| Some(*panel) == focused)
+                .or_else(|| q_panels.iter(world).next());
+            let target = panel.and_then(|(_, panel)| panel.world()).unwrap_or(EditorWorld::Local);
+            (target, None)
+        }
+    };
+
+    match target {
+        EditorWorld::Local => {
+            let parent = sibling.and_then(|sibling| world.get::<Parent>(sibling)).map(Parent::get);
+            paste_local_entities(world, entities, parent)
+        }
+        EditorWorld::Remote { connection } => {
+            // (the parents of copied entities are only known by the IDs they were copied with)
+            let entities = entities
+                .iter()
+                .map(|copied| RemoteSnapshot {
+                    entity: RemoteEntity {
+                        entity: copied.entity,
+                        name: copied.name(),
+                    },
+                    parent: copied.parent.map(|entity| RemoteEntity { entity, name: None }),
+                    components: copied.components.clone(),
+                })
+                .collect();
+
+            let origin = world.resource::<SelectionService>().focused();
+            // (the game is asked for the parent of the sibling along the way)
+            let edit = RemoteEdit::Spawn { entities, sibling };
+            let step = HistoryStep::new("Paste", vec![]);
+            history::remote::edit(world, &connection, origin, edit, step);
+            Ok(())
+        }
+    }
+}
+
+fn paste_local_entities(
+    world: &mut World,
+    entities: &[ClipboardEntity],
+    parent: Option<Entity>
+) -> AnyhowResult<()> {
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+
+    // every copy exists before any components go in, so they can refer to each other
+    let copies: HashMap<Entity, Entity> = entities
+        .iter()
+        .map(|copied| (copied.entity, world.spawn_empty().id()))
+        .collect();
+
+    let mut unknown = HashSet::new();
+    for copied in entities {
+        let copy = copies[&copied.entity];
+        for (type_path, json) in &copied.components {
+            let registration = type_registry.get_with_type_path(type_path);
+            let reflect_component = registration.and_then(|registration| {
+                registration.data::<ReflectComponent>()
+            });
+            let (Some(registration), Some(reflect_component)) = (
+                registration,
+                reflect_component,
+            ) else {
+                unknown.insert(type_path.clone());
+                continue;
+            };
+            if registration.type_id() == TypeId::of::<Parent>() ||
+                registration.type_id() == TypeId::of::<Children>() {
+                continue;
+            }
+
+            let mut json = json.clone();
+            remap_entities_in_json(&mut json, &copies);
+            match reflect_from_json(registration, &json, &type_registry) {
+                Ok(value) => {
+                    reflect_component.insert(&mut world.entity_mut(copy), &*value, &type_registry);
+                }
+                Err(error) => warn!("Could not paste `{}`: {}", type_path, error),
+            }
+        }
+
+        // (the copies whose parent wasn't copied go under the target)
+        let copy_parent = copied.parent.and_then(|parent| copies.get(&parent).copied());
+        if let Some(parent) = copy_parent.or(parent) {
+            world.entity_mut(copy).set_parent(parent);
+        }
+    }
+    if !unknown.is_empty() {
+        let mut unknown: Vec<String> = unknown.into_iter().collect();
+        unknown.sort();
+        warn!("Pasted without the components the editor doesn't know: {}", unknown.join(", "));
+    }
+
+    // what undo takes back is the copies at the top, with everything below them
+    let roots: Vec<Entity> = entities
+        .iter()
+        .filter(|copied| !copied.parent.is_some_and(|parent| copies.contains_key(&parent)))
+        .map(|copied| copies[&copied.entity])
+        .collect();
+    let mut operations = vec![];
+    for root in &roots {
+        let entities = EntitySnapshot::take_recursive(world, *root)?;
+        operations.push(HistoryOperation::Spawn { entities });
+    }
+    history::record(world, HistoryStep::new("Paste", operations));
+
+    world.resource_mut::<SelectionService>().select_all(roots.into_iter().map(EditorEntity::Local));
+    Ok(())
+}
+
+fn paste_components(world: &mut World, components: &HashMap<String, Value>) -> AnyhowResult<()> {
+    let selection = world.resource::<SelectionService>();
+    let selected = selection.selected().to_vec();
+    let origin = selection.focused();
+
+    let label = match components.keys().next() {
+        Some(component) if components.len() == 1 => format!("Paste {}", get_short_name(component)),
+        _ => "Paste".to_string(),
+    };
+
+    // (everything is read before anything is pasted, so that no target gets only part of it)
+    let local = selected.iter().any(|target| matches!(target, EditorEntity::Local(_)));
+    let values = if local { read_local_components(world, components)? } else { vec![] };
+
+    let mut operations = vec![];
+    for target in selected {
+        match target {
+            EditorEntity::Local(entity) => {
+                match paste_local_components(world, entity, &values) {
+                    Ok(pasted) => operations.extend(pasted),
+                    Err(error) => warn!("Could not paste the components: {}", error),
+                }
+            }
+            EditorEntity::Remote { connection, entity } => {
+                let edit = RemoteEdit::Insert {
+                    entity,
+                    components: components.clone(),
+                };
+                let step = HistoryStep::new(label.clone(), vec![]);
+                history::remote::edit(world, &connection, origin, edit, step);
+            }
+        }
+    }
+    history::record(world, HistoryStep::new(label, operations));
+
+    Ok(())
+}
+
+// the copied components as the editor's own values, by full type path
+fn read_local_components(
+    world: &World,
+    components: &HashMap<String, Value>
+) -> AnyhowResult<Vec<(String, Box<dyn Reflect>)>> {
+    let type_registry = world.resource::<AppTypeRegistry>().read();
+
+    let mut values = vec![];
+    for (type_path, json) in components {
+        get_reflect_component(&type_registry, type_path)?;
+        let registration = type_registry
+            .get_with_type_path(type_path)
+            .ok_or_else(|| anyhow!("Component `{}` isn't registered", type_path))?;
+        values.push((type_path.clone(), reflect_from_json(registration, json, &type_registry)?));
+    }
+
+    Ok(values)
+}
+
+fn paste_local_components(
+    world: &mut World,
+    entity: Entity,
+    values: &[(String, Box<dyn Reflect>)]
+) -> AnyhowResult<Vec<HistoryOperation>> {
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+    if world.get_entity(entity).is_none() {
+        return Err(anyhow!("Entity {:?} not found", entity));
+    }
+
+    let mut operations = vec![];
+    for (type_path, after) in values {
+        let reflect_component = get_reflect_component(&type_registry, type_path)?;
+        let after = after.clone_value();
+
+        let before = reflect_component
+            .reflect(world.entity(entity))
+            .map(|before| before.clone_value());
+        reflect_component.insert(&mut world.entity_mut(entity), &*after, &type_registry);
+
+        operations.push(match before {
+            Some(before) => HistoryOperation::Change { entity, before, after },
+            None => HistoryOperation::Insert { entity, component: after },
+        });
+    }
+
+    Ok(operations)
+}
+
+fn clipboard_on_input(
+    action_state: Res<ActionState<InputAction>>,
+    q_text_inputs: Query<&TextInputInactive>,
+    mut commands: Commands
+) {
+    // (typing into a field leaves the clipboard alone)
+    if q_text_inputs.iter().any(|inactive| !inactive.0) {
+        return;
+    }
+
+    if action_state.just_pressed(&InputAction::Copy) {
+        commands.add(copy);
+    } else if action_state.just_pressed(&InputAction::Cut) {
+        commands.add(cut);
+    } else if action_state.just_pressed(&InputAction::Paste) {
+        commands.add(paste);
+    }
+}
+
+fn copy_on_menu_item(
+    q_menu_items: Query<&MenuItem, (With<CopyButton>, Changed<MenuItem>)>,
+    mut commands: Commands
+) {
+    let Ok(item) = q_menu_items.get_single() else {
+        return;
+    };
+
+    if item.interacted() {
+        commands.add(copy);
+    }
+}
+
+fn cut_on_menu_item(
+    q_menu_items: Query<&MenuItem, (With<CutButton>, Changed<MenuItem>)>,
+    mut commands: Commands
+) {
+    let Ok(item) = q_menu_items.get_single() else {
+        return;
+    };
+
+    if item.interacted() {
+        commands.add(cut);
+    }
+}
+
+fn paste_on_menu_item(
+    q_menu_items: Query<&MenuItem, (With<PasteButton>, Changed<MenuItem>)>,
+    mut commands: Commands
+) {
+    let Ok(item) = q_menu_items.get_single() else {
+        return;
+    };
+
+    if item.interacted() {
+        commands.add(paste);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::service::EditorService;
+    use super::*;
+
+    // a component that refers to another entity, the way a turret aims at its target
+    #[derive(Component, Reflect)]
+    #[reflect(Component)]
+    struct Aim(Entity);
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.init_resource::<AppTypeRegistry>();
+        world.init_resource::<EditorService>();
+        world.init_resource::<SelectionService>();
+        {
+            let mut type_registry = world.resource::<AppTypeRegistry>().write();
+            type_registry.register::<Name>();
+            type_registry.register::<Parent>();
+            type_registry.register::<Children>();
+            type_registry.register::<Aim>();
+        }
+        world
+    }
+
+    fn named(world: &mut World, name: &str) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = world
+            .query::<(Entity, &Name)>()
+            .iter(world)
+            .filter(|(_, named)| named.as_str() == name)
+            .map(|(entity, _)| entity)
+            .collect();
+        entities.sort();
+        entities
+    }
+
+    // the one entity with a name that isn't `original`
+    fn copy_of(world: &mut World, name: &str, original: Entity) -> Entity {
+        let copies: Vec<Entity> = named(world, name)
+            .into_iter()
+            .filter(|entity| *entity != original)
+            .collect();
+        assert_eq!(copies.len(), 1);
+        copies[0]
+    }
+
+    #[test]
+    fn copies_refer_to_each_other_rather_than_to_the_originals() {
+        let mut world = world();
+        let turret = world.spawn(Name::new("Turret")).id();
+        let barrel = world.spawn(Name::new("Barrel")).set_parent(turret).id();
+        world.entity_mut(turret).insert(Aim(barrel));
+
+        let content = copy_local_entities(&world, &[turret]).unwrap();
+        paste_local_entities(&mut world, &content.entities, None).unwrap();
+
+        let turret_copy = copy_of(&mut world, "Turret", turret);
+        let barrel_copy = copy_of(&mut world, "Barrel", barrel);
+        assert_eq!(world.get::<Aim>(turret_copy).unwrap().0, barrel_copy);
+        assert_eq!(world.get::<Parent>(barrel_copy).map(Parent::get), Some(turret_copy));
+        assert_eq!(world.get::<Aim>(turret).unwrap().0, barrel);
+        assert_eq!(
+            world.resource::<SelectionService>().selected(),
+            [EditorEntity::Local(turret_copy)]
+        );
+
+        // (and undoing the paste takes the copies back, children and all)
+        history::undo(&mut world);
+        assert_eq!(named(&mut world, "Turret"), [turret]);
+        assert_eq!(named(&mut world, "Barrel"), [barrel]);
+    }
+
+    #[test]
+    fn copies_go_under_the_target_unless_their_parent_was_copied_too() {
+        let mut world = world();
+        let level = world.spawn(Name::new("Level")).id();
+        let crate_ = world.spawn(Name::new("Crate")).id();
+        let lid = world.spawn(Name::new("Lid")).set_parent(crate_).id();
+
+        let content = copy_local_entities(&world, &[lid]).unwrap();
+        assert_eq!(content.entities[0].parent, None);
+        paste_local_entities(&mut world, &content.entities, Some(level)).unwrap();
+
+        let lid_copy = copy_of(&mut world, "Lid", lid);
+        assert_eq!(world.get::<Parent>(lid_copy).map(Parent::get), Some(level));
+    }
+
+    #[test]
+    fn entities_copied_from_games_keep_only_the_parents_copied_with_them() {
+        let entity = |index, name: &str| RemoteEntity {
+            entity: Entity::from_raw(index),
+            name: Some(name.to_owned()),
+        };
+        let snapshot = |index, name, parent: Option<RemoteEntity>| RemoteSnapshot {
+            entity: entity(index, name),
+            parent,
+            components: [(Name::type_path().to_owned(), Value::from(name))].into_iter().collect(),
+        };
+        let content = ClipboardContent::from_remote(&[
+            snapshot(1, "Crate", Some(entity(0, "Level"))),
+            snapshot(2, "Lid", Some(entity(1, "Crate"))),
+        ]);
+
+        assert_eq!(content.entities[0].parent, None);
+        assert_eq!(content.entities[1].parent, Some(Entity::from_raw(1)));
+        assert_eq!(content.entities[1].name().as_deref(), Some("Lid"));
+
+        // (which is what goes on the system clipboard, and comes back from it)
+        let json = serde_json::to_string(&content).unwrap();
+        assert_eq!(serde_json::from_str::<ClipboardContent>(&json).unwrap(), content);
+    }
+
+    #[test]
+    fn components_are_pasted_onto_every_selected_entity() {
+        let mut world = world();
+        let first = world.spawn(Name::new("First")).id();
+        let second = world.spawn(Name::new("Second")).id();
+        world.resource_mut::<SelectionService>().select_all([
+            EditorEntity::Local(first),
+            EditorEntity::Local(second),
+        ]);
+
+        let type_registry = world.resource::<AppTypeRegistry>().clone();
+        let json = reflect_to_json(&Name::new("Copy"), &type_registry.read()).unwrap();
+        let components = [(Name::type_path().to_owned(), json)].into_iter().collect();
+        paste_components(&mut world, &components).unwrap();
+        assert_eq!(named(&mut world, "Copy"), [first, second]);
+
+        // (in one step)
+        history::undo(&mut world);
+        assert_eq!(named(&mut world, "First"), [first]);
+        assert_eq!(named(&mut world, "Second"), [second]);
+    }
+}
@@ -3,7 +3,7 @@ use bevy::{
     ecs::storage::SparseSet,
     prelude::*,
     render::{
-        camera::RenderTarget,
+        camera::{ NormalizedRenderTarget, RenderTarget },
         render_resource::{
             Extent3d,
             TextureDescriptor,
@@ -11,23 +11,49 @@ use bevy::{
             TextureFormat,
             TextureUsages,
         },
+        view::RenderLayers,
     },
-    ui::widget::UiImageSize,
+    ui::{ widget::UiImageSize, RelativeCursorPosition, UiSystem },
     utils::HashMap,
 };
 
 use bevy_fluent::Localization;
 
+use bevy_mod_picking::{
+    events::{ Click, Pointer },
+    focus::HoverMap,
+    picking_core::{ CorePlugin, PickSet, PointerBundle },
+    pointer::{ InputPress, Location, PointerButton, PointerId, PointerLocation },
+    DefaultPickingPlugins,
+};
+
 use leafwing_input_manager::{ action_state::ActionState, input_map::InputMap, InputManagerBundle };
 
 use sickle_ui::{ prelude::*, widgets::inputs::slider::SliderAxis };
 
-use crate::{ framework::*, input::*, locale::*, remote::* };
+use uuid::Uuid;
+
+use crate::{
+    framework::*,
+    input::*,
+    locale::*,
+    remote::{
+        *,
+        brp_client::RemoteCameraInfo,
+        connections::{ BrpConnections, RemoteConnection },
+    },
+    selection::{ Focusable, SelectionService },
+};
 
 pub struct CameraControlPlugin;
 
 impl Plugin for CameraControlPlugin {
     fn build(&self, app: &mut App) {
+        // (clicks on a scene are turned into picks by the raycast backend)
+        if !app.is_plugin_added::<CorePlugin>() {
+            app.add_plugins(DefaultPickingPlugins);
+        }
+
         app.init_resource::<ActiveCameraControls>()
             .configure_sets(Update, SpawnCameraControlUpdate.after(WidgetLibraryUpdate))
             .add_plugins(ComponentThemePlugin::<CameraControls>::default())
@@ -42,9 +68,22 @@ impl Plugin for CameraControlPlugin {
                     .in_set(SpawnCameraControlPreUpdate)
                     .run_if(in_state(EditorState::Running))
             )
+            .add_systems(
+                PreUpdate,
+                drive_scene_pointers
+                    .after(UiSystem::Focus)
+                    .in_set(PickSet::Input)
+                    .run_if(in_state(EditorState::Running))
+            )
             .add_systems(
                 Update,
-                (process_camera_control_controls, update_camera_control_controls)
+                (
+                    process_camera_control_controls,
+                    update_camera_control_controls,
+                    update_remote_camera_pickers,
+                    select_remote_camera,
+                    pick_scene_entities,
+                )
                     .chain()
                     .in_set(SpawnCameraControlUpdate)
                     .run_if(in_state(EditorState::Running))
@@ -169,27 +208,39 @@ fn layout(
     input_map.insert(InputAction::CameraRotateYDecrease, GamepadButtonType::West);
     input_map.insert(InputAction::CameraRotateYIncrease, GamepadButtonType::East);
 
+    // each scene gets a render layer of its own, so that views don't show (or pick) each other's
+    let layer = active_camera_controls.free_layer();
+    let render_layers = RenderLayers::none().with(layer);
+
     // sample scene objects
     // circular base
     let scene_ground = commands
-        .spawn(PbrBundle {
-            mesh: meshes.add(Circle::new(4.0)),
-            material: materials.add(Color::WHITE),
-            transform: Transform::from_rotation(
-                Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2)
-            ),
-            ..default()
-        })
+        .spawn((
+            Name::new("Ground"),
+            PbrBundle {
+                mesh: meshes.add(Circle::new(4.0)),
+                material: materials.add(Color::WHITE),
+                transform: Transform::from_rotation(
+                    Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2)
+                ),
+                ..default()
+            },
+            render_layers.clone(),
+        ))
         .id();
 
     // cube
     let scene_cube = commands
-        .spawn(PbrBundle {
-            mesh: meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
-            material: materials.add(Color::srgb_u8(124, 144, 255)),
-            transform: Transform::from_xyz(0.0, 0.5, 0.0),
-            ..default()
-        })
+        .spawn((
+            Name::new("Cube"),
+            PbrBundle {
+                mesh: meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
+                material: materials.add(Color::srgb_u8(124, 144, 255)),
+                transform: Transform::from_xyz(0.0, 0.5, 0.0),
+                ..default()
+            },
+            render_layers.clone(),
+        ))
         .id();
 
     // This is the texture that will be rendered to.
@@ -234,6 +285,7 @@ fn layout(
                 },
                 ..default()
             },
+            render_layers.clone(),
             RemoteCamera,
         ))
         .id();
@@ -250,9 +302,15 @@ fn layout(
                 transform,
                 ..default()
             },
+            render_layers,
         ))
         .id();
 
+    // clicks on the scene go through a pointer of its own, which points into the image the
+    // camera renders to rather than into a window
+    let pointer_id = PointerId::Custom(Uuid::from_u128(container.to_bits().into()));
+    let scene_pointer = commands.spawn(PointerBundle::new(pointer_id)).id();
+
     // spawn the main CameraControl
     commands
         .entity(container)
@@ -262,11 +320,17 @@ fn layout(
                 cube: scene_cube,
                 ground: scene_ground,
                 light: scene_light,
+                pointer: scene_pointer,
+                layer,
             },
             CameraControlSettings::default(),
             InputManagerBundle::with_map(input_map),
             UiImage::new(image_handle),
             UiImageSize::default(),
+            // clicking the scene picks what's under the cursor
+            Interaction::None,
+            RelativeCursorPosition::default(),
+            Focusable,
         ))
         .remove::<SpawnCameraControl>();
 
@@ -313,6 +377,14 @@ fn layout(
             })
             .style()
             .min_width(Val::Px(150.0));
+        scene_controls
+            .row(|_| {})
+            .insert(RemoteCameraPicker {
+                camera_control: container,
+                shown: None,
+            })
+            .style()
+            .min_width(Val::Px(150.0));
     });
 
     active_camera_controls.camera_controls.insert(container, CameraControl {
@@ -320,6 +392,8 @@ fn layout(
         cube: scene_cube,
         ground: scene_ground,
         light: scene_light,
+        pointer: scene_pointer,
+        layer,
     });
 }
 
@@ -336,6 +410,7 @@ fn cleanup_despawned_camera_controls(
 
         commands.entity(data.camera).despawn_recursive();
         commands.entity(data.light).despawn_recursive();
+        commands.entity(data.pointer).despawn_recursive();
     }
 }
 
@@ -374,6 +449,103 @@ fn set_camera_control_cam_viewport(
     }
 }
 
+type PickedCameraControl<'a> = (
+    Entity,
+    &'a CameraControl,
+    &'a Interaction,
+    &'a RelativeCursorPosition,
+    Option<&'a RemoteConnection>,
+);
+
+// move the pointer of each scene to where the cursor is over it, and press and release it with
+// the left mouse button, so that a click picks what it hits (see pick_scene_entities) and a click
+// on nothing selects nothing
+//
+// A view driving a remote camera stands for the game's scene, so its pointer points nowhere and
+// doesn't pick any local entity.
+fn drive_scene_pointers(
+    (mouse, keys): (Res<ButtonInput<MouseButton>>, Res<ButtonInput<KeyCode>>),
+    q_camera_controls: Query<PickedCameraControl>,
+    q_pressed: Query<(Entity, &Interaction), Changed<Interaction>>,
+    (q_cameras, mut q_pointers): (Query<&Camera>, Query<(&PointerId, &mut PointerLocation)>),
+    (hover_map, connections): (Res<HoverMap>, Res<BrpConnections>),
+    (mut presses, mut selection): (EventWriter<InputPress>, ResMut<SelectionService>),
+    mut pressed: Local<Vec<Entity>>
+) {
+    for (container, camera_control, interaction, cursor, binding) in &q_camera_controls {
+        let Ok((pointer_id, mut pointer)) = q_pointers.get_mut(camera_control.pointer) else {
+            continue;
+        };
+
+        let connection = connections.bound(binding);
+        let remote = connection.is_some_and(|connection| connection.client.remote_camera.is_some());
+        let camera = q_cameras
+            .get(camera_control.camera())
+            .ok()
+            .filter(|_| !remote && cursor.mouse_over());
+        pointer.location = camera.and_then(|camera| {
+            let RenderTarget::Image(image) = &camera.target else {
+                return None;
+            };
+            Some(Location {
+                target: NormalizedRenderTarget::Image(image.clone()),
+                position: cursor.normalized? * camera.logical_viewport_size()?,
+            })
+        });
+
+        // (the scene controls sit on top of the scene, and a click on one isn't a pick)
+        let on_control = q_pressed.iter().any(|(entity, interaction)| {
+            entity != container && *interaction == Interaction::Pressed
+        });
+        let pressing = *interaction == Interaction::Pressed && pointer.location.is_some();
+        if mouse.just_pressed(MouseButton::Left) && pressing && !on_control {
+            presses.send(InputPress::new_down(*pointer_id, PointerButton::Primary));
+            pressed.push(container);
+        }
+
+        if mouse.just_released(MouseButton::Left) && pressed.contains(&container) {
+            presses.send(InputPress::new_up(*pointer_id, PointerButton::Primary));
+            pressed.retain(|pressed| *pressed != container);
+
+            // (the backend only reports hits, so there is no click on nothing to pick)
+            let toggle = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+            let hit = hover_map.get(pointer_id).is_some_and(|hits| !hits.is_empty());
+            if !hit && !toggle {
+                selection.clear();
+            }
+        }
+    }
+}
+
+// clicking a scene selects the closest mesh under the cursor (or toggles it, with control held)
+//
+// Only meshes on the render layers of the view's camera are hit by the raycast backend.
+fn pick_scene_entities(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut clicks: EventReader<Pointer<Click>>,
+    q_camera_controls: Query<&CameraControl>,
+    mut selection: ResMut<SelectionService>
+) {
+    let toggle = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    for click in clicks.read() {
+        // (the mouse clicks the editor's UI too, but only the pointers of scenes see through
+        // their cameras)
+        let in_scene = q_camera_controls
+            .iter()
+            .any(|camera_control| camera_control.camera() == click.event.hit.camera);
+        if !in_scene || click.event.button != PointerButton::Primary {
+            continue;
+        }
+
+        let entity = EditorEntity::Local(click.target);
+        if toggle {
+            selection.toggle(entity);
+        } else {
+            selection.select(entity);
+        }
+    }
+}
+
 fn update_camera_control_controls(
     q_camera_control_settings: Query<&CameraControlSettings, Changed<CameraControlSettings>>,
     mut q_rotation_controls: Query<(&mut Checkbox, &SceneRotationControl)>,
@@ -451,6 +623,77 @@ fn process_camera_control_controls(
     }
 }
 
+// list the cameras of the game, once they've been looked for
+fn update_remote_camera_pickers(
+    mut q_pickers: Query<(Entity, &mut RemoteCameraPicker)>,
+    q_bindings: Query<Option<&RemoteConnection>>,
+    connections: Res<BrpConnections>,
+    l10n: Res<Localization>,
+    mut commands: Commands
+) {
+    for (picker_entity, mut picker) in &mut q_pickers {
+        let camera_control = picker.camera_control;
+        let binding = q_bindings.get(camera_control).ok().flatten();
+        let connection = connections.bound(binding);
+
+        let cameras = connection.and_then(|connection| connection.client.remote_cameras.clone());
+        if !picker.is_added() && picker.shown == cameras {
+            continue;
+        }
+        picker.shown = cameras.clone();
+
+        commands.entity(picker_entity).despawn_descendants();
+        let Some(cameras) = cameras else {
+            continue;
+        };
+
+        let remote_camera = connection.and_then(|connection| connection.client.remote_camera);
+        commands.ui_builder(picker_entity).column(|column| {
+            column.label(LabelConfig {
+                label: l10n.lbl("RemoteCamera"),
+                ..default()
+            });
+
+            if cameras.is_empty() {
+                column.label(LabelConfig {
+                    label: l10n.lbl("NoRemoteCameras"),
+                    ..default()
+                });
+                return;
+            }
+
+            let selected = cameras.iter().position(|camera| Some(camera.entity) == remote_camera);
+            let options: Vec<String> = cameras.iter().map(RemoteCameraInfo::label).collect();
+            column.radio_group(options, selected, false).insert(RemoteCameraOptions {
+                camera_control,
+                cameras,
+            });
+        });
+    }
+}
+
+fn select_remote_camera(
+    q_groups: Query<(&RadioGroup, &RemoteCameraOptions), Changed<RadioGroup>>,
+    q_bindings: Query<Option<&RemoteConnection>>,
+    mut connections: ResMut<BrpConnections>
+) {
+    for (radio_group, options) in &q_groups {
+        let Some(camera) = radio_group.selected.and_then(|index| options.cameras.get(index)) else {
+            continue;
+        };
+
+        let binding = q_bindings.get(options.camera_control).ok().flatten();
+        let Some(connection) = connections.bound_mut(binding) else {
+            continue;
+        };
+
+        if connection.client.remote_camera != Some(camera.entity) {
+            info!("driving remote camera {}", camera.label());
+            connection.client.select_remote_camera(camera);
+        }
+    }
+}
+
 fn update_camera_controls(
     time: Res<Time>,
     q_action: Query<(Entity, &ActionState<InputAction>), With<CameraControl>>,
@@ -561,6 +804,17 @@ struct ActiveCameraControls {
     camera_controls: HashMap<Entity, CameraControl>,
 }
 
+impl ActiveCameraControls {
+    // the lowest render layer no scene is on (layer 0 is left to the rest of the editor)
+    fn free_layer(&self) -> usize {
+        (1..)
+            .find(|layer| {
+                self.camera_controls.values().all(|camera_control| camera_control.layer != *layer)
+            })
+            .unwrap_or_default()
+    }
+}
+
 impl Default for ActiveCameraControls {
     fn default() -> Self {
         Self {
@@ -626,6 +880,20 @@ struct SceneLightControl {
     camera_control: Entity,
 }
 
+// where the cameras of the game are listed, so one can be picked to drive
+#[derive(Component, Debug)]
+struct RemoteCameraPicker {
+    camera_control: Entity,
+    shown: Option<Vec<RemoteCameraInfo>>,
+}
+
+// the remote cameras behind the options of a radio group, in the same order
+#[derive(Component, Debug)]
+struct RemoteCameraOptions {
+    camera_control: Entity,
+    cameras: Vec<RemoteCameraInfo>,
+}
+
 #[derive(Component, Debug, Reflect)]
 #[reflect(Component)]
 #[component(storage = "SparseSet")]
@@ -638,6 +906,8 @@ pub struct CameraControl {
     cube: Entity,
     ground: Entity,
     light: Entity,
+    pointer: Entity,
+    layer: usize,
 }
 
 impl Default for CameraControl {
@@ -647,6 +917,8 @@ impl Default for CameraControl {
             cube: Entity::PLACEHOLDER,
             ground: Entity::PLACEHOLDER,
             light: Entity::PLACEHOLDER,
+            pointer: Entity::PLACEHOLDER,
+            layer: 0,
         }
     }
 }
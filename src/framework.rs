@@ -15,6 +15,51 @@ impl From<EditorId> for Cow<'static, str> {
     }
 }
 
+/// An entity the editor can point at, in its own world or in a game it is connected to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Reflect)]
+pub enum EditorEntity {
+    Local(Entity),
+
+    /// An entity of the game on the other end of the named BRP connection.
+    Remote {
+        connection: String,
+        entity: Entity,
+    },
+}
+
+impl EditorEntity {
+    /// The entity, in whichever world it lives.
+    pub fn entity(&self) -> Entity {
+        match self {
+            EditorEntity::Local(entity) => *entity,
+            EditorEntity::Remote { entity, .. } => *entity,
+        }
+    }
+
+    /// The world the entity lives in.
+    pub fn world(&self) -> EditorWorld {
+        match self {
+            EditorEntity::Local(_) => EditorWorld::Local,
+            EditorEntity::Remote { connection, .. } => {
+                EditorWorld::Remote {
+                    connection: connection.clone(),
+                }
+            }
+        }
+    }
+}
+
+/// A world the editor can edit: its own, or that of a game it is connected to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Reflect)]
+pub enum EditorWorld {
+    Local,
+
+    /// The game on the other end of the named BRP connection.
+    Remote {
+        connection: String,
+    },
+}
+
 #[derive(SystemSet, Clone, Hash, Debug, Eq, PartialEq)]
 pub struct UiStartupSet;
 
@@ -44,6 +89,7 @@ pub enum Page {
     About,
     CameraControl,
     Help,
+    NewProject,
     Playground,
     QuillDemo,
     SceneEditor,
@@ -55,11 +101,43 @@ pub struct ExitAppButton;
 
 #[derive(Component, Clone, Copy, Debug, Default, Reflect)]
 #[reflect(Component)]
-pub struct NewProjectButton;
+pub struct OpenFileButton;
 
 #[derive(Component, Clone, Copy, Debug, Default, Reflect)]
 #[reflect(Component)]
-pub struct OpenFileButton;
+pub struct SaveProjectButton;
+
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct CloseProjectButton;
+
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct UndoButton;
+
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct RedoButton;
+
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct CopyButton;
+
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct CutButton;
+
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct PasteButton;
+
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct SelectAllButton;
+
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct SelectNoneButton;
 
 #[derive(Component, Debug, Default, Reflect)]
 #[reflect(Component)]
@@ -1,7 +1,873 @@
 // support for undo/redo (probably lifted from space_editor)
 
-use bevy::prelude::*;
+// every edit made through the editor is recorded as a step of operations that can be applied
+// backwards (undo) and forwards again (redo)
+
+// an activity has its own stack of steps, so undoing in one doesn't undo what was done in another
+
+use std::{ any::TypeId, time::Duration };
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::{ prelude::*, reflect::{ ReflectMut, TypeRegistry }, utils::{ HashMap, Instant } };
+
+use bevy_simple_text_input::TextInputInactive;
+use leafwing_input_manager::prelude::*;
+use sickle_ui::prelude::*;
+
+use crate::{
+    framework::*,
+    input::{ EditorInputPlugin, InputAction },
+    remote::{
+        brp_client::BrpClientPlugin,
+        builtin_verbs::{
+            BrpEntityResponse,
+            BrpGetResponse,
+            BrpListResponse,
+            BrpOkResponse,
+            BrpQueryResponse,
+        },
+    },
+    service::EditorService,
+};
+
+pub mod remote;
+
+use remote::{
+    advance_remote_jobs,
+    count_remote_sessions,
+    receive_answers,
+    RemoteCopied,
+    RemoteEdited,
+    RemoteHistory,
+    RemoteOperation,
+};
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EditorInputPlugin>() {
+            app.add_plugins(EditorInputPlugin);
+        }
+        if !app.is_plugin_added::<BrpClientPlugin>() {
+            app.add_plugins(BrpClientPlugin);
+        }
+
+        app.init_resource::<RemoteHistory>()
+            .add_event::<HistoryApplied>()
+            .add_event::<RemoteEdited>()
+            .add_event::<RemoteCopied>()
+            .add_systems(
+                Update,
+                (follow_activity, (undo_redo_on_input, undo_on_menu_item, redo_on_menu_item))
+                    .chain()
+                    .after(WidgetLibraryUpdate)
+                    .run_if(in_state(EditorState::Running))
+            )
+            .add_systems(
+                Update,
+                (
+                    count_remote_sessions,
+                    (
+                        receive_answers::<BrpQueryResponse>,
+                        receive_answers::<BrpListResponse>,
+                        receive_answers::<BrpGetResponse>,
+                        receive_answers::<BrpEntityResponse>,
+                        receive_answers::<BrpOkResponse>,
+                    ),
+                    advance_remote_jobs,
+                ).chain()
+            );
+    }
+}
+
+/// How soon after a step another one with the same merge key has to come to be folded into it.
+pub const MERGE_WINDOW: Duration = Duration::from_millis(1000);
 
 /// The history service manages action histories for activities.
 #[derive(Resource, Default, Debug)]
-pub struct HistoryService {}
+pub struct HistoryService {
+    // the activity whose stack is used
+    activity: EditorId,
+
+    stacks: HashMap<EditorId, HistoryStack>,
+
+    // entities that undo or redo despawned and spawned again, from the ID they were recorded with
+    // to the one they have now
+    respawned: HashMap<Entity, Entity>,
+
+    // bumped by every step recorded, undone or redone
+    revision: u64,
+}
+
+#[derive(Default, Debug)]
+struct HistoryStack {
+    undo: Vec<HistoryStep>,
+    redo: Vec<HistoryStep>,
+}
+
+/// One edit as the user sees it, which is undone and redone as a whole.
+#[derive(Debug)]
+pub struct HistoryStep {
+    /// What was done, like "Rename".
+    pub label: String,
+
+    /// What was changed, in the order it was changed in.
+    pub operations: Vec<HistoryOperation>,
+
+    merge_key: Option<String>,
+    recorded: Instant,
+
+    // tells the step apart once it's recorded
+    id: u64,
+}
+
+impl HistoryStep {
+    pub fn new(label: impl Into<String>, operations: Vec<HistoryOperation>) -> Self {
+        Self {
+            label: label.into(),
+            operations,
+            merge_key: None,
+            recorded: Instant::now(),
+            id: 0,
+        }
+    }
+
+    /// Folds this step into the previous one if that had the same key and was recorded less than
+    /// [`MERGE_WINDOW`] ago, so that dragging a value around is undone in one go.
+    pub fn merging(mut self, key: impl Into<String>) -> Self {
+        self.merge_key = Some(key.into());
+        self
+    }
+
+    // take on the later state of another step, keeping the earlier state of this one
+    fn merge(&mut self, later: HistoryStep) {
+        for operation in later.operations {
+            if !self.operations.iter_mut().any(|earlier| earlier.absorb(&operation)) {
+                self.operations.push(operation);
+            }
+        }
+        self.recorded = later.recorded;
+    }
+}
+
+/// A change to the editor's world that knows how to take itself back.
+///
+/// Components are kept as reflected values, and are inserted again through their
+/// [`ReflectComponent`], so their types need to be registered with `#[reflect(Component)]`.
+#[derive(Debug)]
+pub enum HistoryOperation {
+    /// A component was added to an entity.
+    Insert {
+        entity: Entity,
+        component: Box<dyn Reflect>,
+    },
+
+    /// A component was taken off an entity.
+    Remove {
+        entity: Entity,
+        component: Box<dyn Reflect>,
+    },
+
+    /// A component of an entity went from one value to another.
+    Change {
+        entity: Entity,
+        before: Box<dyn Reflect>,
+        after: Box<dyn Reflect>,
+    },
+
+    /// Entities were spawned, parents before their children.
+    Spawn {
+        entities: Vec<EntitySnapshot>,
+    },
+
+    /// Entities were despawned, parents before their children.
+    Despawn {
+        entities: Vec<EntitySnapshot>,
+    },
+
+    /// An entity was moved in the hierarchy.
+    Reparent {
+        entity: Entity,
+        before: Option<Entity>,
+        after: Option<Entity>,
+    },
+
+    /// Something changed in a game the editor is connected to, in the session of the connection
+    /// given (see [`RemoteHistory::session`]).
+    Remote {
+        connection: String,
+        session: u32,
+        operation: RemoteOperation,
+    },
+}
+
+impl HistoryOperation {
+    // take on the later state of the same change, if it is one
+    fn absorb(&mut self, later: &HistoryOperation) -> bool {
+        match (self, later) {
+            (
+                HistoryOperation::Change { entity, after, .. },
+                HistoryOperation::Change { entity: later_entity, after: later_after, .. },
+            ) if entity == later_entity && type_id(&**after) == type_id(&**later_after) => {
+                *after = later_after.clone_value();
+                true
+            }
+            (
+                HistoryOperation::Remote { connection, operation, .. },
+                HistoryOperation::Remote { connection: later_connection, operation: later, .. },
+            ) if connection == later_connection => operation.merge(later),
+            _ => false,
+        }
+    }
+}
+
+/// What it takes to spawn an entity again: its place in the hierarchy and its reflected components.
+#[derive(Debug)]
+pub struct EntitySnapshot {
+    pub entity: Entity,
+    pub parent: Option<Entity>,
+
+    /// Everything but [`Parent`] and [`Children`], which are put back from `parent`.
+    pub components: Vec<Box<dyn Reflect>>,
+}
+
+impl EntitySnapshot {
+    /// Snapshots an entity and all of its descendants, parents before their children.
+    pub fn take_recursive(world: &World, entity: Entity) -> AnyhowResult<Vec<EntitySnapshot>> {
+        let type_registry = world.resource::<AppTypeRegistry>().read();
+
+        let mut snapshots = vec![];
+        let mut pending = vec![entity];
+        while let Some(entity) = pending.pop() {
+            let snapshot = Self::take(world, entity, &type_registry)?;
+            if let Some(children) = world.get::<Children>(entity) {
+                pending.extend(children.iter().rev());
+            }
+            snapshots.push(snapshot);
+        }
+
+        Ok(snapshots)
+    }
+
+    /// Snapshots a single entity.
+    pub fn take(
+        world: &World,
+        entity: Entity,
+        type_registry: &TypeRegistry
+    ) -> AnyhowResult<EntitySnapshot> {
+        let entity_ref = world
+            .get_entity(entity)
+            .ok_or_else(|| anyhow!("Entity {:?} not found", entity))?;
+
+        let mut components = vec![];
+        for component_id in entity_ref.archetype().components() {
+            let info = world.components().get_info(component_id);
+            let Some(type_id) = info.and_then(|info| info.type_id()) else {
+                continue;
+            };
+            if type_id == TypeId::of::<Parent>() || type_id == TypeId::of::<Children>() {
+                continue;
+            }
+
+            let reflect_component = type_registry.get_type_data::<ReflectComponent>(type_id);
+            let value = reflect_component.and_then(|reflect| reflect.reflect(entity_ref));
+            let Some(value) = value else {
+                continue;
+            };
+            components.push(value.clone_value());
+        }
+
+        Ok(EntitySnapshot {
+            entity,
+            parent: entity_ref.get::<Parent>().map(Parent::get),
+            components,
+        })
+    }
+}
+
+/// Sent after a step has been undone or redone, so that panels can show the result right away.
+///
+/// For a step that changed a remote game, it's sent again once the game has been told.
+#[derive(Event, Clone, Debug)]
+pub struct HistoryApplied {
+    pub label: String,
+    pub undone: bool,
+}
+
+impl HistoryService {
+    /// The activity whose edits are recorded and undone.
+    pub fn activity(&self) -> &EditorId {
+        &self.activity
+    }
+
+    /// Switches to the history of another activity, which is kept separately.
+    pub fn set_activity(&mut self, activity: EditorId) {
+        self.activity = activity;
+    }
+
+    /// Records an edit that has just been made, after which what was undone can't be redone.
+    pub fn record(&mut self, mut step: HistoryStep) {
+        if step.operations.is_empty() {
+            return;
+        }
+
+        self.revision += 1;
+        step.id = self.revision;
+        let stack = self.stacks.entry(self.activity.clone()).or_default();
+        let redo_empty = stack.redo.is_empty();
+        stack.redo.clear();
+
+        if let Some(previous) = stack.undo.last_mut() {
+            let same_key = step.merge_key.is_some() && previous.merge_key == step.merge_key;
+            if redo_empty && same_key && step.recorded - previous.recorded < MERGE_WINDOW {
+                previous.merge(step);
+                return;
+            }
+        }
+        stack.undo.push(step);
+    }
+
+    /// Counts the steps recorded, undone and redone, in any activity, so that whoever keeps the
+    /// edits somewhere can tell whether there have been more since.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.stacks.get(&self.activity).is_some_and(|stack| !stack.undo.is_empty())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.stacks.get(&self.activity).is_some_and(|stack| !stack.redo.is_empty())
+    }
+
+    /// Forgets the history of the current activity.
+    pub fn clear(&mut self) {
+        self.stacks.remove(&self.activity);
+    }
+
+    /// Takes back the last step, and returns its label if there was one.
+    ///
+    /// If part of the step can't be taken back, because the world has changed too much since, the
+    /// parts that were are made again and the step stays where it is.
+    pub fn undo(&mut self, world: &mut World) -> AnyhowResult<Option<String>> {
+        let stack = self.stacks.get_mut(&self.activity);
+        let Some(step) = stack.and_then(|stack| stack.undo.pop()) else {
+            return Ok(None);
+        };
+
+        if let Err(error) = self.apply_step(world, &step, false) {
+            self.stacks.entry(self.activity.clone()).or_default().undo.push(step);
+            return Err(error);
+        }
+        self.revision += 1;
+
+        let label = step.label.clone();
+        self.stacks.entry(self.activity.clone()).or_default().redo.push(step);
+        Ok(Some(label))
+    }
+
+    /// Makes the last undone step again, and returns its label if there was one.
+    ///
+    /// Like [`HistoryService::undo`], a step that can only be made in part isn't made at all.
+    pub fn redo(&mut self, world: &mut World) -> AnyhowResult<Option<String>> {
+        let stack = self.stacks.get_mut(&self.activity);
+        let Some(step) = stack.and_then(|stack| stack.redo.pop()) else {
+            return Ok(None);
+        };
+
+        if let Err(error) = self.apply_step(world, &step, true) {
+            self.stacks.entry(self.activity.clone()).or_default().redo.push(step);
+            return Err(error);
+        }
+        self.revision += 1;
+
+        let label = step.label.clone();
+        self.stacks.entry(self.activity.clone()).or_default().undo.push(step);
+        Ok(Some(label))
+    }
+
+    // a step that a game wouldn't undo (or redo) goes back where it was, so it can be tried again
+    pub(crate) fn put_back(&mut self, id: u64, undone: bool) {
+        for stack in self.stacks.values_mut() {
+            let (from, to) = if undone {
+                (&mut stack.redo, &mut stack.undo)
+            } else {
+                (&mut stack.undo, &mut stack.redo)
+            };
+            if let Some(index) = from.iter().position(|step| step.id == id) {
+                to.push(from.remove(index));
+                self.revision += 1;
+                return;
+            }
+        }
+    }
+
+    // the entity a recorded one is now, after any number of undos and redos
+    fn resolve(&self, mut entity: Entity) -> Entity {
+        // (bounded, in case an ID was reused and the chain loops)
+        for _ in 0..=self.respawned.len() {
+            match self.respawned.get(&entity) {
+                Some(respawned) => {
+                    entity = *respawned;
+                }
+                None => break,
+            }
+        }
+        entity
+    }
+
+    // apply all of a step, or if an operation fails, take back the ones before it
+    fn apply_step(
+        &mut self,
+        world: &mut World,
+        step: &HistoryStep,
+        forwards: bool
+    ) -> AnyhowResult<()> {
+        let mut operations: Vec<&HistoryOperation> = step.operations.iter().collect();
+        if !forwards {
+            operations.reverse();
+        }
+
+        for (index, operation) in operations.iter().enumerate() {
+            let Err(error) = self.apply(world, operation, forwards, step) else {
+                continue;
+            };
+            for applied in operations[..index].iter().rev() {
+                if let Err(error) = self.apply(world, applied, !forwards, step) {
+                    warn!("Could not take back part of {}: {}", step.label, error);
+                }
+            }
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    fn apply(
+        &mut self,
+        world: &mut World,
+        operation: &HistoryOperation,
+        forwards: bool,
+        step: &HistoryStep
+    ) -> AnyhowResult<()> {
+        let type_registry = world.resource::<AppTypeRegistry>().clone();
+        let type_registry = type_registry.read();
+
+        match operation {
+            HistoryOperation::Insert { entity, component } if forwards => {
+                insert_component(world, self.resolve(*entity), &**component, &type_registry)
+            }
+            HistoryOperation::Insert { entity, component } => {
+                remove_component(world, self.resolve(*entity), &**component, &type_registry)
+            }
+            HistoryOperation::Remove { entity, component } if forwards => {
+                remove_component(world, self.resolve(*entity), &**component, &type_registry)
+            }
+            HistoryOperation::Remove { entity, component } => {
+                insert_component(world, self.resolve(*entity), &**component, &type_registry)
+            }
+            HistoryOperation::Change { entity, before, after } => {
+                let value = if forwards { after } else { before };
+                insert_component(world, self.resolve(*entity), &**value, &type_registry)
+            }
+            HistoryOperation::Spawn { entities } if forwards => {
+                self.spawn(world, entities, &type_registry)
+            }
+            HistoryOperation::Spawn { entities } => self.despawn(world, entities),
+            HistoryOperation::Despawn { entities } if forwards => self.despawn(world, entities),
+            HistoryOperation::Despawn { entities } => self.spawn(world, entities, &type_registry),
+            HistoryOperation::Reparent { entity, before, after } => {
+                let parent = if forwards { after } else { before };
+                let parent = parent.map(|parent| self.resolve(parent));
+                let mut entity_mut = get_entity_mut(world, self.resolve(*entity))?;
+                match parent {
+                    Some(parent) => entity_mut.set_parent(parent),
+                    None => entity_mut.remove_parent(),
+                };
+                Ok(())
+            }
+            HistoryOperation::Remote { connection, session, operation } => {
+                // (the game is told in the background, and HistoryApplied is sent again after)
+                let applied = HistoryApplied {
+                    label: step.label.clone(),
+                    undone: !forwards,
+                };
+                let mut remote_history = world
+                    .get_resource_mut::<RemoteHistory>()
+                    .ok_or_else(|| anyhow!("The history plugin hasn't been added"))?;
+                remote_history.replay(connection, *session, operation, forwards, step.id, applied);
+                Ok(())
+            }
+        }
+    }
+
+    fn spawn(
+        &mut self,
+        world: &mut World,
+        entities: &[EntitySnapshot],
+        type_registry: &TypeRegistry
+    ) -> AnyhowResult<()> {
+        // (all of them are spawned first, so they can refer to each other by their new IDs)
+        for snapshot in entities {
+            let spawned = world.spawn_empty().id();
+            // (steps recorded since the last time it was spawned know it by that ID)
+            let previous = self.resolve(snapshot.entity);
+            if previous != snapshot.entity {
+                self.respawned.insert(previous, spawned);
+            }
+            self.respawned.insert(snapshot.entity, spawned);
+        }
+        for snapshot in entities {
+            let spawned = self.resolve(snapshot.entity);
+            for component in &snapshot.components {
+                let mut component = component.clone_value();
+                remap_entities(&mut *component, &|entity| self.resolve(entity));
+                insert_component(world, spawned, &*component, type_registry)?;
+            }
+            if let Some(parent) = snapshot.parent {
+                let parent = self.resolve(parent);
+                if world.get_entity(parent).is_some() {
+                    world.entity_mut(spawned).set_parent(parent);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn despawn(&mut self, world: &mut World, entities: &[EntitySnapshot]) -> AnyhowResult<()> {
+        // (despawning the topmost ones takes their children along, and out of their parents)
+        let recorded: Vec<Entity> = entities
+            .iter()
+            .map(|snapshot| snapshot.entity)
+            .collect();
+        for snapshot in entities {
+            if snapshot.parent.is_some_and(|parent| recorded.contains(&parent)) {
+                continue;
+            }
+            get_entity_mut(world, self.resolve(snapshot.entity))?.despawn_recursive();
+        }
+        Ok(())
+    }
+}
+
+/// Records a step in the history of the editor's current activity, if the editor has one.
+pub fn record(world: &mut World, step: HistoryStep) {
+    if let Some(mut editor) = world.get_resource_mut::<EditorService>() {
+        editor.history.record(step);
+    }
+}
+
+fn type_id(value: &dyn Reflect) -> Option<TypeId> {
+    value.get_represented_type_info().map(|info| info.type_id())
+}
+
+fn get_entity_mut(world: &mut World, entity: Entity) -> AnyhowResult<EntityWorldMut<'_>> {
+    world.get_entity_mut(entity).ok_or_else(|| anyhow!("Entity {:?} not found", entity))
+}
+
+fn get_reflect_component<'a>(
+    value: &dyn Reflect,
+    type_registry: &'a TypeRegistry
+) -> AnyhowResult<&'a ReflectComponent> {
+    type_id(value)
+        .and_then(|type_id| type_registry.get_type_data::<ReflectComponent>(type_id))
+        .ok_or_else(|| anyhow!("`{}` isn't a reflected component", value.reflect_type_path()))
+}
+
+// point the entities a value refers to at the ones they are now
+fn remap_entities(value: &mut dyn Reflect, resolve: &dyn Fn(Entity) -> Entity) {
+    if let Some(entity) = value.downcast_mut::<Entity>() {
+        *entity = resolve(*entity);
+        return;
+    }
+    match value.reflect_mut() {
+        ReflectMut::Struct(value) => {
+            for index in 0..value.field_len() {
+                if let Some(field) = value.field_at_mut(index) {
+                    remap_entities(field, resolve);
+                }
+            }
+        }
+        ReflectMut::TupleStruct(value) => {
+            for index in 0..value.field_len() {
+                if let Some(field) = value.field_mut(index) {
+                    remap_entities(field, resolve);
+                }
+            }
+        }
+        ReflectMut::Tuple(value) => {
+            for index in 0..value.field_len() {
+                if let Some(field) = value.field_mut(index) {
+                    remap_entities(field, resolve);
+                }
+            }
+        }
+        ReflectMut::List(value) => {
+            for index in 0..value.len() {
+                if let Some(item) = value.get_mut(index) {
+                    remap_entities(item, resolve);
+                }
+            }
+        }
+        ReflectMut::Array(value) => {
+            for index in 0..value.len() {
+                if let Some(item) = value.get_mut(index) {
+                    remap_entities(item, resolve);
+                }
+            }
+        }
+        ReflectMut::Map(value) => {
+            for index in 0..value.len() {
+                if let Some((_, item)) = value.get_at_mut(index) {
+                    remap_entities(item, resolve);
+                }
+            }
+        }
+        ReflectMut::Enum(value) => {
+            for index in 0..value.field_len() {
+                if let Some(field) = value.field_at_mut(index) {
+                    remap_entities(field, resolve);
+                }
+            }
+        }
+        ReflectMut::Value(_) => {}
+    }
+}
+
+// insert a component, or overwrite it if the entity already has one
+fn insert_component(
+    world: &mut World,
+    entity: Entity,
+    component: &dyn Reflect,
+    type_registry: &TypeRegistry
+) -> AnyhowResult<()> {
+    let reflect_component = get_reflect_component(component, type_registry)?;
+    let mut entity_mut = get_entity_mut(world, entity)?;
+    reflect_component.apply_or_insert(&mut entity_mut, component, type_registry);
+    Ok(())
+}
+
+fn remove_component(
+    world: &mut World,
+    entity: Entity,
+    component: &dyn Reflect,
+    type_registry: &TypeRegistry
+) -> AnyhowResult<()> {
+    let reflect_component = get_reflect_component(component, type_registry)?;
+    reflect_component.remove(&mut get_entity_mut(world, entity)?);
+    Ok(())
+}
+
+// keep the steps of each activity to itself
+fn follow_activity(editor: Option<ResMut<EditorService>>) {
+    let Some(mut editor) = editor else {
+        return;
+    };
+    if editor.history.activity() != editor.activity.current() {
+        let activity = editor.activity.current().clone();
+        editor.history.set_activity(activity);
+    }
+}
+
+fn undo_redo_on_input(
+    action_state: Res<ActionState<InputAction>>,
+    q_text_inputs: Query<&TextInputInactive>,
+    mut commands: Commands
+) {
+    // (typing into a field has its own undo, or none)
+    if q_text_inputs.iter().any(|inactive| !inactive.0) {
+        return;
+    }
+
+    if action_state.just_pressed(&InputAction::Redo) {
+        commands.add(redo);
+    } else if action_state.just_pressed(&InputAction::Undo) {
+        commands.add(undo);
+    }
+}
+
+fn undo_on_menu_item(
+    q_menu_items: Query<&MenuItem, (With<UndoButton>, Changed<MenuItem>)>,
+    mut commands: Commands
+) {
+    let Ok(item) = q_menu_items.get_single() else {
+        return;
+    };
+
+    if item.interacted() {
+        commands.add(undo);
+    }
+}
+
+fn redo_on_menu_item(
+    q_menu_items: Query<&MenuItem, (With<RedoButton>, Changed<MenuItem>)>,
+    mut commands: Commands
+) {
+    let Ok(item) = q_menu_items.get_single() else {
+        return;
+    };
+
+    if item.interacted() {
+        commands.add(redo);
+    }
+}
+
+/// Undoes the last step of the current activity, and tells the panels about it.
+pub fn undo(world: &mut World) {
+    apply_step(world, true);
+}
+
+/// Redoes the last undone step of the current activity, and tells the panels about it.
+pub fn redo(world: &mut World) {
+    apply_step(world, false);
+}
+
+fn apply_step(world: &mut World, undone: bool) {
+    if !world.contains_resource::<EditorService>() {
+        return;
+    }
+
+    let result = world.resource_scope(|world, mut editor: Mut<EditorService>| {
+        if undone { editor.history.undo(world) } else { editor.history.redo(world) }
+    });
+    match result {
+        Ok(Some(label)) => {
+            info!("{} {}", if undone { "Undid" } else { "Redid" }, label);
+            world.send_event(HistoryApplied { label, undone });
+        }
+        Ok(None) => {}
+        Err(error) => warn!("Could not {}: {}", if undone { "undo" } else { "redo" }, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.init_resource::<AppTypeRegistry>();
+        world.resource::<AppTypeRegistry>().write().register::<Name>();
+        world
+    }
+
+    // rename an entity the way a panel would, and return what takes it back
+    fn rename(world: &mut World, entity: Entity, name: &str) -> HistoryOperation {
+        let before = world.get::<Name>(entity).unwrap().clone();
+        let after = Name::new(name.to_owned());
+        world.entity_mut(entity).insert(after.clone());
+        HistoryOperation::Change { entity, before: Box::new(before), after: Box::new(after) }
+    }
+
+    fn names(world: &mut World) -> Vec<String> {
+        let mut names: Vec<String> = world
+            .query::<&Name>()
+            .iter(world)
+            .map(|name| name.to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn steps_are_undone_and_redone() {
+        let mut world = world();
+        let entity = world.spawn(Name::new("Player")).id();
+        let mut history = HistoryService::default();
+        let operation = rename(&mut world, entity, "Hero");
+        history.record(HistoryStep::new("Rename", vec![operation]));
+
+        assert_eq!(history.undo(&mut world).unwrap().as_deref(), Some("Rename"));
+        assert_eq!(names(&mut world), ["Player"]);
+        assert!(!history.can_undo());
+        assert!(history.can_redo());
+
+        assert_eq!(history.redo(&mut world).unwrap().as_deref(), Some("Rename"));
+        assert_eq!(names(&mut world), ["Hero"]);
+        assert_eq!(history.redo(&mut world).unwrap(), None);
+        assert_eq!(history.revision(), 3);
+
+        // a new step can't be followed by what was undone before it
+        history.undo(&mut world).unwrap();
+        let operation = rename(&mut world, entity, "Villain");
+        history.record(HistoryStep::new("Rename", vec![operation]));
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn drags_are_undone_in_one_go() {
+        let mut world = world();
+        let entity = world.spawn(Name::new("Player")).id();
+        let mut history = HistoryService::default();
+        for name in ["P", "Pl", "Pla"] {
+            let operation = rename(&mut world, entity, name);
+            history.record(HistoryStep::new("Rename", vec![operation]).merging("name"));
+        }
+
+        history.undo(&mut world).unwrap();
+        assert_eq!(names(&mut world), ["Player"]);
+        assert!(!history.can_undo());
+        history.redo(&mut world).unwrap();
+        assert_eq!(names(&mut world), ["Pla"]);
+    }
+
+    #[test]
+    fn steps_that_fail_partway_are_taken_back() {
+        let mut world = world();
+        let entity = world.spawn(Name::new("Player")).id();
+        let lamp = world.spawn(Name::new("Lamp")).id();
+        let mut history = HistoryService::default();
+        let light = rename(&mut world, lamp, "Light");
+        let hero = rename(&mut world, entity, "Hero");
+        history.record(HistoryStep::new("Rename", vec![light, hero]));
+
+        // (undoing goes backwards, so the player is renamed back before the lamp turns out gone)
+        world.despawn(lamp);
+        assert!(history.undo(&mut world).is_err());
+        assert_eq!(names(&mut world), ["Hero"]);
+        assert!(history.can_undo());
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn respawned_entities_are_followed() {
+        let mut world = world();
+        let entity = world.spawn(Name::new("Player")).id();
+        world.spawn(Name::new("Lamp"));
+        let mut history = HistoryService::default();
+        let operation = rename(&mut world, entity, "Hero");
+        history.record(HistoryStep::new("Rename", vec![operation]));
+        let entities = EntitySnapshot::take_recursive(&world, entity).unwrap();
+        world.despawn(entity);
+        history.record(HistoryStep::new("Delete", vec![HistoryOperation::Despawn { entities }]));
+
+        // the rename is undone on the entity that was spawned again, however many times it was
+        history.undo(&mut world).unwrap();
+        history.undo(&mut world).unwrap();
+        assert_eq!(names(&mut world), ["Lamp", "Player"]);
+        history.redo(&mut world).unwrap();
+        history.redo(&mut world).unwrap();
+        assert_eq!(names(&mut world), ["Lamp"]);
+        history.undo(&mut world).unwrap();
+        history.undo(&mut world).unwrap();
+        assert_eq!(names(&mut world), ["Lamp", "Player"]);
+    }
+
+    #[test]
+    fn steps_a_game_refused_are_put_back() {
+        let mut world = world();
+        let entity = world.spawn(Name::new("Player")).id();
+        let mut history = HistoryService::default();
+        let operation = rename(&mut world, entity, "Hero");
+        history.record(HistoryStep::new("Rename", vec![operation]));
+        history.undo(&mut world).unwrap();
+
+        let id = history.stacks[history.activity()].redo[0].id;
+        let revision = history.revision();
+        history.put_back(id, true);
+        assert!(history.can_undo());
+        assert!(!history.can_redo());
+        assert_eq!(history.revision(), revision + 1);
+    }
+}
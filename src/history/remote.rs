@@ -0,0 +1,1629 @@
+//! Undo and redo for edits made to a remote game over BRP.
+//!
+//! A remote edit goes through [`edit`] rather than straight to the [`BrpClient`]. Before the edit
+//! is sent, whatever it is about to change is read back from the game: `QUERY` for the names and
+//! parents of its entities, then `LIST` and `GET` for the components the edit touches. The names
+//! and parents are kept for the edits that come soon after, as long as they know every entity the
+//! edit is about, but an edit that destroys entities always reads them again. Once the
+//! game has carried out the edit, the step is recorded with the values from before and after, and a
+//! [`RemoteEdited`] is sent for the panel that asked for it.
+//!
+//! Undoing the step sends the opposite requests (an `INSERT` of the old values, a `DESTROY` of what
+//! was spawned, a `SPAWN` and `REPARENT` of what was destroyed, and so on), one at a time and in
+//! order, over the same connection. Edits, undos and redos on one connection are carried out one
+//! after another, in the order they were asked for. If the game refuses to undo (or redo) a step,
+//! the rest of it is dropped and the step goes back where it was in the history.
+//!
+//! Remote entity IDs don't survive a restart of the game, so every entity is recorded along with
+//! its `Name`. If the connection was lost since a step was recorded, each entity is looked for by
+//! its ID first, as long as that still has the same name, and otherwise by its name alone. An
+//! entity without a name can't be found again that way, so a step that touches one can't be undone
+//! once the game has restarted.
+//!
+//! Copying entities to the clipboard takes the same reads as destroying them, so [`copy`] is here
+//! too, and pasting them is a [`RemoteEdit::Spawn`] like any other edit.
+//!
+//! [`BrpClient`]: crate::remote::brp_client::BrpClient
+
+use std::{ collections::VecDeque, time::Duration };
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::{ prelude::*, reflect::TypePath, utils::{ HashMap, Instant } };
+use serde_json::Value;
+
+use crate::remote::{
+    brp_client::{ BrpClient, BrpRequestId, BrpResponse },
+    builtin_verbs::{
+        entity_from_json,
+        remap_entities_in_json,
+        BrpDestroyRequest,
+        BrpEntityResponse,
+        BrpGetRequest,
+        BrpGetResponse,
+        BrpInsertRequest,
+        BrpListRequest,
+        BrpListResponse,
+        BrpOkResponse,
+        BrpQuery,
+        BrpQueryRequest,
+        BrpQueryResponse,
+        BrpRemoveRequest,
+        BrpReparentRequest,
+        BrpSpawnRequest,
+    },
+    connections::BrpConnections,
+};
+
+use crate::service::EditorService;
+
+use super::{ HistoryApplied, HistoryOperation, HistoryStep };
+
+// how long the names and parents read for one edit are trusted for the next
+const HIERARCHY_MAX_AGE: Duration = Duration::from_secs(2);
+
+/// Keeps track of the remote edits on their way to being recorded, and the steps on their way to
+/// being undone or redone.
+#[derive(Resource, Default, Debug)]
+pub struct RemoteHistory {
+    jobs: Vec<RemoteJob>,
+
+    // entities that undo or redo destroyed and spawned again, by connection and the ID they were
+    // recorded with
+    respawned: HashMap<(String, Entity), Entity>,
+
+    // how many times each connection has been made, as entity IDs only hold within one
+    sessions: HashMap<String, u32>,
+
+    // the names and parents last read from each connection
+    hierarchies: HashMap<String, CachedHierarchy>,
+}
+
+/// A remote entity as it was when a step was recorded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemoteEntity {
+    pub entity: Entity,
+
+    /// The `Name` to look for the entity by, once its ID can't be trusted anymore.
+    pub name: Option<String>,
+}
+
+/// What it takes to spawn a remote entity again.
+#[derive(Clone, Debug)]
+pub struct RemoteSnapshot {
+    pub entity: RemoteEntity,
+    pub parent: Option<RemoteEntity>,
+
+    /// Serialized components by full type path, but for `Parent` and `Children`.
+    pub components: HashMap<String, Value>,
+}
+
+/// A change made to a remote game, by serialized components keyed by full type path.
+#[derive(Clone, Debug)]
+pub enum RemoteOperation {
+    Insert {
+        entity: RemoteEntity,
+        components: HashMap<String, Value>,
+    },
+    Remove {
+        entity: RemoteEntity,
+        components: HashMap<String, Value>,
+    },
+    Change {
+        entity: RemoteEntity,
+        before: HashMap<String, Value>,
+        after: HashMap<String, Value>,
+    },
+
+    /// Entities were spawned, parents before their children.
+    Spawn {
+        entities: Vec<RemoteSnapshot>,
+    },
+
+    /// Entities were destroyed, parents before their children.
+    Despawn {
+        entities: Vec<RemoteSnapshot>,
+    },
+    Reparent {
+        entity: RemoteEntity,
+        before: Option<RemoteEntity>,
+        after: Option<RemoteEntity>,
+    },
+}
+
+/// An edit for [`edit`] to make to a remote entity, and record.
+#[derive(Clone, Debug)]
+pub enum RemoteEdit {
+    /// Inserts components by full type path, or replaces them if the entity has them.
+    Insert {
+        entity: Entity,
+        components: HashMap<String, Value>,
+    },
+
+    /// Removes components by full type path.
+    Remove {
+        entity: Entity,
+        components: Vec<String>,
+    },
+    Reparent {
+        entity: Entity,
+        parent: Option<Entity>,
+    },
+
+    /// Destroys entities along with their descendants.
+    Destroy {
+        entities: Vec<Entity>,
+    },
+
+    /// Spawns copies of entities, parents before their children, and puts the ones whose parent
+    /// wasn't copied along with them next to `sibling`, under the same parent (or at the top if
+    /// there's no sibling). Copies that refer to the other copied entities are pointed at their
+    /// copies instead.
+    Spawn {
+        entities: Vec<RemoteSnapshot>,
+        sibling: Option<Entity>,
+    },
+}
+
+/// Sent once a remote edit made through [`edit`] has been carried out and recorded, or has failed.
+#[derive(Event, Clone, Debug)]
+pub struct RemoteEdited {
+    /// The entity (usually a panel) that made the edit.
+    pub origin: Option<Entity>,
+    pub label: String,
+    pub error: Option<String>,
+}
+
+/// Sent once the entities asked for with [`copy`] have been read, or couldn't be.
+#[derive(Event, Clone, Debug)]
+pub struct RemoteCopied {
+    pub connection: String,
+
+    /// The entity (usually a panel) that asked for the copy.
+    pub origin: Option<Entity>,
+
+    /// The entities along with their descendants, parents before their children.
+    pub entities: Vec<RemoteSnapshot>,
+    pub error: Option<String>,
+}
+
+/// Makes an edit to an entity of a remote game, and records it in the history once it's done.
+///
+/// The step gives the label and any merge key; its operations are filled in with what the game
+/// says the entity was like before the edit. The outcome is sent as a [`RemoteEdited`].
+pub fn edit(
+    world: &mut World,
+    connection: &str,
+    origin: Option<Entity>,
+    edit: RemoteEdit,
+    step: HistoryStep
+) {
+    let capture = RemoteCapture::new(origin, edit, Some(step));
+    world.resource_mut::<RemoteHistory>().jobs.push(RemoteJob {
+        connection: connection.to_string(),
+        pending: None,
+        task: RemoteTask::Capture(Box::new(capture)),
+    });
+}
+
+/// Reads entities of a remote game along with their descendants, everything the game can
+/// serialize about them, and sends what was read as a [`RemoteCopied`].
+pub fn copy(world: &mut World, connection: &str, origin: Option<Entity>, entities: Vec<Entity>) {
+    // (which is what it takes to destroy them, short of destroying them)
+    let mut capture = RemoteCapture::new(origin, RemoteEdit::Destroy { entities }, None);
+    capture.copy = true;
+    world.resource_mut::<RemoteHistory>().jobs.push(RemoteJob {
+        connection: connection.to_string(),
+        pending: None,
+        task: RemoteTask::Capture(Box::new(capture)),
+    });
+}
+
+impl RemoteHistory {
+    /// How many times a connection has been made. Entity IDs from one session may mean nothing (or
+    /// something else) in the next.
+    pub fn session(&self, connection: &str) -> u32 {
+        self.sessions.get(connection).copied().unwrap_or_default()
+    }
+
+    /// Whether anything is still waiting on a game.
+    pub fn is_busy(&self) -> bool {
+        !self.jobs.is_empty()
+    }
+
+    // send the requests that undo (or redo) an operation of a step, once the ones before them are
+    // done
+    pub(super) fn replay(
+        &mut self,
+        connection: &str,
+        session: u32,
+        operation: &RemoteOperation,
+        forwards: bool,
+        step: u64,
+        applied: HistoryApplied
+    ) {
+        self.jobs.push(RemoteJob {
+            connection: connection.to_string(),
+            pending: None,
+            task: RemoteTask::Replay(RemoteReplay {
+                session,
+                step,
+                requests: operation.requests(forwards).into(),
+                names: None,
+                spawned: vec![],
+                applied,
+            }),
+        });
+    }
+
+    // the ID a recorded entity has now, after any number of undos and redos
+    fn follow(&self, connection: &str, mut entity: Entity) -> Entity {
+        // (bounded, in case an ID was reused and the chain loops)
+        for _ in 0..=self.respawned.len() {
+            match self.respawned.get(&(connection.to_string(), entity)) {
+                Some(respawned) => {
+                    entity = *respawned;
+                }
+                None => break,
+            }
+        }
+        entity
+    }
+
+    // point the entities component values refer to at the ones they have been respawned as
+    fn remap(&self, connection: &str, components: &mut HashMap<String, Value>) {
+        let respawned: HashMap<Entity, Entity> = self.respawned
+            .keys()
+            .filter(|(other, _)| other == connection)
+            .map(|(_, entity)| (*entity, self.follow(connection, *entity)))
+            .collect();
+        for value in components.values_mut() {
+            remap_entities_in_json(value, &respawned);
+        }
+    }
+
+    // the entity a recorded one is now, going by its name if need be
+    fn resolve(
+        &self,
+        connection: &str,
+        names: Option<&HashMap<Entity, Option<String>>>,
+        spawned: &[Entity],
+        recorded: &RemoteEntity
+    ) -> AnyhowResult<Entity> {
+        let entity = self.follow(connection, recorded.entity);
+
+        // (the names are only looked up when the IDs may have gone stale, and the entities spawned
+        // since can be trusted)
+        let Some(names) = names.filter(|_| !spawned.contains(&entity)) else {
+            return Ok(entity);
+        };
+
+        // (an unnamed entity with the same ID may well be another one)
+        let name = recorded.name
+            .as_ref()
+            .ok_or_else(|| {
+                anyhow!("Remote entity {} had no name to find it by after a restart", entity)
+            })?;
+        if names.get(&entity).is_some_and(|other| other.as_ref() == Some(name)) {
+            return Ok(entity);
+        }
+
+        let mut named = names
+            .iter()
+            .filter(|(_, other)| other.as_ref() == Some(name))
+            .map(|(entity, _)| *entity);
+        match (named.next(), named.next()) {
+            (Some(entity), None) => Ok(entity),
+            (None, _) => Err(anyhow!("There's no remote entity named `{}` anymore", name)),
+            (Some(_), Some(_)) => Err(anyhow!("More than one remote entity is named `{}`", name)),
+        }
+    }
+
+    fn respawn(&mut self, connection: &str, recorded: Entity, spawned: Entity) {
+        // (steps recorded since the last time it was spawned know it by that ID)
+        let previous = self.follow(connection, recorded);
+        if previous != recorded {
+            self.respawned.insert((connection.to_string(), previous), spawned);
+        }
+        self.respawned.insert((connection.to_string(), recorded), spawned);
+    }
+
+    // the names and parents last read from a connection, if they can be trusted for an edit
+    fn cached_hierarchy(
+        &self,
+        connection: &str,
+        session: u32,
+        edit: &RemoteEdit
+    ) -> Option<&RemoteHierarchy> {
+        // (destroying takes every descendant, including the ones the game has spawned since)
+        if let RemoteEdit::Destroy { .. } = edit {
+            return None;
+        }
+
+        let cached = self.hierarchies.get(connection)?;
+        let known = edit.entities().iter().all(|entity| cached.hierarchy.contains_key(entity));
+        let fresh = cached.read.elapsed() < HIERARCHY_MAX_AGE;
+        (cached.session == session && fresh && known).then_some(&cached.hierarchy)
+    }
+
+    // keep the names and parents as a job that is done left them, or forget them if it isn't
+    // clear what it left
+    fn update_hierarchy(&mut self, job: &RemoteJob, failed: bool) {
+        let kept = match (&job.task, self.hierarchies.get_mut(&job.connection)) {
+            (RemoteTask::Capture(capture), Some(cached)) if !failed => {
+                capture.copy || capture.edit.update(&mut cached.hierarchy)
+            }
+            _ => false,
+        };
+        if !kept {
+            self.hierarchies.remove(&job.connection);
+        }
+    }
+
+    // take a job off the list once it's done, along with the rest of the step if it failed
+    // to undo or redo
+    fn remove_job(&mut self, index: usize, failed: bool) -> RemoteJob {
+        let job = self.jobs.remove(index);
+        self.update_hierarchy(&job, failed);
+        if let (true, RemoteTask::Replay(replay)) = (failed, &job.task) {
+            self.jobs.retain(|other| {
+                !matches!(
+                    &other.task,
+                    RemoteTask::Replay(other) if other.step == replay.step &&
+                        other.applied.undone == replay.applied.undone
+                )
+            });
+        }
+        job
+    }
+}
+
+impl RemoteOperation {
+    // the requests that carry it out, or take it back
+    fn requests(&self, forwards: bool) -> Vec<ReplayRequest> {
+        let insert = |entity: &RemoteEntity, components: &HashMap<String, Value>| {
+            vec![ReplayRequest::Insert { entity: entity.clone(), components: components.clone() }]
+        };
+
+        match self {
+            Self::Insert { entity, components } if forwards => insert(entity, components),
+            Self::Remove { entity, components } if !forwards => insert(entity, components),
+            Self::Insert { entity, components } | Self::Remove { entity, components } => {
+                vec![ReplayRequest::Remove {
+                    entity: entity.clone(),
+                    components: components.keys().cloned().collect(),
+                }]
+            }
+            Self::Change { entity, before, after } => {
+                insert(entity, if forwards { after } else { before })
+            }
+            Self::Spawn { entities } if forwards => spawn_requests(entities),
+            Self::Despawn { entities } if !forwards => spawn_requests(entities),
+            Self::Spawn { entities } | Self::Despawn { entities } => {
+                // (DESTROY leaves the children behind, so they go first)
+                entities
+                    .iter()
+                    .rev()
+                    .map(|snapshot| ReplayRequest::Destroy { entity: snapshot.entity.clone() })
+                    .collect()
+            }
+            Self::Reparent { entity, before, after } => {
+                let parent = if forwards { after } else { before };
+                vec![ReplayRequest::Reparent { entity: entity.clone(), parent: parent.clone() }]
+            }
+        }
+    }
+
+    // take on the later state of another change to the same components of the same entity
+    pub(super) fn merge(&mut self, later: &RemoteOperation) -> bool {
+        match (self, later) {
+            (
+                Self::Change { entity, before, after },
+                Self::Change { entity: later_entity, before: later_before, after: later_after },
+            ) if entity.entity == later_entity.entity && before.len() == later_before.len() &&
+                later_before.keys().all(|key| before.contains_key(key)) => {
+                after.clone_from(later_after);
+                entity.name.clone_from(&later_entity.name);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn spawn_requests(entities: &[RemoteSnapshot]) -> Vec<ReplayRequest> {
+    entities
+        .iter()
+        .map(|snapshot| ReplayRequest::Spawn {
+            entity: snapshot.entity.clone(),
+            parent: snapshot.parent.clone(),
+            components: snapshot.components.clone(),
+        })
+        .collect()
+}
+
+// the name and parent of every remote entity
+type RemoteHierarchy = HashMap<Entity, (Option<String>, Option<Entity>)>;
+
+#[derive(Debug)]
+struct CachedHierarchy {
+    session: u32,
+    read: Instant,
+    hierarchy: RemoteHierarchy,
+}
+
+#[derive(Debug)]
+struct RemoteJob {
+    connection: String,
+
+    // the request that hasn't been answered yet
+    pending: Option<BrpRequestId>,
+
+    task: RemoteTask,
+}
+
+#[derive(Debug)]
+enum RemoteTask {
+    Capture(Box<RemoteCapture>),
+    Replay(RemoteReplay),
+}
+
+// reading what an edit will change, then making it
+#[derive(Debug)]
+struct RemoteCapture {
+    origin: Option<Entity>,
+    edit: RemoteEdit,
+
+    // (taken once the edit is done)
+    step: Option<HistoryStep>,
+
+    // the name and parent of every remote entity
+    hierarchy: Option<RemoteHierarchy>,
+
+    // the components the game can serialize, to snapshot entities before destroying them
+    registered: Option<Vec<String>>,
+
+    // entities whose components have yet to be listed and read, parents first
+    unread: VecDeque<Entity>,
+
+    // the components of the first unread entity that are to be read
+    listed: Option<Vec<String>>,
+
+    read: Vec<(Entity, HashMap<String, Value>)>,
+
+    // whether the entities are only read, to be copied, rather than destroyed
+    copy: bool,
+
+    // whether the edit itself has been sent
+    sent: bool,
+
+    // entities still to be destroyed, children last in line
+    destroying: Vec<Entity>,
+
+    // copies still to be spawned, parents first
+    spawning: VecDeque<RemoteSnapshot>,
+
+    // the copies that have been spawned, as they are recorded
+    spawned: Vec<RemoteSnapshot>,
+
+    // the copies by the entity they were copied from
+    copies: HashMap<Entity, Entity>,
+
+    // the copy that was just spawned and the parent it goes under
+    reparenting: Option<(Entity, Entity)>,
+
+    // copies that refer to copies spawned after them, with the components that are to point at
+    // those, once all of them are spawned
+    remapping: Option<Vec<(Entity, HashMap<String, Value>)>>,
+}
+
+// sending the requests that undo or redo a step
+#[derive(Debug)]
+struct RemoteReplay {
+    // the session the step was recorded in
+    session: u32,
+
+    // the step in the history, to put back if this fails
+    step: u64,
+
+    requests: VecDeque<ReplayRequest>,
+
+    // the name of every remote entity, when the recorded IDs can't be trusted
+    names: Option<HashMap<Entity, Option<String>>>,
+
+    // the entities spawned along the way, which can be trusted
+    spawned: Vec<Entity>,
+
+    // sent once every request has been answered
+    applied: HistoryApplied,
+}
+
+#[derive(Clone, Debug)]
+enum ReplayRequest {
+    Insert {
+        entity: RemoteEntity,
+        components: HashMap<String, Value>,
+    },
+    Remove {
+        entity: RemoteEntity,
+        components: Vec<String>,
+    },
+    Spawn {
+        entity: RemoteEntity,
+        parent: Option<RemoteEntity>,
+        components: HashMap<String, Value>,
+    },
+    Destroy {
+        entity: RemoteEntity,
+    },
+    Reparent {
+        entity: RemoteEntity,
+        parent: Option<RemoteEntity>,
+    },
+}
+
+// what came back for the request a job was waiting on
+pub(super) enum Answer {
+    Query(BrpQueryResponse),
+    List(BrpListResponse),
+    Get(BrpGetResponse),
+    Entity(BrpEntityResponse),
+    Ok,
+}
+
+impl From<BrpQueryResponse> for Answer {
+    fn from(response: BrpQueryResponse) -> Self {
+        Self::Query(response)
+    }
+}
+
+impl From<BrpListResponse> for Answer {
+    fn from(response: BrpListResponse) -> Self {
+        Self::List(response)
+    }
+}
+
+impl From<BrpGetResponse> for Answer {
+    fn from(response: BrpGetResponse) -> Self {
+        Self::Get(response)
+    }
+}
+
+impl From<BrpEntityResponse> for Answer {
+    fn from(response: BrpEntityResponse) -> Self {
+        Self::Entity(response)
+    }
+}
+
+impl From<BrpOkResponse> for Answer {
+    fn from(_: BrpOkResponse) -> Self {
+        Self::Ok
+    }
+}
+
+// what a job does after it has been answered
+enum Progress {
+    Continue,
+    Done,
+    Failed(anyhow::Error),
+}
+
+// count the times each connection is made
+pub(super) fn count_remote_sessions(
+    connections: Res<BrpConnections>,
+    mut history: ResMut<RemoteHistory>,
+    mut connected: Local<Vec<String>>
+) {
+    for connection in connections.iter() {
+        let name = connection.name();
+        let was_connected = connected.iter().any(|connected| connected == name);
+        if connection.is_connected() && !was_connected {
+            connected.push(name.to_string());
+            *history.sessions.entry(name.to_string()).or_default() += 1;
+        } else if !connection.is_connected() && was_connected {
+            connected.retain(|connected| connected != name);
+        }
+    }
+}
+
+// hand each response to the job that was waiting for it
+pub(super) fn receive_answers<T>(
+    mut responses: EventReader<BrpResponse<T>>,
+    mut history: ResMut<RemoteHistory>,
+    mut commands: Commands
+)
+    where T: Clone + Send + Sync + 'static, Answer: From<T>
+{
+    for response in responses.read() {
+        let history = &mut *history;
+        let Some(index) = history.jobs
+            .iter()
+            .position(|job| {
+                job.connection == response.connection && job.pending == Some(response.id)
+            }) else {
+            continue;
+        };
+        history.jobs[index].pending = None;
+
+        let answer = response.result
+            .clone()
+            .map(Answer::from)
+            .map_err(|error| anyhow!("{} failed: {}", response.verb, error));
+        let progress = match answer {
+            Ok(answer) => history.answer(index, answer),
+            Err(error) => Progress::Failed(error),
+        };
+        match progress {
+            Progress::Continue => {}
+            Progress::Done => {
+                let job = history.remove_job(index, false);
+                finish(job, None, &mut commands);
+            }
+            Progress::Failed(error) => {
+                let job = history.remove_job(index, true);
+                finish(job, Some(error), &mut commands);
+            }
+        }
+    }
+}
+
+// send the next request of the first job on every connection, unless it's waiting on one
+pub(super) fn advance_remote_jobs(
+    mut history: ResMut<RemoteHistory>,
+    mut connections: ResMut<BrpConnections>,
+    mut commands: Commands
+) {
+    let history = &mut *history;
+    let mut index = 0;
+    while index < history.jobs.len() {
+        let job = &history.jobs[index];
+
+        // (the jobs on a connection go one at a time, in order, so that an undo can't overtake
+        // the edit it undoes)
+        let blocked = history.jobs[..index]
+            .iter()
+            .any(|other| other.connection == job.connection);
+        if job.pending.is_some() || blocked {
+            index += 1;
+            continue;
+        }
+
+        let name = job.connection.clone();
+        let connection = connections
+            .get_mut(&name)
+            .filter(|connection| connection.is_connected());
+        let sent = match connection {
+            Some(connection) => history.send(index, &mut connection.client, &mut commands),
+            None => Err(anyhow!("Not connected to {}", name)),
+        };
+        match sent {
+            Ok(Some(request_id)) => {
+                history.jobs[index].pending = Some(request_id);
+                index += 1;
+            }
+            Ok(None) => {
+                let job = history.remove_job(index, false);
+                finish(job, None, &mut commands);
+            }
+            Err(error) => {
+                let job = history.remove_job(index, true);
+                finish(job, Some(error), &mut commands);
+
+                // (the rest of a step that failed may have gone from before this one)
+                index = 0;
+            }
+        }
+    }
+}
+
+// record the edit, or tell the panels what was undone, or what went wrong
+fn finish(job: RemoteJob, error: Option<anyhow::Error>, commands: &mut Commands) {
+    match job.task {
+        RemoteTask::Capture(capture) if capture.copy => {
+            let entities = match (&capture.hierarchy, &error) {
+                (Some(hierarchy), None) => snapshots(hierarchy, &capture.read),
+                _ => vec![],
+            };
+            let copied = RemoteCopied {
+                connection: job.connection,
+                origin: capture.origin,
+                entities,
+                error: error.map(|error| error.to_string()),
+            };
+            commands.add(move |world: &mut World| {
+                world.send_event(copied);
+            });
+        }
+        RemoteTask::Capture(mut capture) => {
+            let step = capture.step.take();
+            let label = step.as_ref().map(|step| step.label.clone()).unwrap_or_default();
+            let edited = RemoteEdited {
+                origin: capture.origin,
+                label,
+                error: error.map(|error| error.to_string()),
+            };
+            commands.add(move |world: &mut World| {
+                if let Some(step) = step.filter(|_| edited.error.is_none()) {
+                    super::record(world, step);
+                }
+                world.send_event(edited);
+            });
+        }
+        RemoteTask::Replay(replay) => {
+            let applied = replay.applied;
+            match error {
+                Some(error) => {
+                    let verb = if applied.undone { "undo" } else { "redo" };
+                    warn!("Could not {} {} in {}: {}", verb, applied.label, job.connection, error);
+
+                    // (so that it can be tried again)
+                    commands.add(move |world: &mut World| {
+                        if let Some(mut editor) = world.get_resource_mut::<EditorService>() {
+                            editor.history.put_back(replay.step, applied.undone);
+                        }
+                    });
+                }
+                None => {
+                    commands.add(move |world: &mut World| {
+                        world.send_event(applied);
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl RemoteHistory {
+    // the next request a job needs answered, or None if it's done
+    fn send(
+        &mut self,
+        index: usize,
+        client: &mut BrpClient,
+        commands: &mut Commands
+    ) -> AnyhowResult<Option<BrpRequestId>> {
+        let connection = self.jobs[index].connection.clone();
+        let session = self.session(&connection);
+
+        // (the names and parents read for the edit before may do for this one)
+        let cached = match &self.jobs[index].task {
+            RemoteTask::Capture(capture) if capture.hierarchy.is_none() => {
+                self.cached_hierarchy(&connection, session, &capture.edit).cloned()
+            }
+            _ => None,
+        };
+
+        let (request, names, spawned) = match &mut self.jobs[index].task {
+            RemoteTask::Capture(capture) => {
+                if let Some(hierarchy) = cached {
+                    capture.set_hierarchy(hierarchy);
+                }
+                return capture.send(client, commands, &connection, session);
+            }
+            RemoteTask::Replay(replay) => {
+                // (the IDs may have gone stale, so find out which ones there are now)
+                if replay.session != session && replay.names.is_none() {
+                    return query_hierarchy(client, commands).map(Some);
+                }
+
+                let Some(request) = replay.requests.front().cloned() else {
+                    return Ok(None);
+                };
+                (request, replay.names.clone(), replay.spawned.clone())
+            }
+        };
+
+        let resolve = |entity: &RemoteEntity| {
+            self.resolve(&connection, names.as_ref(), &spawned, entity)
+        };
+        let request_id = match request {
+            ReplayRequest::Insert { entity, mut components } => {
+                self.remap(&connection, &mut components);
+                let request = BrpInsertRequest { entity: resolve(&entity)?, components };
+                client.insert(request, None, commands)
+            }
+            ReplayRequest::Remove { entity, components } => {
+                let request = BrpRemoveRequest { entity: resolve(&entity)?, components };
+                client.remove(request, None, commands)
+            }
+            ReplayRequest::Spawn { mut components, .. } => {
+                self.remap(&connection, &mut components);
+                client.spawn(BrpSpawnRequest { components }, None, commands)
+            }
+            ReplayRequest::Destroy { entity } => {
+                let request = BrpDestroyRequest { entity: resolve(&entity)? };
+                client.destroy(request, None, commands)
+            }
+            ReplayRequest::Reparent { entity, parent } => {
+                let request = BrpReparentRequest {
+                    entities: vec![resolve(&entity)?],
+                    parent: parent.as_ref().map(resolve).transpose()?,
+                };
+                client.reparent(request, None, commands)
+            }
+        };
+        request_id.map(Some)
+    }
+
+    // take in what the game said, and whether that was the last of it
+    fn answer(&mut self, index: usize, answer: Answer) -> Progress {
+        let connection = self.jobs[index].connection.clone();
+
+        let session = self.session(&connection);
+        let queried = matches!(answer, Answer::Query(_));
+
+        let replay = match &mut self.jobs[index].task {
+            RemoteTask::Capture(capture) => {
+                let progress = capture.answer(answer);
+
+                // (kept for the edits that come soon after)
+                if let (true, Some(hierarchy)) = (queried, &capture.hierarchy) {
+                    self.hierarchies.insert(connection, CachedHierarchy {
+                        session,
+                        read: Instant::now(),
+                        hierarchy: hierarchy.clone(),
+                    });
+                }
+                return progress;
+            }
+            RemoteTask::Replay(replay) => replay,
+        };
+
+        if let Answer::Query(response) = answer {
+            replay.names = Some(
+                hierarchy_from_rows(&response)
+                    .into_iter()
+                    .map(|(entity, (name, _))| (entity, name))
+                    .collect()
+            );
+            return Progress::Continue;
+        }
+
+        let Some(request) = replay.requests.pop_front() else {
+            return Progress::Done;
+        };
+        if let (ReplayRequest::Spawn { entity, parent, .. }, Answer::Entity(spawned)) = (
+            request,
+            answer,
+        ) {
+            if let Some(names) = &mut replay.names {
+                names.insert(spawned.entity, entity.name.clone());
+            }
+            replay.spawned.push(spawned.entity);
+            if let Some(parent) = parent {
+                replay.requests.push_front(ReplayRequest::Reparent {
+                    entity: entity.clone(),
+                    parent: Some(parent),
+                });
+            }
+            self.respawn(&connection, entity.entity, spawned.entity);
+        }
+
+        Progress::Continue
+    }
+}
+
+impl RemoteCapture {
+    fn new(origin: Option<Entity>, edit: RemoteEdit, step: Option<HistoryStep>) -> Self {
+        Self {
+            origin,
+            edit,
+            step,
+            hierarchy: None,
+            registered: None,
+            unread: default(),
+            listed: None,
+            read: vec![],
+            copy: false,
+            sent: false,
+            destroying: vec![],
+            spawning: default(),
+            spawned: vec![],
+            copies: default(),
+            reparenting: None,
+            remapping: None,
+        }
+    }
+
+    fn send(
+        &mut self,
+        client: &mut BrpClient,
+        commands: &mut Commands,
+        connection: &str,
+        session: u32
+    ) -> AnyhowResult<Option<BrpRequestId>> {
+        let Some(hierarchy) = &self.hierarchy else {
+            return query_hierarchy(client, commands).map(Some);
+        };
+
+        if let RemoteEdit::Destroy { .. } | RemoteEdit::Spawn { .. } = self.edit {
+            if self.registered.is_none() {
+                return client.list(BrpListRequest { entity: None }, None, commands).map(Some);
+            }
+        }
+        if let Some(entity) = self.unread.front() {
+            let request = match self.listed.take() {
+                Some(components) => {
+                    let request = BrpGetRequest { entity: *entity, components };
+                    client.get(request, None, commands)
+                }
+                None => client.list(BrpListRequest { entity: Some(*entity) }, None, commands),
+            };
+            return request.map(Some);
+        }
+
+        // (a copy is done once everything has been read)
+        if self.copy {
+            return Ok(None);
+        }
+
+        // everything it changes has been read, so make the edit
+        if !self.sent {
+            self.sent = true;
+            if let Some(step) = &mut self.step {
+                for operation in self.edit.taken_back_by(hierarchy, &self.read) {
+                    step.operations.push(HistoryOperation::Remote {
+                        connection: connection.to_string(),
+                        session,
+                        operation,
+                    });
+                }
+            }
+
+            let request_id = match self.edit.clone() {
+                RemoteEdit::Insert { entity, components } => {
+                    client.insert(BrpInsertRequest { entity, components }, self.origin, commands)
+                }
+                RemoteEdit::Remove { entity, components } => {
+                    client.remove(BrpRemoveRequest { entity, components }, self.origin, commands)
+                }
+                RemoteEdit::Reparent { entity, parent } => {
+                    let request = BrpReparentRequest {
+                        entities: vec![entity],
+                        parent,
+                    };
+                    client.reparent(request, self.origin, commands)
+                }
+                RemoteEdit::Destroy { .. } => {
+                    // DESTROY leaves the children behind, so they go first, one at a time
+                    self.destroying = self.read
+                        .iter()
+                        .map(|(entity, _)| *entity)
+                        .collect();
+                    return self.destroy_next(client, commands);
+                }
+                RemoteEdit::Spawn { entities, .. } => {
+                    self.spawning = entities.into();
+                    return self.spawn_next(client, commands, connection, session);
+                }
+            };
+            return request_id.map(Some);
+        }
+
+        match self.edit {
+            RemoteEdit::Spawn { .. } => self.spawn_next(client, commands, connection, session),
+            _ => self.destroy_next(client, commands),
+        }
+    }
+
+    // spawn the copies one at a time, each followed by a REPARENT if it has a parent
+    fn spawn_next(
+        &mut self,
+        client: &mut BrpClient,
+        commands: &mut Commands,
+        connection: &str,
+        session: u32
+    ) -> AnyhowResult<Option<BrpRequestId>> {
+        if let Some((entity, parent)) = self.reparenting.take() {
+            let request = BrpReparentRequest {
+                entities: vec![entity],
+                parent: Some(parent),
+            };
+            return client.reparent(request, self.origin, commands).map(Some);
+        }
+        if let Some(snapshot) = self.spawning.front() {
+            let components = self.spawned_components(snapshot);
+            return client.spawn(BrpSpawnRequest { components }, self.origin, commands).map(Some);
+        }
+
+        // then the copies can be pointed at the ones that were spawned after them
+        let remapping = self.remapping.get_or_insert_with(|| {
+            self.spawned
+                .iter_mut()
+                .filter_map(|snapshot| {
+                    let components: HashMap<String, Value> = remapped(
+                        &snapshot.components,
+                        &self.copies
+                    )
+                        .into_iter()
+                        .filter(|(component, value)| {
+                            snapshot.components.get(component) != Some(value)
+                        })
+                        .collect();
+                    snapshot.components.extend(components.clone());
+                    (!components.is_empty()).then_some((snapshot.entity.entity, components))
+                })
+                .collect()
+        });
+        if let Some((entity, components)) = remapping.pop() {
+            let request = BrpInsertRequest { entity, components };
+            return client.insert(request, self.origin, commands).map(Some);
+        }
+
+        // (the copies are only known once they are spawned, so they are recorded last)
+        if let Some(step) = &mut self.step {
+            step.operations.push(HistoryOperation::Remote {
+                connection: connection.to_string(),
+                session,
+                operation: RemoteOperation::Spawn {
+                    entities: std::mem::take(&mut self.spawned),
+                },
+            });
+        }
+        Ok(None)
+    }
+
+    // what a copy is spawned with: the components the game can deserialize, pointed at the
+    // copies spawned so far
+    fn spawned_components(&self, snapshot: &RemoteSnapshot) -> HashMap<String, Value> {
+        let mut components = remapped(&snapshot.components, &self.copies);
+        components.retain(|component, _| self.reads(component));
+        components
+    }
+
+    // a copy has been spawned, so record it and put it where it goes
+    fn spawned_as(&mut self, entity: Entity) {
+        let RemoteEdit::Spawn { sibling, .. } = &self.edit else {
+            return;
+        };
+        let Some(snapshot) = self.spawning.pop_front() else {
+            return;
+        };
+
+        let components = self.spawned_components(&snapshot);
+        self.copies.insert(snapshot.entity.entity, entity);
+
+        let copied_parent = snapshot.parent.and_then(|copied| {
+            let entity = *self.copies.get(&copied.entity)?;
+            Some(RemoteEntity { entity, name: copied.name })
+        });
+        let parent = copied_parent.or_else(|| {
+            let hierarchy = self.hierarchy.as_ref()?;
+            let parent = hierarchy.get(&(*sibling)?)?.1?;
+            Some(RemoteEntity {
+                entity: parent,
+                name: hierarchy.get(&parent).and_then(|(name, _)| name.clone()),
+            })
+        });
+        self.reparenting = parent.as_ref().map(|parent| (entity, parent.entity));
+
+        self.spawned.push(RemoteSnapshot {
+            entity: RemoteEntity { entity, name: snapshot.entity.name },
+            parent,
+            components,
+        });
+    }
+
+    fn destroy_next(
+        &mut self,
+        client: &mut BrpClient,
+        commands: &mut Commands
+    ) -> AnyhowResult<Option<BrpRequestId>> {
+        let Some(entity) = self.destroying.pop() else {
+            return Ok(None);
+        };
+        client.destroy(BrpDestroyRequest { entity }, self.origin, commands).map(Some)
+    }
+
+    // take in the names and parents of the remote entities, and find out what to read
+    fn set_hierarchy(&mut self, hierarchy: RemoteHierarchy) {
+        self.unread = match &self.edit {
+            RemoteEdit::Insert { entity, .. } | RemoteEdit::Remove { entity, .. } => {
+                [*entity].into()
+            }
+            RemoteEdit::Destroy { entities } => {
+                // (an entity below another one is read along with that one)
+                entities
+                    .iter()
+                    .filter(|entity| !has_ancestor_among(&hierarchy, **entity, entities))
+                    .flat_map(|entity| descendants(&hierarchy, *entity))
+                    .collect()
+            }
+            RemoteEdit::Reparent { .. } | RemoteEdit::Spawn { .. } => default(),
+        };
+        self.hierarchy = Some(hierarchy);
+    }
+
+    fn answer(&mut self, answer: Answer) -> Progress {
+        match answer {
+            Answer::Query(response) => {
+                self.set_hierarchy(hierarchy_from_rows(&response));
+                Progress::Continue
+            }
+            Answer::List(response) if response.entity.is_none() => {
+                self.registered = Some(response.components);
+                Progress::Continue
+            }
+            Answer::List(response) => {
+                let listed: Vec<String> = response.components
+                    .into_iter()
+                    .filter(|component| self.reads(component))
+                    .collect();
+                // (GET fails on a component the entity doesn't have, so only those listed are read)
+                if listed.is_empty() {
+                    if let Some(entity) = self.unread.pop_front() {
+                        self.read.push((entity, default()));
+                    }
+                } else {
+                    self.listed = Some(listed);
+                }
+                Progress::Continue
+            }
+            Answer::Get(response) => {
+                self.unread.pop_front();
+                self.read.push((response.entity, response.components));
+                Progress::Continue
+            }
+            // (the edit may take more than one request)
+            Answer::Entity(response) => {
+                self.spawned_as(response.entity);
+                Progress::Continue
+            }
+            Answer::Ok => Progress::Continue,
+        }
+    }
+
+    // whether a component of an entity is needed to take the edit back, or for a copy, whether it
+    // can be spawned
+    fn reads(&self, component: &str) -> bool {
+        match &self.edit {
+            RemoteEdit::Insert { components, .. } => components.contains_key(component),
+            RemoteEdit::Remove { components, .. } => components.iter().any(|c| c == component),
+            RemoteEdit::Destroy { .. } | RemoteEdit::Spawn { .. } => {
+                // (the hierarchy is put back with REPARENT)
+                let hierarchy = [Parent::type_path(), Children::type_path()];
+                !hierarchy.contains(&component) &&
+                    self.registered.as_ref().is_some_and(|registered| {
+                        registered.iter().any(|registered| registered == component)
+                    })
+            }
+            RemoteEdit::Reparent { .. } => false,
+        }
+    }
+}
+
+impl RemoteEdit {
+    // the entities whose names and parents the edit needs
+    fn entities(&self) -> Vec<Entity> {
+        match self {
+            RemoteEdit::Insert { entity, .. } | RemoteEdit::Remove { entity, .. } => vec![*entity],
+            RemoteEdit::Reparent { entity, parent } => {
+                [Some(*entity), *parent].into_iter().flatten().collect()
+            }
+            RemoteEdit::Destroy { entities } => entities.clone(),
+            RemoteEdit::Spawn { sibling, .. } => sibling.iter().copied().collect(),
+        }
+    }
+
+    // bring the names and parents up to date with the edit, or tell that it can't be
+    fn update(&self, hierarchy: &mut RemoteHierarchy) -> bool {
+        let Some(entity) = self.entities().first().copied() else {
+            return false;
+        };
+        let Some((name, parent)) = hierarchy.get_mut(&entity) else {
+            return false;
+        };
+
+        match self {
+            RemoteEdit::Insert { components, .. } => {
+                if let Some(value) = components.get(Name::type_path()) {
+                    *name = value.as_str().map(str::to_owned);
+                }
+                true
+            }
+            RemoteEdit::Remove { components, .. } => {
+                if components.iter().any(|component| component == Name::type_path()) {
+                    *name = None;
+                }
+                true
+            }
+            RemoteEdit::Reparent { parent: after, .. } => {
+                *parent = *after;
+                true
+            }
+            // (what was spawned or destroyed is only known to the step)
+            RemoteEdit::Destroy { .. } | RemoteEdit::Spawn { .. } => false,
+        }
+    }
+
+    // what the edit does, given what the game was like before it, so that it can be taken back
+    fn taken_back_by(
+        &self,
+        hierarchy: &RemoteHierarchy,
+        read: &[(Entity, HashMap<String, Value>)]
+    ) -> Vec<RemoteOperation> {
+        let remote_entity = |entity: Entity| RemoteEntity {
+            entity,
+            name: hierarchy.get(&entity).and_then(|(name, _)| name.clone()),
+        };
+        let parent_of = |entity: &Entity| {
+            hierarchy
+                .get(entity)
+                .and_then(|(_, parent)| *parent)
+                .map(remote_entity)
+        };
+        let before = read
+            .first()
+            .map(|(_, components)| components.clone())
+            .unwrap_or_default();
+
+        match self {
+            RemoteEdit::Insert { entity, components } => {
+                // (a renamed entity is looked for by its new name)
+                let mut entity = remote_entity(*entity);
+                if let Some(name) = components.get(Name::type_path()).and_then(Value::as_str) {
+                    entity.name = Some(name.to_string());
+                }
+
+                // the components it had are changed, and the rest are added
+                let (changed, added): (HashMap<_, _>, HashMap<_, _>) = components
+                    .clone()
+                    .into_iter()
+                    .partition(|(component, _)| before.contains_key(component));
+
+                let mut operations = vec![];
+                if !changed.is_empty() {
+                    operations.push(RemoteOperation::Change {
+                        entity: entity.clone(),
+                        before,
+                        after: changed,
+                    });
+                }
+                if !added.is_empty() {
+                    operations.push(RemoteOperation::Insert { entity, components: added });
+                }
+                operations
+            }
+            RemoteEdit::Remove { entity, .. } => {
+                vec![RemoteOperation::Remove { entity: remote_entity(*entity), components: before }]
+            }
+            RemoteEdit::Reparent { entity, parent } => {
+                vec![RemoteOperation::Reparent {
+                    entity: remote_entity(*entity),
+                    before: parent_of(entity),
+                    after: parent.map(remote_entity),
+                }]
+            }
+            RemoteEdit::Destroy { .. } => {
+                vec![RemoteOperation::Despawn { entities: snapshots(hierarchy, read) }]
+            }
+            // (the copies are recorded once they have been spawned)
+            RemoteEdit::Spawn { .. } => vec![],
+        }
+    }
+}
+
+// the entities that were read, as they can be spawned again
+fn snapshots(
+    hierarchy: &RemoteHierarchy,
+    read: &[(Entity, HashMap<String, Value>)]
+) -> Vec<RemoteSnapshot> {
+    let remote_entity = |entity: Entity| RemoteEntity {
+        entity,
+        name: hierarchy.get(&entity).and_then(|(name, _)| name.clone()),
+    };
+    read.iter()
+        .map(|(entity, components)| RemoteSnapshot {
+            entity: remote_entity(*entity),
+            parent: hierarchy
+                .get(entity)
+                .and_then(|(_, parent)| *parent)
+                .map(remote_entity),
+            components: components.clone(),
+        })
+        .collect()
+}
+
+fn remapped(
+    components: &HashMap<String, Value>,
+    map: &HashMap<Entity, Entity>
+) -> HashMap<String, Value> {
+    let mut components = components.clone();
+    for value in components.values_mut() {
+        remap_entities_in_json(value, map);
+    }
+    components
+}
+
+fn query_hierarchy(client: &mut BrpClient, commands: &mut Commands) -> AnyhowResult<BrpRequestId> {
+    let request = BrpQueryRequest {
+        data: BrpQuery {
+            option: vec![Name::type_path().to_string(), Parent::type_path().to_string()],
+            ..default()
+        },
+        filter: default(),
+    };
+    client.query(request, None, commands)
+}
+
+fn hierarchy_from_rows(response: &BrpQueryResponse) -> RemoteHierarchy {
+    response.rows
+        .iter()
+        .map(|row| {
+            let name = row.components
+                .get(Name::type_path())
+                .and_then(Value::as_str)
+                .map(str::to_owned);
+            let parent = row.components.get(Parent::type_path()).and_then(entity_from_json);
+            (row.entity, (name, parent))
+        })
+        .collect()
+}
+
+fn has_ancestor_among(hierarchy: &RemoteHierarchy, entity: Entity, among: &[Entity]) -> bool {
+    let mut parent = hierarchy.get(&entity).and_then(|(_, parent)| *parent);
+    // (bounded, in case the hierarchy was read while it was changing)
+    for _ in 0..hierarchy.len() {
+        let Some(ancestor) = parent else {
+            return false;
+        };
+        if among.contains(&ancestor) {
+            return true;
+        }
+        parent = hierarchy.get(&ancestor).and_then(|(_, parent)| *parent);
+    }
+    false
+}
+
+// an entity and everything below it, parents first
+fn descendants(hierarchy: &RemoteHierarchy, entity: Entity) -> Vec<Entity> {
+    let mut found = vec![entity];
+    let mut index = 0;
+    while index < found.len() {
+        let parent = found[index];
+        let mut children: Vec<Entity> = hierarchy
+            .iter()
+            .filter(|(_, (_, other))| *other == Some(parent))
+            .map(|(child, _)| *child)
+            .collect();
+        children.sort();
+        found.extend(children);
+        index += 1;
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ reflect::serde::TypedReflectSerializer, time::TimeUpdateStrategy };
+    use serde_json::json;
+
+    use crate::remote::{
+        brp_client::BrpClientPlugin,
+        connections::{ BrpConnection, DEFAULT_CONNECTION },
+        transport::InProcessTransport,
+        EditorRemotePlugin,
+    };
+
+    use super::*;
+
+    const TRANSFORM: &str = "bevy_transform::components::transform::Transform";
+
+    // how much time passes in both apps with each update
+    const FRAME: Duration = Duration::from_millis(50);
+
+    // how long an edit, undo or redo is given to go through
+    // (long enough for a job's QUERY, LIST, GET and the edit itself, a frame each, and then some)
+    const STEP: Duration = Duration::from_millis(500);
+
+    fn change(index: u32, component: &str, before: Value, after: Value) -> RemoteOperation {
+        RemoteOperation::Change {
+            entity: RemoteEntity {
+                entity: Entity::from_raw(index),
+                name: Some("Player".to_string()),
+            },
+            before: [(component.to_string(), before)].into_iter().collect(),
+            after: [(component.to_string(), after)].into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn later_changes_to_the_same_components_merge() {
+        let mut operation = change(1, TRANSFORM, json!(0), json!(1));
+        let mut later = change(1, TRANSFORM, json!(1), json!(2));
+        if let RemoteOperation::Change { entity, .. } = &mut later {
+            entity.name = Some("Hero".to_string());
+        }
+        assert!(operation.merge(&later));
+
+        // (undoing still goes back to where it started, and finds the entity by its latest name)
+        let RemoteOperation::Change { entity, before, after } = operation else {
+            panic!("a change merged into something else");
+        };
+        assert_eq!(before[TRANSFORM], json!(0));
+        assert_eq!(after[TRANSFORM], json!(2));
+        assert_eq!(entity.name.as_deref(), Some("Hero"));
+    }
+
+    #[test]
+    fn other_operations_dont_merge() {
+        let mut operation = change(1, TRANSFORM, json!(0), json!(1));
+        assert!(!operation.merge(&change(2, TRANSFORM, json!(1), json!(2))));
+        assert!(!operation.merge(&change(1, "bevy_core::name::Name", json!("a"), json!("b"))));
+
+        let insert = RemoteOperation::Insert {
+            entity: RemoteEntity { entity: Entity::from_raw(1), name: None },
+            components: [(TRANSFORM.to_string(), json!(2))].into_iter().collect(),
+        };
+        assert!(!operation.merge(&insert));
+        assert!(!insert.clone().merge(&operation));
+
+        let RemoteOperation::Change { after, .. } = operation else {
+            panic!("a change turned into something else");
+        };
+        assert_eq!(after[TRANSFORM], json!(1));
+    }
+
+    fn names(names: &[(u32, Option<&str>)]) -> HashMap<Entity, Option<String>> {
+        names
+            .iter()
+            .map(|(index, name)| (Entity::from_raw(*index), name.map(str::to_owned)))
+            .collect()
+    }
+
+    #[test]
+    fn entities_are_found_by_name_after_a_restart() {
+        let history = RemoteHistory::default();
+        let player = RemoteEntity { entity: Entity::from_raw(1), name: Some("Player".into()) };
+        let resolve = |names: &HashMap<Entity, Option<String>>, spawned: &[Entity]| {
+            let resolved = history.resolve("game", Some(names), spawned, &player);
+            resolved.map_err(|error| error.to_string())
+        };
+
+        // (the IDs are trusted within a session)
+        assert_eq!(history.resolve("game", None, &[], &player).unwrap(), Entity::from_raw(1));
+
+        let same = names(&[(1, Some("Player")), (2, Some("Lamp"))]);
+        assert_eq!(resolve(&same, &[]), Ok(Entity::from_raw(1)));
+        let moved = names(&[(1, Some("Lamp")), (5, Some("Player"))]);
+        assert_eq!(resolve(&moved, &[]), Ok(Entity::from_raw(5)));
+
+        let gone = names(&[(1, Some("Lamp"))]);
+        assert!(resolve(&gone, &[]).unwrap_err().contains("no remote entity named `Player`"));
+        let twice = names(&[(4, Some("Player")), (5, Some("Player"))]);
+        assert!(resolve(&twice, &[]).unwrap_err().contains("More than one"));
+
+        // (unless it was spawned since, in which case its ID is good)
+        assert_eq!(resolve(&gone, &[Entity::from_raw(1)]), Ok(Entity::from_raw(1)));
+
+        let unnamed = RemoteEntity { entity: Entity::from_raw(1), name: None };
+        assert!(history.resolve("game", Some(&same), &[], &unnamed).is_err());
+    }
+
+    // a game with a player, and an editor with a history connected to it
+    fn apps() -> (App, App, Entity) {
+        let mut game = App::new();
+        game.add_plugins((MinimalPlugins, EditorRemotePlugin::default().without_listening()))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+            .register_type::<Transform>()
+            .register_type::<Parent>()
+            .register_type::<Children>();
+        let player = game.world_mut().spawn((Name::new("Player"), Transform::default())).id();
+
+        // (HistoryPlugin brings the input plugins, which this doesn't need)
+        let mut editor = App::new();
+        editor.add_plugins((MinimalPlugins, BrpClientPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+            .init_resource::<EditorService>()
+            .init_resource::<RemoteHistory>()
+            .add_event::<HistoryApplied>()
+            .add_event::<RemoteEdited>()
+            .add_systems(
+                Update,
+                (
+                    count_remote_sessions,
+                    receive_answers::<BrpQueryResponse>,
+                    receive_answers::<BrpListResponse>,
+                    receive_answers::<BrpGetResponse>,
+                    receive_answers::<BrpEntityResponse>,
+                    receive_answers::<BrpOkResponse>,
+                    advance_remote_jobs,
+                ).chain()
+            );
+
+        let transport = InProcessTransport::new(game.world()).unwrap();
+        let mut connections = editor.world_mut().resource_mut::<BrpConnections>();
+        let connection = connections.primary_mut().unwrap();
+        connection.client.set_transport(transport);
+        connection.connect();
+
+        let (mut editor, mut game) = (editor, game);
+        run_for(&mut editor, &mut game, STEP);
+        (editor, game, player)
+    }
+
+    fn run_for(editor: &mut App, game: &mut App, time: Duration) {
+        for _ in 0..time.as_millis() / FRAME.as_millis() {
+            editor.update();
+            game.update();
+        }
+    }
+
+    // move the player in the game from the editor, and record it
+    fn move_player(editor: &mut App, game: &mut App, player: Entity, x: f32) {
+        let transform = Transform::from_xyz(x, 0.0, 0.0);
+        let type_registry = game.world().resource::<AppTypeRegistry>().read();
+        let value = serde_json
+            ::to_value(TypedReflectSerializer::new(&transform, &type_registry))
+            .unwrap();
+        drop(type_registry);
+
+        let edit = RemoteEdit::Insert {
+            entity: player,
+            components: [(TRANSFORM.to_string(), value)].into_iter().collect(),
+        };
+        let step = HistoryStep::new("Move", vec![]);
+        super::edit(editor.world_mut(), DEFAULT_CONNECTION, None, edit, step);
+        run_for(editor, game, STEP);
+    }
+
+    fn x(game: &App, entity: Entity) -> f32 {
+        game.world().get::<Transform>(entity).unwrap().translation.x
+    }
+
+    fn primary(editor: &mut App) -> &mut BrpConnection {
+        editor.world_mut().resource_mut::<BrpConnections>().into_inner().primary_mut().unwrap()
+    }
+
+    fn history(editor: &App) -> &super::super::HistoryService {
+        &editor.world().resource::<EditorService>().history
+    }
+
+    #[test]
+    fn remote_edits_are_undone_and_redone() {
+        let (mut editor, mut game, player) = apps();
+        move_player(&mut editor, &mut game, player, 2.0);
+        assert_eq!(x(&game, player), 2.0);
+        assert!(history(&editor).can_undo());
+
+        super::super::undo(editor.world_mut());
+        run_for(&mut editor, &mut game, STEP);
+        assert_eq!(x(&game, player), 0.0);
+
+        super::super::redo(editor.world_mut());
+        run_for(&mut editor, &mut game, STEP);
+        assert_eq!(x(&game, player), 2.0);
+        assert!(!editor.world().resource::<RemoteHistory>().is_busy());
+    }
+
+    #[test]
+    fn undo_finds_entities_by_name_after_a_reconnect() {
+        let (mut editor, mut game, player) = apps();
+        move_player(&mut editor, &mut game, player, 2.0);
+
+        // the game restarts, and the player comes back as another entity
+        primary(&mut editor).disconnect();
+        run_for(&mut editor, &mut game, FRAME);
+        let moved = Transform::from_xyz(2.0, 0.0, 0.0);
+        let respawned = game.world_mut().spawn((Name::new("Player"), moved)).id();
+        game.world_mut().despawn(player);
+        primary(&mut editor).connect();
+        run_for(&mut editor, &mut game, STEP);
+        assert_eq!(editor.world().resource::<RemoteHistory>().session(DEFAULT_CONNECTION), 2);
+
+        super::super::undo(editor.world_mut());
+        run_for(&mut editor, &mut game, STEP);
+        assert_eq!(x(&game, respawned), 0.0);
+    }
+
+    #[test]
+    fn steps_a_game_refuses_go_back_in_the_history() {
+        let (mut editor, mut game, player) = apps();
+        move_player(&mut editor, &mut game, player, 2.0);
+        game.world_mut().despawn(player);
+
+        super::super::undo(editor.world_mut());
+        assert!(history(&editor).can_redo());
+        run_for(&mut editor, &mut game, STEP);
+        assert!(history(&editor).can_undo());
+        assert!(!history(&editor).can_redo());
+    }
+}
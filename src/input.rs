@@ -11,14 +11,17 @@ use bevy_dev_tools::ui_debug_overlay::*;
 
 use leafwing_input_manager::Actionlike;
 use leafwing_input_manager::plugin::InputManagerPlugin;
+use leafwing_input_manager::prelude::*;
 
 pub struct EditorInputPlugin;
 
 impl Plugin for EditorInputPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<InputConfig>().add_plugins(
-            InputManagerPlugin::<InputAction>::default()
-        );
+        app.init_resource::<InputConfig>()
+            .add_plugins(InputManagerPlugin::<InputAction>::default())
+            // the actions that work no matter which panel has focus
+            .init_resource::<ActionState<InputAction>>()
+            .insert_resource(global_input_map());
 
         // add the standard debug overlay if dev tools are enabled
         #[cfg(feature = "bevy_dev_tools")]
@@ -39,6 +42,37 @@ pub enum InputAction {
     CameraRotateYIncrease,
     CameraRotateYDecrease,
     ToggleRemoteFpsCounter,
+    Undo,
+    Redo,
+    Copy,
+    Cut,
+    Paste,
+}
+
+fn global_input_map() -> InputMap<InputAction> {
+    // (with the default clash strategy, Ctrl+Shift+Z doesn't also undo)
+    let mut input_map = InputMap::default();
+    input_map.insert(
+        InputAction::Undo,
+        ButtonlikeChord::modified(ModifierKey::Control, KeyCode::KeyZ)
+    );
+    input_map.insert(
+        InputAction::Redo,
+        ButtonlikeChord::modified(ModifierKey::Control, KeyCode::KeyZ).with(ModifierKey::Shift)
+    );
+    input_map.insert(
+        InputAction::Copy,
+        ButtonlikeChord::modified(ModifierKey::Control, KeyCode::KeyC)
+    );
+    input_map.insert(
+        InputAction::Cut,
+        ButtonlikeChord::modified(ModifierKey::Control, KeyCode::KeyX)
+    );
+    input_map.insert(
+        InputAction::Paste,
+        ButtonlikeChord::modified(ModifierKey::Control, KeyCode::KeyV)
+    );
+    input_map
 }
 
 // input config resource
@@ -9,6 +9,11 @@ use crate::{
     framework::*,
     locale::Translator,
     prelude::camera_control::widget::UiCameraControlExt,
+    widget::{
+        connect_to::UiConnectToExt,
+        inspector::UiInspectorExt,
+        outliner::UiOutlinerExt,
+    },
 };
 
 pub fn layout(
@@ -42,7 +47,7 @@ pub fn layout(
                                 true,
                                 |tab_container| {
                                     tab_container.add_tab(l10n.lbl("Relationships"), |panel| {
-                                        panel.insert(TreeViewPanel);
+                                        panel.outliner();
                                     });
                                 }
                             );
@@ -88,8 +93,11 @@ pub fn layout(
                         },
                         true,
                         |tab_container| {
-                            tab_container.add_tab(l10n.lbl("Placeholder"), |placeholder| {
-                                placeholder.style().padding(UiRect::all(Val::Px(10.0)));
+                            tab_container.add_tab(l10n.lbl("ConnectTo"), |panel| {
+                                panel.connect_to();
+                            });
+                            tab_container.add_tab(l10n.lbl("Inspector"), |panel| {
+                                panel.inspector();
                             });
                         }
                     );
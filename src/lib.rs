@@ -1,21 +1,32 @@
-use bevy::{ core::NonSendMarker, prelude::* };
+use bevy::prelude::*;
 
 //use bevy_defer::AsyncPlugin;
 
-use native_dialog::FileDialog;
 use service::EditorService;
 use sickle_ui::{ prelude::*, ui_commands::SetCursorExt, SickleUiPlugin };
 
+use activity::new_project::NewProjectPlugin;
+use clipboard::ClipboardPlugin;
 use framework::*;
+use history::HistoryPlugin;
 use input::EditorInputPlugin;
 use layout::footer::spawn_footer;
 use locale::EditorLocalePlugin;
-use remote::*;
+use project::ProjectPlugin;
+use remote::{ *, link::RemoteLinkPlugin, replica::RemoteReplicaPlugin };
 use router::EditorRouterPlugin;
+use selection::SelectionPlugin;
 use theme::*;
+use widget::{
+    connect_to::ConnectToPlugin,
+    inspector::InspectorPlugin,
+    link_conflict::LinkConflictPlugin,
+    outliner::OutlinerPlugin,
+};
 
 pub mod activity;
 pub mod asset;
+pub mod clipboard;
 pub mod construct;
 pub mod framework;
 pub mod history;
@@ -23,8 +34,10 @@ pub mod input;
 pub mod locale;
 pub mod layout;
 pub mod logging;
+pub mod project;
 pub mod remote;
 pub mod router;
+pub mod selection;
 pub mod service;
 pub mod setup;
 pub mod signals;
@@ -53,6 +66,29 @@ impl Plugin for EditorPlugin {
             // This plugin maps inputs to an input-type agnostic action-state
             // We need to provide it with an enum which stores the possible actions a player could take
             .add_plugins(EditorInputPlugin)
+
+            // holds what is selected and which panel has focus
+            .add_plugins(SelectionPlugin)
+            // undoes and redoes the edits made in the panels
+            .add_plugins(HistoryPlugin)
+            // copies, cuts and pastes entities and components, within the editor and between games
+            .add_plugins(ClipboardPlugin)
+            // keeps track of the project being worked on, and handles Open, Save and Close
+            .add_plugins(ProjectPlugin)
+            // makes new projects from templates
+            .add_plugins(NewProjectPlugin)
+            // lists the games running on this machine so the editor can connect to one
+            .add_plugins(ConnectToPlugin)
+            // mirrors remote entities into local proxies for the panels to query
+            .add_plugins(RemoteReplicaPlugin)
+            // shows the components of the selected entity and edits them
+            .add_plugins(InspectorPlugin)
+            // shows the entity hierarchy and picks the entity to inspect
+            .add_plugins(OutlinerPlugin)
+            // pushes local changes to linked remote entities
+            .add_plugins(RemoteLinkPlugin)
+            // asks which side to keep when the editor and a game both changed a linked component
+            .add_plugins(LinkConflictPlugin)
             // set up bevy_defer
             //.add_plugins(AsyncPlugin::default_settings())
 
@@ -124,8 +160,8 @@ impl Plugin for EditorPlugin {
 
             // also need to support adding new tabs to the containers and removing them
 
-            // handle selecting New, Open, Exit, etc from the menu
-            .add_systems(PreUpdate, (exit_app_on_menu_item, new_project, open_file))
+            // handle selecting Exit from the menu
+            .add_systems(PreUpdate, exit_app_on_menu_item)
 
             // update_current_page checks the menu for updates while the rest handle radios and dropdowns
             .add_systems(
@@ -144,36 +180,6 @@ impl Plugin for EditorPlugin {
     }
 }
 
-// TODO move all these to the router?
-fn new_project(
-    q_menu_items: Query<&MenuItem, (With<NewProjectButton>, Changed<MenuItem>)>,
-    mut _commands: Commands
-) {
-    let Ok(item) = q_menu_items.get_single() else {
-        return;
-    };
-
-    if item.interacted() {
-        info!("NEW PROJECT");
-    }
-}
-
-fn open_file(
-    q_menu_items: Query<&MenuItem, (With<OpenFileButton>, Changed<MenuItem>)>,
-    _native_dialogs_on_main_thread: Option<NonSend<NonSendMarker>>,
-    mut _commands: Commands
-) {
-    let Ok(item) = q_menu_items.get_single() else {
-        return;
-    };
-
-    if item.interacted() {
-        info!("OPEN FILE");
-        let file = FileDialog::new().set_location("~").show_open_single_file().unwrap();
-        info!("file: {:?}", &file);
-    }
-}
-
 // BEGIN: sickle editor example systems (menu navigation)
 fn exit_app_on_menu_item(
     q_menu_items: Query<&MenuItem, (With<ExitAppButton>, Changed<MenuItem>)>,
@@ -0,0 +1,767 @@
+//! A project is a folder holding a game's scenes, assets, editor layouts and locales, described by
+//! a manifest file at the top of it, along with where the game listens for BRP requests.
+//!
+//! The manifest is JSON, with every path relative to the project folder:
+//!
+//! ```json
+//! {
+//!   "name": "breakout",
+//!   "scenes": ["assets/scenes/main.scn.ron"],
+//!   "assets": "assets",
+//!   "layouts": [],
+//!   "locale": { "default": "en-US", "supported": ["en-US"], "folder": "assets/locales" },
+//!   "remote": { "url": "http://127.0.0.1:15702" }
+//! }
+//! ```
+//!
+//! Opening a project connects to its game. The project counts as changed when there have been
+//! edits since it was saved, or when the editor was connected to another game, which saving then
+//! makes the project's target. Saving edits asks the game for its world as a scene (with the
+//! `SCENE` verb), which is written to the first of the project's scenes once it arrives, and only
+//! then do the edits count as saved. Likewise, a project that is saved on its way out is only
+//! closed once its scene is written, and stays open if it can't be.
+//!
+//! New projects are made from templates by the
+//! [New Project activity](crate::activity::new_project).
+
+use std::{ fs, path::{ Path, PathBuf } };
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::{ ecs::world::CommandQueue, prelude::*, window::PrimaryWindow };
+
+use native_dialog::{ FileDialog, MessageDialog, MessageType };
+use serde::{ Deserialize, Serialize };
+use sickle_ui::prelude::*;
+
+use crate::{
+    framework::*,
+    history::HistoryPlugin,
+    locale::DEFAULT_LOCALE,
+    remote::{
+        brp_client::{ BrpRequestId, BrpResponse },
+        builtin_verbs::{ BrpSceneRequest, BrpSceneResponse },
+        connections::BrpConnections,
+        DEFAULT_PORT,
+    },
+    service::EditorService,
+};
+
+/// The name of the manifest file at the top of a project folder.
+pub const MANIFEST_FILE: &str = "beverage.json";
+
+// what a new scene file holds: a scene with nothing in it
+const EMPTY_SCENE: &str = "(\n  resources: {},\n  entities: {},\n)\n";
+
+pub struct ProjectPlugin;
+
+impl Plugin for ProjectPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<HistoryPlugin>() {
+            app.add_plugins(HistoryPlugin);
+        }
+
+        app.init_resource::<ProjectService>()
+            .add_event::<BrpResponse<BrpSceneResponse>>()
+            .add_systems(OnEnter(EditorState::Running), open_project_on_start)
+            .add_systems(
+                PreUpdate,
+                (open_project_on_menu_item, save_project_on_menu_item, close_project_on_menu_item)
+            )
+            .add_systems(
+                Update,
+                (write_saved_scenes, follow_remote_target, show_project_in_window_title).chain()
+            );
+    }
+}
+
+/// A project to open once the editor is up, like one named on the command line.
+#[derive(Resource, Debug)]
+pub struct OpenProjectOnStart(pub PathBuf);
+
+/// The project service holds the project that is open, if there is one.
+#[derive(Resource, Default, Debug)]
+pub struct ProjectService {
+    current: Option<Project>,
+
+    // scenes asked of the game, to be written once they arrive
+    // (even if the project has been closed since)
+    saving: Vec<SceneSave>,
+}
+
+#[derive(Debug)]
+struct SceneSave {
+    connection: String,
+    id: BrpRequestId,
+    folder: PathBuf,
+    file: PathBuf,
+    revision: u64,
+
+    // what to do once the scene is written, like closing the project that was saved on its way out
+    then: Option<fn(&mut World)>,
+}
+
+impl ProjectService {
+    pub fn current(&self) -> Option<&Project> {
+        self.current.as_ref()
+    }
+
+    pub fn current_mut(&mut self) -> Option<&mut Project> {
+        self.current.as_mut()
+    }
+
+    /// Whether the open project has changed since it was saved, given the history's
+    /// [`revision`](crate::history::HistoryService::revision).
+    pub fn is_dirty(&self, revision: u64) -> bool {
+        self.current.as_ref().is_some_and(|project| project.is_dirty(revision))
+    }
+}
+
+/// An open project, along with what it was like when it was last saved.
+#[derive(Debug)]
+pub struct Project {
+    folder: PathBuf,
+    manifest: ProjectManifest,
+
+    saved: ProjectManifest,
+    saved_revision: u64,
+}
+
+impl Project {
+    /// Reads the manifest of the project in a folder, or of the manifest file itself.
+    pub fn load(path: &Path) -> AnyhowResult<Self> {
+        let (folder, file) = if path.is_dir() {
+            (path.to_path_buf(), path.join(MANIFEST_FILE))
+        } else {
+            let folder = path.parent().ok_or_else(|| anyhow!("{} has no folder", path.display()))?;
+            (folder.to_path_buf(), path.to_path_buf())
+        };
+
+        let json = fs
+            ::read_to_string(&file)
+            .map_err(|error| anyhow!("Could not read {}: {}", file.display(), error))?;
+        let manifest: ProjectManifest = serde_json
+            ::from_str(&json)
+            .map_err(|error| anyhow!("{} isn't a project manifest: {}", file.display(), error))?;
+
+        Ok(Self {
+            folder,
+            saved: manifest.clone(),
+            manifest,
+            saved_revision: 0,
+        })
+    }
+
+    pub fn folder(&self) -> &Path {
+        &self.folder
+    }
+
+    pub fn manifest(&self) -> &ProjectManifest {
+        &self.manifest
+    }
+
+    pub fn manifest_mut(&mut self) -> &mut ProjectManifest {
+        &mut self.manifest
+    }
+
+    /// Where a path in the manifest is on disk.
+    pub fn path(&self, relative: impl AsRef<Path>) -> PathBuf {
+        self.folder.join(relative)
+    }
+
+    pub fn is_dirty(&self, revision: u64) -> bool {
+        self.manifest != self.saved || revision != self.saved_revision
+    }
+
+    /// Whether there have been edits since the project's scene was last written.
+    pub fn has_unsaved_edits(&self, revision: u64) -> bool {
+        revision != self.saved_revision
+    }
+
+    /// Writes the manifest. The edits are saved separately, along with the game's scene (see
+    /// [`save`](self::save)).
+    pub fn save(&mut self) -> AnyhowResult<()> {
+        write_manifest(&self.folder, &self.manifest)?;
+        self.saved = self.manifest.clone();
+        Ok(())
+    }
+
+    // where the edits to the game's world are saved, the scene it starts with
+    fn scene_file(&self) -> AnyhowResult<PathBuf> {
+        let scene = self.manifest.scenes.first();
+        let scene = scene.ok_or_else(|| anyhow!("{} has no scene to save", self.manifest.name))?;
+        Ok(self.path(scene))
+    }
+}
+
+/// What is in a project, as it's written to the [`MANIFEST_FILE`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ProjectManifest {
+    pub name: String,
+
+    /// Scene files, the first of which is the one the game starts with.
+    pub scenes: Vec<PathBuf>,
+
+    /// The folder the game loads its assets from.
+    pub assets: PathBuf,
+
+    /// Editor layouts saved with the project.
+    pub layouts: Vec<PathBuf>,
+
+    pub locale: ProjectLocale,
+    pub remote: ProjectRemote,
+}
+
+impl ProjectManifest {
+    /// The manifest of a new project, with one empty scene.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            scenes: vec![PathBuf::from("assets/scenes/main.scn.ron")],
+            ..default()
+        }
+    }
+}
+
+impl Default for ProjectManifest {
+    fn default() -> Self {
+        Self {
+            name: "untitled".into(),
+            scenes: vec![],
+            assets: PathBuf::from("assets"),
+            layouts: vec![],
+            locale: default(),
+            remote: default(),
+        }
+    }
+}
+
+/// The locales the game is translated into.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ProjectLocale {
+    pub default: String,
+    pub supported: Vec<String>,
+
+    /// Holds a folder of Fluent files for each supported locale.
+    pub folder: PathBuf,
+}
+
+impl Default for ProjectLocale {
+    fn default() -> Self {
+        Self {
+            default: DEFAULT_LOCALE.into(),
+            supported: vec![DEFAULT_LOCALE.into()],
+            folder: PathBuf::from("assets/locales"),
+        }
+    }
+}
+
+/// Where the game listens for BRP requests.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ProjectRemote {
+    pub url: String,
+}
+
+impl ProjectRemote {
+    pub fn local(port: u16) -> Self {
+        Self { url: format!("http://127.0.0.1:{}", port) }
+    }
+}
+
+impl Default for ProjectRemote {
+    fn default() -> Self {
+        Self::local(DEFAULT_PORT)
+    }
+}
+
+/// Makes a project in a folder, with the folders its manifest names and an empty file for each of
+/// its scenes. Files that are already there are left alone, but there mustn't be a manifest yet.
+pub fn create(folder: &Path, manifest: &ProjectManifest) -> AnyhowResult<()> {
+    if folder.join(MANIFEST_FILE).exists() {
+        return Err(anyhow!("There already is a project in {}", folder.display()));
+    }
+
+    fs::create_dir_all(folder.join(&manifest.assets))?;
+    fs::create_dir_all(folder.join("layouts"))?;
+    for locale in &manifest.locale.supported {
+        fs::create_dir_all(folder.join(&manifest.locale.folder).join(locale))?;
+    }
+    for scene in &manifest.scenes {
+        let scene = folder.join(scene);
+        if let Some(parent) = scene.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if !scene.exists() {
+            fs::write(scene, EMPTY_SCENE)?;
+        }
+    }
+
+    write_manifest(folder, manifest)
+}
+
+fn write_scene(file: &Path, ron: &str) -> AnyhowResult<()> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(file, ron).map_err(|error| anyhow!("Could not write {}: {}", file.display(), error))
+}
+
+fn write_manifest(folder: &Path, manifest: &ProjectManifest) -> AnyhowResult<()> {
+    let file = folder.join(MANIFEST_FILE);
+    let json = serde_json::to_string_pretty(manifest)?;
+    fs::write(&file, json).map_err(|error| anyhow!("Could not write {}: {}", file.display(), error))
+}
+
+/// Asks for a project manifest to open, closing the open project first.
+pub fn open(world: &mut World) {
+    if !close_then(world, open) {
+        return;
+    }
+
+    let file = FileDialog::new()
+        .set_location("~")
+        .add_filter("Beverage project", &["json"])
+        .show_open_single_file();
+    match file {
+        Ok(Some(file)) => open_path(world, &file),
+        Ok(None) => {}
+        Err(error) => warn!("Could not pick a project: {}", error),
+    }
+}
+
+/// Opens the project in a folder, or of a manifest file, and connects to its game.
+pub fn open_path(world: &mut World, path: &Path) {
+    let mut project = match Project::load(path) {
+        Ok(project) => project,
+        Err(error) => {
+            warn!("Could not open a project: {}", error);
+            return;
+        }
+    };
+
+    // (edits made before the project was opened aren't part of it)
+    if let Some(mut editor) = world.get_resource_mut::<EditorService>() {
+        editor.history.clear();
+        project.saved_revision = editor.history.revision();
+    }
+
+    if let Some(mut connections) = world.get_resource_mut::<BrpConnections>() {
+        let url = &project.manifest.remote.url;
+        let name = match connections.find_url(url) {
+            Some(name) => name.clone(),
+            None => {
+                connections.add(project.manifest.name.clone(), url.clone());
+                project.manifest.name.clone()
+            }
+        };
+        connections.set_primary(name.clone());
+        if let Some(connection) = connections.get_mut(&name) {
+            connection.connect();
+        }
+    }
+
+    info!("Opened project {} in {}", project.manifest.name, project.folder.display());
+    world.resource_mut::<ProjectService>().current = Some(project);
+}
+
+/// Saves the open project, asking its game for the scene to save the edits to if there are any.
+pub fn save(world: &mut World) {
+    if let Err(error) = save_current(world, None) {
+        warn!("Could not save the project: {}", error);
+    }
+}
+
+// save the open project, and return whether it's all saved already; otherwise its scene is still
+// to be written, and `then` runs once it is
+fn save_current(world: &mut World, then: Option<fn(&mut World)>) -> AnyhowResult<bool> {
+    let revision = revision(world);
+    let mut projects = world.resource_mut::<ProjectService>();
+    let Some(project) = projects.current.as_mut() else {
+        return Ok(true);
+    };
+    project.save()?;
+    info!("Saved project {}", project.manifest.name);
+    if !project.has_unsaved_edits(revision) {
+        return Ok(true);
+    }
+
+    let file = project.scene_file()?;
+    let folder = project.folder.clone();
+    let url = project.manifest.remote.url.clone();
+    let (connection, id) = request_scene(world, &url)?;
+
+    let save = SceneSave { connection, id, folder, file, revision, then };
+    world.resource_mut::<ProjectService>().saving.push(save);
+    Ok(false)
+}
+
+/// Closes the open project, asking first whether to save it if it has changed, and returns whether
+/// it was closed (or there was none).
+///
+/// A project that is saved first isn't closed until the game's scene is written, which takes a
+/// round trip to the game, so it's still open when this returns. It is kept open if the scene can't
+/// be written, so the edits aren't lost.
+pub fn close(world: &mut World) -> bool {
+    close_then(world, |world| {
+        close(world);
+    })
+}
+
+// close the open project, or if it has to be saved first, call `then` once it is
+// (which can close it without asking again, unless there were more edits in the meantime)
+fn close_then(world: &mut World, then: fn(&mut World)) -> bool {
+    let revision = revision(world);
+    let Some(project) = world.resource::<ProjectService>().current() else {
+        return true;
+    };
+
+    if project.is_dirty(revision) {
+        let name = project.manifest.name.clone();
+        let save_first = confirm(
+            "Unsaved changes",
+            &format!("{} has changed. Save the changes before closing it?", name)
+        );
+        if save_first {
+            match save_current(world, Some(then)) {
+                Ok(true) => {}
+                Ok(false) => {
+                    info!("Closing {} once its edits are saved", name);
+                    return false;
+                }
+                Err(error) => {
+                    warn!("Could not save the project: {}", error);
+                    return false;
+                }
+            }
+        } else if !confirm("Unsaved changes", &format!("Close {} and lose the changes?", name)) {
+            return false;
+        }
+    }
+
+    if let Some(mut editor) = world.get_resource_mut::<EditorService>() {
+        editor.history.clear();
+    }
+    if let Some(project) = world.resource_mut::<ProjectService>().current.take() {
+        info!("Closed project {}", project.manifest.name);
+    }
+    true
+}
+
+// ask the game at a URL for its world as a scene, over the connection to it
+fn request_scene(world: &mut World, url: &str) -> AnyhowResult<(String, BrpRequestId)> {
+    if !world.contains_resource::<BrpConnections>() {
+        return Err(anyhow!("The editor can't connect to games to save the edits"));
+    }
+
+    world.resource_scope(|world, mut connections: Mut<BrpConnections>| {
+        let connection = connections
+            .find_url(url)
+            .cloned()
+            .and_then(|name| connections.get_mut(&name))
+            .filter(|connection| connection.is_connected())
+            .ok_or_else(|| anyhow!("The game at {} isn't connected to save the edits", url))?;
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, world);
+        let result = connection.client.call::<_, BrpSceneResponse>(
+            "SCENE",
+            BrpSceneRequest {},
+            None,
+            &mut commands
+        );
+        queue.apply(world);
+        Ok((connection.name().to_owned(), result?))
+    })
+}
+
+// write the scenes asked of the game, and count their edits as saved if the project is still open
+fn write_saved_scenes(
+    mut responses: EventReader<BrpResponse<BrpSceneResponse>>,
+    mut projects: ResMut<ProjectService>,
+    mut commands: Commands
+) {
+    for response in responses.read() {
+        let answers = |save: &SceneSave| {
+            save.connection == response.connection && save.id == response.id
+        };
+        let Some(index) = projects.saving.iter().position(answers) else {
+            continue;
+        };
+        let save = projects.saving.remove(index);
+
+        let written = match &response.result {
+            Ok(scene) => write_scene(&save.file, &scene.ron),
+            Err(error) => Err(anyhow!("The game didn't send its scene: {}", error)),
+        };
+        if let Err(error) = written {
+            warn!("Could not save the edits: {}", error);
+            if save.then.is_some() {
+                let text = format!(
+                    "The edits couldn't be saved, so the project is still open.\n\n{}",
+                    error
+                );
+                commands.add(move |_: &mut World| alert("Unsaved changes", &text));
+            }
+            continue;
+        }
+
+        info!("Saved the edits to {}", save.file.display());
+        let saved_here = |project: &Project| project.folder == save.folder;
+        let Some(project) = projects.current.as_mut().filter(|project| saved_here(project)) else {
+            continue;
+        };
+        project.saved_revision = save.revision;
+        if let Some(then) = save.then {
+            commands.add(then);
+        }
+    }
+}
+
+fn revision(world: &World) -> u64 {
+    world.get_resource::<EditorService>().map_or(0, |editor| editor.history.revision())
+}
+
+// ask a yes or no question
+pub(crate) fn confirm(title: &str, text: &str) -> bool {
+    MessageDialog::new()
+        .set_type(MessageType::Warning)
+        .set_title(title)
+        .set_text(text)
+        .show_confirm()
+        .unwrap_or_else(|error| {
+            warn!("Could not ask \"{}\": {}", text, error);
+            false
+        })
+}
+
+// tell the user something went wrong
+fn alert(title: &str, text: &str) {
+    let shown = MessageDialog::new()
+        .set_type(MessageType::Error)
+        .set_title(title)
+        .set_text(text)
+        .show_alert();
+    if let Err(error) = shown {
+        warn!("Could not tell \"{}\": {}", text, error);
+    }
+}
+
+fn open_project_on_start(world: &mut World) {
+    if let Some(OpenProjectOnStart(path)) = world.remove_resource::<OpenProjectOnStart>() {
+        open_path(world, &path);
+    }
+}
+
+// (commands are applied on the main thread, which is where the native dialogs have to be shown)
+fn open_project_on_menu_item(
+    q_menu_items: Query<&MenuItem, (With<OpenFileButton>, Changed<MenuItem>)>,
+    mut commands: Commands
+) {
+    let Ok(item) = q_menu_items.get_single() else {
+        return;
+    };
+
+    if item.interacted() {
+        commands.add(open);
+    }
+}
+
+fn save_project_on_menu_item(
+    q_menu_items: Query<&MenuItem, (With<SaveProjectButton>, Changed<MenuItem>)>,
+    mut commands: Commands
+) {
+    let Ok(item) = q_menu_items.get_single() else {
+        return;
+    };
+
+    if item.interacted() {
+        commands.add(save);
+    }
+}
+
+fn close_project_on_menu_item(
+    q_menu_items: Query<&MenuItem, (With<CloseProjectButton>, Changed<MenuItem>)>,
+    mut commands: Commands
+) {
+    let Ok(item) = q_menu_items.get_single() else {
+        return;
+    };
+
+    if item.interacted() {
+        commands.add(|world: &mut World| {
+            close(world);
+        });
+    }
+}
+
+// the game the editor is connected to is the one the project is saved with
+fn follow_remote_target(
+    connections: Option<Res<BrpConnections>>,
+    mut projects: ResMut<ProjectService>
+) {
+    let Some(url) = connections
+        .as_ref()
+        .and_then(|connections| connections.primary())
+        .map(|connection| &connection.client.url) else {
+        return;
+    };
+
+    let moved = |project: &Project| project.manifest.remote.url != *url;
+    if projects.current().is_some_and(moved) {
+        if let Some(project) = projects.current_mut() {
+            project.manifest.remote.url = url.clone();
+        }
+    }
+}
+
+fn show_project_in_window_title(
+    projects: Res<ProjectService>,
+    editor: Option<Res<EditorService>>,
+    mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut untitled: Local<Option<String>>
+) {
+    let Ok(mut window) = q_windows.get_single_mut() else {
+        return;
+    };
+    let untitled = untitled.get_or_insert_with(|| window.title.clone());
+
+    let revision = editor.map_or(0, |editor| editor.history.revision());
+    let title = match projects.current() {
+        Some(project) => {
+            let dirty = if project.is_dirty(revision) { " *" } else { "" };
+            format!("{}{} - {}", project.manifest.name, dirty, untitled)
+        }
+        None => untitled.clone(),
+    };
+    if window.title != title {
+        window.title = title;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remote::brp_client::BrpError;
+
+    use super::*;
+
+    // an empty folder of its own for each test
+    fn scratch_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("beverage-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    // what the scene of a project made with ProjectManifest::new holds
+    fn main_scene(folder: &Path) -> String {
+        fs::read_to_string(folder.join("assets/scenes/main.scn.ron")).unwrap()
+    }
+
+    #[test]
+    fn manifests_round_trip() {
+        let folder = scratch_folder("round-trip");
+        let mut manifest = ProjectManifest::new("breakout");
+        manifest.remote = ProjectRemote::local(15703);
+        manifest.locale.supported.push("fr-FR".into());
+        create(&folder, &manifest).unwrap();
+
+        // from the folder or the manifest in it
+        assert_eq!(Project::load(&folder).unwrap().manifest(), &manifest);
+        assert_eq!(Project::load(&folder.join(MANIFEST_FILE)).unwrap().manifest(), &manifest);
+
+        assert_eq!(main_scene(&folder), EMPTY_SCENE);
+        assert!(folder.join("assets/locales/fr-FR").is_dir());
+        assert!(create(&folder, &manifest).is_err(), "there already is a project");
+    }
+
+    #[test]
+    fn missing_fields_are_defaults() {
+        let manifest: ProjectManifest = serde_json::from_str(r#"{ "name": "pong" }"#).unwrap();
+        assert_eq!(manifest, ProjectManifest { name: "pong".into(), ..default() });
+    }
+
+    #[test]
+    fn changes_and_edits_make_projects_dirty() {
+        let folder = scratch_folder("dirty");
+        create(&folder, &ProjectManifest::new("breakout")).unwrap();
+        let mut project = Project::load(&folder).unwrap();
+        assert!(!project.is_dirty(0));
+
+        // edits count until the scene is written
+        assert!(project.is_dirty(1));
+        assert!(project.has_unsaved_edits(1));
+
+        // a changed manifest counts until it's saved, which doesn't save the edits
+        project.manifest_mut().remote = ProjectRemote::local(15703);
+        assert!(project.is_dirty(0));
+        project.save().unwrap();
+        assert!(!project.is_dirty(0));
+        assert!(project.has_unsaved_edits(1));
+        assert_eq!(Project::load(&folder).unwrap().manifest().remote, ProjectRemote::local(15703));
+    }
+
+    // an app with a project open, that has asked the game for its scene to close after it's saved
+    fn saving_app(folder: &Path) -> App {
+        create(folder, &ProjectManifest::new("breakout")).unwrap();
+        let project = Project::load(folder).unwrap();
+        let save = SceneSave {
+            connection: "game".into(),
+            id: BrpRequestId(7),
+            folder: folder.to_path_buf(),
+            file: project.scene_file().unwrap(),
+            revision: 3,
+            then: Some(|world| {
+                world.resource_mut::<ProjectService>().current = None;
+            }),
+        };
+
+        let mut app = App::new();
+        app.add_event::<BrpResponse<BrpSceneResponse>>()
+            .insert_resource(ProjectService { current: Some(project), saving: vec![save] })
+            .add_systems(Update, write_saved_scenes);
+        app
+    }
+
+    fn scene_response(result: Result<BrpSceneResponse, BrpError>) -> BrpResponse<BrpSceneResponse> {
+        BrpResponse {
+            connection: "game".into(),
+            id: BrpRequestId(7),
+            verb: "SCENE".into(),
+            entity: None,
+            result,
+        }
+    }
+
+    #[test]
+    fn saved_scenes_are_written_before_closing() {
+        let folder = scratch_folder("close");
+        let mut app = saving_app(&folder);
+        app.update();
+        assert!(app.world().resource::<ProjectService>().current().is_some());
+
+        let ron = "(\n  resources: {},\n  entities: { 4294967296: (components: {}) },\n)\n";
+        app.world_mut().send_event(scene_response(Ok(BrpSceneResponse { ron: ron.into() })));
+        app.update();
+        assert_eq!(main_scene(&folder), ron);
+        assert!(app.world().resource::<ProjectService>().current().is_none());
+    }
+
+    #[test]
+    fn projects_whose_scene_isnt_saved_stay_dirty() {
+        let folder = scratch_folder("unsaved");
+        let mut app = saving_app(&folder);
+
+        // (nothing is to happen after this save, or the failure would be shown in a dialog)
+        app.world_mut().resource_mut::<ProjectService>().saving[0].then = None;
+        let error = BrpError::Timeout(std::time::Duration::from_secs(5));
+        app.world_mut().send_event(scene_response(Err(error)));
+        app.update();
+
+        let projects = app.world().resource::<ProjectService>();
+        assert!(projects.saving.is_empty());
+        assert!(projects.current().unwrap().has_unsaved_edits(3));
+        assert_eq!(main_scene(&folder), EMPTY_SCENE);
+    }
+}
@@ -1,50 +1,380 @@
-use std::{ any::Any, sync::{ Arc, Mutex } };
+use std::{
+    any::Any,
+    fmt,
+    sync::{ atomic::{ AtomicBool, Ordering }, Arc },
+    time::{ Duration, Instant },
+};
 
 use bevy::{ prelude::*, tasks::IoTaskPool, utils::HashMap };
 
-use anyhow::anyhow;
-use ehttp::Request;
+use serde::{ de::DeserializeOwned, Serialize };
 use serde_json::Value;
+use smol::channel::{ self, Receiver, Sender };
 
 use crate::remote::*;
-use super::{ builtin_verbs::*, BrpRequest, DEFAULT_PORT };
+use super::{
+    builtin_verbs::*,
+    connections::{
+        check_heartbeat,
+        sync_connection_state,
+        update_connections,
+        BrpConnections,
+        RemoteConnection,
+        DEFAULT_CONNECTION,
+    },
+    discovery::{ refresh_discovered_servers, DiscoveredServers },
+    encoding::BrpEncoding,
+    transport::{ BrpTransport, EhttpTransport },
+    BrpRequest,
+    DEFAULT_PORT,
+};
 
-// ehttp builder
-struct EhttpBuilder;
-pub trait RemoteRequestBuilder: Send + Sync + 'static {
-    // TODO accept callback closure instead of returning ehttp::Request
-    fn post(&self, url: String, body: String) -> ehttp::Request;
+// sets up the BrpConnections resource and turns the responses of its clients into events
+pub struct BrpClientPlugin;
+
+impl Plugin for BrpClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BrpConnections>()
+            .init_resource::<DiscoveredServers>()
+            .register_type::<RemoteConnection>()
+            .add_event::<BrpResponse<BrpQueryResponse>>()
+            .add_event::<BrpResponse<BrpGetResponse>>()
+            .add_event::<BrpResponse<BrpListResponse>>()
+            .add_event::<BrpResponse<BrpEntityResponse>>()
+            .add_event::<BrpResponse<BrpOkResponse>>()
+            .add_event::<BrpResponse<BrpPingResponse>>()
+            .add_event::<BrpResponse<Value>>()
+            .add_event::<BrpTransportError>()
+            .add_event::<BrpTimeoutError>()
+            // responses are delivered before Update so every panel sees them the same frame
+            .add_systems(
+                PreUpdate,
+                (expire_brp_requests, process_brp_responses, send_held_brp_requests).chain()
+            )
+            .add_systems(
+                Update,
+                (check_heartbeat, update_connections, sync_connection_state).chain()
+            )
+            .add_systems(Update, refresh_discovered_servers);
+    }
+}
+
+/// Identifies a request sent with [`BrpClient`]. The [`BrpResponse`] to it carries the same ID.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+pub struct BrpRequestId(pub u32);
+
+impl fmt::Display for BrpRequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Why a request sent with [`BrpClient`] didn't produce a response.
+#[derive(Clone, Debug)]
+pub enum BrpError {
+    /// The request never made it to the server, or the response never made it back.
+    Transport(String),
+
+    /// The server responded with an error.
+    Remote(String),
+
+    /// The response didn't have the expected shape.
+    Decode(String),
+
+    /// The server didn't respond in time (see [`BrpCallOptions::timeout`]).
+    Timeout(Duration),
+
+    /// The request was cancelled, or superseded by a newer one, before the response arrived.
+    Cancelled,
+}
+
+impl fmt::Display for BrpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BrpError::Transport(message) => write!(f, "transport error: {}", message),
+            BrpError::Remote(message) => write!(f, "remote error: {}", message),
+            BrpError::Decode(message) => write!(f, "decode error: {}", message),
+            BrpError::Timeout(after) => write!(f, "timed out after {:?}", after),
+            BrpError::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+/// Sent when the response to a [`BrpClient`] request arrives.
+///
+/// The response is delivered both as a regular event and as an observer trigger. If the request
+/// was made on behalf of a local entity, the trigger targets that entity, so a panel can simply
+/// observe its own responses.
+///
+/// Responses to custom verbs sent with [`BrpClient::call`] need their event type added to the app
+/// with `add_event::<BrpResponse<T>>()`.
+#[derive(Event, Clone, Debug)]
+pub struct BrpResponse<T> {
+    /// The name of the connection the request was sent over.
+    pub connection: String,
+
+    /// The ID returned when the request was sent.
+    pub id: BrpRequestId,
+
+    /// The verb of the request.
+    pub verb: String,
+
+    /// The local entity the request was made on behalf of, if any.
+    pub entity: Option<Entity>,
+
+    /// The deserialized response, or what went wrong.
+    pub result: Result<T, BrpError>,
+}
+
+/// Sent when a request sent with [`BrpClient`] couldn't reach the server.
+///
+/// This usually means the server has gone away, so the connection is considered lost.
+#[derive(Event, Clone, Debug)]
+pub struct BrpTransportError {
+    /// The name of the connection the request was sent over.
+    pub connection: String,
+
+    /// The ID of the request that failed.
+    pub id: BrpRequestId,
+
+    /// The verb of the request.
+    pub verb: String,
+
+    /// What went wrong.
+    pub message: String,
+}
+
+const CAMERA_TYPE_PATH: &str = "bevy_render::camera::camera::Camera";
+const NAME_TYPE_PATH: &str = "bevy_core::name::Name";
+
+/// A camera found in the remote app by [`BrpClient::fetch_remote_cameras`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemoteCameraInfo {
+    /// The remote entity.
+    pub entity: Entity,
+
+    /// The `Name` of the entity, if it has one.
+    pub name: Option<String>,
+
+    /// The `order` of the camera, which decides what is drawn on top.
+    pub order: isize,
+
+    /// What the camera renders to, such as `Window(Primary)` or `Image`.
+    ///
+    /// Bevy 0.14 doesn't reflect the render target, so this is only known to newer games.
+    pub target: Option<String>,
+
+    /// Whether the camera is rendering.
+    pub is_active: bool,
+}
+
+impl RemoteCameraInfo {
+    /// The query that finds every camera, with its name if it has one.
+    pub fn query() -> BrpQuery {
+        BrpQuery {
+            // must use full type path
+            components: vec![CAMERA_TYPE_PATH.to_string()],
+            option: vec![NAME_TYPE_PATH.to_string()],
+            ..default()
+        }
+    }
+
+    /// Reads the cameras out of the response to a camera query, ordered by `order` and then name.
+    pub fn from_rows(rows: &[BrpQueryRow]) -> Vec<Self> {
+        let mut cameras: Vec<Self> = rows.iter().map(Self::from_row).collect();
+        cameras.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.name.cmp(&b.name)));
+        cameras
+    }
+
+    fn from_row(row: &BrpQueryRow) -> Self {
+        let camera = row.components.get(CAMERA_TYPE_PATH);
+        let field = |name: &str| camera.and_then(|camera| camera.get(name));
+
+        Self {
+            entity: row.entity,
+            name: row.components
+                .get(NAME_TYPE_PATH)
+                .and_then(|name| name.as_str())
+                .map(str::to_owned),
+            order: field("order")
+                .and_then(|order| order.as_i64())
+                .unwrap_or_default() as isize,
+            target: field("target").map(describe_render_target),
+            is_active: field("is_active")
+                .and_then(|is_active| is_active.as_bool())
+                .unwrap_or(true),
+        }
+    }
+
+    /// A short description for lists: the name (or entity), order and target.
+    pub fn label(&self) -> String {
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => self.entity.to_string(),
+        };
+        match &self.target {
+            Some(target) => format!("{} (order {}, {})", name, self.order, target),
+            None => format!("{} (order {})", name, self.order),
+        }
+    }
+}
+
+// enum variants are serialized as { "Variant": value }, so Window(Primary) comes out as
+// { "Window": "Primary" }
+fn describe_render_target(target: &Value) -> String {
+    match target {
+        Value::String(variant) => variant.clone(),
+        Value::Object(map) =>
+            match map.iter().next() {
+                Some((variant, Value::String(inner))) => format!("{}({})", variant, inner),
+                Some((variant, _)) => variant.clone(),
+                None => String::new(),
+            }
+        _ => target.to_string(),
+    }
+}
+
+/// Sent when a request sent with [`BrpClient`] got no response in time.
+///
+/// The game may be stuck, or paused in a debugger. The response to the request, if it ever comes,
+/// is ignored.
+#[derive(Event, Clone, Debug)]
+pub struct BrpTimeoutError {
+    /// The name of the connection the request was sent over.
+    pub connection: String,
+
+    /// The ID of the request that timed out.
+    pub id: BrpRequestId,
+
+    /// The verb of the request.
+    pub verb: String,
+
+    /// How long the request waited.
+    pub after: Duration,
+}
+
+/// How a request is sent by [`BrpClient::call_with`].
+#[derive(Clone, Debug, Default)]
+pub struct BrpCallOptions {
+    /// How long to wait for the response, instead of [`BrpClient::timeout`].
+    pub timeout: Option<Duration>,
+
+    /// Requests sent with the same key replace each other: sending a new one cancels any older
+    /// one still waiting for its response, for instance an update of the same remote component.
+    ///
+    /// Only one request with a key is on its way to the server at a time. A newer one waits until
+    /// the server has answered, and is replaced in turn if yet another comes along in the meantime,
+    /// so that stale requests never reach the server.
+    pub supersede: Option<String>,
+}
+
+impl BrpCallOptions {
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn superseding(mut self, key: impl Into<String>) -> Self {
+        self.supersede = Some(key.into());
+        self
+    }
+}
+
+// runs on the main thread once a response has arrived
+type BrpCompletion = Box<dyn FnOnce(&mut World) + Send>;
+
+// builds the completion that fails a request, when it times out or is cancelled
+type BrpFailure = Box<dyn FnOnce(BrpError) -> BrpCompletion + Send + Sync>;
+
+// a BrpResponder that can wait in the client (which is shared between systems) until it's sent
+type HeldResponder = Box<dyn FnOnce(Result<Value, BrpError>) + Send + Sync>;
+
+// a superseding request that waits for an older one with the same key to be answered
+struct HeldRequest {
+    id: BrpRequestId,
+    request: BrpRequest,
+    respond: HeldResponder,
+    sent: SentRequest,
+}
+
+// a superseding request on its way to the server, which holds back newer ones until it's answered
+// (or given up on, since a server that never answers would hold them back forever)
+#[derive(Clone)]
+struct SentRequest {
+    answered: Arc<AtomicBool>,
+    deadline: Instant,
 }
 
-impl RemoteRequestBuilder for EhttpBuilder {
-    fn post(&self, url: String, body: String) -> ehttp::Request {
-        ehttp::Request::post(url, body.into())
+impl SentRequest {
+    fn is_out(&self, now: Instant) -> bool {
+        !self.answered.load(Ordering::Acquire) && self.deadline > now
     }
 }
 
-// container for HTTP request task spawner
-#[derive(Resource)]
+// a request still waiting for its response
+struct PendingRequest {
+    deadline: Instant,
+    timeout: Duration,
+    supersede: Option<String>,
+
+    // set by whichever comes first: the response, the timeout, or a cancellation
+    settled: Arc<AtomicBool>,
+    fail: BrpFailure,
+}
+
+// container for HTTP request task spawner (one per connection in BrpConnections)
 pub struct BrpClient {
+    // name of the connection this client belongs to
+    pub connection: String,
+
     // id seq
     pub last_id: u32,
 
-    // where we store the bits of the remote camera EntityId
-    pub remote_entity_dungeon: Arc<Mutex<Option<Entity>>>,
+    // how request and response bodies are encoded on the wire
+    pub encoding: BrpEncoding,
 
-    // in case we want to do this another way
-    pub request_builder: Box<dyn RemoteRequestBuilder>,
+    // the remote entity that the local RemoteCamera controls, once known
+    pub remote_camera: Option<Entity>,
+
+    // the cameras found in the remote app, ordered by Camera::order (None until looked for)
+    pub remote_cameras: Option<Vec<RemoteCameraInfo>>,
+
+    // the name of the camera that was picked last, which is looked for again after a reconnect
+    pub preferred_camera: Option<String>,
+
+    // how requests reach the server (HTTP unless replaced, see transport)
+    pub transport: Arc<dyn BrpTransport>,
 
     // server URL http://host:port
     pub url: String,
+
+    // how long to wait for a response, unless the request says otherwise
+    pub timeout: Duration,
+
+    // responses waiting to be turned into events
+    completions: (Sender<BrpCompletion>, Receiver<BrpCompletion>),
+
+    // requests waiting for their responses, so they can time out or be cancelled
+    pending: HashMap<BrpRequestId, PendingRequest>,
+
+    // by supersede key, the request that went out last, and the newest one waiting to follow it
+    sent: HashMap<String, SentRequest>,
+    held: HashMap<String, HeldRequest>,
 }
 
 impl core::fmt::Debug for BrpClient {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("BrpClient")
+            .field("connection", &self.connection)
             .field("last_id", &self.last_id)
-            .field("remote_entity_dungeon", &self.remote_entity_dungeon)
-            //.field("request_builder", &self.request_builder)
+            .field("encoding", &self.encoding)
+            .field("remote_camera", &self.remote_camera)
+            .field("remote_cameras", &self.remote_cameras)
+            .field("preferred_camera", &self.preferred_camera)
             .field("url", &self.url)
+            .field("timeout", &self.timeout)
+            .field("pending", &self.pending.len())
+            .field("held", &self.held.len())
             .finish()
     }
 }
@@ -53,93 +383,304 @@ impl Default for BrpClient {
     fn default() -> Self {
         // Create the URL. We're going to need it to issue the HTTP request.
         let url = format!("http://{}:{}", "127.0.0.1", DEFAULT_PORT);
-        info!("BRP server URL: {}", url);
+        BrpClient::new(DEFAULT_CONNECTION.to_string(), url)
+    }
+}
+
+// convenience BRP entry point
+impl BrpClient {
+    pub fn new(connection: String, url: String) -> Self {
+        info!("BRP server URL ({}): {}", connection, url);
 
         Self {
+            connection,
             last_id: 0,
-            remote_entity_dungeon: Arc::new(Mutex::new(Option::<Entity>::None)),
-            request_builder: Box::new(EhttpBuilder),
+            encoding: BrpEncoding::default(),
+            remote_camera: None,
+            remote_cameras: None,
+            preferred_camera: None,
+            transport: Arc::new(EhttpTransport::default()),
             url,
+            timeout: Duration::from_secs(5),
+            completions: channel::unbounded(),
+            pending: default(),
+            sent: default(),
+            held: default(),
         }
     }
-}
 
-// convenience BRP entry point resource
-impl BrpClient {
-    pub fn fetch_remote_camera(
+    // query the remote world; the rows arrive as a BrpResponse<BrpQueryResponse>
+    pub fn query(
         &mut self,
-        entity: Entity,
+        request: BrpQueryRequest,
+        entity: Option<Entity>,
         commands: &mut Commands
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<BrpRequestId> {
+        self.call::<_, BrpQueryResponse>("QUERY", request, entity, commands)
+    }
+
+    // fetch components of a remote entity; they arrive as a BrpResponse<BrpGetResponse>
+    pub fn get(
+        &mut self,
+        request: BrpGetRequest,
+        entity: Option<Entity>,
+        commands: &mut Commands
+    ) -> anyhow::Result<BrpRequestId> {
+        self.call::<_, BrpGetResponse>("GET", request, entity, commands)
+    }
+
+    // list registered components, or the ones on a remote entity
+    pub fn list(
+        &mut self,
+        request: BrpListRequest,
+        entity: Option<Entity>,
+        commands: &mut Commands
+    ) -> anyhow::Result<BrpRequestId> {
+        self.call::<_, BrpListResponse>("LIST", request, entity, commands)
+    }
+
+    // spawn a remote entity; its ID arrives as a BrpResponse<BrpEntityResponse>
+    pub fn spawn(
+        &mut self,
+        request: BrpSpawnRequest,
+        entity: Option<Entity>,
+        commands: &mut Commands
+    ) -> anyhow::Result<BrpRequestId> {
+        self.call::<_, BrpEntityResponse>("SPAWN", request, entity, commands)
+    }
+
+    pub fn insert(
+        &mut self,
+        request: BrpInsertRequest,
+        entity: Option<Entity>,
+        commands: &mut Commands
+    ) -> anyhow::Result<BrpRequestId> {
+        self.call::<_, BrpOkResponse>("INSERT", request, entity, commands)
+    }
+
+    pub fn remove(
+        &mut self,
+        request: BrpRemoveRequest,
+        entity: Option<Entity>,
+        commands: &mut Commands
+    ) -> anyhow::Result<BrpRequestId> {
+        self.call::<_, BrpOkResponse>("REMOVE", request, entity, commands)
+    }
+
+    pub fn destroy(
+        &mut self,
+        request: BrpDestroyRequest,
+        entity: Option<Entity>,
+        commands: &mut Commands
+    ) -> anyhow::Result<BrpRequestId> {
+        self.call::<_, BrpOkResponse>("DESTROY", request, entity, commands)
+    }
+
+    pub fn reparent(
+        &mut self,
+        request: BrpReparentRequest,
+        entity: Option<Entity>,
+        commands: &mut Commands
+    ) -> anyhow::Result<BrpRequestId> {
+        self.call::<_, BrpOkResponse>("REPARENT", request, entity, commands)
+    }
+
+    // send any verb, including custom ones; the response arrives as a BrpResponse<T>
+    // (if the local entity is given, it is marked with RemoteRequest until the response arrives)
+    pub fn call<P, T>(
+        &mut self,
+        verb: &str,
+        params: P,
+        entity: Option<Entity>,
+        commands: &mut Commands
+    ) -> anyhow::Result<BrpRequestId>
+        where P: Serialize, T: DeserializeOwned + Clone + Send + Sync + 'static
+    {
+        self.call_with::<P, T>(verb, params, entity, default(), commands)
+    }
+
+    // like call, with a timeout of its own or superseding older requests
+    pub fn call_with<P, T>(
+        &mut self,
+        verb: &str,
+        params: P,
+        entity: Option<Entity>,
+        options: BrpCallOptions,
+        commands: &mut Commands
+    ) -> anyhow::Result<BrpRequestId>
+        where P: Serialize, T: DeserializeOwned + Clone + Send + Sync + 'static
+    {
         let request_id = self.next_id();
+        let params = serde_json::to_value(params)?;
+        let request = brp_request_from(request_id, params, verb);
 
-        let request = BrpQueryRequest {
-            data: BrpQuery {
-                // must use full type path
-                components: vec!["bevy_render::camera::camera::Camera".to_string()],
-                ..default()
-            },
-            filter: default(),
+        if let Some(key) = &options.supersede {
+            let superseded: Vec<BrpRequestId> = self.pending
+                .iter()
+                .filter(|(_, pending)| pending.supersede.as_ref() == Some(key))
+                .map(|(id, _)| *id)
+                .collect();
+            for id in superseded {
+                trace!("request {} superseded by {}", id, request_id);
+                self.cancel(id);
+            }
+        }
+
+        self.spawn_task::<T>(BrpRequestId(request_id), verb, entity, request, options, commands);
+
+        Ok(BrpRequestId(request_id))
+    }
+
+    /// Gives up on a request. Unless its response has already arrived, the response is ignored,
+    /// and a [`BrpResponse`] with [`BrpError::Cancelled`] is sent in its place.
+    ///
+    /// The server may still carry out the request.
+    pub fn cancel(&mut self, id: BrpRequestId) -> bool {
+        let Some(pending) = self.pending.remove(&id) else {
+            return false;
         };
-        let request = serde_json::to_value(request)?;
-        let request = self.ehttp_request_from(request_id, request, "QUERY", "fetch_remote_camera")?;
 
-        self.spawn_task(request_id, entity, true, request, commands);
+        self.fail(pending, BrpError::Cancelled)
+    }
 
-        Ok(())
+    // whether any request is still waiting for its response
+    pub fn is_waiting(&self) -> bool {
+        self.pending.values().any(|pending| !pending.settled.load(Ordering::Acquire))
     }
 
-    // increment the id counter and return the next value
-    pub fn next_id(&mut self) -> u32 {
-        self.last_id += 1;
-        self.last_id
+    // time out the requests that have waited too long, and forget the ones that were answered
+    fn expire(&mut self, now: Instant) {
+        let expired: Vec<BrpRequestId> = self.pending
+            .iter()
+            .filter(|(_, pending)| {
+                pending.settled.load(Ordering::Acquire) || pending.deadline <= now
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in expired {
+            let Some(pending) = self.pending.remove(&id) else {
+                continue;
+            };
+            let timeout = pending.timeout;
+            if self.fail(pending, BrpError::Timeout(timeout)) {
+                warn!("BRP request {} to `{}` timed out after {:?}", id, self.connection, timeout);
+            }
+        }
+    }
+
+    // send the held requests whose key is no longer out, unless they were given up on meanwhile
+    fn send_held(&mut self, now: Instant) {
+        let ready: Vec<String> = self.held
+            .keys()
+            .filter(|key| !self.sent.get(*key).is_some_and(|sent| sent.is_out(now)))
+            .cloned()
+            .collect();
+
+        for key in ready {
+            let Some(held) = self.held.remove(&key) else {
+                continue;
+            };
+            if !self.pending.contains_key(&held.id) {
+                continue;
+            }
+
+            trace!("sending held request {}", held.id);
+            self.sent.insert(key, held.sent);
+            self.transport.send(&self.url, self.encoding, held.request, held.respond);
+        }
+
+        self.sent.retain(|_, sent| sent.is_out(now));
+    }
+
+    // deliver the error in place of the response, unless the response got there first
+    fn fail(&self, pending: PendingRequest, error: BrpError) -> bool {
+        if pending.settled.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+
+        let _ = self.completions.0.send_blocking((pending.fail)(error));
+        true
     }
 
-    // insert the provided Transform into the specified remote Entity
-    pub fn post_transform(
+    // look for every camera in the remote app (see RemoteCameraInfo::from_rows)
+    pub fn fetch_remote_cameras(
         &mut self,
         entity: Entity,
-        transform: Transform,
         commands: &mut Commands
-    ) -> anyhow::Result<()> {
-        let mut components = HashMap::<String, Value>::new();
-        let request_id = self.next_id();
-        let value = serde_json::to_value(transform)?;
-
-        // must use full type path
-        components.insert("bevy_transform::components::transform::Transform".to_string(), value);
-        match *self.remote_entity_dungeon.lock().unwrap() {
-            Some(remote_entity) => {
-                trace!("remote_entity (post_transform): {}", remote_entity);
-                let request = BrpInsertRequest {
-                    entity: remote_entity,
-                    components,
-                };
-                let request = serde_json::to_value(request)?;
-                let request = self.ehttp_request_from(
-                    request_id,
-                    request,
-                    "INSERT",
-                    "post_transform"
-                )?;
-                self.spawn_task(request_id, entity, false, request, commands);
-
-                Ok(())
-            }
-            None => Err(anyhow!("no remote camera entity found")),
+    ) -> anyhow::Result<BrpRequestId> {
+        let request = BrpQueryRequest {
+            data: RemoteCameraInfo::query(),
+            filter: default(),
+        };
+
+        self.query(request, Some(entity), commands)
+    }
+
+    // remember the cameras that were found and keep driving the same one while it is there, or else
+    // drive the preferred one, or else the first active one
+    pub fn set_remote_cameras(&mut self, cameras: Vec<RemoteCameraInfo>) {
+        let current = self.remote_camera.and_then(|entity| {
+            cameras.iter().find(|camera| camera.entity == entity)
+        });
+        let preferred = self.preferred_camera.as_ref().and_then(|name| {
+            cameras.iter().find(|camera| camera.name.as_ref() == Some(name))
+        });
+        let picked = current
+            .or(preferred)
+            .or_else(|| cameras.iter().find(|camera| camera.is_active))
+            .or_else(|| cameras.first());
+
+        self.remote_camera = picked.map(|camera| camera.entity);
+        self.remote_cameras = Some(cameras);
+    }
+
+    // drive the given remote camera, and look for it by name after a reconnect
+    pub fn select_remote_camera(&mut self, camera: &RemoteCameraInfo) {
+        self.remote_camera = Some(camera.entity);
+        if camera.name.is_some() {
+            self.preferred_camera = camera.name.clone();
         }
     }
 
+    // the remote entities may be gone, so look for the cameras again when asked
+    pub fn forget_remote_cameras(&mut self) {
+        self.remote_camera = None;
+        self.remote_cameras = None;
+    }
+
+    // increment the id counter and return the next value
+    pub fn next_id(&mut self) -> u32 {
+        self.last_id += 1;
+        self.last_id
+    }
+
     pub fn set_url(&mut self, url: String) {
         self.url = url;
     }
 
+    // use a compact binary encoding instead of JSON (the server must be new enough to understand it)
+    pub fn set_encoding(&mut self, encoding: BrpEncoding) {
+        self.encoding = encoding;
+    }
+
+    // send requests some other way than HTTP, e.g. to an app in the same process
+    pub fn set_transport(&mut self, transport: impl BrpTransport) {
+        self.transport = Arc::new(transport);
+    }
+
+    pub fn with_transport(mut self, transport: impl BrpTransport) -> Self {
+        self.set_transport(transport);
+        self
+    }
+
     // convenience function to spawn or despawn the remote FPS counter widget
     pub fn spawn_fps_marker(
         &mut self,
         entity: &Entity,
         visibility: bool,
         type_registry: &Res<AppTypeRegistry>,
+        options: BrpCallOptions,
         commands: &mut Commands
     ) -> anyhow::Result<()> {
         let mut marker = DespawnRemoteFpsCounter.type_id();
@@ -148,92 +689,330 @@ impl BrpClient {
         }
         if let Some(marker) = type_registry.read().get_type_info(marker) {
             let marker = marker.type_path();
-            let request_id = self.next_id();
             let mut components = HashMap::<String, Value>::new();
             // I _think_ this is how you send an empty struct component
             components.insert(marker.to_string(), Value::Object(default()));
             let request = BrpSpawnRequest { components };
-            let request = serde_json::to_value(request)?;
-            let request = self.ehttp_request_from(
-                request_id,
-                request,
+            self.call_with::<_, BrpEntityResponse>(
                 "SPAWN",
-                "spawn_fps_marker"
+                request,
+                Some(*entity),
+                options,
+                commands
             )?;
-            self.spawn_task(request_id, *entity, false, request, commands);
         }
         Ok(())
     }
 
-    // convenience function to use ehttp to spawn an HTTP request in a Bevy task
-    // TODO replace request arg with closure that generates and handles a request
-    // (basically the outer closure of the task)
-    pub fn spawn_task(
-        &self,
-        request_id: u32,
-        local_entity: Entity,
-        store_remote_entity: bool,
-        request: Request,
+    // hand the request to the transport, and spawn a Bevy task that waits for the response
+    // (the task finishes once the response has been decoded and queued up as an event)
+    fn spawn_task<T>(
+        &mut self,
+        request_id: BrpRequestId,
+        verb: &str,
+        local_entity: Option<Entity>,
+        request: BrpRequest,
+        options: BrpCallOptions,
         commands: &mut Commands
-    ) {
-        // can't write to the resource from within a thread, so we use this
-        let camera_balloon = self.remote_entity_dungeon.clone();
+    )
+        where T: DeserializeOwned + Clone + Send + Sync + 'static
+    {
+        let completions = self.completions.0.clone();
+        let connection = self.connection.clone();
+        let verb = verb.to_string();
         let thread_pool = IoTaskPool::get();
 
-        // spawn an async task for the long network op
+        // keep track of the request until it is settled one way or another
+        let timeout = options.timeout.unwrap_or(self.timeout);
+        let settled = Arc::new(AtomicBool::new(false));
+        let fail: BrpFailure = {
+            let connection = connection.clone();
+            let verb = verb.clone();
+            Box::new(move |error| {
+                let response = BrpResponse::<T> {
+                    connection,
+                    id: request_id,
+                    verb,
+                    entity: local_entity,
+                    result: Err(error),
+                };
+                Box::new(move |world: &mut World| deliver_response(world, response))
+            })
+        };
+        let now = Instant::now();
+        self.pending.insert(request_id, PendingRequest {
+            deadline: now + timeout,
+            timeout,
+            supersede: options.supersede.clone(),
+            settled: settled.clone(),
+            fail,
+        });
+
+        let sent = SentRequest { answered: default(), deadline: now + timeout };
+        let answered = sent.answered.clone();
+        let (done_sender, done_receiver) = channel::bounded::<()>(1);
+        let respond: HeldResponder = Box::new(move |response: Result<Value, BrpError>| {
+            answered.store(true, Ordering::Release);
+
+            // too late, the request timed out or was cancelled
+            if settled.swap(true, Ordering::AcqRel) {
+                trace!("ignoring late response to BRP request {}", request_id);
+                let _ = done_sender.send_blocking(());
+                return;
+            }
+
+            let result = decode_response::<T>(request_id, response);
+            if let Err(error) = &result {
+                error!("BRP error: {}", error);
+            }
+
+            let response = BrpResponse {
+                connection,
+                id: request_id,
+                verb,
+                entity: local_entity,
+                result,
+            };
+
+            // float the response back to the main thread
+            let _ = completions.send_blocking(
+                Box::new(move |world: &mut World| deliver_response(world, response))
+            );
+            let _ = done_sender.send_blocking(());
+        });
+
+        // (sent right away rather than from the task, so the order of requests is kept)
+        match options.supersede {
+            // an older request with the key is still out, so wait for it (and drop any request
+            // that was waiting already, which call_with has cancelled)
+            Some(key) if self.sent.get(&key).is_some_and(|sent| sent.is_out(now)) => {
+                trace!("holding request {} until the last `{}` is answered", request_id, key);
+                self.held.insert(key, HeldRequest { id: request_id, request, respond, sent });
+            }
+            Some(key) => {
+                self.sent.insert(key, sent);
+                self.transport.send(&self.url, self.encoding, request, respond);
+            }
+            None => self.transport.send(&self.url, self.encoding, request, respond),
+        }
+
         let task = thread_pool.spawn(async move {
-            ehttp::fetch(request, move |response: ehttp::Result<ehttp::Response>| {
-                match response {
-                    Ok(response) => {
-                        trace!("Request ID: {}, status code: {:?}", request_id, response.status);
-                        let response = response.text().unwrap();
-                        trace!("Response: {}", serde_json::to_string(&response).unwrap());
-
-                        // if this is a response to the camera query, we need to save it from within this closure
-                        if store_remote_entity {
-                            // get an entity ID
-                            let remote_entity = match
-                                serde_json::from_str::<BrpQueryResponse>(response)
-                            {
-                                Ok(value) => value.rows[0].entity,
-                                _ => Entity::PLACEHOLDER,
-                            };
-
-                            // float the data back to the resource
-                            *camera_balloon.lock().unwrap() = Some(remote_entity);
-                        }
-                    }
-                    // FIXME go to Disconnected state if there's an error
-                    Err(error) => {
-                        error!("BRP error: {}", error);
-                    }
-                }
-            });
+            let _ = done_receiver.recv().await;
         });
 
-        if local_entity == Entity::PLACEHOLDER {
-            // this just means we aren't storing any data about the running task
-            warn!("making transient BRP request");
-        } else {
-            commands.entity(local_entity).insert(RemoteRequest { task });
+        match local_entity {
+            Some(local_entity) => {
+                commands.entity(local_entity).insert(RemoteRequest { id: request_id, task });
+            }
+            None => {
+                // nothing local is waiting on this one, so let it run on its own
+                task.detach();
+            }
         }
     }
+}
 
-    fn ehttp_request_from(
-        &self,
-        request_id: u32,
-        value: Value,
-        verb: &str,
-        label: &str
-    ) -> anyhow::Result<Request> {
-        let request = BrpRequest {
-            request: verb.to_string(),
-            id: request_id.into(),
-            params: value,
-        };
+// wrap the params in the envelope every verb expects
+fn brp_request_from(request_id: u32, params: Value, verb: &str) -> BrpRequest {
+    let request = BrpRequest {
+        request: verb.to_string(),
+        id: request_id.into(),
+        params,
+    };
+
+    trace!("{}: {}", verb, serde_json::to_string(&request).unwrap_or_default());
+    request
+}
+
+// turn the response from the transport into the type the caller asked for
+fn decode_response<T: DeserializeOwned>(
+    request_id: BrpRequestId,
+    response: Result<Value, BrpError>
+) -> Result<T, BrpError> {
+    let value = response?;
+    trace!("Request ID: {}, response: {}", request_id, value);
+
+    if value.get("status").and_then(Value::as_str) == Some("ERROR") {
+        let message = value.get("message").and_then(Value::as_str).unwrap_or_default();
+        return Err(BrpError::Remote(message.to_string()));
+    }
+
+    serde_json::from_value(value).map_err(|error| BrpError::Decode(error.to_string()))
+}
+
+fn deliver_response<T: Clone + Send + Sync + 'static>(world: &mut World, response: BrpResponse<T>) {
+    let target = response.entity.filter(|entity| world.get_entity(*entity).is_some());
+
+    // the local entity is no longer waiting (unless it has already sent a newer request)
+    if let Some(mut entity) = target.and_then(|entity| world.get_entity_mut(entity)) {
+        if entity.get::<RemoteRequest>().is_some_and(|request| request.id == response.id) {
+            entity.remove::<RemoteRequest>();
+        }
+    }
+
+    // let the heartbeat know the server can't be reached
+    match &response.result {
+        Err(BrpError::Transport(message)) => {
+            world.send_event(BrpTransportError {
+                connection: response.connection.clone(),
+                id: response.id,
+                verb: response.verb.clone(),
+                message: message.clone(),
+            });
+        }
+        Err(BrpError::Timeout(after)) => {
+            world.send_event(BrpTimeoutError {
+                connection: response.connection.clone(),
+                id: response.id,
+                verb: response.verb.clone(),
+                after: *after,
+            });
+        }
+        _ => {}
+    }
+
+    world.send_event(response.clone());
+    match target {
+        Some(entity) => world.trigger_targets(response, entity),
+        None => world.trigger(response),
+    }
+}
+
+// a game paused in the debugger never answers, so don't wait on it forever
+fn expire_brp_requests(connections: Option<ResMut<BrpConnections>>) {
+    let Some(mut connections) = connections else {
+        return;
+    };
+
+    let now = Instant::now();
+    for connection in connections.bypass_change_detection().iter_mut() {
+        connection.client.expire(now);
+    }
+}
+
+// send the superseding requests that were waiting for older ones to be answered
+fn send_held_brp_requests(connections: Option<ResMut<BrpConnections>>) {
+    let Some(mut connections) = connections else {
+        return;
+    };
+
+    let now = Instant::now();
+    for connection in connections.bypass_change_detection().iter_mut() {
+        connection.client.send_held(now);
+    }
+}
+
+// drain the responses that arrived since last frame
+fn process_brp_responses(world: &mut World) {
+    let Some(connections) = world.get_resource::<BrpConnections>() else {
+        return;
+    };
+
+    let clients: Vec<_> = connections
+        .iter()
+        .map(|connection| {
+            (connection.client.transport.clone(), connection.client.completions.1.clone())
+        })
+        .collect();
+    for (transport, receiver) in clients {
+        transport.poll();
+        while let Ok(completion) = receiver.try_recv() {
+            completion(world);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use bevy::{ ecs::world::CommandQueue, tasks::TaskPool };
+    use serde_json::json;
+
+    use crate::remote::transport::BrpResponder;
+    use super::*;
+
+    // the params of a request, and how to answer it until it has been
+    type Sent = (Value, Option<BrpResponder>);
+
+    // keeps what it's sent, for the test to answer when it likes
+    #[derive(Clone, Default)]
+    struct ManualTransport(Arc<Mutex<Vec<Sent>>>);
+
+    impl BrpTransport for ManualTransport {
+        fn send(&self, _: &str, _: BrpEncoding, request: BrpRequest, respond: BrpResponder) {
+            self.0.lock().unwrap().push((request.params, Some(respond)));
+        }
+    }
+
+    impl ManualTransport {
+        // the params of every request that went out, in order
+        fn sent(&self) -> Vec<Value> {
+            self.0.lock().unwrap().iter().map(|(params, _)| params.clone()).collect()
+        }
+
+        fn answer(&self, index: usize) {
+            let respond = self.0.lock().unwrap()[index].1.take().unwrap();
+            respond(Ok(json!({ "status": "OK" })));
+        }
+    }
+
+    fn push(client: &mut BrpClient, key: &str, value: u32) {
+        IoTaskPool::get_or_init(TaskPool::new);
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let options = BrpCallOptions::default().superseding(key);
+        client.call_with::<_, Value>("PUSH", value, None, options, &mut commands).unwrap();
+    }
+
+    #[test]
+    fn only_the_newest_request_follows_the_one_out() {
+        let transport = ManualTransport::default();
+        let mut client = BrpClient::default().with_transport(transport.clone());
+
+        push(&mut client, "camera", 1);
+        push(&mut client, "camera", 2);
+        push(&mut client, "camera", 3);
+        client.send_held(Instant::now());
+        assert_eq!(transport.sent(), [json!(1)]);
+
+        // 2 was replaced before it could go out
+        transport.answer(0);
+        client.send_held(Instant::now());
+        assert_eq!(transport.sent(), [json!(1), json!(3)]);
+
+        // nothing is out once 3 is answered, so the next one goes right away
+        transport.answer(1);
+        client.send_held(Instant::now());
+        push(&mut client, "camera", 4);
+        assert_eq!(transport.sent(), [json!(1), json!(3), json!(4)]);
+    }
+
+    #[test]
+    fn other_keys_dont_wait() {
+        let transport = ManualTransport::default();
+        let mut client = BrpClient::default().with_transport(transport.clone());
+
+        push(&mut client, "camera", 1);
+        push(&mut client, "light", 2);
+        assert_eq!(transport.sent(), [json!(1), json!(2)]);
+    }
+
+    #[test]
+    fn a_server_that_never_answers_holds_nothing_back_for_good() {
+        let transport = ManualTransport::default();
+        let mut client = BrpClient::default().with_transport(transport.clone());
+
+        push(&mut client, "camera", 1);
+        push(&mut client, "camera", 2);
+        assert_eq!(transport.sent(), [json!(1)]);
 
-        let request = serde_json::to_string(&request)?;
-        trace!("{}: {}", label, request);
-        Ok(self.request_builder.as_ref().post(self.url.to_string(), request))
+        // (the held request gives up at the same time, so it's only sent if it lives longer)
+        let later = Instant::now() + client.timeout / 2;
+        client.sent.values_mut().for_each(|sent| sent.deadline = later);
+        client.send_held(later);
+        assert_eq!(transport.sent(), [json!(1), json!(2)]);
     }
 }
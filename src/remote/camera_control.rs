@@ -1,12 +1,19 @@
-use std::marker::PhantomData;
+use std::{ marker::PhantomData, time::Duration };
 
-use bevy::{ prelude::*, tasks::{ block_on, poll_once } };
+use bevy::prelude::*;
 
 use leafwing_input_manager::action_state::ActionState;
 
-use sickle_ui::ui_commands::UpdateStatesExt;
-
-use crate::{ input::{ InputAction, InputConfig }, remote::{ *, brp_client::BrpClient } };
+use crate::{
+    input::{ InputAction, InputConfig },
+    remote::{
+        *,
+        brp_client::{ BrpCallOptions, BrpClientPlugin, RemoteCameraInfo },
+        connections::{ BrpConnections, RemoteConnection },
+        link::{ RemoteLink, RemoteLinkPlugin },
+        replica::{ spawn_replica, RemoteComponents, RemoteReplica, RemoteReplicaPlugin },
+    },
+};
 
 pub struct CameraControlRemotePlugin<T: Component>(PhantomData<T>);
 
@@ -16,55 +23,79 @@ impl<T: Component> Default for CameraControlRemotePlugin<T> {
     }
 }
 
-// FIXME there can be only one of these at a time
+// each camera drives the remote camera of the connection it is bound to (see RemoteConnection)
 // TODO create in_running_state which also checks for EditorState::Running
 impl<T: Component> Plugin for CameraControlRemotePlugin<T> {
     fn build(&self, app: &mut App) {
-        // FIXME BrpClient should be handled by remote service
-        app.init_resource::<BrpClient>()
-            .register_type::<RemoteFpsCounter>()
+        // FIXME BrpConnections should be handled by remote service
+        if !app.is_plugin_added::<BrpClientPlugin>() {
+            app.add_plugins(BrpClientPlugin);
+        }
+        if !app.is_plugin_added::<RemoteLinkPlugin>() {
+            app.add_plugins(RemoteLinkPlugin);
+        }
+        if !app.is_plugin_added::<RemoteReplicaPlugin>() {
+            app.add_plugins(RemoteReplicaPlugin);
+        }
+
+        app.register_type::<RemoteFpsCounter>()
             .register_type::<DespawnRemoteFpsCounter>()
 
             .add_systems(
                 Update,
-                init_connect::<T>.run_if(in_state(RemoteConnectionState::Disconnected))
-            )
-            .add_systems(
-                Update,
-                connect_to_camera::<T>.run_if(in_state(RemoteConnectionState::Connecting))
-            )
-            .add_systems(
-                Update,
-                poll_responses::<T>.run_if(in_state(RemoteConnectionState::Checking))
-            )
-            .add_systems(
-                Update,
-                (check_toggle_fps::<T>, check_toggle_fps_response::<T>, sync_camera).run_if(
-                    in_state(RemoteConnectionState::Connected)
+                (
+                    init_connect::<T>,
+                    connect_to_camera::<T>,
+                    read_remote_cameras::<T>,
+                    check_toggle_fps::<T>,
+                    link_camera,
                 )
             );
     }
 }
 
-type RemoteActionQuery<'a> = (Entity, &'a ActionState<InputAction>, Option<&'a RemoteRequest>);
+// the replica that mirrors the remote cameras, once connected
+#[derive(Component)]
+struct RemoteCameraReplica(Entity);
+
+type RemoteActionQuery<'a> = (
+    Entity,
+    &'a ActionState<InputAction>,
+    Option<&'a RemoteRequest>,
+    Option<&'a RemoteConnection>,
+);
+
+type RemoteCameraSearchQuery<'a> = (Entity, Option<&'a RemoteConnection>, Has<RemoteCameraReplica>);
+
+type RemoteCameraLinkQuery<'a> = (Entity, Option<&'a RemoteLink>, Option<&'a RemoteConnection>);
 
 fn check_toggle_fps<T: Component>(
     q_action: Query<RemoteActionQuery, With<T>>,
     type_registry: Res<AppTypeRegistry>,
     mut input: ResMut<InputConfig>,
-    mut brp: ResMut<BrpClient>,
+    mut connections: ResMut<BrpConnections>,
     mut commands: Commands
 ) {
-    for (entity, action_state, request) in &q_action {
-        // if not already sending a toggle and the F key was just pressed...
-        if request.is_none() && action_state.just_pressed(&InputAction::ToggleRemoteFpsCounter) {
-            // toggle and send over the wire
+    for (entity, action_state, _, binding) in &q_action {
+        let Some(connection) = connections.bound_mut(binding) else {
+            continue;
+        };
+
+        // if connected and the F key was just pressed...
+        if
+            connection.is_connected() &&
+            action_state.just_pressed(&InputAction::ToggleRemoteFpsCounter)
+        {
+            // toggle and send over the wire (while the last toggle is on its way, only the newest
+            // one waits to follow it; see BrpCallOptions::supersede)
             input.remote_fps = !input.remote_fps;
+            let options = BrpCallOptions::default().superseding(format!("fps counter {}", entity));
             if
-                let Err(error) = brp.spawn_fps_marker(
+                let Err(error) = connection.client.spawn_fps_marker(
                     &entity,
                     input.remote_fps,
                     &type_registry,
+                    options,
                     &mut commands
                 )
             {
@@ -74,145 +105,111 @@ fn check_toggle_fps<T: Component>(
     }
 }
 
-fn check_toggle_fps_response<T: Component>(
-    mut q_action: Query<(Entity, &mut RemoteRequest), With<T>>,
-    mut commands: Commands
-) {
-    for (entity, mut request) in q_action.iter_mut() {
-        if block_on(poll_once(&mut request.task)).is_some() {
-            commands.entity(entity).remove::<RemoteRequest>();
-        }
-    }
-}
-
 // step 1: wait for a key press
 fn init_connect<T: Component>(
-    q_action: Query<&ActionState<InputAction>, (With<T>, Without<RemoteRequest>)>,
-    mut commands: Commands
+    q_action: Query<RemoteActionQuery, With<T>>,
+    mut connections: ResMut<BrpConnections>
 ) {
-    for action_state in &q_action {
+    for (_, action_state, request, binding) in &q_action {
+        if request.is_some() {
+            continue;
+        }
+
         if
             action_state.pressed(&InputAction::CameraRotateYDecrease) ||
             action_state.pressed(&InputAction::CameraRotateYIncrease) ||
             action_state.just_pressed(&InputAction::ToggleRemoteFpsCounter)
         {
             // try to sync client camera to server
-            commands.next_state(RemoteConnectionState::Connecting);
+            if let Some(connection) = connections.bound_mut(binding) {
+                connection.connect();
+            }
         }
     }
 }
 
-// step 2: run a query to get the entity ID of the remote camera
+// step 2: once connected, mirror the remote cameras to learn their entity IDs
 fn connect_to_camera<T: Component>(
-    mut camera: Query<RemoteTransformArgs, With<T>>,
-    mut brp: ResMut<BrpClient>,
+    camera: Query<RemoteCameraSearchQuery, With<T>>,
+    connections: Res<BrpConnections>,
     mut commands: Commands
 ) {
-    if let Ok(camera) = camera.get_single_mut() {
-        // spawn a task to connect to the remote server
-        match brp.fetch_remote_camera(camera.0, &mut commands) {
-            Ok(_) => {
-                trace!("spawning fetch_remote_camera task");
-
-                // change the RemoteConnectionState to Checking
-                commands.next_state(RemoteConnectionState::Checking);
-            }
-            Err(error) => { error!("Could not spawn task to get remote camera: {}", error) }
+    for (entity, binding, replicated) in &camera {
+        if replicated {
+            continue;
+        }
+        if !connections.bound(binding).is_some_and(|connection| connection.is_connected()) {
+            continue;
         }
+
+        // (a game without cameras may still be loading, and cameras come and go, so keep looking)
+        let replica = RemoteReplica::new(RemoteCameraInfo::query(), default())
+            .with_interval(Duration::from_secs(2));
+        let connection = binding.map(|binding| binding.0.clone());
+        let replica = spawn_replica(&mut commands, entity, replica, connection);
+        commands.entity(entity).insert(RemoteCameraReplica(replica));
     }
 }
 
-// step 3: see if the Camera entity has returned yet
-fn poll_responses<T: Component>(
-    mut camera: Query<RemoteTransformArgs, With<T>>,
-    brp: Res<BrpClient>,
-    mut commands: Commands
+// step 3: pick a camera whenever the replica has polled the game
+fn read_remote_cameras<T: Component>(
+    camera: Query<(&RemoteCameraReplica, Option<&RemoteConnection>), With<T>>,
+    q_replicas: Query<Ref<RemoteReplica>>,
+    q_components: Query<&RemoteComponents>,
+    mut connections: ResMut<BrpConnections>
 ) {
-    match camera.get_single_mut() {
-        Ok(camera) => {
-            // check to see if running task has completed
-            if let Some(mut request) = camera.2 {
-                if block_on(poll_once(&mut request.task)).is_some() {
-                    let entity = camera.0;
-                    commands.entity(entity).remove::<RemoteRequest>();
-
-                    // check to see if we have an entity
-                    let entity = match brp.remote_entity_dungeon.lock() {
-                        Ok(mutex) => mutex.as_ref().is_some(),
-                        Err(_) => false,
-                    };
-
-                    // change the RemoteConnectionState to Connected
-                    info!("BRP request completed");
-                    if entity {
-                        info!("...and found a camera!");
-                        commands.next_state(RemoteConnectionState::Connected);
-                    } else {
-                        info!("[...]");
-                        commands.next_state(RemoteConnectionState::Disconnected);
-                    }
-                }
+    for (replica, binding) in &camera {
+        let Ok(replica) = q_replicas.get(replica.0) else {
+            continue;
+        };
+        if !replica.is_changed() || replica.is_added() {
+            continue;
+        }
+        let Some(connection) = connections.bound_mut(binding) else {
+            continue;
+        };
+
+        // an empty list is the error state, which the camera control widget shows
+        let cameras = match replica.error() {
+            None => RemoteCameraInfo::from_rows(&replica.rows(&q_components)),
+            Some(error) => {
+                error!("Could not look for remote cameras: {}", error);
+                vec![]
             }
+        };
+        if connection.client.remote_cameras.as_ref() == Some(&cameras) {
+            continue;
         }
-        Err(error) => error!("Error loading camera: {}", error),
+
+        if cameras.is_empty() {
+            warn!("The game `{}` has no cameras", connection.name());
+        } else {
+            info!("Found {} camera(s) in the game `{}`", cameras.len(), connection.name());
+        }
+        connection.client.set_remote_cameras(cameras);
     }
 }
 
-// step 4: propagate local camera to remote
-fn sync_camera(
-    mut camera: Query<RemoteTransformArgs, With<RemoteCamera>>,
-    mut brp: ResMut<BrpClient>,
+// step 4: link the local camera to the remote one, so RemoteLinkPlugin pushes its transform
+fn link_camera(
+    cameras: Query<RemoteCameraLinkQuery, With<RemoteCamera>>,
+    connections: Res<BrpConnections>,
     mut commands: Commands
 ) {
-    match camera.get_single_mut() {
-        Ok(camera) => {
-            // send update or mark pending
-            let entity = camera.0;
-            let transform = camera.1;
-            if transform.is_changed() {
-                let mut running = camera.2.is_some();
-                let pending = camera.3.is_some();
-                let mut result: anyhow::Result<()> = Ok(());
-
-                // check to see if running task has completed
-                if let Some(mut request) = camera.2 {
-                    if block_on(poll_once(&mut request.task)).is_some() {
-                        commands.entity(entity).remove::<RemoteRequest>();
-                        running = false;
-                    }
-                }
-
-                // then we send the serialized Transform to the server
-                // -if a request is already running, mark with RemotePending so we get to it later
-                // -if no request is running, send one if either we moved or already pending
-
-                // see if there is a running request
-                // if so, then mark with RemotePending if not already
-                if running && !pending {
-                    // (if the request just finished it will still be marked running, which is fine)
-                    // (next tick it will have no RunningRequest but will be marked RemotePending)
-                    commands.entity(entity).insert(RemotePending);
-                } else if !running && pending {
-                    // if no running request and an update is pending, kick off a new request
-                    result = brp.post_transform(entity, *transform, &mut commands);
-
-                    // remove RemotePending
-                    commands.entity(entity).remove::<RemotePending>();
-                } else if !running && !pending {
-                    // if no running request, kick off a new request
-                    result = brp.post_transform(entity, *transform, &mut commands);
-
-                    // don't mark with Pending until there is data to send
-                }
-
-                // handle error caused while spawning request task
-                if let Err(error) = result {
-                    error!("BRP error: {}", error);
-                }
+    for (entity, link, binding) in &cameras {
+        let remote_camera = connections
+            .bound(binding)
+            .and_then(|connection| connection.client.remote_camera);
+
+        match (remote_camera, link) {
+            (Some(remote_camera), Some(link)) if link.remote == remote_camera => {}
+            (Some(remote_camera), _) => {
+                commands.entity(entity).insert(RemoteLink::new(remote_camera));
             }
-        }
-        Err(_error) => {
-            //error!("camera error: {}", error)
+            (None, Some(_)) => {
+                commands.entity(entity).remove::<RemoteLink>();
+            }
+            (None, None) => {}
         }
     }
 }
@@ -0,0 +1,404 @@
+use std::time::Duration;
+
+use bevy::{ prelude::*, utils::HashMap };
+
+use super::{
+    brp_client::{
+        BrpClient,
+        BrpError,
+        BrpRequestId,
+        BrpResponse,
+        BrpTimeoutError,
+        BrpTransportError,
+    },
+    builtin_verbs::{ BrpPingRequest, BrpPingResponse },
+    RemoteConnectionState,
+};
+
+// the name of the connection that exists from the start
+pub const DEFAULT_CONNECTION: &str = "default";
+
+/// Binds a panel (or any other entity) to the named connection in [`BrpConnections`].
+///
+/// Entities without one use the primary connection.
+#[derive(Component, Clone, Debug, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Component)]
+pub struct RemoteConnection(pub String);
+
+/// Keeps track of whether the BRP server of a connection is still there.
+///
+/// While connected, the server is sent a `PING` every `interval`. If a ping goes unanswered for a
+/// whole interval, or any request fails to reach the server, the connection drops back to
+/// [`RemoteConnectionState::Disconnected`]. An error from the server still counts as an answer, so
+/// servers that don't know `PING` stay connected. When any request times out, the server is pinged
+/// right away rather than at the next interval, in case it is stuck. From there it reconnects on
+/// its own, waiting
+/// `min_backoff` before the first attempt and twice as long after each failed one, up to
+/// `max_backoff`.
+#[derive(Debug)]
+pub struct BrpHeartbeat {
+    /// Time between pings while connected.
+    pub interval: Duration,
+
+    /// Time to wait before the first attempt to reconnect.
+    pub min_backoff: Duration,
+
+    /// The longest time to wait between attempts to reconnect.
+    pub max_backoff: Duration,
+
+    // counts down to the next ping, or the next attempt to reconnect
+    timer: Timer,
+
+    // current wait between attempts to reconnect
+    backoff: Duration,
+
+    // the ping that hasn't been answered yet
+    ping: Option<BrpRequestId>,
+
+    // whether the connection was lost, as opposed to never having been made
+    reconnecting: bool,
+}
+
+impl Default for BrpHeartbeat {
+    fn default() -> Self {
+        let interval = Duration::from_secs(1);
+        let min_backoff = Duration::from_millis(500);
+
+        Self {
+            interval,
+            min_backoff,
+            max_backoff: Duration::from_secs(30),
+            timer: Timer::new(interval, TimerMode::Repeating),
+            backoff: min_backoff,
+            ping: None,
+            reconnecting: false,
+        }
+    }
+}
+
+impl BrpHeartbeat {
+    /// Whether the connection was lost and is waiting to be made again.
+    pub fn is_reconnecting(&self) -> bool {
+        self.reconnecting
+    }
+}
+
+/// One BRP server the editor talks to, along with the state of the connection to it.
+///
+/// Remote entity IDs don't survive a restart of the server. Anything holding remote entities should
+/// resolve them again once the connection is back in [`RemoteConnectionState::Connected`].
+#[derive(Debug)]
+pub struct BrpConnection {
+    /// Sends requests to this server.
+    pub client: BrpClient,
+
+    /// Pings the server and reconnects to it.
+    pub heartbeat: BrpHeartbeat,
+
+    state: RemoteConnectionState,
+}
+
+impl BrpConnection {
+    pub fn new(client: BrpClient) -> Self {
+        Self {
+            client,
+            heartbeat: default(),
+            state: default(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.client.connection
+    }
+
+    pub fn state(&self) -> &RemoteConnectionState {
+        &self.state
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state == RemoteConnectionState::Connected
+    }
+
+    // start connecting, unless already connected or on the way there
+    pub fn connect(&mut self) {
+        if self.state == RemoteConnectionState::Disconnected {
+            self.set_state(RemoteConnectionState::Connecting);
+        }
+    }
+
+    // give up on the server until asked to connect again
+    pub fn disconnect(&mut self) {
+        self.heartbeat.reconnecting = false;
+        self.set_state(RemoteConnectionState::Disconnected);
+    }
+
+    // the server went away, so try to get it back
+    pub fn lose(&mut self) {
+        if self.state == RemoteConnectionState::Disconnected {
+            return;
+        }
+
+        info!("BRP connection `{}` lost, reconnecting", self.name());
+        self.heartbeat.reconnecting = true;
+
+        // whatever the remote entities were, they may be gone when the server comes back
+        // (the preferred camera is kept, and looked for again by name)
+        self.client.forget_remote_cameras();
+        self.set_state(RemoteConnectionState::Disconnected);
+    }
+
+    fn set_state(&mut self, state: RemoteConnectionState) {
+        if self.state == state {
+            return;
+        }
+
+        let heartbeat = &mut self.heartbeat;
+        heartbeat.ping = None;
+        match state {
+            RemoteConnectionState::Connected => {
+                heartbeat.timer = Timer::new(heartbeat.interval, TimerMode::Repeating);
+                heartbeat.backoff = heartbeat.min_backoff;
+                heartbeat.reconnecting = false;
+            }
+            RemoteConnectionState::Disconnected => {
+                heartbeat.timer = Timer::new(heartbeat.backoff, TimerMode::Once);
+            }
+            _ => {}
+        }
+
+        trace!("BRP connection `{}`: {:?} -> {:?}", self.client.connection, self.state, state);
+        self.state = state;
+    }
+}
+
+/// The BRP servers the editor is connected to, by name.
+///
+/// There's always a [`DEFAULT_CONNECTION`] to begin with, which is also the primary connection. The
+/// primary connection is the one reflected in the global [`RemoteConnectionState`], and the one used
+/// by panels without a [`RemoteConnection`].
+#[derive(Resource, Debug)]
+pub struct BrpConnections {
+    connections: HashMap<String, BrpConnection>,
+    primary: String,
+}
+
+impl Default for BrpConnections {
+    fn default() -> Self {
+        let mut connections = HashMap::new();
+        connections.insert(
+            DEFAULT_CONNECTION.to_string(),
+            BrpConnection::new(BrpClient::default())
+        );
+
+        Self {
+            connections,
+            primary: DEFAULT_CONNECTION.to_string(),
+        }
+    }
+}
+
+impl BrpConnections {
+    // add a connection to the server at the given URL (replacing any connection with the same name)
+    pub fn add(&mut self, name: impl Into<String>, url: impl Into<String>) -> &mut BrpConnection {
+        let name = name.into();
+        let connection = BrpConnection::new(BrpClient::new(name.clone(), url.into()));
+        self.connections.insert(name.clone(), connection);
+        self.connections.get_mut(&name).unwrap()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<BrpConnection> {
+        self.connections.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BrpConnection> {
+        self.connections.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut BrpConnection> {
+        self.connections.get_mut(name)
+    }
+
+    pub fn primary(&self) -> Option<&BrpConnection> {
+        self.connections.get(&self.primary)
+    }
+
+    pub fn primary_mut(&mut self) -> Option<&mut BrpConnection> {
+        self.connections.get_mut(&self.primary)
+    }
+
+    pub fn primary_name(&self) -> &str {
+        &self.primary
+    }
+
+    pub fn set_primary(&mut self, name: impl Into<String>) {
+        self.primary = name.into();
+    }
+
+    // the connection a panel is bound to, or the primary one if it isn't bound
+    pub fn bound(&self, binding: Option<&RemoteConnection>) -> Option<&BrpConnection> {
+        match binding {
+            Some(binding) => self.get(&binding.0),
+            None => self.primary(),
+        }
+    }
+
+    pub fn bound_mut(&mut self, binding: Option<&RemoteConnection>) -> Option<&mut BrpConnection> {
+        match binding {
+            Some(binding) => self.get_mut(&binding.0),
+            None => self.primary_mut(),
+        }
+    }
+
+    // the name of a connection to the given URL, if there is one
+    pub fn find_url(&self, url: &str) -> Option<&String> {
+        self.connections
+            .iter()
+            .find(|(_, connection)| connection.client.url == url)
+            .map(|(name, _)| name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.connections.keys()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BrpConnection> {
+        self.connections.values()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut BrpConnection> {
+        self.connections.values_mut()
+    }
+}
+
+// handshake, heartbeat, and reconnect for every connection
+pub(crate) fn update_connections(
+    time: Res<Time<Real>>,
+    mut connections: ResMut<BrpConnections>,
+    mut commands: Commands
+) {
+    for connection in connections.iter_mut() {
+        match connection.state {
+            RemoteConnectionState::Connecting => {
+                // the first ping doubles as the handshake
+                connection.set_state(RemoteConnectionState::Checking);
+                send_ping(connection, &mut commands);
+            }
+            RemoteConnectionState::Connected => {
+                if !connection.heartbeat.timer.tick(time.delta()).just_finished() {
+                    continue;
+                }
+
+                // the last ping still hasn't come back
+                if connection.heartbeat.ping.is_some() {
+                    warn!("BRP server `{}` missed a heartbeat", connection.name());
+                    connection.lose();
+                    continue;
+                }
+
+                send_ping(connection, &mut commands);
+            }
+            RemoteConnectionState::Disconnected => {
+                // try again once the backoff has passed, waiting longer next time
+                let heartbeat = &mut connection.heartbeat;
+                if !heartbeat.reconnecting || !heartbeat.timer.tick(time.delta()).just_finished() {
+                    continue;
+                }
+
+                info!(
+                    "reconnecting to BRP server `{}` (waited {:?})",
+                    connection.client.connection,
+                    heartbeat.backoff
+                );
+                heartbeat.backoff = (heartbeat.backoff * 2).min(heartbeat.max_backoff);
+                connection.set_state(RemoteConnectionState::Connecting);
+            }
+            RemoteConnectionState::Checking => {}
+        }
+    }
+}
+
+fn send_ping(connection: &mut BrpConnection, commands: &mut Commands) {
+    match connection.client.call::<_, BrpPingResponse>("PING", BrpPingRequest {}, None, commands) {
+        Ok(request_id) => {
+            connection.heartbeat.ping = Some(request_id);
+        }
+        Err(error) => error!("Could not send BRP heartbeat: {}", error),
+    }
+}
+
+// finish the handshake, or drop the connection if a ping fails or the server can't be reached
+pub(crate) fn check_heartbeat(
+    mut pongs: EventReader<BrpResponse<BrpPingResponse>>,
+    mut transport_errors: EventReader<BrpTransportError>,
+    mut timeouts: EventReader<BrpTimeoutError>,
+    mut connections: ResMut<BrpConnections>,
+    mut commands: Commands
+) {
+    for pong in pongs.read() {
+        let Some(connection) = connections.get_mut(&pong.connection) else {
+            continue;
+        };
+        if connection.heartbeat.ping != Some(pong.id) {
+            continue;
+        }
+        connection.heartbeat.ping = None;
+
+        // a server without PING still answers, with an error, so only silence counts as gone
+        let alive = match &pong.result {
+            Ok(_) | Err(BrpError::Remote(_) | BrpError::Decode(_)) => true,
+            Err(BrpError::Transport(_) | BrpError::Timeout(_)) => false,
+            Err(BrpError::Cancelled) => continue,
+        };
+
+        match (alive, connection.state.clone()) {
+            (true, RemoteConnectionState::Checking) => {
+                info!("connected to BRP server `{}`", connection.name());
+                connection.set_state(RemoteConnectionState::Connected);
+            }
+            (false, _) => connection.lose(),
+            _ => {}
+        }
+    }
+
+    for error in transport_errors.read() {
+        warn!(
+            "BRP server `{}` unreachable ({} {}): {}",
+            error.connection,
+            error.verb,
+            error.id,
+            error.message
+        );
+        if let Some(connection) = connections.get_mut(&error.connection) {
+            connection.lose();
+        }
+    }
+
+    // a request that took too long may mean the game is stuck, so check on it now
+    for timeout in timeouts.read() {
+        let Some(connection) = connections.get_mut(&timeout.connection) else {
+            continue;
+        };
+        if connection.is_connected() && connection.heartbeat.ping.is_none() {
+            debug!("{} {} timed out, pinging `{}`", timeout.verb, timeout.id, connection.name());
+            send_ping(connection, &mut commands);
+        }
+    }
+}
+
+// the global state follows the primary connection, so the footer can show it
+pub(crate) fn sync_connection_state(
+    connections: Res<BrpConnections>,
+    state: Option<Res<State<RemoteConnectionState>>>,
+    next_state: Option<ResMut<NextState<RemoteConnectionState>>>
+) {
+    let (Some(state), Some(mut next_state)) = (state, next_state) else {
+        return;
+    };
+
+    if let Some(primary) = connections.primary() {
+        if *state.get() != primary.state {
+            next_state.set(primary.state.clone());
+        }
+    }
+}
+
@@ -0,0 +1,622 @@
+//! Keeps local entities and entities in a remote app in step.
+//!
+//! Tag a local entity with a [`RemoteLink`] naming a remote entity, and whenever one of the linked
+//! components changes locally, its new value is sent to the remote entity with an `INSERT`. That is
+//! all it takes to drag a light, a prop or a camera around in the editor and see it move in the
+//! game. Changes are found by change detection, so only the components whose change tick moved
+//! since the last push are serialized at all.
+//!
+//! A push doesn't wait for the previous one to be answered: the new one supersedes it (see
+//! [`BrpCallOptions::superseding`]), and goes out as soon as the game has answered, so a slow game
+//! gets the latest value next rather than a backlog of stale ones. A pull is waited for, and
+//! changes made in the meantime are coalesced: once it is answered, whatever differs from what the
+//! server last received goes out in a single request. Links are also throttled to one push per
+//! [`RemoteLink::min_interval`].
+//!
+//! ## Pulling
+//!
+//! The game can move the entity too, for instance through physics. Unless
+//! [`RemoteLink::pull_interval`] is `None`, the link also reads the components back with a `GET`
+//! every so often. Links whose [`ConflictPolicy`] isn't [`ConflictPolicy::EditorWins`] also read
+//! them back before every push, so the game's changes aren't overwritten unseen. Whether the editor
+//! changed a component is told by its change tick, and whether the game did by comparing its value
+//! with the one both sides last agreed on:
+//!
+//! * if only the editor changed it, the editor's value is pushed;
+//! * if only the game changed it, the game's value is inserted locally;
+//! * if both changed it, the [`ConflictPolicy`] of the link decides. With [`ConflictPolicy::Ask`],
+//!   a [`RemoteLinkConflict`] event is sent and the component is left alone on both sides until
+//!   [`RemoteLink::resolve`] is called. The editor asks the user which side to keep (see
+//!   [`LinkConflictPlugin`](crate::widget::link_conflict::LinkConflictPlugin)).
+//!
+//! The first pull has nothing to compare with, so any difference is settled by the policy right
+//! away, with [`ConflictPolicy::Ask`] taking the game's value.
+
+use std::time::Duration;
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::{
+    ecs::{ component::Tick, world::CommandQueue },
+    prelude::*,
+    reflect::{ serde::TypedReflectSerializer, TypeRegistry },
+    utils::HashMap,
+};
+use serde_json::Value;
+
+use super::{
+    brp_client::{ BrpCallOptions, BrpClientPlugin, BrpRequestId, BrpResponse },
+    builtin_verbs::{
+        component_type_path,
+        deserialize_components,
+        get_reflect_component,
+        insert_reflected_components,
+        BrpGetRequest,
+        BrpGetResponse,
+        BrpInsertRequest,
+        BrpOkResponse,
+    },
+    connections::{ BrpConnections, RemoteConnection },
+};
+
+pub struct RemoteLinkPlugin;
+
+impl Plugin for RemoteLinkPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<BrpClientPlugin>() {
+            app.add_plugins(BrpClientPlugin);
+        }
+
+        app.add_event::<RemoteLinkConflict>().add_systems(
+            Update,
+            (finish_remote_links, pull_remote_links, push_remote_links).chain()
+        );
+    }
+}
+
+/// What a link does when the editor and the game both changed a component since they last agreed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Push the editor's value to the game.
+    #[default]
+    EditorWins,
+
+    /// Take the game's value in the editor.
+    GameWins,
+
+    /// Send a [`RemoteLinkConflict`] and wait for [`RemoteLink::resolve`].
+    Ask,
+}
+
+/// One side of a link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkSide {
+    Editor,
+    Game,
+}
+
+/// Sent when both sides of a link with [`ConflictPolicy::Ask`] changed a component.
+#[derive(Event, Clone, Debug)]
+pub struct RemoteLinkConflict {
+    /// The local entity holding the link.
+    pub entity: Entity,
+
+    /// The remote entity.
+    pub remote: Entity,
+
+    /// The full type path of the component.
+    pub component: String,
+
+    /// The serialized value in the editor.
+    pub editor: Value,
+
+    /// The serialized value in the game.
+    pub game: Value,
+}
+
+/// Keeps components of a remote entity in step with the same components of this entity.
+///
+/// The link uses the connection named by a [`RemoteConnection`] on the same entity, or the primary
+/// connection if there isn't one. To link to a different remote entity, insert a new link rather
+/// than changing `remote`, so nothing is skipped for having been synced with the old one.
+#[derive(Component, Debug)]
+pub struct RemoteLink {
+    /// The remote entity to sync with.
+    pub remote: Entity,
+
+    /// The components to sync, by full type path (or short name, if unambiguous).
+    ///
+    /// Each must be registered in the editor with `#[reflect(Component)]`. Components the local
+    /// entity doesn't have are left alone.
+    pub components: Vec<String>,
+
+    /// The shortest time between two pushes.
+    pub min_interval: Duration,
+
+    /// How often to check for changes made by the game, or `None` to only push.
+    pub pull_interval: Option<Duration>,
+
+    /// What to do when both sides changed a component.
+    pub policy: ConflictPolicy,
+
+    // the request that hasn't been answered yet
+    in_flight: Option<BrpRequestId>,
+
+    // whether that request is a pull, which everything else waits for
+    pulling: bool,
+
+    // when the last push went out
+    last_sent: Option<Duration>,
+
+    // when the last pull came back
+    last_pulled: Option<Duration>,
+
+    // the change tick as of which every local change has been pushed (or merged with the game's)
+    checked: Option<Tick>,
+
+    // the value of each component that both sides last agreed on
+    synced: HashMap<String, Value>,
+
+    // components both sides changed, and which side should win once known
+    conflicts: HashMap<String, Option<LinkSide>>,
+}
+
+impl RemoteLink {
+    // link the transform of this entity to the given remote entity
+    pub fn new(remote: Entity) -> Self {
+        Self {
+            remote,
+            components: vec!["bevy_transform::components::transform::Transform".to_string()],
+            min_interval: Duration::from_millis(33),
+            pull_interval: Some(Duration::from_millis(250)),
+            policy: default(),
+            in_flight: None,
+            pulling: false,
+            last_sent: None,
+            last_pulled: None,
+            checked: None,
+            synced: default(),
+            conflicts: default(),
+        }
+    }
+
+    pub fn with_components(mut self, components: Vec<String>) -> Self {
+        self.components = components;
+        self
+    }
+
+    pub fn with_min_interval(mut self, min_interval: Duration) -> Self {
+        self.min_interval = min_interval;
+        self
+    }
+
+    pub fn with_pull_interval(mut self, pull_interval: Option<Duration>) -> Self {
+        self.pull_interval = pull_interval;
+        self
+    }
+
+    pub fn with_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Whether a request is waiting for the server.
+    pub fn is_in_flight(&self) -> bool {
+        self.in_flight.is_some()
+    }
+
+    /// The components waiting for [`RemoteLink::resolve`].
+    pub fn conflicts(&self) -> impl Iterator<Item = &String> {
+        self.conflicts
+            .iter()
+            .filter(|(_, winner)| winner.is_none())
+            .map(|(component, _)| component)
+    }
+
+    /// Settles a conflict over a component, which takes effect with the next pull.
+    pub fn resolve(&mut self, component: &str, winner: LinkSide) {
+        if let Some(conflict) = self.conflicts.get_mut(component) {
+            *conflict = Some(winner);
+        }
+    }
+
+    // whether the throttle allows another push now
+    fn is_due(&self, now: Duration) -> bool {
+        match self.last_sent {
+            Some(last_sent) => now.saturating_sub(last_sent) >= self.min_interval,
+            None => true,
+        }
+    }
+
+    // whether the game is read before every push, so its changes aren't overwritten unseen
+    fn looks_first(&self) -> bool {
+        self.pull_interval.is_some() && self.policy != ConflictPolicy::EditorWins
+    }
+
+    // whether it's time to check on the game
+    fn is_pull_due(&self, now: Duration) -> bool {
+        match (self.pull_interval, self.last_pulled) {
+            (Some(pull_interval), Some(last_pulled)) => {
+                now.saturating_sub(last_pulled) >= pull_interval
+            }
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+// the request a link is about to send
+enum LinkRequest {
+    Get(BrpGetRequest),
+    Insert(BrpInsertRequest),
+}
+
+// push what changed locally (or check on the game first, unless the editor wins anyway), and pull
+// every so often
+fn push_remote_links(world: &mut World) {
+    let now = world.resource::<Time<Real>>().elapsed();
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+
+    let mut outgoing = vec![];
+    {
+        let mut links = world.query::<(EntityRef, &RemoteLink, Option<&RemoteConnection>)>();
+        let connections = world.resource::<BrpConnections>();
+        for (entity_ref, link, binding) in links.iter(world) {
+            if link.is_in_flight() && link.pulling {
+                continue;
+            }
+            if !connections.bound(binding).is_some_and(|connection| connection.is_connected()) {
+                continue;
+            }
+
+            let changes = match local_changes(world, &type_registry, entity_ref, link) {
+                Ok(changes) => changes,
+                Err(error) => {
+                    warn!("Could not check linked components of {}: {}", entity_ref.id(), error);
+                    continue;
+                }
+            };
+            let touched: Vec<String> = changes
+                .iter()
+                .filter(|(path, changed)| **changed && !link.conflicts.contains_key(*path))
+                .map(|(path, _)| path.clone())
+                .collect();
+
+            let pushing = !touched.is_empty() && link.is_due(now);
+            let request = if link.is_pull_due(now) || (pushing && link.looks_first()) {
+                // (a pull waits for the push before it)
+                if link.is_in_flight() {
+                    continue;
+                }
+
+                // merge_remote_link pushes whatever should be pushed once the game has answered
+                Some(
+                    LinkRequest::Get(BrpGetRequest {
+                        entity: link.remote,
+                        components: changes.into_keys().collect(),
+                    })
+                )
+            } else if pushing {
+                let local = match local_components(&type_registry, entity_ref, &touched) {
+                    Ok(local) => local,
+                    Err(error) => {
+                        let entity = entity_ref.id();
+                        warn!("Could not serialize linked components of {}: {}", entity, error);
+                        continue;
+                    }
+                };
+
+                // (a component can be touched without changing, or be set to the game's value)
+                let changed: HashMap<String, Value> = local
+                    .into_iter()
+                    .filter(|(path, value)| link.synced.get(path) != Some(value))
+                    .collect();
+                (!changed.is_empty()).then(|| {
+                    LinkRequest::Insert(BrpInsertRequest {
+                        entity: link.remote,
+                        components: changed,
+                    })
+                })
+            } else {
+                continue;
+            };
+
+            outgoing.push((entity_ref.id(), binding.cloned(), request));
+        }
+    }
+
+    let checked = world.change_tick();
+    for (entity, binding, request) in outgoing {
+        match request {
+            Some(request) => send_link_request(world, entity, binding.as_ref(), request, now),
+            None => {
+                if let Some(mut link) = world.get_mut::<RemoteLink>(entity) {
+                    link.bypass_change_detection().checked = Some(checked);
+                }
+            }
+        }
+    }
+}
+
+// the full path of each linked component the local entity has, and whether it changed since the
+// link last checked
+fn local_changes(
+    world: &World,
+    type_registry: &TypeRegistry,
+    entity_ref: EntityRef,
+    link: &RemoteLink
+) -> AnyhowResult<HashMap<String, bool>> {
+    let this_run = world.read_change_tick();
+
+    let mut changes = HashMap::new();
+    for component_path in &link.components {
+        let path = component_type_path(type_registry, component_path)?;
+        let Some(component_id) = type_registry
+            .get_with_type_path(path)
+            .and_then(|registration| world.components().get_id(registration.type_id())) else {
+            continue;
+        };
+        let Some(ticks) = entity_ref.get_change_ticks_by_id(component_id) else {
+            continue;
+        };
+
+        let changed = link.checked.map_or(true, |checked| ticks.is_changed(checked, this_run));
+        changes.insert(path.to_string(), changed);
+    }
+
+    Ok(changes)
+}
+
+// serialize the given linked components the local entity has
+fn local_components(
+    type_registry: &TypeRegistry,
+    entity_ref: EntityRef,
+    component_paths: &[String]
+) -> AnyhowResult<HashMap<String, Value>> {
+    let mut local = HashMap::new();
+    for component_path in component_paths {
+        let reflect_component = get_reflect_component(type_registry, component_path)?;
+        let Some(reflected) = reflect_component.reflect(entity_ref) else {
+            continue;
+        };
+
+        // keyed like the responses, in case the link was given short names
+        let value = serde_json
+            ::to_value(TypedReflectSerializer::new(reflected, type_registry))
+            .map_err(|error| anyhow!("`{}`: {}", component_path, error))?;
+        local.insert(component_type_path(type_registry, component_path)?.to_string(), value);
+    }
+
+    Ok(local)
+}
+
+fn send_link_request(
+    world: &mut World,
+    entity: Entity,
+    binding: Option<&RemoteConnection>,
+    request: LinkRequest,
+    now: Duration
+) {
+    world.resource_scope(|world, mut connections: Mut<BrpConnections>| {
+        let Some(connection) = connections.bound_mut(binding) else {
+            return;
+        };
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, world);
+        let (result, pushed) = match request {
+            LinkRequest::Get(request) => {
+                (connection.client.get(request, Some(entity), &mut commands), None)
+            }
+            LinkRequest::Insert(request) => {
+                // (a push that is still waiting is superseded, the server gets the newer value)
+                let pushed = request.components.clone();
+                let options = BrpCallOptions::default().superseding(format!("link {}", entity));
+                let result = connection.client.call_with::<_, BrpOkResponse>(
+                    "INSERT",
+                    request,
+                    Some(entity),
+                    options,
+                    &mut commands
+                );
+                (result, Some(pushed))
+            }
+        };
+        queue.apply(world);
+
+        let request_id = match result {
+            Ok(request_id) => request_id,
+            Err(error) => {
+                error!("Could not sync linked components: {}", error);
+                return;
+            }
+        };
+
+        let checked = world.change_tick();
+        let Some(mut link) = world.get_mut::<RemoteLink>(entity) else {
+            return;
+        };
+        link.in_flight = Some(request_id);
+        link.pulling = pushed.is_none();
+        if let Some(pushed) = pushed {
+            link.last_sent = Some(now);
+            link.checked = Some(checked);
+            link.synced.extend(pushed);
+        }
+    });
+}
+
+// free up each link whose push was answered
+fn finish_remote_links(
+    mut responses: EventReader<BrpResponse<BrpOkResponse>>,
+    mut links: Query<&mut RemoteLink>
+) {
+    for response in responses.read() {
+        let Some(entity) = response.entity else {
+            continue;
+        };
+        let Ok(mut link) = links.get_mut(entity) else {
+            continue;
+        };
+        if link.in_flight != Some(response.id) {
+            continue;
+        }
+        link.in_flight = None;
+
+        // (not resending here, or a link to a despawned entity would fail forever)
+        if let Err(error) = &response.result {
+            warn!("Could not update remote entity {}: {}", link.remote, error);
+        }
+    }
+}
+
+// hand each answered pull to merge_remote_link, which needs the whole world
+fn pull_remote_links(
+    time: Res<Time<Real>>,
+    mut responses: EventReader<BrpResponse<BrpGetResponse>>,
+    mut links: Query<&mut RemoteLink>,
+    mut commands: Commands
+) {
+    for response in responses.read() {
+        let Some(entity) = response.entity else {
+            continue;
+        };
+        let Ok(mut link) = links.get_mut(entity) else {
+            continue;
+        };
+        if link.in_flight != Some(response.id) {
+            continue;
+        }
+        link.in_flight = None;
+        link.last_pulled = Some(time.elapsed());
+
+        match &response.result {
+            Ok(get) => {
+                let game = get.components.clone();
+                commands.add(move |world: &mut World| merge_remote_link(world, entity, game));
+            }
+            Err(error) => warn!("Could not read remote entity {}: {}", link.remote, error),
+        }
+    }
+}
+
+// compare both sides with what they last agreed on, then take the game's changes and push ours
+fn merge_remote_link(world: &mut World, entity: Entity, game: HashMap<String, Value>) {
+    let now = world.resource::<Time<Real>>().elapsed();
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+
+    let Some(entity_ref) = world.get_entity(entity) else {
+        return;
+    };
+    let Some(link) = entity_ref.get::<RemoteLink>() else {
+        return;
+    };
+    let local = local_changes(world, &type_registry, entity_ref, link).and_then(|changes| {
+        let local = local_components(&type_registry, entity_ref, &link.components)?;
+        Ok((changes, local))
+    });
+    let (changes, local) = match local {
+        Ok(local) => local,
+        Err(error) => {
+            warn!("Could not serialize linked components of {}: {}", entity, error);
+            return;
+        }
+    };
+    let remote = link.remote;
+    let policy = link.policy;
+    let mut synced = link.synced.clone();
+    let mut conflicts = link.conflicts.clone();
+
+    let mut pull = HashMap::new();
+    let mut push = HashMap::new();
+    let mut asked = vec![];
+    for (path, editor_value) in local {
+        let Some(game_value) = game.get(&path) else {
+            continue;
+        };
+        if *game_value == editor_value {
+            synced.insert(path.clone(), editor_value);
+            conflicts.remove(&path);
+            continue;
+        }
+
+        // (a component set to the game's value, or touched without changing, hasn't been edited)
+        let base = synced.get(&path);
+        let touched = changes.get(&path).copied().unwrap_or(true);
+        let editor_changed = touched && base != Some(&editor_value);
+        let game_changed = base != Some(game_value);
+        let winner = match conflicts.get(&path) {
+            Some(Some(winner)) => Some(*winner),
+            // still waiting for an answer
+            Some(None) => None,
+            None if !game_changed => Some(LinkSide::Editor),
+            None if !editor_changed => Some(LinkSide::Game),
+            // nothing to compare with the first time, and no reason to bother anyone about it
+            None if base.is_none() && policy == ConflictPolicy::Ask => Some(LinkSide::Game),
+            None => {
+                match policy {
+                    ConflictPolicy::EditorWins => Some(LinkSide::Editor),
+                    ConflictPolicy::GameWins => Some(LinkSide::Game),
+                    ConflictPolicy::Ask => {
+                        asked.push(RemoteLinkConflict {
+                            entity,
+                            remote,
+                            component: path.clone(),
+                            editor: editor_value.clone(),
+                            game: game_value.clone(),
+                        });
+                        conflicts.insert(path.clone(), None);
+                        None
+                    }
+                }
+            }
+        };
+
+        match winner {
+            Some(LinkSide::Editor) => {
+                conflicts.remove(&path);
+                push.insert(path, editor_value);
+            }
+            Some(LinkSide::Game) => {
+                conflicts.remove(&path);
+                synced.insert(path.clone(), game_value.clone());
+                pull.insert(path, game_value.clone());
+            }
+            None => {}
+        }
+    }
+
+    // the game's values go in as they are, so they won't look like local changes next time
+    if !pull.is_empty() {
+        let result = deserialize_components(&type_registry, pull).and_then(|reflected| {
+            insert_reflected_components(&type_registry, world.entity_mut(entity), reflected)
+        });
+        if let Err(error) = result {
+            warn!("Could not apply remote changes to {}: {}", entity, error);
+        }
+    }
+
+    // (whatever changed locally has been weighed against the game's changes now)
+    let checked = world.change_tick();
+    let Some(mut link) = world.get_mut::<RemoteLink>(entity) else {
+        return;
+    };
+    link.synced = synced;
+    link.conflicts = conflicts;
+    link.checked = Some(checked);
+    let binding = world.get::<RemoteConnection>(entity).cloned();
+
+    for conflict in asked {
+        info!("Both the editor and the game changed `{}` of {}", conflict.component, entity);
+        world.send_event(conflict);
+    }
+
+    if !push.is_empty() {
+        let request = LinkRequest::Insert(BrpInsertRequest {
+            entity: remote,
+            components: push,
+        });
+        send_link_request(world, entity, binding.as_ref(), request, now);
+    }
+}
@@ -0,0 +1,572 @@
+//! Mirrors entities of a remote app into the editor world.
+//!
+//! A [`RemoteReplica`] names the remote entities and components to mirror. The editor keeps a local
+//! proxy entity for each remote entity that matches, as a child of the replica, and polls the
+//! server to keep the proxies up to date. Each proxy carries a [`RemoteProxy`] with the ID of the
+//! remote entity, and a [`RemoteComponents`] with the latest serialized value of each component.
+//! Widgets can then query ordinary local components rather than talking to the server themselves.
+//!
+//! A replica mirrors either the entities matching a query, with the components it selects, or a
+//! single entity with every component the game can serialize (see [`RemoteReplica::entity`]). The
+//! replica counts as changed whenever a poll has been answered, so a widget only has to look at
+//! the proxies again when `Ref<RemoteReplica>::is_changed` says so.
+//!
+//! Components listed in [`RemoteReplica::local_components`] are also deserialized and inserted on
+//! the proxy as real components, so that `Query<&Transform, With<RemoteProxy>>` works. Be careful
+//! with components that do something just by being there: a mirrored `Camera` will render, and a
+//! mirrored `Parent` points at an entity in the wrong world.
+
+use std::time::Duration;
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::{ prelude::*, utils::{ HashMap, HashSet } };
+use serde_json::Value;
+
+use super::{
+    brp_client::{ BrpClientPlugin, BrpError, BrpRequestId, BrpResponse },
+    builtin_verbs::{
+        component_type_path,
+        deserialize_components,
+        get_reflect_component,
+        insert_reflected_components,
+        BrpGetRequest,
+        BrpGetResponse,
+        BrpListRequest,
+        BrpListResponse,
+        BrpQuery,
+        BrpQueryFilter,
+        BrpQueryRequest,
+        BrpQueryResponse,
+        BrpQueryRow,
+    },
+    connections::{ BrpConnections, RemoteConnection },
+};
+
+pub struct RemoteReplicaPlugin;
+
+impl Plugin for RemoteReplicaPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<BrpClientPlugin>() {
+            app.add_plugins(BrpClientPlugin);
+        }
+
+        app.register_type::<RemoteProxy>().add_systems(
+            Update,
+            (
+                despawn_orphaned_replicas,
+                poll_replicas,
+                update_replicas,
+                receive_replica_lists,
+                receive_replica_components,
+            ).chain()
+        );
+    }
+}
+
+/// Mirrors remote entities into local proxy entities, which are spawned as children of the entity
+/// holding the replica.
+///
+/// The replica talks to the connection named by a [`RemoteConnection`] on the same entity, or the
+/// primary connection if there isn't one. Nothing is polled while that connection is down, and the
+/// proxies keep their last known values until it comes back.
+#[derive(Component)]
+pub struct RemoteReplica {
+    /// Which remote entities to mirror, and which of their components.
+    pub source: RemoteReplicaSource,
+
+    /// Components (by full type path, or short name if unambiguous) to also insert on the proxies
+    /// as real components.
+    ///
+    /// Each must be registered in the editor with `#[reflect(Component)]`, and be mirrored.
+    pub local_components: Vec<String>,
+
+    /// Time between polls.
+    pub interval: Duration,
+
+    /// The entity the replica was made for, if any. The replica is despawned along with it.
+    pub owner: Option<Entity>,
+
+    // counts down to the next poll
+    timer: Timer,
+
+    // the request of the poll that hasn't been answered yet
+    pending: Option<BrpRequestId>,
+
+    // the components the game can serialize, once it has said (for a single entity)
+    registered: Option<HashSet<String>>,
+
+    // the components of the entity that the game can't serialize (for a single entity)
+    unreflected: Vec<String>,
+
+    // what went wrong with the last poll
+    error: Option<String>,
+
+    // remote entity -> local proxy
+    proxies: HashMap<Entity, Entity>,
+}
+
+/// What a [`RemoteReplica`] mirrors.
+#[derive(Clone)]
+pub enum RemoteReplicaSource {
+    /// The entities matching a query, with the components it selects.
+    Query {
+        query: BrpQuery,
+        filter: BrpQueryFilter,
+    },
+
+    /// One entity, with every component it has that the game can serialize. The others are listed
+    /// in a [`RemoteUnreflected`] on the proxy.
+    Entity(Entity),
+}
+
+impl RemoteReplica {
+    pub fn new(query: BrpQuery, filter: BrpQueryFilter) -> Self {
+        Self::from_source(RemoteReplicaSource::Query { query, filter })
+    }
+
+    /// Mirrors one remote entity with all of its components.
+    pub fn entity(remote: Entity) -> Self {
+        Self::from_source(RemoteReplicaSource::Entity(remote))
+    }
+
+    fn from_source(source: RemoteReplicaSource) -> Self {
+        Self {
+            source,
+            local_components: vec![],
+            interval: Duration::from_millis(500),
+            owner: None,
+            timer: Timer::new(Duration::ZERO, TimerMode::Once),
+            pending: None,
+            registered: None,
+            unreflected: vec![],
+            error: None,
+            proxies: default(),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_local_components(mut self, local_components: Vec<String>) -> Self {
+        self.local_components = local_components;
+        self
+    }
+
+    pub fn owned_by(mut self, owner: Entity) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    /// The local proxy of a remote entity, if it has one.
+    pub fn local(&self, remote: Entity) -> Option<Entity> {
+        self.proxies.get(&remote).copied()
+    }
+
+    /// Every remote entity being mirrored, paired with its local proxy.
+    pub fn proxies(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.proxies.iter().map(|(remote, local)| (*remote, *local))
+    }
+
+    /// What went wrong with the last poll, if it failed.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// The mirrored entities as a `QUERY` would have returned them, from the
+    /// [`RemoteComponents`] of the proxies.
+    pub fn rows(&self, q_components: &Query<&RemoteComponents>) -> Vec<BrpQueryRow> {
+        self.proxies
+            .iter()
+            .filter_map(|(remote, proxy)| {
+                let components = q_components.get(*proxy).ok()?;
+                Some(BrpQueryRow {
+                    entity: *remote,
+                    components: components.0.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Polls again right away rather than waiting out the interval.
+    ///
+    /// A poll that is already on its way may have been answered before whatever prompted this, so
+    /// its answer is ignored.
+    pub fn refresh(&mut self) {
+        self.pending = None;
+        self.timer = Timer::new(Duration::ZERO, TimerMode::Once);
+    }
+
+    // the poll is over, whatever came of it, so wait out the interval before the next one
+    fn answered(&mut self) {
+        self.pending = None;
+        self.timer = Timer::new(self.interval, TimerMode::Once);
+    }
+}
+
+/// Spawns a replica for a widget on an entity of its own, bound to the named connection (or to the
+/// primary connection), and despawned along with the widget.
+pub fn spawn_replica(
+    commands: &mut Commands,
+    owner: Entity,
+    replica: RemoteReplica,
+    connection: Option<String>
+) -> Entity {
+    let mut replica = commands.spawn((Name::new("Remote Replica"), replica.owned_by(owner)));
+    if let Some(connection) = connection {
+        replica.insert(RemoteConnection(connection));
+    }
+    replica.id()
+}
+
+/// A local stand-in for a remote entity, mirrored by the [`RemoteReplica`] on `replica`.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct RemoteProxy {
+    /// The remote entity.
+    pub remote: Entity,
+
+    /// The local entity holding the replica.
+    pub replica: Entity,
+}
+
+/// The latest serialized value of each mirrored component of a remote entity, by full type path.
+#[derive(Component, Clone, Debug, Default)]
+pub struct RemoteComponents(pub HashMap<String, Value>);
+
+/// The components of a remote entity that the game can't serialize, so only their names are known.
+///
+/// Only the proxy of a replica of a single entity has this (see [`RemoteReplica::entity`]).
+#[derive(Component, Clone, Debug, Default)]
+pub struct RemoteUnreflected(pub Vec<String>);
+
+// replicas go away with the widgets they were made for
+fn despawn_orphaned_replicas(
+    replicas: Query<(Entity, &RemoteReplica)>,
+    entities: &bevy::ecs::entity::Entities,
+    mut commands: Commands
+) {
+    for (entity, replica) in &replicas {
+        if replica.owner.is_some_and(|owner| !entities.contains(owner)) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// send a query for every replica that is due
+fn poll_replicas(
+    time: Res<Time<Real>>,
+    mut replicas: Query<(Entity, &mut RemoteReplica, Option<&RemoteConnection>)>,
+    mut connections: ResMut<BrpConnections>,
+    mut commands: Commands
+) {
+    for (entity, mut replica, binding) in &mut replicas {
+        let Some(connection) = connections.bound_mut(binding) else {
+            continue;
+        };
+        if !connection.is_connected() || replica.pending.is_some() {
+            continue;
+        }
+
+        // (the interval is counted from the last response, so a slow server isn't swamped)
+        let replica = replica.bypass_change_detection();
+        if !replica.timer.tick(time.delta()).finished() {
+            continue;
+        }
+
+        let request_id = match &replica.source {
+            RemoteReplicaSource::Query { query, filter } => {
+                let request = BrpQueryRequest {
+                    data: query.clone(),
+                    filter: filter.clone(),
+                };
+                connection.client.query(request, Some(entity), &mut commands)
+            }
+
+            // LIST names every component of an entity, but GET fails unless the game can
+            // serialize them all, so first find out which ones it can
+            RemoteReplicaSource::Entity(remote) => {
+                let request = BrpListRequest {
+                    entity: replica.registered.as_ref().map(|_| *remote),
+                };
+                connection.client.list(request, Some(entity), &mut commands)
+            }
+        };
+        match request_id {
+            Ok(request_id) => {
+                replica.pending = Some(request_id);
+            }
+            Err(error) => error!("Could not poll remote replica: {}", error),
+        }
+    }
+}
+
+// hand each answered poll to update_replica, which needs the whole world to insert components
+fn update_replicas(
+    mut responses: EventReader<BrpResponse<BrpQueryResponse>>,
+    mut replicas: Query<&mut RemoteReplica>,
+    mut commands: Commands
+) {
+    for response in responses.read() {
+        let Some(entity) = response.entity else {
+            continue;
+        };
+        let Ok(mut replica) = replicas.get_mut(entity) else {
+            continue;
+        };
+        if replica.pending != Some(response.id) {
+            continue;
+        }
+
+        match &response.result {
+            Ok(query) => {
+                let replica = replica.bypass_change_detection();
+                replica.answered();
+                replica.error = None;
+                let rows = query.rows.clone();
+                commands.add(move |world: &mut World| update_replica(world, entity, rows, None));
+            }
+            Err(error) => fail_replica(replica, entity, error, &mut commands),
+        }
+    }
+}
+
+// learn which components the game can serialize, then get the ones the entity has
+fn receive_replica_lists(
+    mut responses: EventReader<BrpResponse<BrpListResponse>>,
+    mut replicas: Query<&mut RemoteReplica>,
+    mut connections: ResMut<BrpConnections>,
+    mut commands: Commands
+) {
+    for response in responses.read() {
+        let Some(entity) = response.entity else {
+            continue;
+        };
+        let Ok(mut replica) = replicas.get_mut(entity) else {
+            continue;
+        };
+        if replica.pending != Some(response.id) {
+            continue;
+        }
+        let RemoteReplicaSource::Entity(remote) = replica.source else {
+            continue;
+        };
+
+        let list = match &response.result {
+            Ok(list) => list,
+            Err(error) => {
+                fail_replica(replica, entity, error, &mut commands);
+                continue;
+            }
+        };
+
+        let replica = replica.bypass_change_detection();
+        let Some(registered) = &replica.registered else {
+            replica.registered = Some(list.components.iter().cloned().collect());
+            replica.refresh();
+            continue;
+        };
+
+        let (reflected, unreflected): (Vec<String>, Vec<String>) = list.components
+            .iter()
+            .cloned()
+            .partition(|type_path| registered.contains(type_path));
+        replica.unreflected = unreflected;
+
+        let Some(connection) = connections.get_mut(&response.connection) else {
+            replica.answered();
+            continue;
+        };
+        let request = BrpGetRequest {
+            entity: remote,
+            components: reflected,
+        };
+        match connection.client.get(request, Some(entity), &mut commands) {
+            Ok(request_id) => {
+                replica.pending = Some(request_id);
+            }
+            Err(error) => {
+                error!("Could not poll remote replica: {}", error);
+                replica.answered();
+            }
+        }
+    }
+}
+
+fn receive_replica_components(
+    mut responses: EventReader<BrpResponse<BrpGetResponse>>,
+    mut replicas: Query<&mut RemoteReplica>,
+    mut commands: Commands
+) {
+    for response in responses.read() {
+        let Some(entity) = response.entity else {
+            continue;
+        };
+        let Ok(mut replica) = replicas.get_mut(entity) else {
+            continue;
+        };
+        if replica.pending != Some(response.id) {
+            continue;
+        }
+
+        let got = match &response.result {
+            Ok(got) => got,
+            Err(error) => {
+                fail_replica(replica, entity, error, &mut commands);
+                continue;
+            }
+        };
+
+        let replica = replica.bypass_change_detection();
+        replica.answered();
+        replica.error = None;
+
+        let rows = vec![BrpQueryRow {
+            entity: got.entity,
+            components: got.components.clone(),
+        }];
+        let unreflected = Some(replica.unreflected.clone());
+        commands.add(move |world: &mut World| update_replica(world, entity, rows, unreflected));
+    }
+}
+
+// an entity the server doesn't know is gone, but one it didn't answer for may well still be there
+fn fail_replica(
+    mut replica: Mut<RemoteReplica>,
+    entity: Entity,
+    error: &BrpError,
+    commands: &mut Commands
+) {
+    warn!("Could not poll remote replica: {}", error);
+    replica.answered();
+    replica.error = Some(error.to_string());
+
+    if let (RemoteReplicaSource::Entity(_), BrpError::Remote(_)) = (&replica.source, error) {
+        commands.add(move |world: &mut World| update_replica(world, entity, vec![], None));
+    }
+}
+
+// spawn, update and despawn proxies to match the rows of the latest poll
+fn update_replica(
+    world: &mut World,
+    entity: Entity,
+    rows: Vec<BrpQueryRow>,
+    unreflected: Option<Vec<String>>
+) {
+    let Some(mut replica) = world.get_mut::<RemoteReplica>(entity) else {
+        return;
+    };
+    let mut proxies = std::mem::take(&mut replica.proxies);
+    let local_components = replica.local_components.clone();
+
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+
+    // the rows are keyed by full path, even if the replica was given short names
+    let local_components: Vec<String> = local_components
+        .iter()
+        .filter_map(|path| {
+            match component_type_path(&type_registry, path) {
+                Ok(path) => Some(path.to_string()),
+                Err(error) => {
+                    warn!("Can't mirror {} locally: {}", path, error);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    let mut seen = HashSet::new();
+    for row in rows {
+        seen.insert(row.entity);
+
+        // a proxy can be despawned from under us, so make sure it's still there
+        let proxy = match proxies.get(&row.entity) {
+            Some(proxy) if world.get_entity(*proxy).is_some() => *proxy,
+            _ => {
+                let proxy = world
+                    .spawn((
+                        RemoteProxy {
+                            remote: row.entity,
+                            replica: entity,
+                        },
+                        RemoteComponents::default(),
+                        Name::new(format!("Remote {}", row.entity)),
+                    ))
+                    .set_parent(entity)
+                    .id();
+                proxies.insert(row.entity, proxy);
+                proxy
+            }
+        };
+
+        if
+            let Err(error) = update_proxy(
+                world,
+                proxy,
+                row.components,
+                &local_components,
+                &type_registry
+            )
+        {
+            warn!("Could not update proxy of remote entity {}: {}", row.entity, error);
+        }
+        if let Some(unreflected) = &unreflected {
+            world.entity_mut(proxy).insert(RemoteUnreflected(unreflected.clone()));
+        }
+    }
+
+    // the rest no longer exist on the server, or no longer match
+    proxies.retain(|remote, proxy| {
+        if seen.contains(remote) {
+            return true;
+        }
+        if let Some(proxy) = world.get_entity_mut(*proxy) {
+            proxy.despawn_recursive();
+        }
+        false
+    });
+
+    if let Some(mut replica) = world.get_mut::<RemoteReplica>(entity) {
+        replica.proxies = proxies;
+    }
+}
+
+fn update_proxy(
+    world: &mut World,
+    proxy: Entity,
+    components: HashMap<String, Value>,
+    local_components: &[String],
+    type_registry: &bevy::reflect::TypeRegistry
+) -> AnyhowResult<()> {
+    let mut proxy = world.entity_mut(proxy);
+    let Some(mut previous) = proxy.get_mut::<RemoteComponents>() else {
+        return Err(anyhow!("The proxy has lost its RemoteComponents"));
+    };
+
+    // only touch what changed, so change detection means something to widgets
+    if previous.0 == components {
+        return Ok(());
+    }
+    let changed: HashMap<String, Value> = components
+        .iter()
+        .filter(|(path, value)| {
+            local_components.contains(path) && previous.0.get(*path) != Some(*value)
+        })
+        .map(|(path, value)| (path.clone(), value.clone()))
+        .collect();
+    let removed: Vec<String> = previous.0
+        .keys()
+        .filter(|path| local_components.contains(path) && !components.contains_key(*path))
+        .cloned()
+        .collect();
+    previous.0 = components;
+
+    for path in removed {
+        get_reflect_component(type_registry, &path)?.remove(&mut proxy);
+    }
+
+    let reflected = deserialize_components(type_registry, changed)?;
+    insert_reflected_components(type_registry, proxy, reflected)
+}
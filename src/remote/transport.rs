@@ -0,0 +1,236 @@
+//! How [`BrpClient`](super::brp_client::BrpClient) requests reach a server, and how the responses
+//! come back.
+//!
+//! Clients talk HTTP through [`EhttpTransport`] unless told otherwise. An [`InProcessTransport`]
+//! skips the network and hands requests straight to the [`BrpMailbox`](super::BrpMailbox) of
+//! another [`App`] in the same process, which makes an editor and a game that can be stepped in
+//! lockstep:
+//!
+//! ```ignore
+//! let mut game = App::new();
+//! game.add_plugins((MinimalPlugins, EditorRemotePlugin::default().without_listening()));
+//!
+//! let mut editor = App::new();
+//! editor.add_plugins((MinimalPlugins, BrpClientPlugin));
+//! let transport = InProcessTransport::new(game.world())?;
+//! editor
+//!     .world_mut()
+//!     .resource_mut::<BrpConnections>()
+//!     .primary_mut()
+//!     .unwrap()
+//!     .client.set_transport(transport);
+//!
+//! // a request sent during one editor update is answered by the next game update, and the
+//! // response arrives in the editor update after that
+//! editor.update();
+//! game.update();
+//! editor.update();
+//! ```
+
+use std::{ collections::VecDeque, sync::{ atomic::{ AtomicU64, Ordering }, Arc, Mutex } };
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::prelude::*;
+use serde_json::Value;
+use smol::channel::{ self, Receiver, Sender, TryRecvError, TrySendError };
+
+use super::{
+    brp_client::BrpError,
+    encoding::BrpEncoding,
+    response_from,
+    BrpMailboxSender,
+    BrpMessage,
+    BrpRequest,
+    CLIENT_HEADER,
+};
+
+// numbers the transports of this process, so each can name itself to the server
+static NEXT_CLIENT: AtomicU64 = AtomicU64::new(0);
+
+// a name for a new transport that no other client of the server has
+fn client_name(kind: &str) -> String {
+    format!("{}-{}-{}", kind, std::process::id(), NEXT_CLIENT.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Called with the whole response to a request (including its `status` and `id`), or with the
+/// reason there isn't one. It may be called from any thread.
+pub type BrpResponder = Box<dyn FnOnce(Result<Value, BrpError>) + Send>;
+
+/// Carries requests from a [`BrpClient`](super::brp_client::BrpClient) to a server, and the
+/// responses back.
+///
+/// The client takes care of IDs, timeouts, cancellation and decoding the response; a transport only
+/// has to deliver the request and call the responder exactly once.
+pub trait BrpTransport: Send + Sync + 'static {
+    /// Sends a request to the server at `url`, in the given encoding if the transport encodes at
+    /// all, and arranges for `respond` to be called with the response.
+    fn send(&self, url: &str, encoding: BrpEncoding, request: BrpRequest, respond: BrpResponder);
+
+    /// Called by the client once a frame, before responses are turned into events.
+    ///
+    /// Transports that don't deliver responses on their own can do it here.
+    fn poll(&self) {}
+}
+
+/// Sends requests over HTTP with `ehttp`, and calls back from its thread.
+pub struct EhttpTransport {
+    // sent as the CLIENT_HEADER
+    client: String,
+}
+
+impl Default for EhttpTransport {
+    fn default() -> Self {
+        Self { client: client_name("ehttp") }
+    }
+}
+
+impl BrpTransport for EhttpTransport {
+    fn send(&self, url: &str, encoding: BrpEncoding, request: BrpRequest, respond: BrpResponder) {
+        let body = match encoding.encode(&request) {
+            Ok(body) => body,
+            Err(error) => {
+                respond(Err(BrpError::Transport(format!("could not encode request: {}", error))));
+                return;
+            }
+        };
+
+        // the response comes back in the same encoding as the request
+        let request = ehttp::Request {
+            headers: ehttp::Headers::new(
+                &[
+                    ("Accept", encoding.content_type()),
+                    ("Content-Type", encoding.content_type()),
+                    (CLIENT_HEADER, &self.client),
+                ]
+            ),
+            ..ehttp::Request::post(url, body)
+        };
+
+        ehttp::fetch(request, move |response: ehttp::Result<ehttp::Response>| {
+            respond(decode_http_response(response));
+        });
+    }
+}
+
+// turn the raw HTTP response back into JSON, whatever it was encoded as
+fn decode_http_response(response: ehttp::Result<ehttp::Response>) -> Result<Value, BrpError> {
+    let response = response.map_err(BrpError::Transport)?;
+    trace!("status code: {:?}", response.status);
+
+    let encoding = response
+        .content_type()
+        .and_then(BrpEncoding::from_content_type)
+        .unwrap_or_default();
+    encoding.decode::<Value>(&response.bytes).map_err(|error| BrpError::Decode(error.to_string()))
+}
+
+// a request that has been handed to the server, and the channel its result comes back on
+struct InProcessRequest {
+    id: Value,
+    receiver: Receiver<AnyhowResult<Value>>,
+    respond: BrpResponder,
+}
+
+/// Hands requests straight to the [`BrpMailbox`](super::BrpMailbox) of another [`App`] in the same
+/// process, where its [`RemoteVerbs`](super::RemoteVerbs) answer them as they would an HTTP client.
+///
+/// Nothing happens in the background. The server answers when its app updates, and the response is
+/// delivered the next time the client polls its transport, at the start of its own update. Nothing
+/// is encoded, so the encoding of the client doesn't matter.
+pub struct InProcessTransport {
+    mailbox: Sender<BrpMessage>,
+    client: String,
+
+    // requests that didn't fit in the mailbox yet, oldest first
+    backlog: Mutex<VecDeque<(BrpMessage, InProcessRequest)>>,
+
+    // requests in the mailbox, waiting for the server to answer
+    waiting: Mutex<Vec<InProcessRequest>>,
+}
+
+impl InProcessTransport {
+    /// Connects to the world of an app with the
+    /// [`EditorRemotePlugin`](super::EditorRemotePlugin), which doesn't need to be listening.
+    pub fn new(server: &World) -> AnyhowResult<Self> {
+        let mailbox = server
+            .get_resource::<BrpMailboxSender>()
+            .ok_or_else(|| anyhow!("The server app doesn't have the EditorRemotePlugin"))?;
+
+        Ok(Self {
+            mailbox: mailbox.0.clone(),
+            client: client_name("in-process"),
+            backlog: default(),
+            waiting: default(),
+        })
+    }
+
+    // move as much of the backlog into the mailbox as fits, keeping the requests in order
+    fn flush(&self) {
+        let mut closed = vec![];
+        {
+            let mut backlog = self.backlog.lock().unwrap();
+            while let Some((message, request)) = backlog.pop_front() {
+                match self.mailbox.try_send(message) {
+                    Ok(()) => self.waiting.lock().unwrap().push(request),
+                    Err(TrySendError::Full(message)) => {
+                        backlog.push_front((message, request));
+                        break;
+                    }
+                    Err(TrySendError::Closed(_)) => closed.push(request),
+                }
+            }
+        }
+
+        // (responders are called outside the locks, in case one of them sends another request)
+        for request in closed {
+            (request.respond)(Err(BrpError::Transport("the server app is gone".to_string())));
+        }
+    }
+}
+
+impl BrpTransport for InProcessTransport {
+    fn send(&self, _url: &str, _encoding: BrpEncoding, request: BrpRequest, respond: BrpResponder) {
+        let (sender, receiver) = channel::bounded(1);
+        let id = request.id.clone();
+        let message = BrpMessage {
+            request,
+            client: self.client.clone(),
+            sender: Arc::new(Mutex::new(Some(sender))),
+        };
+
+        self.backlog.lock().unwrap().push_back((message, InProcessRequest { id, receiver, respond }));
+        self.flush();
+    }
+
+    fn poll(&self) {
+        self.flush();
+
+        let waiting = std::mem::take(&mut *self.waiting.lock().unwrap());
+        let mut still_waiting = vec![];
+        for request in waiting {
+            let result = match request.receiver.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => {
+                    still_waiting.push(request);
+                    continue;
+                }
+                Err(TryRecvError::Closed) => {
+                    let error = BrpError::Transport("the server dropped the request".to_string());
+                    (request.respond)(Err(error));
+                    continue;
+                }
+            };
+
+            // the same response an HTTP client would get, minus the encoding
+            let result = result.and_then(|value| {
+                match value {
+                    Value::Object(map) => Ok(map),
+                    _ => Err(anyhow!("Response wasn't an object")),
+                }
+            });
+            (request.respond)(Ok(Value::Object(response_from(request.id, result))));
+        }
+
+        self.waiting.lock().unwrap().extend(still_waiting);
+    }
+}
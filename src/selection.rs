@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+
+use sickle_ui::prelude::*;
+
+use crate::framework::*;
+
+/// Keeps track of what is selected and which panel has focus, and tells everyone when either
+/// changes.
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectionService>()
+            .add_event::<SelectionChanged>()
+            .add_event::<FocusChanged>()
+            .add_event::<SelectAllRequested>()
+            .add_systems(
+                Update,
+                (focus_pressed_panels, select_all_on_menu_item, select_none_on_menu_item)
+                    .after(WidgetLibraryUpdate)
+                    .run_if(in_state(EditorState::Running))
+            )
+            .add_systems(PostUpdate, send_selection_events);
+    }
+}
+
+/// The selection service holds the entities that are selected, in the editor or in the games it is
+/// connected to, and the panel that was used last.
+///
+/// Unlike the services in [`EditorService`](crate::service::EditorService), it's a resource of its
+/// own, so that the panels that read and write it don't borrow all the others.
+#[derive(Resource, Default, Debug)]
+pub struct SelectionService {
+    // in the order they were selected in, so the last one is the primary one
+    selected: Vec<EditorEntity>,
+    focused: Option<Entity>,
+}
+
+impl SelectionService {
+    /// Everything that is selected, in the order it was selected in.
+    pub fn selected(&self) -> &[EditorEntity] {
+        &self.selected
+    }
+
+    /// The entity that was selected last, which panels that show one entity show.
+    pub fn primary(&self) -> Option<&EditorEntity> {
+        self.selected.last()
+    }
+
+    pub fn is_selected(&self, entity: &EditorEntity) -> bool {
+        self.selected.contains(entity)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.selected.is_empty()
+    }
+
+    /// Selects only this entity.
+    pub fn select(&mut self, entity: EditorEntity) {
+        self.selected.clear();
+        self.selected.push(entity);
+    }
+
+    /// Adds an entity to the selection, as the primary one.
+    pub fn add(&mut self, entity: EditorEntity) {
+        self.selected.retain(|selected| *selected != entity);
+        self.selected.push(entity);
+    }
+
+    /// Adds an entity to the selection, or takes it out if it's already there.
+    pub fn toggle(&mut self, entity: EditorEntity) {
+        if self.is_selected(&entity) {
+            self.deselect(&entity);
+        } else {
+            self.add(entity);
+        }
+    }
+
+    pub fn deselect(&mut self, entity: &EditorEntity) {
+        self.selected.retain(|selected| selected != entity);
+    }
+
+    /// Selects these entities instead of whatever was selected, with the last one as the primary.
+    pub fn select_all(&mut self, entities: impl IntoIterator<Item = EditorEntity>) {
+        self.selected.clear();
+        for entity in entities {
+            self.add(entity);
+        }
+    }
+
+    /// Keeps only the selected entities for which `keep` returns true.
+    pub fn retain(&mut self, keep: impl FnMut(&EditorEntity) -> bool) {
+        self.selected.retain(keep);
+    }
+
+    pub fn clear(&mut self) {
+        self.selected.clear();
+    }
+
+    /// The panel that was clicked last, if it's one that can have focus.
+    pub fn focused(&self) -> Option<Entity> {
+        self.focused
+    }
+
+    pub fn focus(&mut self, panel: Option<Entity>) {
+        self.focused = panel;
+    }
+}
+
+/// Sent after the selection has changed, with everything that is now selected.
+#[derive(Event, Clone, Debug)]
+pub struct SelectionChanged {
+    pub selected: Vec<EditorEntity>,
+}
+
+/// Sent after another panel (or none) has taken focus.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct FocusChanged {
+    pub panel: Option<Entity>,
+}
+
+/// Sent when "Select All" is picked, for the panel that has focus to select everything it shows.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct SelectAllRequested {
+    pub panel: Option<Entity>,
+}
+
+/// Marks a panel that takes focus when anything in it is clicked.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct Focusable;
+
+fn focus_pressed_panels(
+    mouse: Res<ButtonInput<MouseButton>>,
+    q_pressed: Query<(Entity, &Interaction), Changed<Interaction>>,
+    q_focusable: Query<(), With<Focusable>>,
+    q_parents: Query<&Parent>,
+    mut selection: ResMut<SelectionService>
+) {
+    if !mouse.any_just_pressed([MouseButton::Left, MouseButton::Right]) {
+        return;
+    }
+    let Some((pressed, _)) = q_pressed
+        .iter()
+        .find(|(_, interaction)| **interaction == Interaction::Pressed) else {
+        return;
+    };
+
+    // the closest panel the pressed node is in, which may be the node itself
+    // (clicking outside of any, like on the menu, leaves the focus where it was)
+    let Some(panel) = std::iter::once(pressed)
+        .chain(q_parents.iter_ancestors(pressed))
+        .find(|entity| q_focusable.contains(*entity)) else {
+        return;
+    };
+    if selection.focused != Some(panel) {
+        selection.focus(Some(panel));
+    }
+}
+
+fn select_all_on_menu_item(
+    q_menu_items: Query<&MenuItem, (With<SelectAllButton>, Changed<MenuItem>)>,
+    selection: Res<SelectionService>,
+    mut requests: EventWriter<SelectAllRequested>
+) {
+    let Ok(item) = q_menu_items.get_single() else {
+        return;
+    };
+
+    if item.interacted() {
+        requests.send(SelectAllRequested {
+            panel: selection.focused(),
+        });
+    }
+}
+
+fn select_none_on_menu_item(
+    q_menu_items: Query<&MenuItem, (With<SelectNoneButton>, Changed<MenuItem>)>,
+    mut selection: ResMut<SelectionService>
+) {
+    let Ok(item) = q_menu_items.get_single() else {
+        return;
+    };
+
+    if item.interacted() {
+        selection.clear();
+    }
+}
+
+// the service is borrowed mutably all over, so only real changes are reported
+fn send_selection_events(
+    selection: Res<SelectionService>,
+    mut last: Local<SelectionService>,
+    mut selection_changes: EventWriter<SelectionChanged>,
+    mut focus_changes: EventWriter<FocusChanged>
+) {
+    if !selection.is_changed() {
+        return;
+    }
+
+    if last.selected != selection.selected {
+        last.selected.clone_from(&selection.selected);
+        selection_changes.send(SelectionChanged {
+            selected: selection.selected.clone(),
+        });
+    }
+    if last.focused != selection.focused {
+        last.focused = selection.focused;
+        focus_changes.send(FocusChanged {
+            panel: selection.focused,
+        });
+    }
+}
@@ -81,7 +81,7 @@ pub fn build_menu(
                     shortcut: vec![KeyCode::KeyN].into(),
                     alt_code: KeyCode::KeyN.into(),
                     ..default()
-                }).insert(NewProjectButton);
+                }).insert(Page::NewProject);
 
                 menu.menu_item(MenuItemConfig {
                     name: l10n.lbl("Open"),
@@ -95,14 +95,14 @@ pub fn build_menu(
                     shortcut: vec![KeyCode::KeyS].into(),
                     alt_code: KeyCode::KeyS.into(),
                     ..default()
-                });
+                }).insert(SaveProjectButton);
 
                 menu.menu_item(MenuItemConfig {
                     name: l10n.lbl("Close"),
                     shortcut: vec![KeyCode::KeyC].into(),
                     alt_code: KeyCode::KeyC.into(),
                     ..default()
-                });
+                }).insert(CloseProjectButton);
             }
         );
 
@@ -114,26 +114,37 @@ pub fn build_menu(
                 alt_code: KeyCode::KeyE.into(),
             },
             |menu| {
+                // (Ctrl+Z and Ctrl+Shift+Z are bound as input actions, see the history plugin,
+                // and so are Ctrl+C, Ctrl+X and Ctrl+V, see the clipboard plugin)
+                menu.menu_item(MenuItemConfig {
+                    name: l10n.lbl("Undo"),
+                    alt_code: KeyCode::KeyU.into(),
+                    ..default()
+                }).insert(UndoButton);
+
+                menu.menu_item(MenuItemConfig {
+                    name: l10n.lbl("Redo"),
+                    alt_code: KeyCode::KeyR.into(),
+                    ..default()
+                }).insert(RedoButton);
+
                 menu.menu_item(MenuItemConfig {
                     name: l10n.lbl("Copy"),
-                    shortcut: vec![KeyCode::KeyC].into(),
                     alt_code: KeyCode::KeyC.into(),
                     ..default()
-                });
+                }).insert(CopyButton);
 
                 menu.menu_item(MenuItemConfig {
                     name: l10n.lbl("Cut"),
-                    shortcut: vec![KeyCode::KeyX].into(),
                     alt_code: KeyCode::KeyX.into(),
                     ..default()
-                });
+                }).insert(CutButton);
 
                 menu.menu_item(MenuItemConfig {
                     name: l10n.lbl("Paste"),
-                    shortcut: vec![KeyCode::KeyV].into(),
                     alt_code: KeyCode::KeyV.into(),
                     ..default()
-                });
+                }).insert(PasteButton);
             }
         );
 
@@ -150,14 +161,14 @@ pub fn build_menu(
                     shortcut: vec![KeyCode::KeyA].into(),
                     alt_code: KeyCode::KeyA.into(),
                     ..default()
-                });
+                }).insert(SelectAllButton);
 
                 menu.menu_item(MenuItemConfig {
                     name: l10n.lbl("SelectNone"),
                     shortcut: vec![KeyCode::KeyD].into(),
                     alt_code: KeyCode::KeyD.into(),
                     ..default()
-                });
+                }).insert(SelectNoneButton);
             }
         );
 
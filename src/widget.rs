@@ -1,7 +1,86 @@
 // define each standard editor widget as a plugin in the widget folder and add the mod here.
 
 use bevy::prelude::*;
+use bevy_simple_text_input::{
+    TextInputBundle,
+    TextInputInactive,
+    TextInputPlugin,
+    TextInputSettings,
+};
+
+pub mod connect_to;
+pub mod inspector;
+pub mod link_conflict;
+pub mod outliner;
 
 /// The widget service provices all registered widgets and allows plugins to register their own.
 #[derive(Resource, Default, Debug)]
 pub struct WidgetService {}
+
+/// Adds text inputs, and makes sure only the one that was clicked last takes the keyboard.
+///
+/// Widgets spawn their text inputs inactive, with an [`Interaction`], and add this plugin if it
+/// isn't there yet.
+pub struct TextInputFocusPlugin;
+
+impl Plugin for TextInputFocusPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<TextInputPlugin>() {
+            app.add_plugins(TextInputPlugin);
+        }
+
+        app.add_systems(PreUpdate, focus_clicked_text_input.after(bevy::ui::UiSystem::Focus));
+    }
+}
+
+/// A text input in the style of the editor's panels, holding `text`.
+///
+/// It starts out inactive, so typing only goes to the one that was clicked (see
+/// [`TextInputFocusPlugin`]). Change the style of the [`NodeBundle`] for a wider input.
+pub fn text_input(text: &str) -> (NodeBundle, TextInputBundle, Interaction) {
+    (
+        NodeBundle {
+            style: Style {
+                min_width: Val::Px(120.0),
+                border: UiRect::all(Val::Px(1.0)),
+                padding: UiRect::horizontal(Val::Px(4.0)),
+                ..default()
+            },
+            border_color: Color::srgb(0.4, 0.4, 0.4).into(),
+            ..default()
+        },
+        TextInputBundle::default()
+            .with_value(text)
+            .with_text_style(TextStyle {
+                font_size: 14.0,
+                ..default()
+            })
+            .with_settings(TextInputSettings {
+                retain_on_submit: true,
+                ..default()
+            })
+            .with_inactive(true),
+        Interaction::None,
+    )
+}
+
+type ClickedTextInputFilter = (Changed<Interaction>, With<TextInputInactive>);
+
+// clicking a text input starts editing it, and stops editing any other
+fn focus_clicked_text_input(
+    q_clicked: Query<(Entity, &Interaction), ClickedTextInputFilter>,
+    mut q_inputs: Query<(Entity, &mut TextInputInactive)>
+) {
+    let Some((clicked, _)) = q_clicked
+        .iter()
+        .find(|(_, interaction)| **interaction == Interaction::Pressed) else {
+        return;
+    };
+
+    for (entity, mut inactive) in &mut q_inputs {
+        let should_be_inactive = entity != clicked;
+        if inactive.0 != should_be_inactive {
+            inactive.0 = should_be_inactive;
+        }
+    }
+}
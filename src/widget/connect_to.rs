@@ -0,0 +1,133 @@
+use bevy::prelude::*;
+
+use bevy_fluent::Localization;
+
+use sickle_ui::prelude::*;
+
+use crate::{
+    framework::*,
+    locale::Translator,
+    remote::{
+        brp_client::BrpClientPlugin,
+        connections::BrpConnections,
+        discovery::{ BrpAnnouncement, DiscoveredServers },
+    },
+};
+
+// lists the games running on this machine and connects to the one that is picked
+pub struct ConnectToPlugin;
+
+impl Plugin for ConnectToPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<BrpClientPlugin>() {
+            app.add_plugins(BrpClientPlugin);
+        }
+
+        app.add_systems(
+            Update,
+            (update_connect_to_panels, connect_to_selected_server)
+                .chain()
+                .after(WidgetLibraryUpdate)
+                .run_if(in_state(EditorState::Running))
+        );
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct ConnectToPanel;
+
+// the servers behind the options of a radio group, in the same order
+#[derive(Component, Debug)]
+struct ConnectToServers(Vec<BrpAnnouncement>);
+
+// rebuild the list whenever a game starts or stops
+fn update_connect_to_panels(
+    q_panels: Query<(Entity, Ref<ConnectToPanel>)>,
+    discovered: Res<DiscoveredServers>,
+    connections: Res<BrpConnections>,
+    l10n: Res<Localization>,
+    mut commands: Commands
+) {
+    for (panel, marker) in &q_panels {
+        if !marker.is_added() && !discovered.is_changed() {
+            continue;
+        }
+
+        commands.entity(panel).despawn_descendants();
+        commands.ui_builder(panel).column(|column| {
+            if discovered.servers.is_empty() {
+                column.label(LabelConfig {
+                    label: l10n.lbl("NoServersFound"),
+                    ..default()
+                });
+                return;
+            }
+
+            // keep showing which game we are already talking to
+            let primary_url = connections.primary().map(|primary| primary.client.url.clone());
+            let selected = discovered.servers
+                .iter()
+                .position(|server| Some(&server.url) == primary_url.as_ref());
+
+            let options: Vec<String> = discovered.servers.iter().map(server_label).collect();
+            column
+                .radio_group(options, selected, false)
+                .insert(ConnectToServers(discovered.servers.clone()));
+        });
+    }
+}
+
+fn connect_to_selected_server(
+    q_groups: Query<(Ref<RadioGroup>, &ConnectToServers), Changed<RadioGroup>>,
+    mut connections: ResMut<BrpConnections>
+) {
+    for (radio_group, servers) in &q_groups {
+        // a new group shows the server that is already connected, rather than picking one
+        if radio_group.is_added() {
+            continue;
+        }
+
+        let Some(server) = radio_group.selected.and_then(|index| servers.0.get(index)) else {
+            continue;
+        };
+
+        // reuse a connection that already goes to this server
+        let name = match connections.find_url(&server.url) {
+            Some(name) => name.clone(),
+            None => {
+                let name = format!("{} ({})", server.app, server.pid);
+                connections.add(name.clone(), server.url.clone());
+                name
+            }
+        };
+
+        if connections.primary_name() != name {
+            info!("connecting to {} at {}", name, server.url);
+            connections.set_primary(name.clone());
+        }
+        if let Some(connection) = connections.get_mut(&name) {
+            connection.connect();
+        }
+    }
+}
+
+fn server_label(server: &BrpAnnouncement) -> String {
+    format!("{} :{} (pid {}, Bevy {})", server.app, server.port, server.pid, server.bevy_version)
+}
+
+pub trait UiConnectToExt {
+    fn connect_to(&mut self) -> UiBuilder<Entity>;
+}
+
+impl UiConnectToExt for UiBuilder<'_, Entity> {
+    fn connect_to(&mut self) -> UiBuilder<Entity> {
+        let panel = self
+            .insert((Name::new("Connect To"), ConnectToPanel))
+            .style()
+            .padding(UiRect::all(Val::Px(10.0)))
+            .id();
+
+        self.commands().ui_builder(panel)
+    }
+}
@@ -0,0 +1,918 @@
+//! The inspector shows the components of an entity as forms generated from reflection, and lets the
+//! user edit them.
+//!
+//! A panel inspects whatever its [`InspectorPanel::target`] points at, which follows the primary
+//! entity of the [`SelectionService`](crate::selection::SelectionService) unless told otherwise.
+//! Components of a local entity are read straight from the world and edited in place. Components
+//! of a remote entity are mirrored by a [`RemoteReplica`] of the entity, and edits are sent back
+//! with `INSERT` (through the [remote history](crate::history::remote)). Either way
+//! the edit can be undone, and the panel refreshes every [`InspectorPanel::interval`], so it keeps
+//! up with changes made by the game.
+//!
+//! Each component can be copied from the button on its first row, to be pasted onto other entities
+//! through the [clipboard](crate::clipboard).
+
+use std::time::Duration;
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::{
+    prelude::*,
+    reflect::TypeRegistry,
+    utils::get_short_name,
+};
+
+use bevy_fluent::Localization;
+use bevy_simple_text_input::{ TextInputInactive, TextInputSubmitEvent };
+use serde_json::Value;
+use sickle_ui::prelude::*;
+
+use crate::{
+    clipboard::{ self, ClipboardContent, ClipboardPlugin },
+    framework::*,
+    history::{
+        self,
+        remote::{ RemoteEdit, RemoteEdited },
+        HistoryApplied,
+        HistoryOperation,
+        HistoryPlugin,
+        HistoryStep,
+    },
+    locale::Translator,
+    remote::{
+        brp_client::BrpClientPlugin,
+        builtin_verbs::get_reflect_component,
+        replica::{
+            spawn_replica,
+            RemoteComponents,
+            RemoteReplica,
+            RemoteReplicaPlugin,
+            RemoteUnreflected,
+        },
+    },
+    selection::{ Focusable, SelectionChanged, SelectionPlugin },
+    widget::{ text_input, TextInputFocusPlugin },
+};
+
+pub mod form;
+
+use form::{
+    apply_field_edit,
+    color_hex,
+    form_rows,
+    reflect_from_json,
+    reflect_to_json,
+    FieldEdit,
+    FormRow,
+    FormRowKind,
+};
+
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<BrpClientPlugin>() {
+            app.add_plugins(BrpClientPlugin);
+        }
+        if !app.is_plugin_added::<TextInputFocusPlugin>() {
+            app.add_plugins(TextInputFocusPlugin);
+        }
+        if !app.is_plugin_added::<SelectionPlugin>() {
+            app.add_plugins(SelectionPlugin);
+        }
+        if !app.is_plugin_added::<HistoryPlugin>() {
+            app.add_plugins(HistoryPlugin);
+        }
+        if !app.is_plugin_added::<ClipboardPlugin>() {
+            app.add_plugins(ClipboardPlugin);
+        }
+        if !app.is_plugin_added::<RemoteReplicaPlugin>() {
+            app.add_plugins(RemoteReplicaPlugin);
+        }
+
+        app.add_systems(
+            Update,
+            (
+                follow_selection,
+                refresh_on_history,
+                follow_inspector_targets,
+                read_remote_inspectors,
+                receive_remote_edits,
+                refresh_local_inspectors,
+                (
+                    edit_checkboxes,
+                    edit_dropdowns,
+                    edit_text_inputs,
+                    edit_with_buttons,
+                    copy_inspected_components,
+                ),
+                update_inspector_panels,
+            )
+                .chain()
+                .after(WidgetLibraryUpdate)
+                .run_if(in_state(EditorState::Running))
+        );
+    }
+}
+
+/// Shows the components of [`target`](Self::target) as editable forms.
+#[derive(Component)]
+pub struct InspectorPanel {
+    /// The entity to inspect, if any.
+    pub target: Option<EditorEntity>,
+
+    /// Whether [`target`](Self::target) is set to the primary selected entity whenever the
+    /// selection changes.
+    pub follow_selection: bool,
+
+    /// Time between refreshes.
+    pub interval: Duration,
+
+    // the target the components were read from
+    shown: Option<EditorEntity>,
+
+    // the components of the target, by full type path
+    components: Vec<InspectedComponent>,
+
+    // what went wrong the last time the components were read or edited
+    error: Option<String>,
+
+    // counts down to the next refresh
+    timer: Timer,
+
+    // the replica mirroring a remote target
+    replica: Option<Entity>,
+
+    // the replica should poll again right away
+    stale: bool,
+
+    // the form needs to be rebuilt
+    dirty: bool,
+}
+
+impl Default for InspectorPanel {
+    fn default() -> Self {
+        Self {
+            target: None,
+            follow_selection: true,
+            interval: Duration::from_millis(500),
+            shown: None,
+            components: vec![],
+            error: None,
+            timer: Timer::new(Duration::ZERO, TimerMode::Once),
+            replica: None,
+            stale: false,
+            dirty: true,
+        }
+    }
+}
+
+impl InspectorPanel {
+    // refresh again right away rather than waiting out the interval
+    pub fn refresh(&mut self) {
+        self.timer = Timer::new(Duration::ZERO, TimerMode::Once);
+        self.stale = true;
+    }
+
+    // forget everything about the previous target
+    fn show_target(&mut self) {
+        self.shown = self.target.clone();
+        self.components.clear();
+        self.error = None;
+        self.dirty = true;
+        self.refresh();
+    }
+
+    fn show(&mut self, components: Vec<InspectedComponent>) {
+        if self.components != components {
+            self.components = components;
+            self.dirty = true;
+        }
+    }
+
+    // the components can't be read any more, e.g. because the entity is gone
+    fn fail_to_read(&mut self, error: impl ToString) {
+        self.show(vec![]);
+        self.fail(error);
+    }
+
+    fn fail(&mut self, error: impl ToString) {
+        let error = Some(error.to_string());
+        if self.error != error {
+            self.error = error;
+            self.dirty = true;
+        }
+    }
+
+    fn row(&self, component: &str, path: &str) -> Option<&FormRow> {
+        self.components
+            .iter()
+            .find(|inspected| inspected.type_path == component)
+            .and_then(|inspected| inspected.rows.iter().find(|row| row.path == path))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct InspectedComponent {
+    type_path: String,
+
+    // the value as it came over BRP (local components are read straight from the world instead)
+    json: Option<Value>,
+
+    rows: Vec<FormRow>,
+}
+
+impl InspectedComponent {
+    // a component the editor doesn't know can still be shown as it was sent
+    fn from_json(type_path: String, json: Value, type_registry: &TypeRegistry) -> Self {
+        let label = get_short_name(&type_path);
+        let rows = match type_registry.get_with_type_path(&type_path) {
+            Some(registration) =>
+                match reflect_from_json(registration, &json, type_registry) {
+                    Ok(value) => form_rows(&label, &*value),
+                    Err(error) => read_only_rows(label, error.to_string()),
+                }
+            None => read_only_rows(label, json.to_string()),
+        };
+
+        Self {
+            type_path,
+            json: Some(json),
+            rows,
+        }
+    }
+
+    // a component the game can't serialize can only be named
+    fn unreflected(type_path: String) -> Self {
+        let rows = vec![
+            FormRow::new(String::new(), get_short_name(&type_path), 0, FormRowKind::Heading)
+        ];
+
+        Self {
+            type_path,
+            json: None,
+            rows,
+        }
+    }
+}
+
+fn read_only_rows(label: String, text: String) -> Vec<FormRow> {
+    vec![FormRow::new(String::new(), label, 0, FormRowKind::ReadOnly(text))]
+}
+
+/// The component and field of an inspected entity that a widget of an [`InspectorPanel`] edits.
+#[derive(Component, Clone, Debug)]
+pub struct InspectorField {
+    pub panel: Entity,
+
+    /// The full type path of the component.
+    pub component: String,
+
+    /// The reflection path of the field within the component.
+    pub path: String,
+}
+
+// the button that copies a component
+#[derive(Component, Clone, Debug)]
+struct InspectorCopy {
+    panel: Entity,
+    component: String,
+}
+
+// a button that edits a field, like the ones adding and removing items of lists
+#[derive(Component, Clone, Debug)]
+struct InspectorFieldButton {
+    field: InspectorField,
+    edit: FieldEdit,
+}
+
+// start over whenever the panel is pointed at another entity, with a replica if it is remote
+fn follow_inspector_targets(
+    mut q_panels: Query<(Entity, &mut InspectorPanel)>,
+    mut commands: Commands
+) {
+    for (panel_entity, mut panel) in &mut q_panels {
+        if panel.shown == panel.target {
+            continue;
+        }
+
+        let panel = panel.bypass_change_detection();
+        panel.show_target();
+        if let Some(replica) = panel.replica.take() {
+            commands.entity(replica).despawn_recursive();
+        }
+        if let Some(EditorEntity::Remote { connection, entity }) = &panel.target {
+            let replica = RemoteReplica::entity(*entity).with_interval(panel.interval);
+            let replica = spawn_replica(
+                &mut commands,
+                panel_entity,
+                replica,
+                Some(connection.clone())
+            );
+            panel.replica = Some(replica);
+        }
+    }
+}
+
+// show the components the replica has mirrored, whenever it has polled the game
+fn read_remote_inspectors(
+    mut q_panels: Query<&mut InspectorPanel>,
+    mut q_replicas: Query<&mut RemoteReplica>,
+    q_proxies: Query<(&RemoteComponents, Option<&RemoteUnreflected>)>,
+    type_registry: Res<AppTypeRegistry>
+) {
+    for mut panel in &mut q_panels {
+        let panel = panel.bypass_change_detection();
+        let Some(EditorEntity::Remote { entity, .. }) = panel.target else {
+            continue;
+        };
+        let Some(mut replica) = panel.replica.and_then(|replica| q_replicas.get_mut(replica).ok())
+        else {
+            continue;
+        };
+
+        if std::mem::take(&mut panel.stale) {
+            replica.bypass_change_detection().refresh();
+        }
+        if !replica.is_changed() {
+            continue;
+        }
+
+        if let Some(error) = replica.error() {
+            panel.fail_to_read(error);
+            continue;
+        }
+        // (nothing has been mirrored until the first poll is answered)
+        let Some(proxy) = replica.local(entity) else {
+            continue;
+        };
+        let Ok((components, unreflected)) = q_proxies.get(proxy) else {
+            continue;
+        };
+
+        let type_registry = type_registry.read();
+        let unreflected = unreflected.map(|unreflected| unreflected.0.clone()).unwrap_or_default();
+        let mut components: Vec<InspectedComponent> = components.0
+            .iter()
+            .map(|(type_path, json)| {
+                InspectedComponent::from_json(type_path.clone(), json.clone(), &type_registry)
+            })
+            .chain(unreflected.into_iter().map(InspectedComponent::unreflected))
+            .collect();
+        components.sort_by(|a, b| a.type_path.cmp(&b.type_path));
+        panel.show(components);
+    }
+}
+
+// the game has carried out an edit (or not), so show what it's like now
+fn receive_remote_edits(
+    mut edits: EventReader<RemoteEdited>,
+    mut q_panels: Query<&mut InspectorPanel>
+) {
+    for edited in edits.read() {
+        let Some(Ok(mut panel)) = edited.origin.map(|origin| q_panels.get_mut(origin)) else {
+            continue;
+        };
+
+        let panel = panel.bypass_change_detection();
+        match &edited.error {
+            None => panel.refresh(),
+            Some(error) => panel.fail(error),
+        }
+    }
+}
+
+fn follow_selection(
+    mut changes: EventReader<SelectionChanged>,
+    mut q_panels: Query<&mut InspectorPanel>
+) {
+    let Some(change) = changes.read().last() else {
+        return;
+    };
+
+    let primary = change.selected.last();
+    for mut panel in &mut q_panels {
+        if panel.follow_selection && panel.target.as_ref() != primary {
+            panel.target = primary.cloned();
+        }
+    }
+}
+
+// show what undo and redo did right away
+fn refresh_on_history(
+    mut applied: EventReader<HistoryApplied>,
+    mut q_panels: Query<&mut InspectorPanel>
+) {
+    if applied.read().last().is_none() {
+        return;
+    }
+
+    for mut panel in &mut q_panels {
+        panel.bypass_change_detection().refresh();
+    }
+}
+
+// read the components of local targets that are due for a refresh
+fn refresh_local_inspectors(world: &mut World) {
+    let delta = world.resource::<Time<Real>>().delta();
+
+    let mut due = vec![];
+    let mut q_panels = world.query::<(Entity, &mut InspectorPanel)>();
+    for (panel_entity, mut panel) in q_panels.iter_mut(world) {
+        let panel = panel.bypass_change_detection();
+        let Some(EditorEntity::Local(entity)) = panel.target else {
+            continue;
+        };
+        if panel.timer.tick(delta).finished() {
+            panel.timer = Timer::new(panel.interval, TimerMode::Once);
+            due.push((panel_entity, entity));
+        }
+    }
+    if due.is_empty() {
+        return;
+    }
+
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+    for (panel_entity, entity) in due {
+        let components = local_components(world, entity, &type_registry);
+        let Some(mut panel) = world.get_mut::<InspectorPanel>(panel_entity) else {
+            continue;
+        };
+
+        let panel = panel.bypass_change_detection();
+        match components {
+            Ok(components) => panel.show(components),
+            Err(error) => panel.fail_to_read(error),
+        }
+    }
+}
+
+fn local_components(
+    world: &World,
+    entity: Entity,
+    type_registry: &TypeRegistry
+) -> AnyhowResult<Vec<InspectedComponent>> {
+    let entity_ref = world
+        .get_entity(entity)
+        .ok_or_else(|| anyhow!("Entity {:?} not found", entity))?;
+
+    let mut components = vec![];
+    for component_id in entity_ref.archetype().components() {
+        let info = world.components().get_info(component_id);
+        let Some(type_id) = info.and_then(|info| info.type_id()) else {
+            continue;
+        };
+        let Some(registration) = type_registry.get(type_id) else {
+            continue;
+        };
+        let Some(reflect_component) = registration.data::<ReflectComponent>() else {
+            continue;
+        };
+        let Some(value) = reflect_component.reflect(entity_ref) else {
+            continue;
+        };
+
+        let type_path = registration.type_info().type_path();
+        components.push(InspectedComponent {
+            type_path: type_path.to_string(),
+            json: None,
+            rows: form_rows(&get_short_name(type_path), value),
+        });
+    }
+    components.sort_by(|a, b| a.type_path.cmp(&b.type_path));
+
+    Ok(components)
+}
+
+fn edit_checkboxes(
+    q_checkboxes: Query<(&Checkbox, &InspectorField), Changed<Checkbox>>,
+    q_panels: Query<&InspectorPanel>,
+    mut commands: Commands
+) {
+    for (checkbox, field) in &q_checkboxes {
+        let Ok(panel) = q_panels.get(field.panel) else {
+            continue;
+        };
+
+        // (a checkbox that was just spawned counts as changed too)
+        let row = panel.row(&field.component, &field.path);
+        if row.is_some_and(|row| row.kind == FormRowKind::Bool(checkbox.checked)) {
+            continue;
+        }
+
+        edit_field(&mut commands, field, FieldEdit::Bool(checkbox.checked));
+    }
+}
+
+fn edit_dropdowns(
+    q_dropdowns: Query<(&Dropdown, &InspectorField), Changed<Dropdown>>,
+    q_panels: Query<&InspectorPanel>,
+    mut commands: Commands
+) {
+    for (dropdown, field) in &q_dropdowns {
+        let Ok(panel) = q_panels.get(field.panel) else {
+            continue;
+        };
+        let Some(FormRowKind::Variant { variants, selected }) = panel
+            .row(&field.component, &field.path)
+            .map(|row| &row.kind) else {
+            continue;
+        };
+        let Some(value) = dropdown.value() else {
+            continue;
+        };
+        if value == *selected {
+            continue;
+        }
+        let Some(variant) = variants.get(value) else {
+            continue;
+        };
+
+        edit_field(&mut commands, field, FieldEdit::Variant(variant.clone()));
+    }
+}
+
+fn edit_text_inputs(
+    mut submissions: EventReader<TextInputSubmitEvent>,
+    mut q_inputs: Query<(&InspectorField, &mut TextInputInactive)>,
+    mut commands: Commands
+) {
+    for submission in submissions.read() {
+        let Ok((field, mut inactive)) = q_inputs.get_mut(submission.entity) else {
+            continue;
+        };
+
+        // done editing, so the panel is free to rebuild
+        inactive.0 = true;
+        edit_field(&mut commands, field, FieldEdit::Text(submission.value.clone()));
+    }
+}
+
+fn edit_with_buttons(
+    q_buttons: Query<(&Interaction, &InspectorFieldButton), Changed<Interaction>>,
+    mut commands: Commands
+) {
+    for (interaction, button) in &q_buttons {
+        if *interaction == Interaction::Pressed {
+            edit_field(&mut commands, &button.field, button.edit.clone());
+        }
+    }
+}
+
+fn edit_field(commands: &mut Commands, field: &InspectorField, edit: FieldEdit) {
+    let field = field.clone();
+    commands.add(move |world: &mut World| {
+        let panel = world.get::<InspectorPanel>(field.panel);
+        let Some(target) = panel.and_then(|panel| panel.target.clone()) else {
+            return;
+        };
+
+        let result = match target {
+            EditorEntity::Local(entity) => edit_local_component(world, entity, &field, &edit),
+            EditorEntity::Remote { connection, entity } => {
+                edit_remote_component(world, &connection, entity, &field, &edit)
+            }
+        };
+
+        let Some(mut panel) = world.get_mut::<InspectorPanel>(field.panel) else {
+            return;
+        };
+        let panel = panel.bypass_change_detection();
+        match result {
+            Ok(()) => {
+                panel.error = None;
+                panel.dirty = true;
+                panel.refresh();
+            }
+            Err(error) => {
+                warn!("Could not edit {}{}: {}", field.component, field.path, error);
+                panel.fail(error);
+            }
+        }
+    });
+}
+
+fn edit_local_component(
+    world: &mut World,
+    entity: Entity,
+    field: &InspectorField,
+    edit: &FieldEdit
+) -> AnyhowResult<()> {
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+
+    let reflect_component = get_reflect_component(&type_registry, &field.component)?;
+    let mut entity_mut = world
+        .get_entity_mut(entity)
+        .ok_or_else(|| anyhow!("Entity {:?} not found", entity))?;
+    let mut component = reflect_component
+        .reflect_mut(&mut entity_mut)
+        .ok_or_else(|| anyhow!("Entity {:?} has no component `{}`", entity, field.component))?;
+
+    let before = component.clone_value();
+    apply_field_edit(&mut *component, &field.path, edit, &type_registry)?;
+    let after = component.clone_value();
+
+    // (changing the same field again right away, like while trying out values, is one step)
+    let label = format!("Edit {}{}", get_short_name(&field.component), field.path);
+    let merge_key = format!("{:?}{}{}", entity, field.component, field.path);
+    history::record(
+        world,
+        HistoryStep::new(label, vec![HistoryOperation::Change { entity, before, after }]).merging(
+            merge_key
+        )
+    );
+    Ok(())
+}
+
+// edit the last value that came over BRP, and send the whole component back
+fn edit_remote_component(
+    world: &mut World,
+    connection: &str,
+    entity: Entity,
+    field: &InspectorField,
+    edit: &FieldEdit
+) -> AnyhowResult<()> {
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+
+    let registration = type_registry
+        .get_with_type_path(&field.component)
+        .ok_or_else(|| anyhow!("Component `{}` isn't registered in the editor", field.component))?;
+
+    let json = {
+        let Some(mut panel) = world.get_mut::<InspectorPanel>(field.panel) else {
+            return Ok(());
+        };
+        let inspected = panel.components
+            .iter_mut()
+            .find(|inspected| inspected.type_path == field.component)
+            .ok_or_else(|| anyhow!("Component `{}` is gone", field.component))?;
+        let json = inspected.json
+            .as_ref()
+            .ok_or_else(|| anyhow!("Component `{}` hasn't arrived yet", field.component))?;
+
+        let mut value = reflect_from_json(registration, json, &type_registry)?;
+        apply_field_edit(&mut *value, &field.path, edit, &type_registry)?;
+        let json = reflect_to_json(&*value, &type_registry)?;
+
+        // show the edit right away rather than after the next refresh
+        inspected.rows = form_rows(&get_short_name(&field.component), &*value);
+        inspected.json = Some(json.clone());
+        json
+    };
+
+    // (the game is asked what the component was first, so the edit can be undone)
+    let label = format!("Edit {}{}", get_short_name(&field.component), field.path);
+    let merge_key = format!("{}{:?}{}{}", connection, entity, field.component, field.path);
+    let edit = RemoteEdit::Insert {
+        entity,
+        components: [(field.component.clone(), json)].into_iter().collect(),
+    };
+    history::remote::edit(
+        world,
+        connection,
+        Some(field.panel),
+        edit,
+        HistoryStep::new(label, vec![]).merging(merge_key)
+    );
+    Ok(())
+}
+
+fn copy_inspected_components(
+    q_buttons: Query<(&Interaction, &InspectorCopy), Changed<Interaction>>,
+    mut commands: Commands
+) {
+    for (interaction, copy) in &q_buttons {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let copy = copy.clone();
+        commands.add(move |world: &mut World| {
+            match inspected_component(world, copy.panel, &copy.component) {
+                Ok(json) => {
+                    let content = ClipboardContent {
+                        components: [(copy.component, json)].into_iter().collect(),
+                        ..default()
+                    };
+                    clipboard::put(world, content);
+                }
+                Err(error) => warn!("Could not copy {}: {}", copy.component, error),
+            }
+        });
+    }
+}
+
+// a component of the target, serialized the way BRP sends it
+fn inspected_component(world: &World, panel: Entity, component: &str) -> AnyhowResult<Value> {
+    let panel = world
+        .get::<InspectorPanel>(panel)
+        .ok_or_else(|| anyhow!("The inspector is gone"))?;
+
+    match &panel.target {
+        Some(EditorEntity::Local(entity)) => {
+            let type_registry = world.resource::<AppTypeRegistry>().read();
+            let reflect_component = get_reflect_component(&type_registry, component)?;
+            let entity_ref = world
+                .get_entity(*entity)
+                .ok_or_else(|| anyhow!("Entity {:?} not found", entity))?;
+            let value = reflect_component
+                .reflect(entity_ref)
+                .ok_or_else(|| anyhow!("Entity {:?} has no component `{}`", entity, component))?;
+            reflect_to_json(value, &type_registry)
+        }
+        Some(EditorEntity::Remote { .. }) => {
+            panel.components
+                .iter()
+                .find(|inspected| inspected.type_path == component)
+                .and_then(|inspected| inspected.json.clone())
+                .ok_or_else(|| anyhow!("Component `{}` hasn't arrived yet", component))
+        }
+        None => Err(anyhow!("Nothing is inspected")),
+    }
+}
+
+// rebuild the forms of panels whose components changed
+fn update_inspector_panels(
+    mut q_panels: Query<(Entity, &mut InspectorPanel)>,
+    q_inputs: Query<(&InspectorField, &TextInputInactive)>,
+    l10n: Res<Localization>,
+    mut commands: Commands
+) {
+    for (panel_entity, mut panel) in &mut q_panels {
+        if !panel.dirty {
+            continue;
+        }
+
+        // rebuilding would throw away whatever is being typed
+        let editing = q_inputs
+            .iter()
+            .any(|(field, inactive)| field.panel == panel_entity && !inactive.0);
+        if editing {
+            continue;
+        }
+
+        let panel = panel.bypass_change_detection();
+        panel.dirty = false;
+
+        commands.entity(panel_entity).despawn_descendants();
+        commands.ui_builder(panel_entity).scroll_view(None, |scroll_view| {
+            if let Some(error) = &panel.error {
+                scroll_view.label(LabelConfig {
+                    label: error.clone(),
+                    ..default()
+                });
+            }
+
+            if panel.target.is_none() {
+                scroll_view.label(LabelConfig {
+                    label: l10n.lbl("NothingSelected"),
+                    ..default()
+                });
+                return;
+            }
+
+            // (a component the game can't serialize can't be copied either)
+            let local = matches!(panel.target, Some(EditorEntity::Local(_)));
+            for inspected in &panel.components {
+                let copyable = local || inspected.json.is_some();
+                for (index, row) in inspected.rows.iter().enumerate() {
+                    let copy = copyable && index == 0;
+                    let component = &inspected.type_path;
+                    spawn_form_row(scroll_view, panel_entity, component, row, copy, &l10n);
+                }
+            }
+        });
+    }
+}
+
+fn spawn_form_row(
+    builder: &mut UiBuilder<Entity>,
+    panel: Entity,
+    component: &str,
+    row: &FormRow,
+    copy: bool,
+    l10n: &Localization
+) {
+    let field = InspectorField {
+        panel,
+        component: component.to_string(),
+        path: row.path.clone(),
+    };
+
+    // components are spaced apart, and fields are indented under their parents
+    let top = if row.depth == 0 { 8.0 } else { 0.0 };
+    builder
+        .row(|line| {
+            line.label(LabelConfig {
+                label: row.label.clone(),
+                ..default()
+            })
+                .style()
+                .min_width(Val::Px(100.0));
+
+            match &row.kind {
+                FormRowKind::Heading => {}
+                FormRowKind::List => {
+                    let add = InspectorFieldButton {
+                        field: field.clone(),
+                        edit: FieldEdit::Push,
+                    };
+                    spawn_button(line, l10n.lbl("AddItem"), add);
+                }
+                FormRowKind::Bool(checked) => {
+                    line.checkbox(String::new(), *checked).insert(field.clone());
+                }
+                FormRowKind::Number(text) | FormRowKind::Text(text) => {
+                    line.spawn((text_input(text), field.clone()));
+                }
+                FormRowKind::Variant { variants, selected } => {
+                    line.dropdown(variants.clone(), *selected)
+                        .insert(field.clone())
+                        .style()
+                        .min_width(Val::Px(120.0));
+                }
+                FormRowKind::Color(color) => {
+                    line.spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(48.0),
+                            height: Val::Px(16.0),
+                            margin: UiRect::right(Val::Px(8.0)),
+                            ..default()
+                        },
+                        background_color: (*color).into(),
+                        ..default()
+                    });
+                    line.spawn((text_input(&color_hex(*color)), field.clone()));
+                }
+                FormRowKind::ReadOnly(text) => {
+                    line.label(LabelConfig {
+                        label: text.clone(),
+                        ..default()
+                    });
+                }
+            }
+
+            if row.removable {
+                let remove = InspectorFieldButton {
+                    field: field.clone(),
+                    edit: FieldEdit::Remove,
+                };
+                spawn_button(line, l10n.lbl("RemoveItem"), remove);
+            }
+
+            if copy {
+                let copy = InspectorCopy {
+                    panel,
+                    component: component.to_string(),
+                };
+                spawn_button(line, l10n.lbl("Copy"), copy);
+            }
+        })
+        .style()
+        .padding(UiRect::left(Val::Px(12.0 * (row.depth as f32))))
+        .margin(UiRect::top(Val::Px(top)));
+}
+
+// a small bordered button at the end of a row
+fn spawn_button(line: &mut UiBuilder<Entity>, label: String, bundle: impl Bundle) {
+    line.container(
+        (
+            NodeBundle {
+                style: Style {
+                    margin: UiRect::left(Val::Px(8.0)),
+                    padding: UiRect::axes(Val::Px(6.0), Val::Px(1.0)),
+                    border: UiRect::all(Val::Px(1.0)),
+                    ..default()
+                },
+                border_color: Color::srgb(0.4, 0.4, 0.4).into(),
+                ..default()
+            },
+            Interaction::None,
+            bundle,
+        ),
+        |button| {
+            button.label(LabelConfig {
+                label,
+                ..default()
+            });
+        }
+    );
+}
+
+pub trait UiInspectorExt {
+    fn inspector(&mut self) -> UiBuilder<Entity>;
+}
+
+impl UiInspectorExt for UiBuilder<'_, Entity> {
+    fn inspector(&mut self) -> UiBuilder<Entity> {
+        let panel = self
+            .insert((Name::new("Inspector"), Focusable, InspectorPanel::default()))
+            .style()
+            .padding(UiRect::all(Val::Px(10.0)))
+            .id();
+
+        self.commands().ui_builder(panel)
+    }
+}
@@ -0,0 +1,602 @@
+//! Turns a reflected component into the rows of an inspector form, and applies edits made in them.
+//!
+//! Nothing here knows about widgets. A form is a flat list of [`FormRow`]s, indented by `depth`,
+//! and each row that can be edited carries the reflection path of its field (like `.translation.x`,
+//! or `[2]` for an item of a list). An edit is a [`FieldEdit`] applied at that path.
+//!
+//! Reflection paths can't reach into maps, so the value of the entry at some index of a map is
+//! written `{index}` (as in `.scores{0}`), in the order the map iterates its entries.
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::{
+    prelude::*,
+    reflect::{
+        serde::{ TypedReflectDeserializer, TypedReflectSerializer },
+        DynamicEnum,
+        DynamicStruct,
+        DynamicTuple,
+        DynamicVariant,
+        GetPath,
+        ReflectMut,
+        ReflectRef,
+        ReflectFromReflect,
+        TypeInfo,
+        TypePathTable,
+        TypeRegistration,
+        TypeRegistry,
+        VariantInfo,
+    },
+};
+use serde::de::DeserializeSeed as _;
+use serde_json::Value;
+
+/// One line of an inspector form.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormRow {
+    /// The reflection path of the field, relative to the component.
+    pub path: String,
+
+    /// The name of the field, or its index.
+    pub label: String,
+
+    /// How far the row is nested in structs, lists and enums.
+    pub depth: usize,
+
+    pub kind: FormRowKind,
+
+    /// Whether the row is an item of a list or an entry of a map, which can be removed.
+    pub removable: bool,
+}
+
+impl FormRow {
+    pub fn new(path: String, label: String, depth: usize, kind: FormRowKind) -> Self {
+        Self { path, label, depth, kind, removable: false }
+    }
+}
+
+/// What a [`FormRow`] shows, and how it can be edited.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FormRowKind {
+    /// A struct, tuple, array or map, whose fields follow one level deeper.
+    Heading,
+
+    /// A list, whose items follow one level deeper and can be added to.
+    List,
+
+    /// A checkbox.
+    Bool(bool),
+
+    /// A number, edited as text.
+    Number(String),
+
+    /// A string.
+    Text(String),
+
+    /// The variant of an enum (including `Option`), whose fields follow one level deeper.
+    Variant { variants: Vec<String>, selected: usize },
+
+    /// A swatch of a color and its hex code, under the row for its color space.
+    ///
+    /// The hex code is edited as text, and the color stays in its space.
+    Color(Color),
+
+    /// Anything else, shown as it would be debug printed.
+    ReadOnly(String),
+}
+
+/// A change made to one row of a form.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldEdit {
+    Bool(bool),
+
+    /// New text for a number or a string, parsed according to the type of the field.
+    Text(String),
+
+    /// Switch an enum to another variant, with default values for its fields.
+    Variant(String),
+
+    /// Add an item with its default value to the end of a list.
+    Push,
+
+    /// Remove the item of a list, or the entry of a map, at the path.
+    Remove,
+}
+
+/// Lists the rows of the form for a reflected value, starting with a row for the value itself.
+pub fn form_rows(label: &str, value: &dyn Reflect) -> Vec<FormRow> {
+    let mut rows = vec![];
+    push_rows(value, String::new(), label.to_string(), 0, &mut rows);
+    rows
+}
+
+// the rows of one value, and of its fields after it
+fn push_rows(
+    value: &dyn Reflect,
+    path: String,
+    label: String,
+    depth: usize,
+    rows: &mut Vec<FormRow>
+) {
+    // a name is a struct with a hash in it, so it's edited as a whole
+    if let Some(name) = value.downcast_ref::<Name>() {
+        rows.push(FormRow::new(path, label, depth, FormRowKind::Text(name.to_string())));
+        return;
+    }
+    let color = value.downcast_ref::<Color>().copied();
+
+    match value.reflect_ref() {
+        ReflectRef::Struct(value) => {
+            rows.push(FormRow::new(path.clone(), label, depth, FormRowKind::Heading));
+            for index in 0..value.field_len() {
+                let (Some(name), Some(field)) = (value.name_at(index), value.field_at(index)) else {
+                    continue;
+                };
+                push_rows(field, format!("{}.{}", path, name), name.to_string(), depth + 1, rows);
+            }
+        }
+        ReflectRef::TupleStruct(value) => {
+            rows.push(FormRow::new(path.clone(), label, depth, FormRowKind::Heading));
+            for (index, field) in value.iter_fields().enumerate() {
+                push_rows(field, format!("{}.{}", path, index), index.to_string(), depth + 1, rows);
+            }
+        }
+        ReflectRef::Tuple(value) => {
+            rows.push(FormRow::new(path.clone(), label, depth, FormRowKind::Heading));
+            for (index, field) in value.iter_fields().enumerate() {
+                push_rows(field, format!("{}.{}", path, index), index.to_string(), depth + 1, rows);
+            }
+        }
+        ReflectRef::List(value) => {
+            rows.push(FormRow::new(path.clone(), label, depth, FormRowKind::List));
+            for (index, item) in value.iter().enumerate() {
+                let first = rows.len();
+                push_rows(item, format!("{}[{}]", path, index), index.to_string(), depth + 1, rows);
+                rows[first].removable = true;
+            }
+        }
+        ReflectRef::Array(value) => {
+            rows.push(FormRow::new(path.clone(), label, depth, FormRowKind::Heading));
+            for (index, item) in value.iter().enumerate() {
+                push_rows(item, format!("{}[{}]", path, index), index.to_string(), depth + 1, rows);
+            }
+        }
+        ReflectRef::Map(value) => {
+            // (the keys are only shown, as the labels of their values)
+            rows.push(FormRow::new(path.clone(), label, depth, FormRowKind::Heading));
+            for (index, (key, item)) in value.iter().enumerate() {
+                let first = rows.len();
+                let item_path = format!("{}{{{}}}", path, index);
+                push_rows(item, item_path, format!("{:?}", key), depth + 1, rows);
+                rows[first].removable = true;
+            }
+        }
+        ReflectRef::Enum(value) => {
+            let variants: Vec<String> = match value.get_represented_type_info() {
+                Some(TypeInfo::Enum(info)) => {
+                    info.variant_names()
+                        .iter()
+                        .map(|name| name.to_string())
+                        .collect()
+                }
+                _ => vec![value.variant_name().to_string()],
+            };
+            let selected = variants
+                .iter()
+                .position(|name| name == value.variant_name())
+                .unwrap_or_default();
+            let kind = FormRowKind::Variant { variants, selected };
+            rows.push(FormRow::new(path.clone(), label, depth, kind));
+            if let Some(color) = color {
+                let kind = FormRowKind::Color(color);
+                rows.push(FormRow::new(path.clone(), String::new(), depth + 1, kind));
+            }
+
+            for (index, field) in value.iter_fields().enumerate() {
+                let name = match field.name() {
+                    Some(name) => name.to_string(),
+                    None => index.to_string(),
+                };
+                let field_path = format!("{}.{}", path, name);
+                push_rows(field.value(), field_path, name, depth + 1, rows);
+            }
+        }
+        ReflectRef::Value(value) => {
+            rows.push(FormRow::new(path, label, depth, value_row_kind(value)));
+        }
+    }
+}
+
+fn value_row_kind(value: &dyn Reflect) -> FormRowKind {
+    if let Some(value) = value.downcast_ref::<bool>() {
+        return FormRowKind::Bool(*value);
+    }
+    if let Some(text) = number_text(value) {
+        return FormRowKind::Number(text);
+    }
+    if let Some(value) = value.downcast_ref::<String>() {
+        return FormRowKind::Text(value.clone());
+    }
+    if let Some(value) = value.downcast_ref::<std::borrow::Cow<'static, str>>() {
+        return FormRowKind::Text(value.to_string());
+    }
+
+    FormRowKind::ReadOnly(format!("{:?}", value))
+}
+
+// the numbers are all edited the same way, so the types are only listed twice
+macro_rules! number_types {
+    ($mac:ident, $value:expr, $($arg:expr),*) => {
+        $mac!(
+            $value, $($arg),*;
+            f32, f64, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize
+        )
+    };
+}
+
+macro_rules! format_number {
+    ($value:expr, ; $($ty:ty),*) => {
+        $(
+            if let Some(value) = $value.downcast_ref::<$ty>() {
+                return Some(value.to_string());
+            }
+        )*
+    };
+}
+
+macro_rules! parse_number {
+    ($value:expr, $text:expr; $($ty:ty),*) => {
+        $(
+            if let Some(value) = $value.downcast_mut::<$ty>() {
+                *value = $text.trim().parse().map_err(|error| anyhow!("`{}`: {}", $text, error))?;
+                return Ok(());
+            }
+        )*
+    };
+}
+
+fn number_text(value: &dyn Reflect) -> Option<String> {
+    number_types!(format_number, value,);
+    None
+}
+
+/// Applies an edit to the field at `path` of a reflected value.
+///
+/// The registry is needed to make up the fields of an enum variant that is switched to, and the
+/// items added to lists; they get their default values, so their types need `#[reflect(Default)]`.
+pub fn apply_field_edit(
+    value: &mut dyn Reflect,
+    path: &str,
+    edit: &FieldEdit,
+    type_registry: &TypeRegistry
+) -> AnyhowResult<()> {
+    match edit {
+        FieldEdit::Bool(checked) => {
+            let Some(field) = field_at(value, path)?.downcast_mut::<bool>() else {
+                return Err(anyhow!("`{}` isn't a bool", path));
+            };
+            *field = *checked;
+            Ok(())
+        }
+        FieldEdit::Text(text) => set_text(field_at(value, path)?, text),
+        FieldEdit::Variant(variant) => {
+            set_variant(field_at(value, path)?, variant, type_registry)
+        }
+        FieldEdit::Push => push_item(field_at(value, path)?, type_registry),
+        FieldEdit::Remove => remove_item(value, path),
+    }
+}
+
+// the field at a path, which can go through the entries of maps
+fn field_at<'a>(value: &'a mut dyn Reflect, path: &str) -> AnyhowResult<&'a mut dyn Reflect> {
+    let (path, entry) = match path.split_once('{') {
+        Some((path, entry)) => (path, Some(entry)),
+        None => (path, None),
+    };
+    let field = match path {
+        "" => value,
+        _ => value.reflect_path_mut(path).map_err(|error| anyhow!("`{}`: {}", path, error))?,
+    };
+    let Some(entry) = entry else {
+        return Ok(field);
+    };
+
+    let (index, rest) = map_entry_index(entry)?;
+    let ReflectMut::Map(map) = field.reflect_mut() else {
+        return Err(anyhow!("`{}` isn't a map", path));
+    };
+    let (_, item) = map
+        .get_at_mut(index)
+        .ok_or_else(|| anyhow!("`{}` has no entry {}", path, index))?;
+    field_at(item, rest)
+}
+
+// the index at the start of what follows a `{`, and the rest of the path after the `}`
+fn map_entry_index(entry: &str) -> AnyhowResult<(usize, &str)> {
+    let (index, rest) = entry
+        .split_once('}')
+        .ok_or_else(|| anyhow!("`{{{}`: missing `}}`", entry))?;
+    let index = index.parse().map_err(|error| anyhow!("`{{{}}}`: {}", index, error))?;
+    Ok((index, rest))
+}
+
+fn push_item(field: &mut dyn Reflect, type_registry: &TypeRegistry) -> AnyhowResult<()> {
+    let Some(TypeInfo::List(info)) = field.get_represented_type_info() else {
+        return Err(anyhow!("`{}` isn't a list", field.reflect_type_path()));
+    };
+    let item = default_of(type_registry, info.item_type_id(), info.item_type_path_table())?;
+    let ReflectMut::List(list) = field.reflect_mut() else {
+        return Err(anyhow!("`{}` isn't a list", field.reflect_type_path()));
+    };
+
+    list.push(item);
+    Ok(())
+}
+
+// remove the list item or map entry that the path ends with
+fn remove_item(value: &mut dyn Reflect, path: &str) -> AnyhowResult<()> {
+    if let Some(entry_path) = path.strip_suffix('}') {
+        let (parent, index) = entry_path
+            .rsplit_once('{')
+            .ok_or_else(|| anyhow!("`{}` isn't an entry of a map", path))?;
+        let index: usize = index.parse().map_err(|error| anyhow!("`{}`: {}", path, error))?;
+
+        let ReflectMut::Map(map) = field_at(value, parent)?.reflect_mut() else {
+            return Err(anyhow!("`{}` isn't a map", parent));
+        };
+        let key = map
+            .get_at(index)
+            .map(|(key, _)| key.clone_value())
+            .ok_or_else(|| anyhow!("`{}` has no entry {}", parent, index))?;
+        map.remove(&*key);
+        return Ok(());
+    }
+
+    let (parent, index) = path
+        .strip_suffix(']')
+        .and_then(|item_path| item_path.rsplit_once('['))
+        .ok_or_else(|| anyhow!("`{}` isn't an item of a list", path))?;
+    let index: usize = index.parse().map_err(|error| anyhow!("`{}`: {}", path, error))?;
+
+    let ReflectMut::List(list) = field_at(value, parent)?.reflect_mut() else {
+        return Err(anyhow!("`{}` isn't a list", parent));
+    };
+    if index >= list.len() {
+        return Err(anyhow!("`{}` has no item {}", parent, index));
+    }
+    list.remove(index);
+    Ok(())
+}
+
+// a value to start a field with, for types registered with `#[reflect(Default)]`
+fn default_of(
+    type_registry: &TypeRegistry,
+    type_id: std::any::TypeId,
+    type_path: &TypePathTable
+) -> AnyhowResult<Box<dyn Reflect>> {
+    type_registry
+        .get_type_data::<ReflectDefault>(type_id)
+        .map(|default| default.default())
+        .ok_or_else(|| anyhow!("`{}` has no default value to start from", type_path.path()))
+}
+
+fn set_text(field: &mut dyn Reflect, text: &str) -> AnyhowResult<()> {
+    if let Some(name) = field.downcast_mut::<Name>() {
+        name.set(text.to_string());
+        return Ok(());
+    }
+    if let Some(color) = field.downcast_mut::<Color>() {
+        let srgba = Srgba::hex(text.trim()).map_err(|error| anyhow!("`{}`: {}", text, error))?;
+        *color = in_space_of(*color, srgba);
+        return Ok(());
+    }
+    if let Some(field) = field.downcast_mut::<String>() {
+        *field = text.to_string();
+        return Ok(());
+    }
+    if let Some(field) = field.downcast_mut::<std::borrow::Cow<'static, str>>() {
+        *field = text.to_string().into();
+        return Ok(());
+    }
+    number_types!(parse_number, field, text);
+
+    Err(anyhow!("Can't edit a `{}` as text", field.reflect_type_path()))
+}
+
+fn set_variant(
+    field: &mut dyn Reflect,
+    variant: &str,
+    type_registry: &TypeRegistry
+) -> AnyhowResult<()> {
+    let Some(TypeInfo::Enum(info)) = field.get_represented_type_info() else {
+        return Err(anyhow!("`{}` isn't an enum", field.reflect_type_path()));
+    };
+    if let ReflectMut::Enum(value) = field.reflect_mut() {
+        if value.variant_name() == variant {
+            return Ok(());
+        }
+    }
+
+    let default_of = |type_id, type_path| default_of(type_registry, type_id, type_path);
+
+    let dynamic_variant = match info.variant(variant) {
+        Some(VariantInfo::Unit(_)) => DynamicVariant::Unit,
+        Some(VariantInfo::Tuple(variant_info)) => {
+            let mut tuple = DynamicTuple::default();
+            for field_info in variant_info.iter() {
+                tuple.insert_boxed(default_of(field_info.type_id(), field_info.type_path_table())?);
+            }
+            DynamicVariant::Tuple(tuple)
+        }
+        Some(VariantInfo::Struct(variant_info)) => {
+            let mut fields = DynamicStruct::default();
+            for field_info in variant_info.iter() {
+                fields.insert_boxed(
+                    field_info.name(),
+                    default_of(field_info.type_id(), field_info.type_path_table())?
+                );
+            }
+            DynamicVariant::Struct(fields)
+        }
+        None => {
+            return Err(anyhow!("`{}` has no variant `{}`", info.type_path(), variant));
+        }
+    };
+
+    field
+        .try_apply(&DynamicEnum::new(variant, dynamic_variant))
+        .map_err(|error| anyhow!("Can't switch to `{}`: {}", variant, error))
+}
+
+/// The hex code of a color, as shown in its [`FormRowKind::Color`] row.
+pub fn color_hex(color: Color) -> String {
+    color.to_srgba().to_hex()
+}
+
+// a color in the same space as another one
+fn in_space_of(like: Color, srgba: Srgba) -> Color {
+    match like {
+        Color::Srgba(_) => Color::Srgba(srgba),
+        Color::LinearRgba(_) => Color::LinearRgba(srgba.into()),
+        Color::Hsla(_) => Color::Hsla(srgba.into()),
+        Color::Hsva(_) => Color::Hsva(srgba.into()),
+        Color::Hwba(_) => Color::Hwba(srgba.into()),
+        Color::Laba(_) => Color::Laba(srgba.into()),
+        Color::Lcha(_) => Color::Lcha(srgba.into()),
+        Color::Oklaba(_) => Color::Oklaba(srgba.into()),
+        Color::Oklcha(_) => Color::Oklcha(srgba.into()),
+        Color::Xyza(_) => Color::Xyza(srgba.into()),
+    }
+}
+
+/// Deserializes a component the way BRP sends it, as a value of its concrete type if possible so
+/// that it can be edited like a local one.
+pub fn reflect_from_json(
+    registration: &TypeRegistration,
+    value: &Value,
+    type_registry: &TypeRegistry
+) -> AnyhowResult<Box<dyn Reflect>> {
+    let reflected = TypedReflectDeserializer::new(registration, type_registry).deserialize(value)?;
+
+    // (what comes out of the deserializer is made of dynamic structs, enums and lists)
+    Ok(
+        registration
+            .data::<ReflectFromReflect>()
+            .and_then(|from_reflect| from_reflect.from_reflect(&*reflected))
+            .unwrap_or(reflected)
+    )
+}
+
+/// Serializes a component the way BRP sends it.
+pub fn reflect_to_json(value: &dyn Reflect, type_registry: &TypeRegistry) -> AnyhowResult<Value> {
+    Ok(serde_json::to_value(TypedReflectSerializer::new(value, type_registry))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::HashMap;
+
+    use super::*;
+
+    #[derive(Reflect, Default, Debug, PartialEq)]
+    #[reflect(Default)]
+    struct Item {
+        count: u32,
+        label: String,
+    }
+
+    #[derive(Reflect, Default, Debug, PartialEq)]
+    enum Shape {
+        #[default]
+        Point,
+        Circle(f32),
+    }
+
+    #[derive(Reflect, Default, Debug)]
+    struct Level {
+        visible: bool,
+        shape: Shape,
+        color: Color,
+        items: Vec<Item>,
+        scores: HashMap<String, Item>,
+    }
+
+    fn registry() -> TypeRegistry {
+        let mut type_registry = TypeRegistry::new();
+        type_registry.register::<Level>();
+        type_registry.register::<Item>();
+        type_registry.register::<f32>();
+        type_registry.register_type_data::<f32, ReflectDefault>();
+        type_registry
+    }
+
+    fn edit(level: &mut Level, path: &str, edit: FieldEdit) -> AnyhowResult<()> {
+        apply_field_edit(level, path, &edit, &registry())
+    }
+
+    #[test]
+    fn fields_are_edited_at_their_paths() {
+        let mut level = Level::default();
+        edit(&mut level, ".visible", FieldEdit::Bool(true)).unwrap();
+        edit(&mut level, ".shape", FieldEdit::Variant("Circle".to_string())).unwrap();
+        edit(&mut level, ".shape.0", FieldEdit::Text(" 2.5".to_string())).unwrap();
+
+        assert!(level.visible);
+        assert_eq!(level.shape, Shape::Circle(2.5));
+    }
+
+    #[test]
+    fn bad_edits_leave_the_value_alone() {
+        let mut level = Level::default();
+        assert!(edit(&mut level, ".visible", FieldEdit::Text("yes".to_string())).is_err());
+        assert!(edit(&mut level, ".shape", FieldEdit::Variant("Square".to_string())).is_err());
+        assert!(edit(&mut level, ".missing", FieldEdit::Bool(true)).is_err());
+        assert!(edit(&mut level, ".items[0]", FieldEdit::Remove).is_err());
+
+        assert!(!level.visible);
+        assert_eq!(level.shape, Shape::Point);
+    }
+
+    #[test]
+    fn colors_are_edited_by_hex_code_in_their_space() {
+        let mut level = Level {
+            color: Color::LinearRgba(LinearRgba::RED),
+            ..default()
+        };
+        edit(&mut level, ".color", FieldEdit::Text("#00ff00".to_string())).unwrap();
+
+        assert_eq!(level.color, Color::LinearRgba(LinearRgba::GREEN));
+        assert_eq!(color_hex(level.color), "#00FF00");
+        assert!(edit(&mut level, ".color", FieldEdit::Text("green".to_string())).is_err());
+    }
+
+    #[test]
+    fn list_items_are_added_and_removed() {
+        let mut level = Level::default();
+        edit(&mut level, ".items", FieldEdit::Push).unwrap();
+        edit(&mut level, ".items", FieldEdit::Push).unwrap();
+        edit(&mut level, ".items[1].count", FieldEdit::Text("3".to_string())).unwrap();
+        edit(&mut level, ".items[0]", FieldEdit::Remove).unwrap();
+
+        assert_eq!(level.items, [Item { count: 3, ..default() }]);
+        let rows = form_rows("Level", &level);
+        let items = rows.iter().find(|row| row.path == ".items").unwrap();
+        assert_eq!(items.kind, FormRowKind::List);
+        assert!(rows.iter().find(|row| row.path == ".items[0]").unwrap().removable);
+    }
+
+    #[test]
+    fn map_entries_are_edited_and_removed() {
+        let mut level = Level::default();
+        level.scores.insert("first".to_string(), Item::default());
+        let rows = form_rows("Level", &level);
+        let entry = rows.iter().find(|row| row.path == ".scores{0}").unwrap();
+        assert_eq!(entry.label, "\"first\"");
+        assert!(entry.removable);
+
+        edit(&mut level, ".scores{0}.label", FieldEdit::Text("gold".to_string())).unwrap();
+        assert_eq!(level.scores["first"].label, "gold");
+        assert!(edit(&mut level, ".scores{1}", FieldEdit::Remove).is_err());
+        edit(&mut level, ".scores{0}", FieldEdit::Remove).unwrap();
+        assert!(level.scores.is_empty());
+    }
+}
@@ -0,0 +1,56 @@
+//! Asks the user which side to keep when both the editor and the game changed a linked component
+//! (see [`ConflictPolicy::Ask`](crate::remote::link::ConflictPolicy::Ask)).
+
+use bevy::{ prelude::*, utils::get_short_name };
+
+use crate::{
+    project::confirm,
+    remote::link::{ LinkSide, RemoteLink, RemoteLinkConflict, RemoteLinkPlugin },
+};
+
+pub struct LinkConflictPlugin;
+
+impl Plugin for LinkConflictPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<RemoteLinkPlugin>() {
+            app.add_plugins(RemoteLinkPlugin);
+        }
+
+        app.add_systems(Update, ask_about_link_conflicts);
+    }
+}
+
+// (commands are applied on the main thread, which is where the native dialogs have to be shown)
+fn ask_about_link_conflicts(
+    mut conflicts: EventReader<RemoteLinkConflict>,
+    mut commands: Commands
+) {
+    for conflict in conflicts.read() {
+        let conflict = conflict.clone();
+        commands.add(move |world: &mut World| resolve_link_conflict(world, conflict));
+    }
+}
+
+// the game's value is kept unless the user says otherwise, as it is the first time a link pulls
+fn resolve_link_conflict(world: &mut World, conflict: RemoteLinkConflict) {
+    let name = world
+        .get::<Name>(conflict.entity)
+        .map_or_else(|| conflict.entity.to_string(), |name| name.to_string());
+    let text = format!(
+        "Both the editor and the game changed {} of {}.\n\nEditor: {}\nGame: {}\n\nKeep the \
+        editor's value? Otherwise the game's is kept.",
+        get_short_name(&conflict.component),
+        name,
+        conflict.editor,
+        conflict.game
+    );
+    let winner = if confirm("Linked entity changed", &text) {
+        LinkSide::Editor
+    } else {
+        LinkSide::Game
+    };
+
+    if let Some(mut link) = world.get_mut::<RemoteLink>(conflict.entity) {
+        link.resolve(&conflict.component, winner);
+    }
+}
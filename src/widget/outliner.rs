@@ -0,0 +1,1316 @@
+//! The outliner shows the hierarchy of entities in the editor or in the game it is connected to, by
+//! `Name` and `Parent`, and lets the user pick, rename, move, duplicate and delete them.
+//!
+//! Clicking a row selects its entity, and control clicking adds it to (or takes it out of) the
+//! selection, which is held by the [`SelectionService`]. Dragging a row onto another
+//! one makes it a child of that one, and dropping it on the empty space below the rows makes it a
+//! root again. Right clicking a row opens a menu to rename, duplicate or delete it.
+//!
+//! Edits to the game go through [`history::remote::edit`] (`REPARENT`, `INSERT` of a new `Name`,
+//! `DESTROY`, and to duplicate, a `SPAWN` of what [`history::remote::copy`] read), and edits to the
+//! editor go straight to its world. Only the entity itself is duplicated, not its children.
+
+use std::time::Duration;
+
+use anyhow::{ anyhow, Result as AnyhowResult };
+use bevy::{
+    prelude::*,
+    reflect::TypePath,
+    ui::FocusPolicy,
+    utils::{ HashMap, HashSet },
+    window::PrimaryWindow,
+};
+
+use bevy_fluent::Localization;
+use bevy_simple_text_input::{ TextInputInactive, TextInputSubmitEvent, TextInputValue };
+use serde_json::Value;
+use sickle_ui::prelude::*;
+
+use crate::{
+    framework::*,
+    history::{
+        self,
+        remote::{ RemoteCopied, RemoteEdit, RemoteEdited },
+        EntitySnapshot,
+        HistoryApplied,
+        HistoryOperation,
+        HistoryPlugin,
+        HistoryStep,
+    },
+    locale::Translator,
+    remote::{
+        brp_client::BrpClientPlugin,
+        builtin_verbs::{ entity_from_json, BrpQuery },
+        connections::BrpConnections,
+        replica::{
+            spawn_replica,
+            RemoteComponents,
+            RemoteProxy,
+            RemoteReplica,
+            RemoteReplicaPlugin,
+        },
+    },
+    selection::{
+        Focusable,
+        SelectAllRequested,
+        SelectionChanged,
+        SelectionPlugin,
+        SelectionService,
+    },
+    widget::{ inspector::form::reflect_to_json, text_input, TextInputFocusPlugin },
+};
+
+pub struct OutlinerPlugin;
+
+impl Plugin for OutlinerPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<BrpClientPlugin>() {
+            app.add_plugins(BrpClientPlugin);
+        }
+        if !app.is_plugin_added::<TextInputFocusPlugin>() {
+            app.add_plugins(TextInputFocusPlugin);
+        }
+        if !app.is_plugin_added::<SelectionPlugin>() {
+            app.add_plugins(SelectionPlugin);
+        }
+        if !app.is_plugin_added::<HistoryPlugin>() {
+            app.add_plugins(HistoryPlugin);
+        }
+        if !app.is_plugin_added::<RemoteReplicaPlugin>() {
+            app.add_plugins(RemoteReplicaPlugin);
+        }
+
+        app.add_systems(
+            Update,
+            (
+                follow_outliner_sources,
+                follow_selection,
+                refresh_on_history,
+                read_remote_outlines,
+                (receive_remote_duplicates, receive_remote_edits),
+                refresh_local_outlines,
+                (
+                    switch_outliner_sources,
+                    search_outlines,
+                    pick_outliner_rows,
+                    select_all_outlined,
+                    toggle_outliner_rows,
+                    drop_outliner_rows,
+                    open_outliner_menus,
+                    choose_outliner_menu_items,
+                    rename_outliner_entities,
+                ).chain(),
+                update_outliner_panels,
+            )
+                .chain()
+                .after(WidgetLibraryUpdate)
+                .run_if(in_state(EditorState::Running))
+        );
+    }
+}
+
+/// Which world an [`OutlinerPanel`] shows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+pub enum OutlinerSource {
+    /// The editor itself, except for its UI.
+    Editor,
+
+    /// The game on the other end of the primary connection.
+    #[default]
+    Game,
+}
+
+/// Shows the entity hierarchy of its [`source`](Self::source) as a tree.
+#[derive(Component)]
+pub struct OutlinerPanel {
+    pub source: OutlinerSource,
+
+    /// Time between refreshes.
+    pub interval: Duration,
+
+    // where the outline came from
+    origin: Option<OutlineOrigin>,
+
+    outline: Outline,
+
+    // entities whose children are shown
+    expanded: HashSet<Entity>,
+
+    // only entities whose names contain this (or that have such descendants) are shown
+    search: String,
+
+    // the entity being renamed, which shows a text input instead of its name
+    renaming: Option<Entity>,
+
+    // the entity whose row was pressed, until the mouse is released somewhere
+    dragging: Option<Entity>,
+
+    // counts down to the next refresh
+    timer: Timer,
+
+    // the replica mirroring the names and parents of the game's entities
+    replica: Option<Entity>,
+
+    // the replica should poll again right away
+    stale: bool,
+
+    // the container the rows are spawned in
+    tree: Option<Entity>,
+
+    // the rows need to be rebuilt
+    dirty: bool,
+}
+
+impl Default for OutlinerPanel {
+    fn default() -> Self {
+        Self {
+            source: default(),
+            interval: Duration::from_millis(1000),
+            origin: None,
+            outline: default(),
+            expanded: default(),
+            search: String::new(),
+            renaming: None,
+            dragging: None,
+            timer: Timer::new(Duration::ZERO, TimerMode::Once),
+            replica: None,
+            stale: false,
+            tree: None,
+            dirty: true,
+        }
+    }
+}
+
+impl OutlinerPanel {
+    /// Refreshes again right away rather than waiting out the interval.
+    pub fn refresh(&mut self) {
+        self.timer = Timer::new(Duration::ZERO, TimerMode::Once);
+        self.stale = true;
+    }
+
+    /// The world the panel shows, once it has shown one.
+    pub fn world(&self) -> Option<EditorWorld> {
+        match self.origin.as_ref()? {
+            OutlineOrigin::Editor => Some(EditorWorld::Local),
+            OutlineOrigin::Connection(connection) => {
+                Some(EditorWorld::Remote {
+                    connection: connection.clone(),
+                })
+            }
+        }
+    }
+
+    fn show(&mut self, outline: Outline) {
+        if self.outline != outline {
+            self.outline = outline;
+            self.dirty = true;
+        }
+    }
+
+    // deselect the entities of this panel's world that have gone away
+    fn deselect_missing(&self, selection: &mut SelectionService) {
+        let missing = |selected: &EditorEntity| {
+            let ours = match (&self.origin, selected) {
+                (Some(OutlineOrigin::Editor), EditorEntity::Local(_)) => true,
+                (
+                    Some(OutlineOrigin::Connection(origin)),
+                    EditorEntity::Remote { connection, .. },
+                ) => origin == connection,
+                _ => false,
+            };
+            ours && !self.outline.nodes.contains_key(&selected.entity())
+        };
+
+        if selection.selected().iter().any(missing) {
+            selection.retain(|selected| !missing(selected));
+        }
+    }
+
+    fn editor_entity(&self, entity: Entity) -> Option<EditorEntity> {
+        match self.origin.as_ref()? {
+            OutlineOrigin::Editor => Some(EditorEntity::Local(entity)),
+            OutlineOrigin::Connection(connection) => {
+                Some(EditorEntity::Remote {
+                    connection: connection.clone(),
+                    entity,
+                })
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum OutlineOrigin {
+    Editor,
+    Connection(String),
+}
+
+// an edit picked from the context menu, or made by dragging
+#[derive(Clone, Debug)]
+enum OutlinerEdit {
+    Rename(String),
+    Reparent(Option<Entity>),
+    Duplicate,
+    Delete,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Outline {
+    nodes: HashMap<Entity, OutlineNode>,
+    roots: Vec<Entity>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct OutlineNode {
+    label: String,
+    parent: Option<Entity>,
+    children: Vec<Entity>,
+}
+
+// a row of the tree as it is shown
+struct OutlineRow {
+    entity: Entity,
+    depth: usize,
+    has_children: bool,
+    expanded: bool,
+}
+
+impl Outline {
+    // build the tree from each entity's name and parent
+    fn new(entities: impl IntoIterator<Item = (Entity, Option<String>, Option<Entity>)>) -> Self {
+        let mut nodes: HashMap<Entity, OutlineNode> = entities
+            .into_iter()
+            .map(|(entity, name, parent)| {
+                let node = OutlineNode {
+                    label: name.unwrap_or_else(|| format!("Entity {}", entity)),
+                    parent,
+                    children: vec![],
+                };
+                (entity, node)
+            })
+            .collect();
+
+        // (a parent that isn't in the outline makes its children roots)
+        let mut roots = vec![];
+        let mut children: Vec<(Entity, Entity)> = vec![];
+        for (entity, node) in &nodes {
+            match node.parent.filter(|parent| nodes.contains_key(parent)) {
+                Some(parent) => children.push((parent, *entity)),
+                None => roots.push(*entity),
+            }
+        }
+        for (parent, child) in children {
+            nodes.get_mut(&parent).unwrap().children.push(child);
+        }
+
+        // keep the order stable from one refresh to the next
+        roots.sort();
+        for node in nodes.values_mut() {
+            node.children.sort();
+        }
+
+        Self { nodes, roots }
+    }
+
+    fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.nodes.get(&entity).and_then(|node| node.parent)
+    }
+
+    fn is_descendant(&self, entity: Entity, ancestor: Entity) -> bool {
+        let mut parent = self.parent(entity);
+        while let Some(entity) = parent {
+            if entity == ancestor {
+                return true;
+            }
+            parent = self.parent(entity);
+        }
+        false
+    }
+
+    // the rows to show, with everything leading to a match expanded while searching
+    fn rows(&self, expanded: &HashSet<Entity>, search: &str) -> Vec<OutlineRow> {
+        let search = search.trim().to_lowercase();
+        let mut rows = vec![];
+        for root in &self.roots {
+            self.push_rows(*root, 0, expanded, &search, &mut rows);
+        }
+        rows
+    }
+
+    // returns whether anything in the subtree matched the search
+    fn push_rows(
+        &self,
+        entity: Entity,
+        depth: usize,
+        expanded: &HashSet<Entity>,
+        search: &str,
+        rows: &mut Vec<OutlineRow>
+    ) -> bool {
+        let Some(node) = self.nodes.get(&entity) else {
+            return false;
+        };
+
+        let index = rows.len();
+        let open = !search.is_empty() || expanded.contains(&entity);
+        rows.push(OutlineRow {
+            entity,
+            depth,
+            has_children: !node.children.is_empty(),
+            expanded: open && !node.children.is_empty(),
+        });
+
+        let mut matched = search.is_empty() || node.label.to_lowercase().contains(search);
+        if open {
+            for child in &node.children {
+                matched |= self.push_rows(*child, depth + 1, expanded, search, rows);
+            }
+        }
+
+        if !matched {
+            rows.truncate(index);
+        }
+        matched
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+struct OutlinerRow {
+    panel: Entity,
+    entity: Entity,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+struct OutlinerToggle {
+    panel: Entity,
+    entity: Entity,
+}
+
+// the space around the rows, where dropping a row makes it a root
+#[derive(Component, Clone, Copy, Debug)]
+struct OutlinerDropZone {
+    panel: Entity,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+struct OutlinerSourceSelect {
+    panel: Entity,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+struct OutlinerSearch {
+    panel: Entity,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+struct OutlinerRename {
+    panel: Entity,
+    entity: Entity,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+struct OutlinerMenu;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OutlinerMenuAction {
+    Rename,
+    Duplicate,
+    Delete,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+struct OutlinerMenuItem {
+    panel: Entity,
+    entity: Entity,
+    action: OutlinerMenuAction,
+}
+
+// start over whenever the panel is pointed at another world
+fn follow_outliner_sources(
+    mut q_panels: Query<(Entity, &mut OutlinerPanel)>,
+    connections: Res<BrpConnections>,
+    mut commands: Commands
+) {
+    for (panel_entity, mut panel) in &mut q_panels {
+        let origin = match panel.source {
+            OutlinerSource::Editor => OutlineOrigin::Editor,
+            OutlinerSource::Game => {
+                OutlineOrigin::Connection(connections.primary_name().to_string())
+            }
+        };
+        if panel.origin.as_ref() == Some(&origin) {
+            continue;
+        }
+
+        let panel = panel.bypass_change_detection();
+        if let Some(replica) = panel.replica.take() {
+            commands.entity(replica).despawn_recursive();
+        }
+        if let OutlineOrigin::Connection(connection) = &origin {
+            let query = BrpQuery {
+                option: vec![Name::type_path().to_string(), Parent::type_path().to_string()],
+                ..default()
+            };
+            let replica = RemoteReplica::new(query, default()).with_interval(panel.interval);
+            let replica = spawn_replica(
+                &mut commands,
+                panel_entity,
+                replica,
+                Some(connection.clone())
+            );
+            panel.replica = Some(replica);
+        }
+
+        panel.origin = Some(origin);
+        panel.outline = default();
+        panel.expanded.clear();
+        panel.renaming = None;
+        panel.dragging = None;
+        panel.dirty = true;
+        panel.refresh();
+    }
+}
+
+// show the names and parents the replica has mirrored, whenever it has polled the game
+fn read_remote_outlines(
+    mut q_panels: Query<&mut OutlinerPanel>,
+    mut q_replicas: Query<&mut RemoteReplica>,
+    q_components: Query<&RemoteComponents>,
+    connections: Res<BrpConnections>,
+    mut selection: ResMut<SelectionService>
+) {
+    for mut panel in &mut q_panels {
+        let panel = panel.bypass_change_detection();
+        let Some(OutlineOrigin::Connection(connection)) = &panel.origin else {
+            continue;
+        };
+        if !connections.get(connection).is_some_and(|connection| connection.is_connected()) {
+            // (the rows are replaced by a note that the game isn't connected)
+            if !panel.outline.nodes.is_empty() {
+                panel.show(default());
+                panel.deselect_missing(&mut selection);
+            }
+            continue;
+        }
+        let Some(mut replica) = panel.replica.and_then(|replica| q_replicas.get_mut(replica).ok())
+        else {
+            continue;
+        };
+
+        if std::mem::take(&mut panel.stale) {
+            replica.bypass_change_detection().refresh();
+        }
+        if !replica.is_changed() {
+            continue;
+        }
+
+        let outline = Outline::new(
+            replica
+                .rows(&q_components)
+                .into_iter()
+                .map(|row| {
+                    let name = row.components
+                        .get(Name::type_path())
+                        .and_then(Value::as_str)
+                        .map(str::to_string);
+                    let parent = row.components
+                        .get(Parent::type_path())
+                        .and_then(entity_from_json);
+                    (row.entity, name, parent)
+                })
+        );
+        panel.show(outline);
+        panel.deselect_missing(&mut selection);
+    }
+}
+
+type LocalOutlineQuery<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, Option<&'static Name>, Option<&'static Parent>),
+    (Without<Node>, Without<RemoteReplica>, Without<RemoteProxy>)
+>;
+
+// the editor's own UI would drown out everything else, so it's left out, as are the replicas of
+// remote entities the panels keep
+fn refresh_local_outlines(
+    time: Res<Time<Real>>,
+    mut q_panels: Query<&mut OutlinerPanel>,
+    q_entities: LocalOutlineQuery,
+    mut selection: ResMut<SelectionService>
+) {
+    for mut panel in &mut q_panels {
+        let panel = panel.bypass_change_detection();
+        if panel.origin != Some(OutlineOrigin::Editor) {
+            continue;
+        }
+        if !panel.timer.tick(time.delta()).finished() {
+            continue;
+        }
+        panel.timer = Timer::new(panel.interval, TimerMode::Once);
+
+        let outline = Outline::new(
+            q_entities
+                .iter()
+                .map(|(entity, name, parent)| {
+                    (entity, name.map(|name| name.to_string()), parent.map(Parent::get))
+                })
+        );
+        panel.show(outline);
+        panel.deselect_missing(&mut selection);
+    }
+}
+
+fn switch_outliner_sources(
+    q_selects: Query<(&Dropdown, &OutlinerSourceSelect), Changed<Dropdown>>,
+    mut q_panels: Query<&mut OutlinerPanel>
+) {
+    for (dropdown, select) in &q_selects {
+        let Ok(mut panel) = q_panels.get_mut(select.panel) else {
+            continue;
+        };
+
+        let source = match dropdown.value() {
+            Some(0) => OutlinerSource::Editor,
+            _ => OutlinerSource::Game,
+        };
+        if panel.source != source {
+            panel.source = source;
+        }
+    }
+}
+
+fn search_outlines(
+    q_searches: Query<(&TextInputValue, &OutlinerSearch), Changed<TextInputValue>>,
+    mut q_panels: Query<&mut OutlinerPanel>
+) {
+    for (value, search) in &q_searches {
+        let Ok(mut panel) = q_panels.get_mut(search.panel) else {
+            continue;
+        };
+        if panel.search == value.0 {
+            continue;
+        }
+
+        let panel = panel.bypass_change_detection();
+        panel.search = value.0.clone();
+        panel.dirty = true;
+    }
+}
+
+// the rows show what is selected, wherever it was selected
+fn follow_selection(
+    mut changes: EventReader<SelectionChanged>,
+    mut q_panels: Query<&mut OutlinerPanel>
+) {
+    if changes.read().last().is_none() {
+        return;
+    }
+
+    for mut panel in &mut q_panels {
+        panel.bypass_change_detection().dirty = true;
+    }
+}
+
+// show what undo and redo did right away
+fn refresh_on_history(
+    mut applied: EventReader<HistoryApplied>,
+    mut q_panels: Query<&mut OutlinerPanel>
+) {
+    if applied.read().last().is_none() {
+        return;
+    }
+
+    for mut panel in &mut q_panels {
+        panel.bypass_change_detection().refresh();
+    }
+}
+
+// pressing a row selects it (or toggles it, with control held), and starts dragging it
+fn pick_outliner_rows(
+    keys: Res<ButtonInput<KeyCode>>,
+    q_rows: Query<(&Interaction, &OutlinerRow), Changed<Interaction>>,
+    mut q_panels: Query<&mut OutlinerPanel>,
+    mut selection: ResMut<SelectionService>
+) {
+    for (interaction, row) in &q_rows {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Ok(mut panel) = q_panels.get_mut(row.panel) else {
+            continue;
+        };
+        let Some(entity) = panel.editor_entity(row.entity) else {
+            continue;
+        };
+        panel.bypass_change_detection().dragging = Some(row.entity);
+
+        if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+            selection.toggle(entity);
+        } else {
+            selection.select(entity);
+        }
+    }
+}
+
+// select everything the focused outliner shows, or the first one if another panel has focus
+fn select_all_outlined(
+    mut requests: EventReader<SelectAllRequested>,
+    q_panels: Query<(Entity, &OutlinerPanel)>,
+    mut selection: ResMut<SelectionService>
+) {
+    let Some(request) = requests.read().last() else {
+        return;
+    };
+    let Some((_, panel)) = request.panel
+        .and_then(|focused| q_panels.get(focused).ok())
+        .or_else(|| q_panels.iter().next()) else {
+        return;
+    };
+
+    // (while searching, only the entities that match)
+    let search = panel.search.trim().to_lowercase();
+    let everything: HashSet<Entity> = panel.outline.nodes.keys().copied().collect();
+    let entities = panel.outline
+        .rows(&everything, "")
+        .into_iter()
+        .filter(|row| panel.outline.nodes[&row.entity].label.to_lowercase().contains(&search))
+        .filter_map(|row| panel.editor_entity(row.entity));
+    selection.select_all(entities);
+}
+
+fn toggle_outliner_rows(
+    q_toggles: Query<(&Interaction, &OutlinerToggle), Changed<Interaction>>,
+    mut q_panels: Query<&mut OutlinerPanel>
+) {
+    for (interaction, toggle) in &q_toggles {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Ok(mut panel) = q_panels.get_mut(toggle.panel) else {
+            continue;
+        };
+
+        let panel = panel.bypass_change_detection();
+        if !panel.expanded.remove(&toggle.entity) {
+            panel.expanded.insert(toggle.entity);
+        }
+        panel.dirty = true;
+    }
+}
+
+// releasing a dragged row over another one moves it there
+fn drop_outliner_rows(
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut q_panels: Query<(Entity, &mut OutlinerPanel)>,
+    q_rows: Query<(&Interaction, &OutlinerRow)>,
+    q_drop_zones: Query<(&Interaction, &OutlinerDropZone)>,
+    mut commands: Commands
+) {
+    if !mouse.just_released(MouseButton::Left) {
+        return;
+    }
+
+    for (panel_entity, mut panel) in &mut q_panels {
+        let Some(dragged) = panel.bypass_change_detection().dragging.take() else {
+            continue;
+        };
+
+        let hovered = |interaction: &Interaction| *interaction == Interaction::Hovered;
+        let over_row = q_rows
+            .iter()
+            .find(|(interaction, row)| row.panel == panel_entity && hovered(interaction))
+            .map(|(_, row)| row.entity);
+        let over_drop_zone = q_drop_zones
+            .iter()
+            .any(|(interaction, zone)| zone.panel == panel_entity && hovered(interaction));
+
+        let parent = match over_row {
+            // (let go where it was picked up, so it was only a click)
+            Some(row) if row == dragged => {
+                continue;
+            }
+            Some(row) if panel.outline.is_descendant(row, dragged) => {
+                warn!("Can't move an entity under one of its own descendants");
+                continue;
+            }
+            Some(row) => Some(row),
+            None if over_drop_zone => None,
+            None => {
+                continue;
+            }
+        };
+        if panel.outline.parent(dragged) == parent {
+            continue;
+        }
+
+        edit_entity(&mut commands, panel_entity, dragged, OutlinerEdit::Reparent(parent));
+    }
+}
+
+// right clicking a row selects it, and opens a menu of things to do with it
+fn open_outliner_menus(
+    mouse: Res<ButtonInput<MouseButton>>,
+    q_rows: Query<(&Interaction, &OutlinerRow)>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_menus: Query<Entity, With<OutlinerMenu>>,
+    l10n: Res<Localization>,
+    mut commands: Commands
+) {
+    if !mouse.just_pressed(MouseButton::Right) {
+        return;
+    }
+    for menu in &q_menus {
+        commands.entity(menu).despawn_recursive();
+    }
+
+    let Some((_, row)) = q_rows
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Hovered) else {
+        return;
+    };
+    let Some(cursor) = q_windows.get_single().ok().and_then(Window::cursor_position) else {
+        return;
+    };
+
+    let menu = commands
+        .spawn((
+            Name::new("Outliner Menu"),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(cursor.x),
+                    top: Val::Px(cursor.y),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(4.0)),
+                    border: UiRect::all(Val::Px(1.0)),
+                    ..default()
+                },
+                background_color: Color::srgb(0.15, 0.155, 0.16).into(),
+                border_color: Color::srgb(0.4, 0.4, 0.4).into(),
+                z_index: ZIndex::Global(100),
+                ..default()
+            },
+            OutlinerMenu,
+        ))
+        .id();
+
+    let actions = [
+        (OutlinerMenuAction::Rename, "Rename"),
+        (OutlinerMenuAction::Duplicate, "Duplicate"),
+        (OutlinerMenuAction::Delete, "Delete"),
+    ];
+    commands.ui_builder(menu).column(|column| {
+        for (action, key) in actions {
+            let item = OutlinerMenuItem {
+                panel: row.panel,
+                entity: row.entity,
+                action,
+            };
+            column.container(
+                (
+                    NodeBundle {
+                        style: Style {
+                            padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    Interaction::None,
+                    item,
+                ),
+                |item| {
+                    item.label(LabelConfig {
+                        label: l10n.lbl(key),
+                        ..default()
+                    });
+                }
+            );
+        }
+    });
+}
+
+// do what was picked from the menu; any click closes it
+fn choose_outliner_menu_items(
+    mouse: Res<ButtonInput<MouseButton>>,
+    q_items: Query<(&Interaction, &OutlinerMenuItem), Changed<Interaction>>,
+    q_menus: Query<Entity, With<OutlinerMenu>>,
+    mut q_panels: Query<&mut OutlinerPanel>,
+    mut commands: Commands
+) {
+    for (interaction, item) in &q_items {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match item.action {
+            OutlinerMenuAction::Rename => {
+                let Ok(mut panel) = q_panels.get_mut(item.panel) else {
+                    continue;
+                };
+                let panel = panel.bypass_change_detection();
+                panel.renaming = Some(item.entity);
+                panel.dirty = true;
+            }
+            OutlinerMenuAction::Duplicate => {
+                edit_entity(&mut commands, item.panel, item.entity, OutlinerEdit::Duplicate);
+            }
+            OutlinerMenuAction::Delete => {
+                edit_entity(&mut commands, item.panel, item.entity, OutlinerEdit::Delete);
+            }
+        }
+    }
+
+    if mouse.just_pressed(MouseButton::Left) {
+        for menu in &q_menus {
+            commands.entity(menu).despawn_recursive();
+        }
+    }
+}
+
+// submitting a new name renames the entity, and escape gives up
+fn rename_outliner_entities(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut submissions: EventReader<TextInputSubmitEvent>,
+    q_inputs: Query<&OutlinerRename>,
+    mut q_panels: Query<&mut OutlinerPanel>,
+    mut commands: Commands
+) {
+    for submission in submissions.read() {
+        let Ok(rename) = q_inputs.get(submission.entity) else {
+            continue;
+        };
+        if let Ok(mut panel) = q_panels.get_mut(rename.panel) {
+            let panel = panel.bypass_change_detection();
+            panel.renaming = None;
+            panel.dirty = true;
+        }
+
+        let name = submission.value.trim().to_string();
+        edit_entity(&mut commands, rename.panel, rename.entity, OutlinerEdit::Rename(name));
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        for mut panel in &mut q_panels {
+            if panel.renaming.is_some() {
+                let panel = panel.bypass_change_detection();
+                panel.renaming = None;
+                panel.dirty = true;
+            }
+        }
+    }
+}
+
+fn edit_entity(commands: &mut Commands, panel_entity: Entity, entity: Entity, edit: OutlinerEdit) {
+    commands.add(move |world: &mut World| {
+        let Some(panel) = world.get::<OutlinerPanel>(panel_entity) else {
+            return;
+        };
+
+        let result = match panel.origin.clone() {
+            Some(OutlineOrigin::Editor) => edit_local_entity(world, entity, &edit),
+            Some(OutlineOrigin::Connection(connection)) => {
+                edit_remote_entity(world, panel_entity, &connection, entity, &edit)
+            }
+            None => Ok(()),
+        };
+        if let Err(error) = result {
+            warn!("Could not edit entity {}: {}", entity, error);
+        }
+
+        if let Some(mut panel) = world.get_mut::<OutlinerPanel>(panel_entity) {
+            panel.bypass_change_detection().refresh();
+        }
+    });
+}
+
+fn edit_local_entity(world: &mut World, entity: Entity, edit: &OutlinerEdit) -> AnyhowResult<()> {
+    let mut entity_mut = world
+        .get_entity_mut(entity)
+        .ok_or_else(|| anyhow!("Entity {:?} not found", entity))?;
+
+    let step = match edit {
+        OutlinerEdit::Rename(name) => {
+            let before = entity_mut.get::<Name>().cloned();
+            let after = Name::new(name.clone());
+            entity_mut.insert(after.clone());
+
+            let operation = match before {
+                Some(before) => HistoryOperation::Change {
+                    entity,
+                    before: Box::new(before),
+                    after: Box::new(after),
+                },
+                None => HistoryOperation::Insert { entity, component: Box::new(after) },
+            };
+            HistoryStep::new("Rename", vec![operation])
+        }
+        OutlinerEdit::Reparent(parent) => {
+            let before = entity_mut.get::<Parent>().map(Parent::get);
+            match parent {
+                Some(parent) => entity_mut.set_parent(*parent),
+                None => entity_mut.remove_parent(),
+            };
+            HistoryStep::new(
+                "Reparent",
+                vec![HistoryOperation::Reparent { entity, before, after: *parent }]
+            )
+        }
+        OutlinerEdit::Duplicate => {
+            let copy = duplicate_local_entity(world, entity)?;
+            let entities = EntitySnapshot::take_recursive(world, copy)?;
+            HistoryStep::new("Duplicate", vec![HistoryOperation::Spawn { entities }])
+        }
+        OutlinerEdit::Delete => {
+            let entities = EntitySnapshot::take_recursive(world, entity)?;
+            world.entity_mut(entity).despawn_recursive();
+            HistoryStep::new("Delete", vec![HistoryOperation::Despawn { entities }])
+        }
+    };
+    history::record(world, step);
+
+    Ok(())
+}
+
+// spawn a copy of every reflected component, next to the original
+fn duplicate_local_entity(world: &mut World, entity: Entity) -> AnyhowResult<Entity> {
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+
+    // the copy is put in the hierarchy properly below
+    let snapshot = EntitySnapshot::take(world, entity, &type_registry)?;
+
+    let mut copy = world.spawn_empty();
+    for value in &snapshot.components {
+        let type_id = value.get_represented_type_info().map(|info| info.type_id());
+        let reflect_component = type_id.and_then(|type_id| {
+            type_registry.get_type_data::<ReflectComponent>(type_id)
+        });
+        if let Some(reflect_component) = reflect_component {
+            reflect_component.insert(&mut copy, &**value, &type_registry);
+        }
+    }
+    if let Some(parent) = snapshot.parent {
+        copy.set_parent(parent);
+    }
+
+    Ok(copy.id())
+}
+
+fn edit_remote_entity(
+    world: &mut World,
+    panel_entity: Entity,
+    connection: &str,
+    entity: Entity,
+    edit: &OutlinerEdit
+) -> AnyhowResult<()> {
+    // (the game is asked what the entity was like first, so the edit can be undone)
+    let (edit, label) = match edit {
+        OutlinerEdit::Rename(name) => {
+            // a name goes over the wire however the editor would serialize it
+            let type_registry = world.resource::<AppTypeRegistry>().read();
+            let name = reflect_to_json(&Name::new(name.clone()), &type_registry)?;
+            let components = [(Name::type_path().to_string(), name)].into_iter().collect();
+            (RemoteEdit::Insert { entity, components }, "Rename")
+        }
+        OutlinerEdit::Reparent(parent) => {
+            (RemoteEdit::Reparent { entity, parent: *parent }, "Reparent")
+        }
+        OutlinerEdit::Delete => (RemoteEdit::Destroy { entities: vec![entity] }, "Delete"),
+        OutlinerEdit::Duplicate => {
+            // the copy is spawned once the entity has been read, see receive_remote_duplicates
+            history::remote::copy(world, connection, Some(panel_entity), vec![entity]);
+            return Ok(());
+        }
+    };
+    let step = HistoryStep::new(label, vec![]);
+    history::remote::edit(world, connection, Some(panel_entity), edit, step);
+    Ok(())
+}
+
+// spawn a copy of a remote entity next to the original, once it has been read
+fn receive_remote_duplicates(
+    mut copies: EventReader<RemoteCopied>,
+    q_panels: Query<(), With<OutlinerPanel>>,
+    mut commands: Commands
+) {
+    for copied in copies.read() {
+        let Some(panel_entity) = copied.origin.filter(|origin| q_panels.contains(*origin)) else {
+            continue;
+        };
+        if let Some(error) = &copied.error {
+            warn!("Could not duplicate remote entity: {}", error);
+            continue;
+        }
+
+        // (the entity comes before its descendants, which aren't duplicated)
+        let Some(original) = copied.entities.first().cloned() else {
+            continue;
+        };
+        let sibling = Some(original.entity.entity);
+        let edit = RemoteEdit::Spawn { entities: vec![original], sibling };
+        let connection = copied.connection.clone();
+        commands.add(move |world: &mut World| {
+            let step = HistoryStep::new("Duplicate", vec![]);
+            history::remote::edit(world, &connection, Some(panel_entity), edit, step);
+        });
+    }
+}
+
+// the game has carried out an edit (or not), so show what it's like now
+fn receive_remote_edits(
+    mut edits: EventReader<RemoteEdited>,
+    mut q_panels: Query<&mut OutlinerPanel>
+) {
+    for edited in edits.read() {
+        let Some(Ok(mut panel)) = edited.origin.map(|origin| q_panels.get_mut(origin)) else {
+            continue;
+        };
+        if let Some(error) = &edited.error {
+            warn!("Could not {} remote entity: {}", edited.label.to_lowercase(), error);
+        }
+
+        panel.bypass_change_detection().refresh();
+    }
+}
+
+// lay out panels the first time (or after something cleared them), and rebuild rows that changed
+fn update_outliner_panels(
+    mut q_panels: Query<(Entity, &mut OutlinerPanel)>,
+    q_existing: Query<()>,
+    q_renames: Query<&OutlinerRename>,
+    connections: Res<BrpConnections>,
+    selection: Res<SelectionService>,
+    l10n: Res<Localization>,
+    mut commands: Commands
+) {
+    for (panel_entity, mut panel) in &mut q_panels {
+        let panel = panel.bypass_change_detection();
+
+        let tree = match panel.tree.filter(|tree| q_existing.contains(*tree)) {
+            Some(tree) => tree,
+            None => {
+                let tree = layout_outliner(panel_entity, panel, &l10n, &mut commands);
+                panel.tree = Some(tree);
+                panel.dirty = true;
+                tree
+            }
+        };
+
+        // (rebuilding would throw away the name being typed, once its input is there)
+        let typing = q_renames.iter().any(|rename| {
+            rename.panel == panel_entity &&
+                panel.renaming == Some(rename.entity) &&
+                panel.outline.nodes.contains_key(&rename.entity)
+        });
+        if !panel.dirty || typing {
+            continue;
+        }
+        panel.dirty = false;
+
+        commands.entity(tree).despawn_descendants();
+        commands.ui_builder(tree).column(|column| {
+            let disconnected = match &panel.origin {
+                Some(OutlineOrigin::Connection(connection)) => {
+                    !connections.get(connection).is_some_and(|connection| connection.is_connected())
+                }
+                _ => false,
+            };
+            if disconnected {
+                column.label(LabelConfig {
+                    label: l10n.lbl("Disconnected"),
+                    ..default()
+                });
+                return;
+            }
+
+            for row in panel.outline.rows(&panel.expanded, &panel.search) {
+                let node = &panel.outline.nodes[&row.entity];
+                let selected = panel
+                    .editor_entity(row.entity)
+                    .is_some_and(|entity| selection.is_selected(&entity));
+                let renaming = panel.renaming == Some(row.entity);
+                spawn_outline_row(column, panel_entity, &row, node, selected, renaming);
+            }
+        });
+    }
+}
+
+// the source and search bar above the rows, which stay put while the rows are rebuilt
+fn layout_outliner(
+    panel_entity: Entity,
+    panel: &OutlinerPanel,
+    l10n: &Localization,
+    commands: &mut Commands
+) -> Entity {
+    commands.entity(panel_entity).despawn_descendants();
+
+    let mut tree = panel_entity;
+    commands.ui_builder(panel_entity).column(|column| {
+        column.row(|bar| {
+            let selected = match panel.source {
+                OutlinerSource::Editor => 0,
+                OutlinerSource::Game => 1,
+            };
+            bar.dropdown(vec![l10n.lbl("Editor"), l10n.lbl("Game")], selected)
+                .insert(OutlinerSourceSelect { panel: panel_entity })
+                .style()
+                .width(Val::Px(100.0));
+
+            bar.label(LabelConfig {
+                label: l10n.lbl("Search"),
+                ..default()
+            })
+                .style()
+                .margin(UiRect::horizontal(Val::Px(8.0)));
+            bar.spawn((text_input(&panel.search), OutlinerSearch { panel: panel_entity }));
+        });
+
+        column
+            .scroll_view(None, |scroll_view| {
+                tree = scroll_view.id();
+            })
+            .insert((Interaction::None, OutlinerDropZone { panel: panel_entity }))
+            .style()
+            .flex_grow(1.0);
+    });
+
+    tree
+}
+
+fn spawn_outline_row(
+    builder: &mut UiBuilder<Entity>,
+    panel: Entity,
+    row: &OutlineRow,
+    node: &OutlineNode,
+    selected: bool,
+    renaming: bool
+) {
+    let background = if selected { Color::srgba(0.25, 0.45, 0.85, 0.5) } else { Color::NONE };
+
+    builder
+        .row(|line| {
+            // (the toggle blocks the click, so expanding a row doesn't select it)
+            let glyph = match (row.has_children, row.expanded) {
+                (false, _) => "",
+                (true, true) => "-",
+                (true, false) => "+",
+            };
+            line.container(
+                (
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Px(16.0),
+                            ..default()
+                        },
+                        focus_policy: FocusPolicy::Block,
+                        ..default()
+                    },
+                    Interaction::None,
+                    OutlinerToggle {
+                        panel,
+                        entity: row.entity,
+                    },
+                ),
+                |toggle| {
+                    if !glyph.is_empty() {
+                        toggle.label(LabelConfig {
+                            label: glyph.to_string(),
+                            ..default()
+                        });
+                    }
+                }
+            );
+
+            if renaming {
+                let rename = OutlinerRename {
+                    panel,
+                    entity: row.entity,
+                };
+                let mut input = text_input(&node.label);
+                input.1.inactive = TextInputInactive(false);
+                line.spawn((input, rename));
+            } else {
+                line.label(LabelConfig {
+                    label: node.label.clone(),
+                    ..default()
+                });
+            }
+        })
+        .insert((
+            Interaction::None,
+            OutlinerRow {
+                panel,
+                entity: row.entity,
+            },
+        ))
+        .style()
+        .padding(UiRect::left(Val::Px(16.0 * (row.depth as f32))))
+        .background_color(background);
+}
+
+pub trait UiOutlinerExt {
+    fn outliner(&mut self) -> UiBuilder<Entity>;
+}
+
+impl UiOutlinerExt for UiBuilder<'_, Entity> {
+    fn outliner(&mut self) -> UiBuilder<Entity> {
+        let panel = self
+            .insert((Name::new("Outliner"), TreeViewPanel, Focusable, OutlinerPanel::default()))
+            .style()
+            .padding(UiRect::all(Val::Px(10.0)))
+            .id();
+
+        self.commands().ui_builder(panel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a level with a player holding a sword, and a lamp
+    fn level() -> Outline {
+        let entity = Entity::from_raw;
+        Outline::new([
+            (entity(1), Some("Level".to_string()), None),
+            (entity(2), Some("Player".to_string()), Some(entity(1))),
+            (entity(3), Some("Sword".to_string()), Some(entity(2))),
+            (entity(4), Some("Lamp".to_string()), Some(entity(1))),
+        ])
+    }
+
+    // the index and depth of each row
+    fn shown(rows: &[OutlineRow]) -> Vec<(u32, usize)> {
+        rows.iter().map(|row| (row.entity.index(), row.depth)).collect()
+    }
+
+    #[test]
+    fn rows_show_the_children_of_expanded_entities() {
+        let outline = level();
+        assert_eq!(shown(&outline.rows(&HashSet::new(), "")), [(1, 0)]);
+
+        let expanded = [Entity::from_raw(1)].into_iter().collect();
+        assert_eq!(shown(&outline.rows(&expanded, "")), [(1, 0), (2, 1), (4, 1)]);
+    }
+
+    #[test]
+    fn search_shows_matches_and_what_leads_to_them() {
+        let outline = level();
+
+        // (whatever is expanded, and whatever the case)
+        let rows = outline.rows(&HashSet::new(), " sWoRd ");
+        assert_eq!(shown(&rows), [(1, 0), (2, 1), (3, 2)]);
+        assert!(rows[1].expanded && !rows[2].expanded);
+
+        // children that don't match are left out
+        assert_eq!(shown(&outline.rows(&HashSet::new(), "play")), [(1, 0), (2, 1)]);
+        assert_eq!(shown(&outline.rows(&HashSet::new(), "l")), [(1, 0), (2, 1), (4, 1)]);
+        assert!(outline.rows(&HashSet::new(), "torch").is_empty());
+    }
+
+    #[test]
+    fn unnamed_entities_are_searched_by_id() {
+        let outline = Outline::new([(Entity::from_raw(7), None, None)]);
+        assert_eq!(shown(&outline.rows(&HashSet::new(), "entity 7")), [(7, 0)]);
+    }
+}
@@ -0,0 +1,119 @@
+//! Drives an editor and a game in the same process, through an `InProcessTransport`, and checks
+//! that the camera control reaches the game.
+
+use std::time::Duration;
+
+use bevy::{ prelude::*, time::TimeUpdateStrategy };
+
+use leafwing_input_manager::action_state::ActionState;
+
+use beverage::{
+    input::{ InputAction, InputConfig },
+    remote::{
+        camera_control::CameraControlRemotePlugin,
+        connections::BrpConnections,
+        link::RemoteLink,
+        transport::InProcessTransport,
+        DespawnRemoteFpsCounter,
+        EditorRemotePlugin,
+        RemoteCamera,
+        RemoteFpsCounter,
+    },
+};
+
+// how much time passes in both apps with each update, so that the links and heartbeats, which go
+// by the clock, do the same thing on every run
+const FRAME: Duration = Duration::from_millis(50);
+
+// marks the editor's camera, which CameraControlRemotePlugin drives the game's camera with
+#[derive(Component)]
+struct EditorCamera;
+
+// a game with a camera, and an editor connected to it (the camera's entity is returned too)
+fn connected_apps() -> (App, App, Entity) {
+    let mut game = App::new();
+    game.add_plugins((MinimalPlugins, EditorRemotePlugin::default().without_listening()))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+        .register_type::<Camera>()
+        .register_type::<Transform>()
+        .register_type::<RemoteFpsCounter>()
+        .register_type::<DespawnRemoteFpsCounter>();
+    let game_camera = game.world_mut()
+        .spawn((Name::new("Camera"), Camera::default(), Transform::default()))
+        .id();
+
+    let mut editor = App::new();
+    editor.add_plugins((MinimalPlugins, CameraControlRemotePlugin::<EditorCamera>::default()))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+        .register_type::<Transform>()
+        .init_resource::<InputConfig>();
+
+    let transport = InProcessTransport::new(game.world()).unwrap();
+    let mut connections = editor.world_mut().resource_mut::<BrpConnections>();
+    let connection = connections.primary_mut().unwrap();
+    connection.client.set_transport(transport);
+    connection.connect();
+
+    (editor, game, game_camera)
+}
+
+// update both apps for a while of their time
+// (each editor update sends its requests, the game answers them in its own update, and the
+// editor gets the answers at the start of its next one)
+fn run_for(editor: &mut App, game: &mut App, time: Duration) {
+    for _ in 0..time.as_millis() / FRAME.as_millis() {
+        editor.update();
+        game.update();
+    }
+}
+
+fn game_fps_markers<T: Component>(game: &mut App) -> usize {
+    game.world_mut().query_filtered::<(), With<T>>().iter(game.world()).count()
+}
+
+// press F on the camera for one editor update
+// (no input plugin ticks the action state here, so the key is let go by hand)
+fn press_f(editor: &mut App, camera: Entity) {
+    let action = InputAction::ToggleRemoteFpsCounter;
+    editor.world_mut().get_mut::<ActionState<InputAction>>(camera).unwrap().press(&action);
+    editor.update();
+    editor.world_mut().get_mut::<ActionState<InputAction>>(camera).unwrap().release(&action);
+}
+
+#[test]
+fn camera_follows_the_editor() {
+    let (mut editor, mut game, game_camera) = connected_apps();
+    let camera = editor.world_mut().spawn((EditorCamera, RemoteCamera, Transform::default())).id();
+
+    // the game's only camera is picked and linked to
+    run_for(&mut editor, &mut game, Duration::from_millis(500));
+    let link = editor.world().get::<RemoteLink>(camera).unwrap();
+    assert_eq!(link.remote, game_camera);
+    assert!(!link.is_in_flight(), "the first pull should be answered before moving");
+    let client = &editor.world().resource::<BrpConnections>().primary().unwrap().client;
+    assert_eq!(client.remote_camera, Some(game_camera));
+
+    let moved = Transform::from_xyz(-2.5, 4.5, 9.0).looking_at(Vec3::ZERO, Vec3::Y);
+    *editor.world_mut().get_mut::<Transform>(camera).unwrap() = moved;
+    run_for(&mut editor, &mut game, Duration::from_millis(200));
+    assert_eq!(game.world().get::<Transform>(game_camera), Some(&moved));
+}
+
+#[test]
+fn fps_counter_toggles() {
+    let (mut editor, mut game, _) = connected_apps();
+    let action_state = ActionState::<InputAction>::default();
+    let camera = editor.world_mut().spawn((EditorCamera, action_state)).id();
+    run_for(&mut editor, &mut game, Duration::from_millis(200));
+    assert!(editor.world().resource::<BrpConnections>().primary().unwrap().is_connected());
+
+    press_f(&mut editor, camera);
+    assert!(editor.world().resource::<InputConfig>().remote_fps);
+    run_for(&mut editor, &mut game, Duration::from_millis(200));
+    assert_eq!(game_fps_markers::<RemoteFpsCounter>(&mut game), 1);
+
+    press_f(&mut editor, camera);
+    assert!(!editor.world().resource::<InputConfig>().remote_fps);
+    run_for(&mut editor, &mut game, Duration::from_millis(200));
+    assert_eq!(game_fps_markers::<DespawnRemoteFpsCounter>(&mut game), 1);
+}